@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_alerts_attack_techniques;
+ALTER TABLE alerts
+    DROP COLUMN IF EXISTS attack_techniques,
+    DROP COLUMN IF EXISTS attack_tactics;
@@ -0,0 +1,10 @@
+-- MITRE ATT&CK context per alert. Derived rules are stamped from the static
+-- catalogue in `siem::attack` after each pass; GuardDuty findings are tagged at
+-- ingest from their finding type plus any ATT&CK indicators they carry. Ids only
+-- (TA0005 / T1562.008) — names live in the catalogue.
+ALTER TABLE alerts
+    ADD COLUMN IF NOT EXISTS attack_tactics    text[] NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS attack_techniques text[] NOT NULL DEFAULT '{}';
+
+-- Coverage matrix: hit counts per technique over a trailing window.
+CREATE INDEX IF NOT EXISTS idx_alerts_attack_techniques ON alerts USING gin (attack_techniques);
@@ -0,0 +1,4 @@
+DROP FUNCTION IF EXISTS try_inet(text);
+DROP TABLE IF EXISTS threat_intel_hits;
+DROP TABLE IF EXISTS threat_indicators;
+DROP TABLE IF EXISTS threat_intel_feeds;
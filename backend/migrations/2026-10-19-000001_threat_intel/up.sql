@@ -0,0 +1,72 @@
+-- Threat-intel feeds loaded from local files by the `siem::threat_intel` worker.
+-- One row per configured feed: `content_hash` is a fingerprint of the manifest
+-- entry and the file's mtime and size (not its bytes), letting a reload skip
+-- unchanged files; the status columns back the overview's feed health panel.
+CREATE TABLE IF NOT EXISTS threat_intel_feeds (
+    name            text PRIMARY KEY,
+    path            text NOT NULL,
+    format          text NOT NULL,
+    confidence      integer NOT NULL,
+    ttl_days        integer NOT NULL,
+    content_hash    text,
+    indicator_count bigint NOT NULL DEFAULT 0,
+    loaded_at       timestamptz,
+    last_error      text,
+    updated_at      timestamptz NOT NULL DEFAULT now()
+);
+
+-- Indicators: `ip` rows carry a parsed `network` (a bare address is a /32 or
+-- /128), `domain` and `user_agent` rows match on the lower-cased `value`.
+-- `valid_until` is the indicator's own validity end, if it has one; `expires_at`
+-- is that, else the feed's TTL from the last reload that still listed it.
+-- `loaded_at` is first sighting (drives the retro-hunt over already-ingested
+-- events); `refreshed_at` is the last reload that still listed it.
+CREATE TABLE IF NOT EXISTS threat_indicators (
+    id           bigserial PRIMARY KEY,
+    feed         text NOT NULL REFERENCES threat_intel_feeds (name) ON DELETE CASCADE,
+    kind         text NOT NULL,
+    value        text NOT NULL,
+    network      cidr,
+    confidence   integer NOT NULL,
+    description  text,
+    valid_until  timestamptz,
+    expires_at   timestamptz NOT NULL,
+    loaded_at    timestamptz NOT NULL DEFAULT now(),
+    refreshed_at timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (feed, kind, value)
+);
+CREATE INDEX IF NOT EXISTS idx_threat_indicators_network ON threat_indicators USING gist (network inet_ops) WHERE network IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_threat_indicators_loaded_at ON threat_indicators (loaded_at);
+
+-- Event-level matches. Indicator fields are snapshotted so hits outlive a feed
+-- being dropped or an indicator expiring.
+CREATE TABLE IF NOT EXISTS threat_intel_hits (
+    id          bigserial PRIMARY KEY,
+    feed        text NOT NULL,
+    kind        text NOT NULL,
+    indicator   text NOT NULL,
+    confidence  integer NOT NULL,
+    source      text NOT NULL,
+    event_uid   text NOT NULL,
+    actor       text,
+    actor_id    text,
+    source_ip   text,
+    user_agent  text,
+    event_time  timestamptz NOT NULL,
+    matched_at  timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (feed, kind, indicator, source, event_uid)
+);
+CREATE INDEX IF NOT EXISTS idx_threat_intel_hits_actor_time ON threat_intel_hits (actor_id, event_time DESC);
+CREATE INDEX IF NOT EXISTS idx_threat_intel_hits_event_time ON threat_intel_hits (event_time);
+
+-- `source_ip` is free text (CloudTrail writes service hostnames like
+-- `cloudformation.amazonaws.com` there), so a bare `::inet` cast would abort the
+-- whole match query on the first non-address row.
+CREATE OR REPLACE FUNCTION try_inet(v text) RETURNS inet
+LANGUAGE plpgsql IMMUTABLE AS $$
+BEGIN
+    RETURN v::inet;
+EXCEPTION WHEN others THEN
+    RETURN NULL;
+END;
+$$;
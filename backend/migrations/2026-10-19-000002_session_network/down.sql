@@ -0,0 +1,4 @@
+ALTER TABLE sessions
+    DROP COLUMN IF EXISTS network_class,
+    DROP COLUMN IF EXISTS as_org,
+    DROP COLUMN IF EXISTS asn;
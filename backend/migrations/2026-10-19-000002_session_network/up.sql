@@ -0,0 +1,8 @@
+-- Network owner and egress class per session, from the GeoLite2 ASN DB and the
+-- configured trusted CIDRs/ASNs. `network_class` is 'trusted' (our own egress),
+-- 'hosting' (cloud/VPN providers) or NULL (an ordinary network — also every row
+-- derived before this existed). Geo detections only trust NULL rows.
+ALTER TABLE sessions
+    ADD COLUMN IF NOT EXISTS asn           bigint,
+    ADD COLUMN IF NOT EXISTS as_org        text,
+    ADD COLUMN IF NOT EXISTS network_class text;
@@ -0,0 +1,21 @@
+-- Risk score time series. `risk::compute` appends a 'raw' sample per actor each
+-- pass; `risk::compact_history` folds raw samples into 'hour' buckets and hour
+-- buckets into 'day' buckets as they age, so a quarter of history stays small.
+-- A bucket's `score` is the mean of its samples, `min_score`/`max_score` the
+-- extremes, and `label`/`components` come from its latest sample.
+CREATE TABLE IF NOT EXISTS risk_score_history (
+    actor_id   text NOT NULL REFERENCES actors (id) ON DELETE CASCADE,
+    resolution text NOT NULL,
+    bucket     timestamptz NOT NULL,
+    score      integer NOT NULL,
+    min_score  integer NOT NULL,
+    max_score  integer NOT NULL,
+    label      text NOT NULL,
+    components jsonb NOT NULL,
+    samples    integer NOT NULL DEFAULT 1,
+    PRIMARY KEY (actor_id, resolution, bucket)
+);
+
+-- Trajectory / movers read one actor's rows by time; compaction scans by age.
+CREATE INDEX IF NOT EXISTS idx_risk_history_actor_bucket ON risk_score_history (actor_id, bucket DESC);
+CREATE INDEX IF NOT EXISTS idx_risk_history_resolution_bucket ON risk_score_history (resolution, bucket);
@@ -0,0 +1,25 @@
+-- Analyst-authored corrections to identity reconciliation, applied by
+-- `actors::reconcile` on every pass so they survive re-derivation:
+--   merge  alias → actor_id   (the raw alias belongs to that actor)
+--   split  alias              (the raw alias stands as its own actor)
+--   kind   actor_id → actor_kind (force person | service | unresolved)
+-- Aliases are matched case-insensitively; store them lowercased.
+CREATE TABLE IF NOT EXISTS actor_overrides (
+    id         bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    kind       text NOT NULL CHECK (kind IN ('merge', 'split', 'kind')),
+    alias      text,
+    actor_id   text REFERENCES actors (id) ON DELETE CASCADE,
+    actor_kind text CHECK (actor_kind IN ('person', 'service', 'unresolved')),
+    reason     text NOT NULL,
+    created_by text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    CHECK (
+        (kind = 'merge' AND alias IS NOT NULL AND actor_id IS NOT NULL)
+        OR (kind = 'split' AND alias IS NOT NULL AND actor_id IS NULL)
+        OR (kind = 'kind' AND actor_id IS NOT NULL AND actor_kind IS NOT NULL)
+    )
+);
+
+-- One alias-level decision per alias, one kind override per actor.
+CREATE UNIQUE INDEX IF NOT EXISTS actor_overrides_alias_uq ON actor_overrides (alias) WHERE kind IN ('merge', 'split');
+CREATE UNIQUE INDEX IF NOT EXISTS actor_overrides_kind_uq ON actor_overrides (actor_id) WHERE kind = 'kind';
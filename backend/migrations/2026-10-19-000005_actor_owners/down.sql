@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS alerts_owner_idx;
+ALTER TABLE alerts DROP COLUMN IF EXISTS owner_kind;
+ALTER TABLE alerts DROP COLUMN IF EXISTS owner;
+DROP VIEW IF EXISTS actor_owner_effective;
+DROP TABLE IF EXISTS actor_owners;
+DROP TABLE IF EXISTS owner_hints;
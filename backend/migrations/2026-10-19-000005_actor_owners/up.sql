@@ -0,0 +1,47 @@
+-- Ownership of non-human actors. The ownership worker refreshes `owner_hints`
+-- from outside systems (IAM role tags, the self-service capability roster,
+-- GitHub CODEOWNERS / repo admin teams); the SIEM pass joins hints onto actors
+-- into `actor_owners`, next to analysts' manual assignments.
+CREATE TABLE IF NOT EXISTS owner_hints (
+    source       text NOT NULL,  -- aws_tags | k8s_namespace | github
+    key          text NOT NULL,  -- role ARN | namespace | org/repo
+    owner        text NOT NULL,
+    owner_kind   text NOT NULL CHECK (owner_kind IN ('team', 'person')),
+    evidence     jsonb NOT NULL DEFAULT '{}',
+    refreshed_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (source, key)
+);
+
+CREATE TABLE IF NOT EXISTS actor_owners (
+    actor_id    text NOT NULL REFERENCES actors (id) ON DELETE CASCADE,
+    source      text NOT NULL CHECK (source IN ('manual', 'aws_tags', 'k8s_namespace', 'github')),
+    owner       text NOT NULL,
+    owner_kind  text NOT NULL CHECK (owner_kind IN ('team', 'person')),
+    evidence    jsonb NOT NULL DEFAULT '{}',
+    -- Manual assignments only.
+    assigned_by text,
+    reason      text,
+    updated_at  timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (actor_id, source)
+);
+CREATE INDEX IF NOT EXISTS actor_owners_owner_idx ON actor_owners (owner);
+
+-- One owner per actor: manual beats tags beats namespace beats GitHub, and a
+-- person with a roster team is owned by that team.
+CREATE OR REPLACE VIEW actor_owner_effective AS
+    SELECT DISTINCT ON (actor_id) actor_id, owner, owner_kind, source
+    FROM (
+        SELECT actor_id, owner, owner_kind, source,
+               CASE source WHEN 'manual' THEN 0 WHEN 'aws_tags' THEN 1
+                           WHEN 'k8s_namespace' THEN 2 ELSE 3 END AS rank
+        FROM actor_owners
+        UNION ALL
+        SELECT id, team, 'team', 'roster', 4
+        FROM actors WHERE kind = 'person' AND team IS NOT NULL
+    ) o
+    ORDER BY actor_id, rank;
+
+-- Routing target stamped on each alert from its actor's effective owner.
+ALTER TABLE alerts ADD COLUMN IF NOT EXISTS owner text;
+ALTER TABLE alerts ADD COLUMN IF NOT EXISTS owner_kind text;
+CREATE INDEX IF NOT EXISTS alerts_owner_idx ON alerts (owner, last_seen DESC);
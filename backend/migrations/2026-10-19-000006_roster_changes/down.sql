@@ -0,0 +1,3 @@
+DROP VIEW IF EXISTS roster_leavers;
+DROP TABLE IF EXISTS roster_changes;
+DROP TABLE IF EXISTS roster_memberships;
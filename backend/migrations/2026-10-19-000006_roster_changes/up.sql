@@ -0,0 +1,42 @@
+-- Roster history. `roster_memberships` is the last accepted snapshot of the
+-- self-service roster, one row per (member, capability); each SIEM pass diffs
+-- the fresh roster against it and appends what moved to `roster_changes`.
+-- `member_key` is the lowercased email (people) or Azure object id (service
+-- principals) — the same id `actors::reconcile` gives the actor.
+CREATE TABLE IF NOT EXISTS roster_memberships (
+    member_key    text NOT NULL,
+    capability    text NOT NULL,
+    email         text,
+    display_name  text,
+    first_seen_at timestamptz NOT NULL,
+    last_seen_at  timestamptz NOT NULL,
+    PRIMARY KEY (member_key, capability)
+);
+
+CREATE TABLE IF NOT EXISTS roster_changes (
+    id           bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    member_key   text NOT NULL,
+    -- NULL = the roster as a whole: joined the company / left every capability.
+    capability   text,
+    change       text NOT NULL CHECK (change IN ('join', 'leave')),
+    email        text,
+    display_name text,
+    changed_at   timestamptz NOT NULL
+);
+CREATE INDEX IF NOT EXISTS roster_changes_member_idx ON roster_changes (member_key, changed_at DESC);
+
+-- People (and SPs) whose latest roster-wide change is a leave, resolved to the
+-- actor that now carries them (an override may have merged the key away).
+CREATE OR REPLACE VIEW roster_leavers AS
+    SELECT l.member_key, COALESCE(aa.actor_id, l.member_key) AS actor_id,
+           l.email, l.display_name, l.changed_at AS left_at
+    FROM (
+        SELECT DISTINCT ON (member_key) member_key, change, email, display_name, changed_at
+        FROM roster_changes
+        WHERE capability IS NULL
+        ORDER BY member_key, changed_at DESC, id DESC
+    ) l
+    LEFT JOIN LATERAL (
+        SELECT x.actor_id FROM actor_aliases x WHERE lower(x.alias) = l.member_key LIMIT 1
+    ) aa ON true
+    WHERE l.change = 'leave';
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS access_review_events;
+DROP TABLE IF EXISTS access_review_items;
+DROP TABLE IF EXISTS access_review_campaigns;
+DROP TABLE IF EXISTS team_leads;
@@ -0,0 +1,59 @@
+-- Access-review campaigns over `grants`. A campaign snapshots the grants in its
+-- scope into items (so the evidence survives re-derivation or revocation),
+-- each with an auto-assigned reviewer. Reviewers approve / revoke / comment per
+-- item; every action is also appended to `access_review_events` as the audit
+-- trail. Closing a fully decided campaign is the sign-off.
+
+-- Who reviews for a team when a grant's actor has no person owner.
+CREATE TABLE IF NOT EXISTS team_leads (
+    team       text PRIMARY KEY,
+    lead       text NOT NULL,
+    updated_by text NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS access_review_campaigns (
+    id          bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    name        text NOT NULL,
+    description text,
+    -- {system, privileged, team} as requested; null keys = unscoped.
+    scope       jsonb NOT NULL DEFAULT '{}',
+    status      text NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'closed')),
+    due_at      timestamptz,
+    created_by  text NOT NULL,
+    created_at  timestamptz NOT NULL DEFAULT now(),
+    closed_by   text,
+    closed_at   timestamptz
+);
+
+CREATE TABLE IF NOT EXISTS access_review_items (
+    id              bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    campaign_id     bigint NOT NULL REFERENCES access_review_campaigns (id) ON DELETE CASCADE,
+    grant_id        bigint REFERENCES grants (id) ON DELETE SET NULL,
+    grant_key       text NOT NULL,
+    actor_id        text,
+    team            text,
+    system          text NOT NULL,
+    role            text NOT NULL,
+    scope           text,
+    privileged      boolean NOT NULL,
+    granted_at      timestamptz,
+    reviewer        text,
+    reviewer_source text NOT NULL CHECK (reviewer_source IN ('owner', 'team_lead', 'manual', 'unassigned')),
+    decision        text CHECK (decision IN ('approve', 'revoke')),
+    comment         text,
+    decided_by      text,
+    decided_at      timestamptz,
+    UNIQUE (campaign_id, grant_key)
+);
+CREATE INDEX IF NOT EXISTS access_review_items_reviewer_idx ON access_review_items (reviewer, campaign_id);
+
+CREATE TABLE IF NOT EXISTS access_review_events (
+    id         bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    item_id    bigint NOT NULL REFERENCES access_review_items (id) ON DELETE CASCADE,
+    action     text NOT NULL CHECK (action IN ('approve', 'revoke', 'comment', 'assign')),
+    comment    text,
+    actor      text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS access_review_events_item_idx ON access_review_events (item_id, created_at);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS team_posture_history;
+DROP TABLE IF EXISTS team_posture;
@@ -0,0 +1,42 @@
+-- Per-team security posture, recomputed by every SIEM pass. A team is an
+-- actor's team-kind effective owner, else its roster team (`actors.team`), so
+-- service identities owned by a capability count towards it too.
+-- `open_alerts` counts open and acked alerts; `anomaly_rate` is the share of
+-- members with at least one anomaly inside the SIEM window.
+CREATE TABLE IF NOT EXISTS team_posture (
+    team                      text PRIMARY KEY,
+    members                   integer NOT NULL,
+    -- {"person": n, "service": n, ...}
+    members_by_kind           jsonb NOT NULL DEFAULT '{}',
+    risk_max                  integer NOT NULL DEFAULT 0,
+    risk_avg                  double precision NOT NULL DEFAULT 0,
+    -- {"critical": n, "high": n, "medium": n, "low": n}
+    risk_distribution         jsonb NOT NULL DEFAULT '{}',
+    open_alerts               integer NOT NULL DEFAULT 0,
+    open_alerts_by_severity   jsonb NOT NULL DEFAULT '{}',
+    privileged_grants         integer NOT NULL DEFAULT 0,
+    dormant                   integer NOT NULL DEFAULT 0,
+    anomalies                 integer NOT NULL DEFAULT 0,
+    anomaly_rate              double precision NOT NULL DEFAULT 0,
+    computed_at               timestamptz NOT NULL DEFAULT now()
+);
+
+-- One row per team per UTC day; the day's last pass wins.
+CREATE TABLE IF NOT EXISTS team_posture_history (
+    team                      text NOT NULL,
+    day                       date NOT NULL,
+    members                   integer NOT NULL,
+    members_by_kind           jsonb NOT NULL DEFAULT '{}',
+    risk_max                  integer NOT NULL DEFAULT 0,
+    risk_avg                  double precision NOT NULL DEFAULT 0,
+    risk_distribution         jsonb NOT NULL DEFAULT '{}',
+    open_alerts               integer NOT NULL DEFAULT 0,
+    open_alerts_by_severity   jsonb NOT NULL DEFAULT '{}',
+    privileged_grants         integer NOT NULL DEFAULT 0,
+    dormant                   integer NOT NULL DEFAULT 0,
+    anomalies                 integer NOT NULL DEFAULT 0,
+    anomaly_rate              double precision NOT NULL DEFAULT 0,
+    computed_at               timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (team, day)
+);
+CREATE INDEX IF NOT EXISTS team_posture_history_day_idx ON team_posture_history (day);
@@ -0,0 +1,4 @@
+DELETE FROM sessions WHERE session_key LIKE 'stitch|%';
+ALTER TABLE sessions
+    DROP COLUMN IF EXISTS chain,
+    DROP COLUMN IF EXISTS source_counts;
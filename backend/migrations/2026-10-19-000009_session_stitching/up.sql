@@ -0,0 +1,11 @@
+-- Sessions are stitched per person across every `ssumgmt_events` source.
+-- `source` becomes the source that opened the session; `source_counts` holds
+-- events per source ({"cloudtrail": n, "github": n, ...}) and `chain` the SSO
+-- logins with the role sessions they spawned.
+ALTER TABLE sessions
+    ADD COLUMN IF NOT EXISTS source_counts jsonb NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS chain         jsonb NOT NULL DEFAULT '[]';
+
+-- The old CloudTrail-only rows (principal|ip|day) are kept — some are
+-- flagged — and age out through retention; stitching starts after the last
+-- of them, so nothing is counted twice.
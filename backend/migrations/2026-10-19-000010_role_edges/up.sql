@@ -0,0 +1,34 @@
+-- The AWS role-assumption graph, derived each SIEM pass from successful
+-- `AssumeRole*` CloudTrail calls. One row per distinct hop: who (an actor, a
+-- role session chaining into another role, or an unresolved principal)
+-- assumed which role by which STS call; the identity provider and the actor
+-- behind the latest such call ride along. `first_seen` survives re-derivation, so a privileged edge
+-- whose `first_seen` is recent is a newly observed path into that role.
+CREATE TABLE IF NOT EXISTS role_edges (
+    id               bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    -- src_kind|src|dst_role|via
+    edge_key         text NOT NULL UNIQUE,
+    -- 'actor' | 'role' | 'principal'
+    src_kind         text NOT NULL,
+    -- actor id, caller role ARN, or the raw principal when no alias matched
+    src              text NOT NULL,
+    dst_role         text NOT NULL,
+    dst_account      text,
+    src_account      text,
+    -- AssumeRole | AssumeRoleWithSAML | AssumeRoleWithWebIdentity
+    via              text NOT NULL,
+    -- 'saml:<provider>' for SAML federation, else the caller's identity source
+    identity_source  text,
+    -- the actor the latest hop was made by or on behalf of, when resolved
+    actor_id         text,
+    privileged       boolean NOT NULL DEFAULT false,
+    first_seen       timestamptz NOT NULL,
+    last_seen        timestamptz NOT NULL,
+    -- successful calls inside the SIEM window as of the last pass
+    event_count      bigint NOT NULL DEFAULT 0,
+    updated_at       timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_role_edges_actor ON role_edges (actor_id);
+CREATE INDEX IF NOT EXISTS idx_role_edges_dst ON role_edges (dst_account, dst_role);
+CREATE INDEX IF NOT EXISTS idx_role_edges_first_seen ON role_edges (first_seen) WHERE privileged;
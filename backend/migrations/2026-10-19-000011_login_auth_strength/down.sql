@@ -0,0 +1,6 @@
+ALTER TABLE sessions
+    DROP COLUMN IF EXISTS mfa_used,
+    DROP COLUMN IF EXISTS auth_method;
+ALTER TABLE cloudtrail_events
+    DROP COLUMN IF EXISTS mfa_used,
+    DROP COLUMN IF EXISTS auth_method;
@@ -0,0 +1,31 @@
+-- Authentication strength of console sign-ins, extracted at ingest by
+-- `cloudtrail::login_auth`. `auth_method` is 'password' (IAM user or root),
+-- 'sso' (IAM Identity Center) or 'federated' (any other SAML/OIDC sign-in).
+-- `mfa_used` is NULL when CloudTrail cannot tell: a federated sign-in only
+-- proves MFA when its session is flagged `mfaAuthenticated`, since MFA done at
+-- the IdP is invisible to AWS (the event's own `MFAUsed` is always "No").
+ALTER TABLE cloudtrail_events
+    ADD COLUMN IF NOT EXISTS auth_method text,
+    ADD COLUMN IF NOT EXISTS mfa_used    boolean;
+
+-- Existing sign-ins, by the same rules as the mapper.
+UPDATE cloudtrail_events SET
+    auth_method = CASE
+        WHEN raw #>> '{userIdentity,type}' IN ('IAMUser', 'Root') THEN 'password'
+        WHEN raw #>> '{userIdentity,sessionContext,sessionIssuer,userName}' LIKE 'AWSReservedSSO\_%' THEN 'sso'
+        WHEN raw #>> '{userIdentity,type}' IN ('AssumedRole', 'FederatedUser', 'SAMLUser', 'WebIdentityUser') THEN 'federated'
+    END,
+    mfa_used = CASE
+        WHEN raw #>> '{userIdentity,type}' IN ('IAMUser', 'Root')
+            THEN (raw #>> '{additionalEventData,MFAUsed}') = 'Yes'
+        WHEN raw #>> '{userIdentity,sessionContext,attributes,mfaAuthenticated}' = 'true' THEN true
+    END
+WHERE event_name = 'ConsoleLogin';
+
+-- A session carries its first sign-in's method and the weakest MFA outcome of
+-- all its sign-ins (any FALSE wins over TRUE, NULL when none told). Rewind the
+-- watermark so the first pass rebuilds the last week with them.
+ALTER TABLE sessions
+    ADD COLUMN IF NOT EXISTS auth_method text,
+    ADD COLUMN IF NOT EXISTS mfa_used    boolean;
+UPDATE ingest_watermarks SET last_event_at = now() - interval '7 days' WHERE source = 'siem_sessions';
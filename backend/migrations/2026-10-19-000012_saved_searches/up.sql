@@ -0,0 +1,25 @@
+-- Saved searches: a named `query_ast` tree, private to its owner or shared with
+-- every console user. A non-null `every_mins` promotes one to a scheduled
+-- search that the worker leader evaluates over successive ingest-time slices
+-- (`last_run_to` is the exclusive start of the next slice), raising an alert
+-- with rule_id `search:<id>` when a slice matches more than `threshold` events.
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id               bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    name             text NOT NULL,
+    description      text,
+    owner            text NOT NULL,
+    ast              jsonb NOT NULL,
+    visibility       text NOT NULL DEFAULT 'private' CHECK (visibility IN ('private', 'shared')),
+    every_mins       integer CHECK (every_mins BETWEEN 1 AND 1440),
+    threshold        bigint NOT NULL DEFAULT 0 CHECK (threshold >= 0),
+    severity         text NOT NULL DEFAULT 'medium' CHECK (severity IN ('low', 'medium', 'high', 'critical')),
+    last_run_at      timestamptz,
+    last_run_to      timestamptz,
+    last_match_count bigint,
+    last_error       text,
+    created_at       timestamptz NOT NULL DEFAULT now(),
+    updated_at       timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS saved_searches_owner_idx ON saved_searches (owner);
+CREATE INDEX IF NOT EXISTS saved_searches_scheduled_idx ON saved_searches (last_run_at)
+    WHERE every_mins IS NOT NULL;
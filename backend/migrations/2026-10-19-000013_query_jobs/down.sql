@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS query_job_rows;
+DROP TABLE IF EXISTS query_jobs;
@@ -0,0 +1,34 @@
+-- Async query jobs: a `query_ast` filter run off the request path on the
+-- API's dedicated jobs pool, its result rows materialised into
+-- `query_job_rows` batch by batch so they can be paged while the job is still
+-- running. `rows_done` is the progress counter; `cancel_requested` is how a
+-- cancel reaches the replica that runs the job (which also gets a
+-- `pg_cancel_backend` on its reader). Jobs and their rows go once
+-- `expires_at` passes.
+CREATE TABLE IF NOT EXISTS query_jobs (
+    id               text PRIMARY KEY,
+    owner            text NOT NULL,
+    spec             jsonb NOT NULL,
+    status           text NOT NULL DEFAULT 'queued'
+                     CHECK (status IN ('queued', 'running', 'done', 'failed', 'cancelled')),
+    cancel_requested boolean NOT NULL DEFAULT false,
+    rows_done        bigint NOT NULL DEFAULT 0,
+    truncated        boolean NOT NULL DEFAULT false,
+    error            text,
+    created_at       timestamptz NOT NULL DEFAULT now(),
+    started_at       timestamptz,
+    finished_at      timestamptz,
+    updated_at       timestamptz NOT NULL DEFAULT now(),
+    expires_at       timestamptz NOT NULL
+);
+CREATE INDEX IF NOT EXISTS query_jobs_owner_idx ON query_jobs (owner, created_at DESC);
+CREATE INDEX IF NOT EXISTS query_jobs_active_idx ON query_jobs (updated_at)
+    WHERE status IN ('queued', 'running');
+CREATE INDEX IF NOT EXISTS query_jobs_expires_idx ON query_jobs (expires_at);
+
+CREATE TABLE IF NOT EXISTS query_job_rows (
+    job_id text NOT NULL REFERENCES query_jobs (id) ON DELETE CASCADE,
+    seq    bigint NOT NULL,
+    data   jsonb NOT NULL,
+    PRIMARY KEY (job_id, seq)
+);
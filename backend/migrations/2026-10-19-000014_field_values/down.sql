@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS field_value_daily;
+DELETE FROM ingest_watermarks WHERE source = 'field_values';
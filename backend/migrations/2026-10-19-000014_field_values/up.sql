@@ -0,0 +1,18 @@
+-- Value dictionary behind `/api/query/facets`: per-day counts of each value of
+-- the events' text fields (and the configured `json.` payload paths), split by
+-- the `source`/`status` facets so those and a time range can be answered
+-- without scanning the event tables. Folded forward by event time on the
+-- worker leader (watermark `field_values`); values are cut to 256 characters.
+CREATE TABLE IF NOT EXISTS field_value_daily (
+    field  text   NOT NULL,
+    source text   NOT NULL,
+    status text   NOT NULL,
+    value  text   NOT NULL,
+    day    date   NOT NULL,
+    count  bigint NOT NULL,
+    PRIMARY KEY (field, value, day, source, status)
+);
+-- Prefix autocomplete: `lower(value) LIKE 'pre%'` within one field.
+CREATE INDEX IF NOT EXISTS field_value_daily_prefix_idx
+    ON field_value_daily (field, lower(value) text_pattern_ops);
+CREATE INDEX IF NOT EXISTS field_value_daily_day_idx ON field_value_daily (day);
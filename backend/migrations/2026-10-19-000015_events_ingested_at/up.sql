@@ -0,0 +1,80 @@
+-- Live tail (`/api/tail/ws`) follows `ssumgmt_events` in commit order rather
+-- than event time: CloudTrail and GitHub deliver events minutes to hours after
+-- they happen, so "new since the last poll" must mean "ingested since". Append
+-- each branch's `created_at` as `ingested_at`. Appending a column keeps
+-- CREATE OR REPLACE valid and the existing column set untouched, so
+-- `src/db/views.rs` (which lists only what Diesel reads) is unchanged.
+CREATE INDEX IF NOT EXISTS idx_github_created_at ON github_audit_events (created_at);
+
+CREATE OR REPLACE VIEW ssumgmt_events AS
+    SELECT
+        'selfservice'::text             AS source,
+        s.id::text                      AS uid,
+        s.timestamp AT TIME ZONE 'UTC'  AS ts,
+        s.principal                     AS actor,
+        s.action                        AS action,
+        s.service                       AS resource,
+        NULL::text                      AS source_ip,
+        'info'::text                    AS level,
+        'success'::text                 AS status,
+        s.request_data                  AS raw,
+        NULL::text                      AS role,
+        NULL::text                      AS identity_source,
+        NULL::text                      AS account_id,
+        NULL::text                      AS caller_account_id,
+        s.created_at AT TIME ZONE 'UTC' AS ingested_at
+    FROM audit_records_selfservice s
+    UNION ALL
+    SELECT
+        'cloudtrail'::text,
+        c.event_id,
+        c.event_time,
+        COALESCE(c.principal_name, c.principal_arn),
+        c.event_name,
+        c.event_source,
+        c.source_ip,
+        CASE WHEN c.error_code IS NOT NULL THEN 'error'   ELSE 'info'    END,
+        CASE WHEN c.error_code IS NOT NULL THEN 'failure' ELSE 'success' END,
+        c.raw,
+        c.assumed_role_arn,
+        c.identity_source,
+        c.recipient_account_id,
+        c.user_identity_account_id,
+        c.created_at
+    FROM cloudtrail_events c
+    UNION ALL
+    SELECT
+        'github'::text,
+        g.document_id,
+        g.event_time,
+        g.actor,
+        g.action,
+        COALESCE(g.repo, g.org),
+        g.source_ip,
+        'info'::text,
+        'success'::text,
+        g.raw,
+        NULL::text,
+        NULL::text,
+        NULL::text,
+        NULL::text,
+        g.created_at
+    FROM github_audit_events g
+    UNION ALL
+    SELECT
+        'ssu-mgmt'::text,
+        a.id::text,
+        a.ts,
+        a.actor,
+        a.action,
+        a.path,
+        a.source_ip,
+        a.level,
+        a.status,
+        a.request_data,
+        a.role,
+        NULL::text,
+        NULL::text,
+        NULL::text,
+        a.created_at
+    FROM ssumgmt_audit a;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS resource_touches;
+DROP TABLE IF EXISTS resources;
+DELETE FROM ingest_watermarks WHERE source = 'resources';
@@ -0,0 +1,35 @@
+-- Resource entities: what events touched, as opposed to who did them. Ids are
+-- `<kind>:<name>`:
+--   bucket:<name>              S3 requestParameters.bucketName / AWS::S3::Bucket resources
+--   role:<account>/<name>      IAM requestParameters.roleName|roleArn / AWS::IAM::Role resources
+--   account:<id>               CloudTrail recipient account
+--   repo:<org/repo>, org:<org> GitHub audit events
+--   path:<path>                self-service request paths
+-- Folded forward by ingest time (`created_at`) on the worker leader
+-- (`service::resources`, watermark `resources`).
+CREATE TABLE IF NOT EXISTS resources (
+    id         text        PRIMARY KEY,
+    kind       text        NOT NULL,
+    name       text        NOT NULL,
+    first_seen timestamptz NOT NULL,
+    last_seen  timestamptz NOT NULL,
+    events     bigint      NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS resources_kind_idx ON resources (kind, last_seen DESC);
+CREATE INDEX IF NOT EXISTS resources_name_prefix_idx ON resources (lower(name) text_pattern_ops);
+
+-- One row per (resource, event). `actor` is the raw event actor; resolve it
+-- through `actor_aliases` like the event view. Pruned with the other derived
+-- tables (`retention.derived_days`).
+CREATE TABLE IF NOT EXISTS resource_touches (
+    resource_id text        NOT NULL,
+    source      text        NOT NULL,
+    uid         text        NOT NULL,
+    ts          timestamptz NOT NULL,
+    actor       text,
+    action      text        NOT NULL,
+    status      text        NOT NULL,
+    PRIMARY KEY (resource_id, source, uid)
+);
+CREATE INDEX IF NOT EXISTS resource_touches_ts_idx ON resource_touches (resource_id, ts DESC);
+CREATE INDEX IF NOT EXISTS resource_touches_prune_idx ON resource_touches (ts);
@@ -0,0 +1,12 @@
+-- A roster diff that would drop more than `siem.roster_max_leave_pct` of the
+-- snapshot is held here instead of being applied, with a `roster_suspect_diff`
+-- alert raised. The same leaver set on `siem.roster_confirm_fetches` fetches
+-- in a row, or an analyst acking that alert, accepts it. Single row.
+CREATE TABLE IF NOT EXISTS roster_pending (
+    id            boolean PRIMARY KEY DEFAULT true CHECK (id),
+    leavers       text[] NOT NULL,
+    members       integer NOT NULL,
+    seen          integer NOT NULL,
+    first_seen_at timestamptz NOT NULL,
+    last_seen_at  timestamptz NOT NULL
+);
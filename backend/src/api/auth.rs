@@ -33,6 +33,15 @@ pub fn principal_of(claims: Option<&Value>) -> String {
     "unknown".to_string()
 }
 
+/// Whether the claims carry `role` in the standard AAD `roles` array claim.
+pub fn has_role(claims: Option<&Value>, role: &str) -> bool {
+    claims
+        .and_then(|c| c.get("roles"))
+        .and_then(|r| r.as_array())
+        .map(|arr| arr.iter().any(|v| v.as_str() == Some(role)))
+        .unwrap_or(false)
+}
+
 pub async fn auth_oauth(
     State(state): State<WebSharedState>,
     OriginalUri(uri): OriginalUri,
@@ -44,10 +53,11 @@ pub async fn auth_oauth(
         return next.run(request).await;
     }
 
-    // The progress WebSocket can't send an Authorization header (browsers don't
-    // allow custom headers on `new WebSocket()`); it carries its bearer token in
-    // the WS subprotocol instead and authenticates inside its own handler.
-    if uri.path() == "/api/progress/ws" {
+    // The progress and live-tail WebSockets can't send an Authorization header
+    // (browsers don't allow custom headers on `new WebSocket()`); they carry
+    // their bearer token in the WS subprotocol instead and authenticate inside
+    // their own handlers.
+    if matches!(uri.path(), "/api/progress/ws" | "/api/tail/ws") {
         return next.run(request).await;
     }
 
@@ -99,15 +109,7 @@ pub async fn role_check(
         return next.run(req).await;
     }
 
-    let allowed = req
-        .extensions()
-        .get::<Value>()
-        .and_then(|c| c.get("roles"))
-        .and_then(|r| r.as_array())
-        .map(|arr| arr.iter().any(|v| v.as_str() == Some(role)))
-        .unwrap_or(false);
-
-    if allowed {
+    if has_role(req.extensions().get::<Value>(), role) {
         next.run(req).await
     } else {
         StatusCode::FORBIDDEN.into_response()
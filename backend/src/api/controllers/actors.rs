@@ -1,14 +1,19 @@
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
-use axum::{Json, Router};
+use axum::{Extension, Json, Router};
 use axum_extra::extract::Query;
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
+use crate::api::auth::principal_of;
 use crate::db::DbPool;
+use crate::service::siem::overrides::{self, ActorOverride, CreateOutcome, NewOverride};
+use crate::service::siem::ownership;
+use crate::service::siem::roster::{self, DepartedRow};
 
 const DEFAULT_LIMIT: i64 = 50;
 const MAX_LIMIT: i64 = 200;
@@ -16,6 +21,19 @@ const MAX_LIMIT: i64 = 200;
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
         .route("/", axum::routing::get(actors_handler))
+        .route("/departed", axum::routing::get(departed_handler))
+        .route(
+            "/overrides",
+            axum::routing::get(list_overrides_handler).post(create_override_handler),
+        )
+        .route(
+            "/overrides/:id",
+            axum::routing::delete(delete_override_handler),
+        )
+        .route(
+            "/:id/owner",
+            axum::routing::put(assign_owner_handler).delete(unassign_owner_handler),
+        )
         .with_state(pool)
 }
 
@@ -27,6 +45,8 @@ pub struct ActorsParams {
     pub kind: Option<String>,
     /// One origin taxonomy value (kubernetes|azure-ad|aws|github|selfservice|unknown).
     pub origin: Option<String>,
+    /// Exact effective owner; `none` → actors nobody owns.
+    pub owner: Option<String>,
     /// `risk` (default) | `recent` | `name`.
     pub sort: Option<String>,
     /// `asc` | `desc`. Omitted → each sort key's natural default.
@@ -59,6 +79,13 @@ struct ActorListRow {
     first_seen: Option<DateTime<Utc>>,
     #[diesel(sql_type = Nullable<Timestamptz>)]
     last_active: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<Text>)]
+    owner: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    owner_kind: Option<String>,
+    /// Which claim won: manual|aws_tags|k8s_namespace|github|roster.
+    #[diesel(sql_type = Nullable<Text>)]
+    owner_source: Option<String>,
 }
 
 #[derive(QueryableByName)]
@@ -81,6 +108,7 @@ async fn actors_handler(
     let offset = params.offset.unwrap_or(0).max(0);
     let kind = params.kind.unwrap_or_default();
     let origin = params.origin.unwrap_or_default();
+    let owner = params.owner.unwrap_or_default();
     // ILIKE pattern; empty `q` → `%%` matches everything (the `$N = '' OR …` guard
     // would also work, but keeping the bind uniform is simpler).
     let q_pat = format!("%{}%", params.q.unwrap_or_default());
@@ -100,18 +128,22 @@ async fn actors_handler(
 
     let where_clause = "WHERE ($1 = '' OR a.kind = $1) \
          AND ($2 = '' OR $2 = ANY(a.origins)) \
-         AND ($3 = '%%' OR a.id ILIKE $3 OR a.email ILIKE $3 OR a.display_name ILIKE $3 OR a.team ILIKE $3)";
+         AND ($3 = '%%' OR a.id ILIKE $3 OR a.email ILIKE $3 OR a.display_name ILIKE $3 OR a.team ILIKE $3) \
+         AND ($4 = '' OR ($4 = 'none' AND o.owner IS NULL) OR o.owner = $4)";
 
     let rows_sql = format!(
         "SELECT a.id, a.display_name, a.email, a.team, a.kind, a.origins, a.sources, \
-                r.score, r.label, a.first_seen, a.last_active \
+                r.score, r.label, a.first_seen, a.last_active, \
+                o.owner, o.owner_kind, o.source AS owner_source \
          FROM actors a LEFT JOIN risk_scores r ON r.actor_id = a.id \
+         LEFT JOIN actor_owner_effective o ON o.actor_id = a.id \
          {where_clause} \
-         ORDER BY {order} LIMIT $4 OFFSET $5"
+         ORDER BY {order} LIMIT $5 OFFSET $6"
     );
     let count_sql = format!(
         "SELECT count(*)::bigint AS total \
          FROM actors a LEFT JOIN risk_scores r ON r.actor_id = a.id \
+         LEFT JOIN actor_owner_effective o ON o.actor_id = a.id \
          {where_clause}"
     );
 
@@ -121,6 +153,7 @@ async fn actors_handler(
             .bind::<Text, _>(&kind)
             .bind::<Text, _>(&origin)
             .bind::<Text, _>(&q_pat)
+            .bind::<Text, _>(&owner)
             .bind::<BigInt, _>(limit)
             .bind::<BigInt, _>(offset)
             .load(&mut conn)?;
@@ -128,6 +161,7 @@ async fn actors_handler(
             .bind::<Text, _>(&kind)
             .bind::<Text, _>(&origin)
             .bind::<Text, _>(&q_pat)
+            .bind::<Text, _>(&owner)
             .get_result::<CountRow>(&mut conn)?
             .total;
         Ok(ActorsPage { rows, total })
@@ -148,3 +182,242 @@ async fn actors_handler(
             .into_response(),
     }
 }
+
+// --- Departed identities ---------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct DepartedParams {
+    /// Also list leavers that hold no grants or GitHub access anymore.
+    pub all: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+/// Everyone whose latest roster change is a leave, with the access they still
+/// hold (unrevoked grants, GitHub memberships) and their last activity.
+async fn departed_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<DepartedParams>,
+) -> Response {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "actors.departed"
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<DepartedRow>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        roster::departed(&mut conn, params.all.unwrap_or(false), limit)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+// --- Identity overrides ----------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct OverridesParams {
+    /// Only overrides naming this actor id or one of its aliases.
+    pub actor: Option<String>,
+}
+
+async fn list_overrides_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<OverridesParams>,
+) -> Response {
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "actors.overrides.list"
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<ActorOverride>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        overrides::list(&mut conn, params.actor.as_deref())
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Record an analyst override. Applied by the next SIEM pass, not immediately.
+async fn create_override_handler(
+    State(pool): State<DbPool>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<NewOverride>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "actors.overrides.create",
+        kind = body.kind.as_str()
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<CreateOutcome> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        overrides::create(&mut conn, body, &who)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(CreateOutcome::Created(row))) => (StatusCode::CREATED, Json(row)).into_response(),
+        Ok(Ok(CreateOutcome::Rejected(msg))) => (StatusCode::BAD_REQUEST, msg).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn delete_override_handler(State(pool): State<DbPool>, Path(id): Path<i64>) -> Response {
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "actors.overrides.delete",
+        override_id = id
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        overrides::delete(&mut conn, id)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => (StatusCode::NOT_FOUND, "override not found").into_response(),
+        Ok(Ok(_)) => Json(json!({ "ok": true })).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+// --- Ownership ---------------------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct AssignOwner {
+    pub owner: String,
+    /// `team` (default) | `person`.
+    pub owner_kind: Option<String>,
+    pub reason: String,
+}
+
+/// Manually assign an actor's owner; beats every derived source. Alerts pick
+/// it up on the next SIEM pass.
+async fn assign_owner_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<String>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<AssignOwner>,
+) -> Response {
+    let owner = body.owner.trim().to_string();
+    let reason = body.reason.trim().to_string();
+    let owner_kind = body.owner_kind.unwrap_or_else(|| "team".to_string());
+    if owner.is_empty() || reason.is_empty() {
+        return (StatusCode::BAD_REQUEST, "owner and reason are required").into_response();
+    }
+    if owner_kind != "team" && owner_kind != "person" {
+        return (StatusCode::BAD_REQUEST, "owner_kind must be team or person").into_response();
+    }
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "actors.owner.assign"
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        ownership::assign(&mut conn, &id, &owner, &owner_kind, &reason, &who)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => (StatusCode::NOT_FOUND, "actor not found").into_response(),
+        Ok(Ok(_)) => Json(json!({ "ok": true })).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn unassign_owner_handler(State(pool): State<DbPool>, Path(id): Path<String>) -> Response {
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "actors.owner.unassign"
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        ownership::unassign(&mut conn, &id)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => (StatusCode::NOT_FOUND, "no manual owner").into_response(),
+        Ok(Ok(_)) => Json(json!({ "ok": true })).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -1,8 +1,12 @@
 //! Entity / Inspect API (plan §7). `GET /entity/{id}` fans an actor's identity,
-//! risk (with the explainable `components` breakdown), stats, sessions, grants,
-//! and recent activity into one payload; `GET /entity/{id}/timeline` returns that
-//! actor's per-source activity buckets. Events are attributed to the canonical
+//! risk (with the explainable `components` breakdown), stats, sessions (each with
+//! any live threat-intel indicator on its IP), threat-intel hits, grants, and
+//! recent activity into one payload; `GET /entity/{id}/timeline` returns that
+//! actor's per-source activity buckets and `GET /entity/{id}/risk-history` its
+//! risk trajectory. Events are attributed to the canonical
 //! actor via `actor_aliases`, so any source's raw identifier resolves.
+//! Resource entities (`/entity/resource/{id}`) live in `entity_resource`, IP
+//! entities (`/entity/ip/{ip}`) in `entity_ip`.
 
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
@@ -11,18 +15,41 @@ use axum::{Json, Router};
 use axum_extra::extract::Query;
 use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
-use diesel::sql_types::{BigInt, Text, Timestamptz};
+use diesel::sql_types::{Array, BigInt, Integer, Nullable, Text, Timestamptz};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+use super::{entity_ip, entity_resource};
 use crate::db::model::{Actor, Anomaly, Grant, RiskScore, Session, SsuMgmtEvent};
 use crate::db::DbPool;
+use crate::service::outcome::Outcome;
 
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
+        .route(
+            "/resources",
+            axum::routing::get(entity_resource::list_handler),
+        )
+        .route(
+            "/resource/:id",
+            axum::routing::get(entity_resource::inspect_handler),
+        )
+        .route(
+            "/resource/:id/activity",
+            axum::routing::get(entity_resource::activity_handler),
+        )
+        .route(
+            "/resource/:id/timeline",
+            axum::routing::get(entity_resource::timeline_handler),
+        )
+        .route("/ip/:ip", axum::routing::get(entity_ip::inspect_handler))
         .route("/:id", axum::routing::get(entity_handler))
         .route("/:id/activity", axum::routing::get(activity_handler))
         .route("/:id/timeline", axum::routing::get(timeline_handler))
+        .route(
+            "/:id/risk-history",
+            axum::routing::get(risk_history_handler),
+        )
         .with_state(pool)
 }
 
@@ -56,6 +83,52 @@ struct IdentityContextRow {
     assumed_role_arn: Option<String>,
 }
 
+/// A live indicator covering one of the bundle's session IPs.
+#[derive(QueryableByName, Serialize)]
+struct SessionIntelRow {
+    #[diesel(sql_type = BigInt)]
+    #[serde(skip)]
+    session_id: i64,
+    #[diesel(sql_type = Text)]
+    feed: String,
+    #[diesel(sql_type = Text)]
+    indicator: String,
+    #[diesel(sql_type = Integer)]
+    confidence: i32,
+    #[diesel(sql_type = Nullable<Text>)]
+    description: Option<String>,
+    #[diesel(sql_type = Timestamptz)]
+    expires_at: DateTime<Utc>,
+}
+
+/// The actor's threat-intel hits, one row per indicator. `active` is false once
+/// the indicator has expired or its feed was dropped; the hits stay on record.
+#[derive(QueryableByName, Serialize)]
+struct IntelHitRow {
+    #[diesel(sql_type = Text)]
+    feed: String,
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = Text)]
+    indicator: String,
+    #[diesel(sql_type = Integer)]
+    confidence: i32,
+    #[diesel(sql_type = Nullable<Text>)]
+    description: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    hits: i64,
+    #[diesel(sql_type = Array<Text>)]
+    sources: Vec<String>,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    expires_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    active: bool,
+}
+
 async fn entity_handler(State(pool): State<DbPool>, Path(id): Path<String>) -> Response {
     let span = tracing::info_span!(
         "db.query",
@@ -107,6 +180,44 @@ async fn entity_handler(State(pool): State<DbPool>, Path(id): Path<String>) -> R
                 .load(&mut conn)
         })?;
 
+        let session_ids: Vec<i64> = sessions.iter().map(|s| s.id).collect();
+        let session_intel_sql =
+            "SELECT s.id AS session_id, i.feed, i.value AS indicator, i.confidence, i.description, i.expires_at \
+             FROM sessions s JOIN threat_indicators i \
+               ON i.kind = 'ip' AND i.expires_at > now() \
+              AND s.source_ip ~ '^[0-9A-Fa-f:.]+$' AND i.network >>= try_inet(s.source_ip) \
+             WHERE s.id = ANY($1) ORDER BY i.confidence DESC";
+        let session_intel: Vec<SessionIntelRow> = q("entity.session_intel", session_intel_sql).in_scope(|| {
+            diesel::sql_query(session_intel_sql)
+                .bind::<Array<BigInt>, _>(&session_ids)
+                .load(&mut conn)
+        })?;
+        let sessions: Vec<serde_json::Value> = sessions
+            .into_iter()
+            .map(|s| {
+                let intel: Vec<&SessionIntelRow> =
+                    session_intel.iter().filter(|r| r.session_id == s.id).collect();
+                let mut v = serde_json::to_value(&s).unwrap_or_default();
+                if let Some(obj) = v.as_object_mut() {
+                    obj.insert("threat_intel".into(), json!(intel));
+                }
+                v
+            })
+            .collect();
+
+        let intel_sql =
+            "SELECT h.feed, h.kind, h.indicator, max(h.confidence) AS confidence, i.description, \
+                    count(*) AS hits, array_agg(DISTINCT h.source) AS sources, \
+                    min(h.event_time) AS first_seen, max(h.event_time) AS last_seen, \
+                    i.expires_at, COALESCE(i.expires_at > now(), false) AS active \
+             FROM threat_intel_hits h \
+             LEFT JOIN threat_indicators i ON i.feed = h.feed AND i.kind = h.kind AND i.value = h.indicator \
+             WHERE h.actor_id = $1 \
+             GROUP BY h.feed, h.kind, h.indicator, i.description, i.expires_at \
+             ORDER BY last_seen DESC LIMIT 50";
+        let intel_hits: Vec<IntelHitRow> = q("entity.threat_intel", intel_sql)
+            .in_scope(|| diesel::sql_query(intel_sql).bind::<Text, _>(&id).load(&mut conn))?;
+
         let grants: Vec<Grant> = q(
             "entity.grants",
             "SELECT * FROM grants WHERE actor_id = $1 ORDER BY granted_at DESC NULLS LAST LIMIT 100",
@@ -192,9 +303,30 @@ async fn entity_handler(State(pool): State<DbPool>, Path(id): Path<String>) -> R
             }
         }
 
+        // Claims in precedence order; the first one is the effective owner.
+        let owners = tracing::info_span!(
+            "db.query",
+            otel.kind = "client",
+            db.system = "postgresql",
+            op = "entity.owners",
+            entity.id = %id
+        )
+        .in_scope(|| crate::service::siem::ownership::claims(&mut conn, &id))?;
+        let roster_changes = tracing::info_span!(
+            "db.query",
+            otel.kind = "client",
+            db.system = "postgresql",
+            op = "entity.roster_changes",
+            entity.id = %id
+        )
+        .in_scope(|| crate::service::siem::roster::changes_of(&mut conn, &id, 50))?;
+
         Ok(Some(json!({
             "identity": actor,
             "risk": risk,
+            "owner": owners.first(),
+            "owners": owners,
+            "roster_changes": roster_changes,
             "stats": {
                 "events_24h": stats.events_24h,
                 "events_7d": stats.events_7d,
@@ -207,6 +339,7 @@ async fn entity_handler(State(pool): State<DbPool>, Path(id): Path<String>) -> R
                 "roles": assumed_roles,
             },
             "sessions": sessions,
+            "threat_intel": intel_hits,
             "grants": grants,
             "anomalies": anomalies,
             "activity": activity,
@@ -312,7 +445,7 @@ pub struct TimelineParams {
 }
 
 #[derive(QueryableByName, Serialize)]
-struct TimelineRow {
+pub(super) struct TimelineRow {
     #[diesel(sql_type = Timestamptz)]
     bucket: DateTime<Utc>,
     #[diesel(sql_type = Text)]
@@ -321,7 +454,51 @@ struct TimelineRow {
     count: i64,
 }
 
-fn parse_ts(s: &str) -> Result<DateTime<Utc>, String> {
+/// 500 bodies shared by the IP and resource entity routes.
+pub(super) fn db_error(e: impl std::fmt::Display) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("db error: {}", e),
+    )
+        .into_response()
+}
+
+pub(super) fn join_error(e: tokio::task::JoinError) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("task join error: {}", e),
+    )
+        .into_response()
+}
+
+pub(super) fn db_span(op: &'static str) -> tracing::Span {
+    tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = op
+    )
+}
+
+/// Answer a blocking service call that returned an [`Outcome`], with `ok` as
+/// the status of `Done`.
+pub(super) fn outcome_response<T: Serialize>(
+    res: Result<anyhow::Result<Outcome<T>>, tokio::task::JoinError>,
+    ok: StatusCode,
+) -> Response {
+    match res {
+        Ok(Ok(Outcome::Done(v))) => (ok, Json(v)).into_response(),
+        Ok(Ok(Outcome::NotFound)) => (StatusCode::NOT_FOUND, "not found").into_response(),
+        Ok(Ok(Outcome::Forbidden(msg))) => (StatusCode::FORBIDDEN, msg).into_response(),
+        Ok(Ok(Outcome::Rejected(msg))) => (StatusCode::BAD_REQUEST, msg).into_response(),
+        Ok(Ok(Outcome::Conflict(msg))) => (StatusCode::CONFLICT, msg).into_response(),
+        Ok(Ok(Outcome::Busy(msg))) => (StatusCode::TOO_MANY_REQUESTS, msg).into_response(),
+        Ok(Err(e)) => db_error(format!("{:#}", e)),
+        Err(e) => join_error(e),
+    }
+}
+
+pub(super) fn parse_ts(s: &str) -> Result<DateTime<Utc>, String> {
     DateTime::parse_from_rfc3339(s)
         .map(|dt| dt.with_timezone(&Utc))
         .or_else(|_| {
@@ -395,3 +572,92 @@ async fn timeline_handler(
             .into_response(),
     }
 }
+
+#[derive(Deserialize)]
+pub struct RiskHistoryParams {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Include each point's `components` breakdown (default false; it is the
+    /// bulk of the payload).
+    pub components: Option<bool>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct RiskHistoryRow {
+    #[diesel(sql_type = Timestamptz)]
+    bucket: DateTime<Utc>,
+    #[diesel(sql_type = Text)]
+    resolution: String,
+    #[diesel(sql_type = Integer)]
+    score: i32,
+    #[diesel(sql_type = Integer)]
+    min_score: i32,
+    #[diesel(sql_type = Integer)]
+    max_score: i32,
+    #[diesel(sql_type = Text)]
+    label: String,
+    #[diesel(sql_type = Integer)]
+    samples: i32,
+    #[diesel(sql_type = Nullable<diesel::sql_types::Jsonb>)]
+    components: Option<serde_json::Value>,
+}
+
+/// Risk trajectory for one actor, oldest first. Points come at whatever
+/// resolution compaction has left them (`raw`, `hour` or `day`), so recent
+/// history is dense and older history coarse.
+async fn risk_history_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<String>,
+    Query(params): Query<RiskHistoryParams>,
+) -> Response {
+    let now = Utc::now();
+    let from = match params.from.as_deref().map(parse_ts).transpose() {
+        Ok(v) => v.unwrap_or(now - Duration::days(30)),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let to = match params.to.as_deref().map(parse_ts).transpose() {
+        Ok(v) => v.unwrap_or(now),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let with_components = params.components.unwrap_or(false);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "entity.risk_history",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<RiskHistoryRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let history_sql =
+            "SELECT bucket, resolution, score, min_score, max_score, label, samples, \
+               CASE WHEN $4 THEN components END AS components \
+             FROM risk_score_history \
+             WHERE actor_id = $1 AND bucket >= $2 AND bucket <= $3 \
+             ORDER BY bucket ASC";
+        span.record("db.statement", history_sql);
+        diesel::sql_query(history_sql)
+            .bind::<Text, _>(id)
+            .bind::<Timestamptz, _>(from)
+            .bind::<Timestamptz, _>(to)
+            .bind::<diesel::sql_types::Bool, _>(with_components)
+            .load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
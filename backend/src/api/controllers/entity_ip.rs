@@ -0,0 +1,367 @@
+//! IP entities: `GET /entity/ip/{ip}` pivots on a source address, or a CIDR
+//! range (URL-encoded, e.g. `10.0.0.0%2F8`), instead of an actor. One payload
+//! carries the addresses seen with their GeoIP/ASN enrichment, first/last
+//! seen, per-source counts, the actors that used them, sessions and alerts
+//! referencing them, and the shared-IP peers the entity graph draws.
+//!
+//! `source_ip` is free text, so a range goes through `try_inet` like the
+//! threat-intel joins and runs under a statement timeout; a single address is
+//! a plain equality.
+
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Nullable, Text, Timestamptz};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::entity::{db_error, join_error};
+use super::graph::PeerRow;
+use crate::db::model::{Alert, Session};
+use crate::db::DbPool;
+use crate::misc::config::load_conf;
+use crate::service::siem::geoip::GeoIp;
+
+const DEFAULT_WINDOW_DAYS: i32 = 30;
+/// Distinct addresses listed for a range.
+const IP_CAP: usize = 100;
+const RANGE_STATEMENT_TIMEOUT: &str = "15s";
+
+/// What the path named: one address, or a network in `addr/prefix` form.
+#[derive(Debug, PartialEq, Eq)]
+enum Target {
+    Addr(IpAddr),
+    Net(IpAddr, u8),
+}
+
+impl Target {
+    /// A full-length prefix (`/32`, `/128`) is just the address.
+    fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let Some((addr, prefix)) = s.split_once('/') else {
+            return s
+                .parse()
+                .map(Target::Addr)
+                .map_err(|_| format!("invalid IP address: {}", s));
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid IP address: {}", addr))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        match prefix.parse::<u8>() {
+            Ok(p) if p == max => Ok(Target::Addr(addr)),
+            Ok(p) if p < max => Ok(Target::Net(addr, p)),
+            _ => Err(format!("invalid prefix length: /{}", prefix)),
+        }
+    }
+
+    /// Bound as `$1`.
+    fn bind_value(&self) -> String {
+        match self {
+            Target::Addr(a) => a.to_string(),
+            Target::Net(a, p) => format!("{}/{}", a, p),
+        }
+    }
+
+    /// Predicate on a free-text IP column against `$1`. An IPv4 address has
+    /// one spelling, so it matches the (indexed) text as is; IPv6 has many
+    /// (`2001:DB8::1`, `2001:db8:0:0::1`), so it is compared as `inet`.
+    fn predicate(&self, col: &str) -> String {
+        match self {
+            Target::Addr(IpAddr::V4(_)) => format!("{col} = $1"),
+            Target::Addr(IpAddr::V6(_)) => {
+                format!("{col} ~ '^[0-9A-Fa-f:.]+$' AND try_inet({col}) = $1::inet")
+            }
+            Target::Net(..) => {
+                format!("{col} ~ '^[0-9A-Fa-f:.]+$' AND try_inet({col}) <<= $1::inet")
+            }
+        }
+    }
+}
+
+/// The API process doesn't otherwise hold the GeoIP DBs; load them on first
+/// use rather than per request.
+fn geoip() -> &'static GeoIp {
+    static GEOIP: OnceLock<GeoIp> = OnceLock::new();
+    GEOIP.get_or_init(|| GeoIp::load(&load_conf().unwrap().geoip))
+}
+
+#[derive(Serialize)]
+struct Enrichment {
+    location: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    asn: Option<u32>,
+    as_org: Option<String>,
+    network_class: Option<&'static str>,
+}
+
+fn enrich(geo: &GeoIp, ip: &str) -> Enrichment {
+    let point = geo.lookup_geo(ip);
+    let network = geo.network(ip);
+    Enrichment {
+        location: geo.lookup(ip),
+        lat: point.as_ref().map(|p| p.lat),
+        lon: point.as_ref().map(|p| p.lon),
+        asn: network.as_ref().and_then(|n| n.asn),
+        as_org: network.as_ref().and_then(|n| n.org.clone()),
+        network_class: network.map(|n| n.class.as_str()),
+    }
+}
+
+#[derive(QueryableByName)]
+struct IpSeenRow {
+    #[diesel(sql_type = Text)]
+    ip: String,
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct IpSourceRow {
+    #[diesel(sql_type = Text)]
+    source: String,
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+    #[diesel(sql_type = BigInt)]
+    failed: i64,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+}
+
+/// One identity seen on the address(es). `actor_id` is the reconciled actor
+/// when the raw identifier resolves through `actor_aliases`.
+#[derive(QueryableByName, Serialize)]
+struct IpActorRow {
+    #[diesel(sql_type = Text)]
+    actor: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    actor_id: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+    #[diesel(sql_type = BigInt)]
+    failed: i64,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct IpParams {
+    /// Window for everything but the alerts' own lifetimes. Default 30, at
+    /// most 365.
+    pub days: Option<i32>,
+}
+
+pub(super) async fn inspect_handler(
+    State(pool): State<DbPool>,
+    Path(ip): Path<String>,
+    Query(params): Query<IpParams>,
+) -> Response {
+    let target = match Target::parse(&ip) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let days = params.days.unwrap_or(DEFAULT_WINDOW_DAYS).clamp(1, 365);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "entity.ip",
+        entity.id = %ip
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<serde_json::Value>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        let value = target.bind_value();
+        let ev = target.predicate("e.source_ip");
+        let window = "e.ts >= now() - make_interval(days => $2)";
+
+        let (ips, sources, actors, peers, sessions, alerts) = conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                if matches!(target, Target::Net(..)) {
+                    diesel::sql_query(format!(
+                        "SET LOCAL statement_timeout = '{RANGE_STATEMENT_TIMEOUT}'"
+                    ))
+                    .execute(conn)?;
+                }
+
+                let ips: Vec<IpSeenRow> = diesel::sql_query(format!(
+                    "SELECT e.source_ip AS ip, count(*) AS events, min(e.ts) AS first_seen, \
+                            max(e.ts) AS last_seen \
+                     FROM ssumgmt_events e WHERE {ev} AND {window} \
+                     GROUP BY e.source_ip ORDER BY events DESC, ip LIMIT {}",
+                    IP_CAP + 1
+                ))
+                .bind::<Text, _>(&value)
+                .bind::<Integer, _>(days)
+                .load(conn)?;
+
+                let sources: Vec<IpSourceRow> = diesel::sql_query(format!(
+                    "SELECT e.source, count(*) AS events, \
+                            count(*) FILTER (WHERE e.status = 'failure') AS failed, \
+                            min(e.ts) AS first_seen, max(e.ts) AS last_seen \
+                     FROM ssumgmt_events e WHERE {ev} AND {window} \
+                     GROUP BY e.source ORDER BY events DESC"
+                ))
+                .bind::<Text, _>(&value)
+                .bind::<Integer, _>(days)
+                .load(conn)?;
+
+                let actors: Vec<IpActorRow> = diesel::sql_query(format!(
+                    "SELECT COALESCE(e.actor, '') AS actor, aa.actor_id, count(*) AS events, \
+                            count(*) FILTER (WHERE e.status = 'failure') AS failed, \
+                            min(e.ts) AS first_seen, max(e.ts) AS last_seen \
+                     FROM ssumgmt_events e LEFT JOIN actor_aliases aa ON aa.alias = e.actor \
+                     WHERE {ev} AND {window} \
+                     GROUP BY 1, 2 ORDER BY events DESC, actor LIMIT 100"
+                ))
+                .bind::<Text, _>(&value)
+                .bind::<Integer, _>(days)
+                .load(conn)?;
+
+                // The entity graph's shared-IP edges, seen from the address:
+                // reconciled actors on each address that more than one uses.
+                let peers: Vec<PeerRow> = diesel::sql_query(format!(
+                    "SELECT ip, peer, weight, first_seen, last_seen FROM ( \
+                         SELECT e.source_ip AS ip, aa.actor_id AS peer, count(*) AS weight, \
+                                min(e.ts) AS first_seen, max(e.ts) AS last_seen, \
+                                count(*) OVER (PARTITION BY e.source_ip) AS sharing \
+                         FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
+                         WHERE {ev} AND {window} \
+                         GROUP BY e.source_ip, aa.actor_id \
+                     ) p WHERE sharing > 1 ORDER BY weight DESC, ip, peer LIMIT 50"
+                ))
+                .bind::<Text, _>(&value)
+                .bind::<Integer, _>(days)
+                .load(conn)?;
+
+                let sessions: Vec<Session> = diesel::sql_query(format!(
+                    "SELECT * FROM sessions s \
+                     WHERE {} AND s.last_seen_at >= now() - make_interval(days => $2) \
+                     ORDER BY s.last_seen_at DESC LIMIT 50",
+                    target.predicate("s.source_ip")
+                ))
+                .bind::<Text, _>(&value)
+                .bind::<Integer, _>(days)
+                .load(conn)?;
+
+                // Rules record addresses under `ip`/`source_ip`, or as
+                // `ips`/`source_ips` arrays.
+                let alerts: Vec<Alert> = diesel::sql_query(format!(
+                    "SELECT * FROM alerts a \
+                     WHERE a.last_seen >= now() - make_interval(days => $2) AND EXISTS ( \
+                         SELECT 1 FROM ( \
+                             SELECT a.evidence ->> 'ip' UNION ALL \
+                             SELECT a.evidence ->> 'source_ip' UNION ALL \
+                             SELECT jsonb_array_elements_text(x) \
+                               FROM (SELECT a.evidence -> 'ips' UNION ALL \
+                                     SELECT a.evidence -> 'source_ips') l(x) \
+                              WHERE jsonb_typeof(x) = 'array' \
+                         ) r(ip) WHERE {} \
+                     ) ORDER BY a.last_seen DESC LIMIT 50",
+                    target.predicate("r.ip")
+                ))
+                .bind::<Text, _>(&value)
+                .bind::<Integer, _>(days)
+                .load(conn)?;
+
+                Ok((ips, sources, actors, peers, sessions, alerts))
+            })?;
+
+        if ips.is_empty() && sessions.is_empty() && alerts.is_empty() {
+            return Ok(None);
+        }
+
+        let geo = geoip();
+        let truncated = ips.len() > IP_CAP;
+        let ips: Vec<serde_json::Value> = ips
+            .into_iter()
+            .take(IP_CAP)
+            .map(|r| {
+                json!({
+                    "ip": r.ip,
+                    "events": r.events,
+                    "first_seen": r.first_seen,
+                    "last_seen": r.last_seen,
+                    "enrichment": enrich(geo, &r.ip),
+                })
+            })
+            .collect();
+        let enrichment = match &target {
+            Target::Addr(a) => Some(enrich(geo, &a.to_string())),
+            Target::Net(..) => None,
+        };
+
+        Ok(Some(json!({
+            "ip": value,
+            "cidr": matches!(target, Target::Net(..)),
+            "window_days": days,
+            "enrichment": enrichment,
+            "first_seen": sources.iter().map(|s| s.first_seen).min(),
+            "last_seen": sources.iter().map(|s| s.last_seen).max(),
+            "events": sources.iter().map(|s| s.events).sum::<i64>(),
+            "ips": ips,
+            "ips_truncated": truncated,
+            "sources": sources,
+            "actors": actors,
+            "peers": peers,
+            "sessions": sessions,
+            "alerts": alerts,
+        })))
+    })
+    .await;
+
+    match res {
+        Ok(Ok(Some(v))) => Json(v).into_response(),
+        Ok(Ok(None)) => (StatusCode::NOT_FOUND, "no activity for this IP").into_response(),
+        Ok(Err(e)) => db_error(e),
+        Err(e) => join_error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_parses_addresses_and_ranges() {
+        let v4: IpAddr = "10.1.2.3".parse().unwrap();
+        assert_eq!(Target::parse("10.1.2.3"), Ok(Target::Addr(v4)));
+        assert_eq!(Target::parse("10.1.2.3/32"), Ok(Target::Addr(v4)));
+        assert_eq!(
+            Target::parse("10.0.0.0/8").unwrap().bind_value(),
+            "10.0.0.0/8"
+        );
+        assert!(matches!(
+            Target::parse("2001:db8::/32"),
+            Ok(Target::Net(_, 32))
+        ));
+        assert!(Target::parse("10.0.0.0/33").is_err());
+        assert!(Target::parse("not-an-ip").is_err());
+        assert!(Target::parse("10.0.0.0/x").is_err());
+    }
+
+    #[test]
+    fn ipv6_addresses_compare_as_inet() {
+        let v6 = Target::parse("2001:DB8:0::1").unwrap();
+        assert_eq!(v6.bind_value(), "2001:db8::1");
+        assert!(v6.predicate("c").contains("try_inet(c) = $1::inet"));
+        assert_eq!(Target::parse("10.1.2.3").unwrap().predicate("c"), "c = $1");
+    }
+}
@@ -0,0 +1,386 @@
+//! Resource entities: the "who touched this" half of an investigation.
+//! `GET /entity/resources` lists them, `GET /entity/resource/{id}` fans a
+//! resource's actors, actions (with failure rates), stats and recent activity
+//! into one payload, and `/activity` and `/timeline` page and bucket its
+//! events like the actor routes. Ids are `<kind>:<name>` (see
+//! `service::resources`), URL-encoded since repo names and paths contain `/`.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::Query;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double, Integer, Nullable, Text, Timestamptz};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::entity::{db_error, join_error, parse_ts, ActivityParams, TimelineParams, TimelineRow};
+use crate::db::model::SsuMgmtEvent;
+use crate::db::DbPool;
+use crate::service::field_values::like_escape;
+use crate::service::resources::RESOURCE_KINDS;
+
+const DEFAULT_WINDOW_DAYS: i32 = 30;
+
+#[derive(QueryableByName, Serialize)]
+struct ResourceRow {
+    #[diesel(sql_type = Text)]
+    id: String,
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+    /// Every touch ever folded, including ones since pruned.
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+}
+
+#[derive(QueryableByName)]
+struct ResourceStatsRow {
+    #[diesel(sql_type = BigInt)]
+    events_24h: i64,
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+    #[diesel(sql_type = BigInt)]
+    failed: i64,
+    #[diesel(sql_type = BigInt)]
+    actors: i64,
+}
+
+/// One actor that touched the resource. `actor_id` is the reconciled actor
+/// when the raw identifier resolves through `actor_aliases`.
+#[derive(QueryableByName, Serialize)]
+struct ResourceActorRow {
+    #[diesel(sql_type = Text)]
+    actor: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    actor_id: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+    #[diesel(sql_type = BigInt)]
+    failed: i64,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct ResourceActionRow {
+    #[diesel(sql_type = Text)]
+    action: String,
+    #[diesel(sql_type = BigInt)]
+    events: i64,
+    #[diesel(sql_type = BigInt)]
+    failed: i64,
+    #[diesel(sql_type = Double)]
+    failure_rate: f64,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(QueryableByName)]
+struct CountRow {
+    #[diesel(sql_type = BigInt)]
+    n: i64,
+}
+
+/// The touched events, newest first, read back from the event view. The
+/// source gate keeps each lookup to the one branch holding the row.
+const ACTIVITY_SQL: &str =
+    "SELECT e.source, e.uid, e.ts, e.actor, e.action, e.resource, e.source_ip, e.level, e.status, e.raw, e.role, e.identity_source, e.account_id, e.caller_account_id \
+     FROM (SELECT source, uid, ts FROM resource_touches WHERE resource_id = $1 \
+           ORDER BY ts DESC, uid LIMIT $2 OFFSET $3) t \
+     CROSS JOIN LATERAL ( \
+         SELECT * FROM ssumgmt_events e WHERE e.source = t.source AND e.uid = t.uid LIMIT 1 \
+     ) e \
+     ORDER BY t.ts DESC, t.uid";
+
+/// The `kind` filter: empty means any; otherwise one of `RESOURCE_KINDS`.
+fn parse_kind(kind: Option<String>) -> Result<Option<String>, String> {
+    match kind.filter(|k| !k.is_empty()) {
+        Some(k) if !RESOURCE_KINDS.contains(&k.as_str()) => Err(format!(
+            "invalid kind: {} (one of {})",
+            k,
+            RESOURCE_KINDS.join(", ")
+        )),
+        kind => Ok(kind),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    /// One of `service::resources::RESOURCE_KINDS`.
+    pub kind: Option<String>,
+    /// Case-insensitive name prefix.
+    pub q: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Resources, most recently touched first.
+pub(super) async fn list_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<ListParams>,
+) -> Response {
+    let kind = match parse_kind(params.kind) {
+        Ok(k) => k,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let prefix = params
+        .q
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("{}%", like_escape(s)));
+    let limit = params.limit.unwrap_or(100).clamp(1, 500);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "entity.resources",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<ResourceRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let list_sql = "SELECT id, kind, name, first_seen, last_seen, events FROM resources \
+             WHERE ($1::text IS NULL OR kind = $1) \
+               AND ($2::text IS NULL OR lower(name) LIKE lower($2) ESCAPE '\\') \
+             ORDER BY last_seen DESC, id LIMIT $3";
+        span.record("db.statement", list_sql);
+        diesel::sql_query(list_sql)
+            .bind::<Nullable<Text>, _>(kind)
+            .bind::<Nullable<Text>, _>(prefix)
+            .bind::<BigInt, _>(limit)
+            .load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => db_error(e),
+        Err(e) => join_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct InspectParams {
+    /// Window for the stats, actors and actions. Default 30, at most 365.
+    pub days: Option<i32>,
+}
+
+pub(super) async fn inspect_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<String>,
+    Query(params): Query<InspectParams>,
+) -> Response {
+    let days = params.days.unwrap_or(DEFAULT_WINDOW_DAYS).clamp(1, 365);
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "entity.resource",
+        entity.id = %id
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<serde_json::Value>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+
+        let resource: Option<ResourceRow> = diesel::sql_query(
+            "SELECT id, kind, name, first_seen, last_seen, events FROM resources WHERE id = $1",
+        )
+        .bind::<Text, _>(&id)
+        .get_result(&mut conn)
+        .optional()?;
+        let Some(resource) = resource else {
+            return Ok(None);
+        };
+
+        let stats: ResourceStatsRow = diesel::sql_query(
+            "SELECT count(*) FILTER (WHERE ts >= now() - interval '24 hours') AS events_24h, \
+                    count(*) AS events, \
+                    count(*) FILTER (WHERE status = 'failure') AS failed, \
+                    count(DISTINCT actor) AS actors \
+             FROM resource_touches \
+             WHERE resource_id = $1 AND ts >= now() - make_interval(days => $2)",
+        )
+        .bind::<Text, _>(&id)
+        .bind::<Integer, _>(days)
+        .get_result(&mut conn)?;
+
+        let actors: Vec<ResourceActorRow> = diesel::sql_query(
+            "SELECT COALESCE(t.actor, '') AS actor, aa.actor_id, count(*) AS events, \
+                    count(*) FILTER (WHERE t.status = 'failure') AS failed, max(t.ts) AS last_seen \
+             FROM resource_touches t LEFT JOIN actor_aliases aa ON aa.alias = t.actor \
+             WHERE t.resource_id = $1 AND t.ts >= now() - make_interval(days => $2) \
+             GROUP BY 1, 2 ORDER BY events DESC, actor LIMIT 50",
+        )
+        .bind::<Text, _>(&id)
+        .bind::<Integer, _>(days)
+        .load(&mut conn)?;
+
+        let actions: Vec<ResourceActionRow> = diesel::sql_query(
+            "SELECT action, count(*) AS events, \
+                    count(*) FILTER (WHERE status = 'failure') AS failed, \
+                    (count(*) FILTER (WHERE status = 'failure'))::float8 / count(*) AS failure_rate, \
+                    max(ts) AS last_seen \
+             FROM resource_touches \
+             WHERE resource_id = $1 AND ts >= now() - make_interval(days => $2) \
+             GROUP BY action ORDER BY events DESC, action LIMIT 50",
+        )
+        .bind::<Text, _>(&id)
+        .bind::<Integer, _>(days)
+        .load(&mut conn)?;
+
+        let activity: Vec<SsuMgmtEvent> = diesel::sql_query(ACTIVITY_SQL)
+            .bind::<Text, _>(&id)
+            .bind::<BigInt, _>(50)
+            .bind::<BigInt, _>(0)
+            .load(&mut conn)?;
+
+        let failure_rate = if stats.events > 0 {
+            stats.failed as f64 / stats.events as f64
+        } else {
+            0.0
+        };
+        Ok(Some(json!({
+            "resource": resource,
+            "window_days": days,
+            "stats": {
+                "events_24h": stats.events_24h,
+                "events": stats.events,
+                "failed": stats.failed,
+                "failure_rate": failure_rate,
+                "actors": stats.actors,
+            },
+            "actors": actors,
+            "actions": actions,
+            "activity": activity,
+        })))
+    })
+    .await;
+
+    match res {
+        Ok(Ok(Some(v))) => Json(v).into_response(),
+        Ok(Ok(None)) => (StatusCode::NOT_FOUND, "resource not found").into_response(),
+        Ok(Err(e)) => db_error(e),
+        Err(e) => join_error(e),
+    }
+}
+
+/// Paginated activity for one resource, with the total still on record.
+pub(super) async fn activity_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<String>,
+    Query(params): Query<ActivityParams>,
+) -> Response {
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "entity.resource_activity",
+        entity.id = %id,
+        db.statement = ACTIVITY_SQL
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<serde_json::Value> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let rows: Vec<SsuMgmtEvent> = diesel::sql_query(ACTIVITY_SQL)
+            .bind::<Text, _>(&id)
+            .bind::<BigInt, _>(limit)
+            .bind::<BigInt, _>(offset)
+            .load(&mut conn)?;
+        let total: CountRow =
+            diesel::sql_query("SELECT count(*) AS n FROM resource_touches WHERE resource_id = $1")
+                .bind::<Text, _>(&id)
+                .get_result(&mut conn)?;
+        Ok(json!({ "rows": rows, "total": total.n }))
+    })
+    .await;
+
+    match res {
+        Ok(Ok(v)) => Json(v).into_response(),
+        Ok(Err(e)) => db_error(e),
+        Err(e) => join_error(e),
+    }
+}
+
+/// Per-source buckets of the resource's events, shaped like the actor
+/// timeline.
+pub(super) async fn timeline_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<String>,
+    Query(params): Query<TimelineParams>,
+) -> Response {
+    let bucket = match params.bucket.as_deref().unwrap_or("hour") {
+        b @ ("minute" | "hour" | "day") => b.to_owned(),
+        other => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("invalid bucket: {}", other),
+            )
+                .into_response()
+        }
+    };
+    let now = Utc::now();
+    let from = match params.from.as_deref().map(parse_ts).transpose() {
+        Ok(v) => v.unwrap_or(now - Duration::days(7)),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let to = match params.to.as_deref().map(parse_ts).transpose() {
+        Ok(v) => v.unwrap_or(now),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "entity.resource_timeline",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<TimelineRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let timeline_sql = "SELECT date_trunc($1, ts) AS bucket, source, count(*) AS count \
+             FROM resource_touches \
+             WHERE resource_id = $2 AND ts >= $3 AND ts <= $4 \
+             GROUP BY 1, 2 ORDER BY 1 ASC";
+        span.record("db.statement", timeline_sql);
+        diesel::sql_query(timeline_sql)
+            .bind::<Text, _>(bucket)
+            .bind::<Text, _>(id)
+            .bind::<Timestamptz, _>(from)
+            .bind::<Timestamptz, _>(to)
+            .load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => db_error(e),
+        Err(e) => join_error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_filter_is_validated() {
+        assert_eq!(parse_kind(None), Ok(None));
+        assert_eq!(parse_kind(Some(String::new())), Ok(None));
+        assert_eq!(parse_kind(Some("role".into())), Ok(Some("role".into())));
+        let err = parse_kind(Some("table".into())).unwrap_err();
+        assert!(err.starts_with("invalid kind: table (one of bucket, role"));
+    }
+}
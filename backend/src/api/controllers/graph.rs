@@ -1,23 +1,33 @@
 use std::collections::{BTreeMap, BTreeSet};
 
 use axum::extract::State;
-use axum::http::StatusCode;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::{Json, Router};
 use axum_extra::extract::Query;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
 use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+use super::entity::parse_ts;
+use super::graph_export::{self, Format};
 use crate::db::DbPool;
+use crate::service::siem::role_graph::{self, RoleEdge, RolePath};
 
 const NODE_CAP: usize = 150;
+/// Role edges rendered by `mode=roles`.
+const ROLE_EDGE_CAP: i64 = 400;
+const DEFAULT_MAX_PATHS: usize = 100;
+/// Window of the activity modes when `from` is not given.
+const DEFAULT_WINDOW_DAYS: i64 = 7;
 
 pub fn routes(pool: DbPool) -> Router {
     Router::new()
         .route("/", axum::routing::get(graph_handler))
+        .route("/export", axum::routing::get(export_handler))
+        .route("/paths", axum::routing::get(paths_handler))
         .with_state(pool)
 }
 
@@ -25,8 +35,14 @@ pub fn routes(pool: DbPool) -> Router {
 pub struct GraphParams {
     pub mode: Option<String>,
     pub actor: Option<String>,
+    /// Activity modes default to the last 7 days; `roles` keeps every hop
+    /// unless given, and then those seen at some point in the window.
     pub from: Option<String>,
     pub to: Option<String>,
+    /// `roles` mode: only hops into or out of this AWS account.
+    pub account: Option<String>,
+    /// `/export` only: `graphml`, `gexf` or `cytoscape`.
+    pub format: Option<String>,
 }
 
 #[derive(QueryableByName)]
@@ -39,6 +55,10 @@ struct EdgeRow {
     weight: i64,
     #[diesel(sql_type = BigInt)]
     failures: i64,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
 }
 
 #[derive(QueryableByName)]
@@ -49,270 +69,625 @@ struct ActorMeta {
     label: String,
     #[diesel(sql_type = Text)]
     kind: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    team: Option<String>,
     #[diesel(sql_type = diesel::sql_types::Integer)]
     score: i32,
 }
 
+const ACTOR_META_SQL: &str = "SELECT a.id AS id, COALESCE(a.display_name, a.id) AS label, a.kind AS kind, a.team AS team, COALESCE(r.score, 0) AS score \
+     FROM actors a LEFT JOIN risk_scores r ON r.actor_id = a.id WHERE a.id = ANY($1)";
+
 #[derive(QueryableByName)]
 struct IpRow {
     #[diesel(sql_type = Nullable<Text>)]
     ip: Option<String>,
     #[diesel(sql_type = BigInt)]
     weight: i64,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    last_seen: DateTime<Utc>,
 }
 
-#[derive(QueryableByName)]
-struct PeerRow {
+/// An actor seen on an address another actor also used (also the IP
+/// entity's `peers`).
+#[derive(QueryableByName, Serialize)]
+pub(super) struct PeerRow {
     #[diesel(sql_type = Nullable<Text>)]
-    ip: Option<String>,
+    pub(super) ip: Option<String>,
     #[diesel(sql_type = Text)]
-    peer: String,
+    pub(super) peer: String,
     #[diesel(sql_type = BigInt)]
-    weight: i64,
+    pub(super) weight: i64,
+    #[diesel(sql_type = Timestamptz)]
+    pub(super) first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    pub(super) last_seen: DateTime<Utc>,
 }
 
 #[derive(Serialize)]
-struct Node {
-    id: String,
+pub(super) struct Node {
+    pub(super) id: String,
     #[serde(rename = "type")]
-    node_type: String,
-    label: String,
-    risk: i32,
+    pub(super) node_type: String,
+    pub(super) label: String,
+    pub(super) risk: i32,
+    /// Actor nodes: the actor's kind (`human`, `service`, …).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) kind: Option<String>,
+    /// Actor nodes: the directory team.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) team: Option<String>,
+    /// Role nodes: the AWS account the role lives in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) account: Option<String>,
+    /// Role nodes: whether the role name looks privileged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) privileged: Option<bool>,
+}
+
+impl Node {
+    /// A node with only its identity; the kind-specific fields are filled in
+    /// by the caller.
+    fn new(id: String, node_type: &str, label: String) -> Self {
+        Self {
+            id,
+            node_type: node_type.to_string(),
+            label,
+            risk: 0,
+            kind: None,
+            team: None,
+            account: None,
+            privileged: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
-struct Edge {
-    from: String,
-    to: String,
-    kind: String,
-    weight: i64,
-    failure: bool,
+pub(super) struct Edge {
+    pub(super) from: String,
+    pub(super) to: String,
+    pub(super) kind: String,
+    pub(super) weight: i64,
+    pub(super) failure: bool,
+    /// Role edges: the STS call and identity provider of the hop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) label: Option<String>,
+    /// When the relationship was first and last observed.
+    pub(super) first_seen: DateTime<Utc>,
+    pub(super) last_seen: DateTime<Utc>,
+}
+
+/// A built graph, before it is rendered as the console's JSON or an export.
+pub(super) struct Graph {
+    pub(super) mode: String,
+    pub(super) nodes: Vec<Node>,
+    pub(super) edges: Vec<Edge>,
+    /// Actors (hops in `roles` mode) shown, of how many matched.
+    pub(super) shown: i64,
+    pub(super) total: i64,
+    /// The window the graph covers; open ends are unbounded.
+    pub(super) from: Option<DateTime<Utc>>,
+    pub(super) to: Option<DateTime<Utc>>,
 }
 
 async fn graph_handler(State(pool): State<DbPool>, Query(params): Query<GraphParams>) -> Response {
-    let mode = params.mode.clone().unwrap_or_else(|| "surface".to_string());
-    let actor = params.actor.clone();
+    match build(pool, params).await {
+        Ok(g) => Json(json!({
+            "nodes": g.nodes,
+            "edges": g.edges,
+            "shownOf": { "shown": g.shown, "total": g.total },
+            "mode": g.mode,
+        }))
+        .into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// The same graph as `/graph`, as a file for Gephi, Cytoscape, Neo4j & co.
+async fn export_handler(State(pool): State<DbPool>, Query(params): Query<GraphParams>) -> Response {
+    let format = match params.format.as_deref().map(Format::parse) {
+        Some(Some(f)) => f,
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "export requires ?format=graphml|gexf|cytoscape",
+            )
+                .into_response()
+        }
+    };
+    let g = match build(pool, params).await {
+        Ok(g) => g,
+        Err(resp) => return resp,
+    };
+
+    let disposition = format!(
+        "attachment; filename=\"graph_{}.{}\"",
+        g.mode,
+        format.extension()
+    );
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    if let Ok(v) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    (StatusCode::OK, headers, graph_export::render(&g, format)).into_response()
+}
+
+/// Validate the request and build its graph; the error is the response to send.
+async fn build(pool: DbPool, params: GraphParams) -> Result<Graph, Response> {
+    // Anything else is the surface view; the name also ends up in export
+    // filenames.
+    let mode = match params.mode.as_deref() {
+        Some(m @ ("investigate" | "entity" | "roles")) => m.to_string(),
+        _ => "surface".to_string(),
+    };
+    let actor = params.actor;
 
     if mode == "entity" && actor.as_deref().unwrap_or("").is_empty() {
-        return (StatusCode::BAD_REQUEST, "entity mode requires ?actor=").into_response();
+        return Err((StatusCode::BAD_REQUEST, "entity mode requires ?actor=").into_response());
+    }
+    let from = params.from.as_deref().map(parse_ts).transpose();
+    let to = params.to.as_deref().map(parse_ts).transpose();
+    let (from, to) = match (from, to) {
+        (Ok(f), Ok(t)) => (f, t),
+        (Err(e), _) | (_, Err(e)) => return Err((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+    if let (Some(f), Some(t)) = (from, to) {
+        if f > t {
+            return Err((StatusCode::BAD_REQUEST, "from is after to").into_response());
+        }
     }
 
-    let span = tracing::info_span!(
-        "db.query",
-        otel.kind = "client",
-        db.system = "postgresql",
-        op = "graph.aggregate",
-        graph.mode = %mode,
-        entity.id = %actor.as_deref().unwrap_or("")
-    );
-    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<serde_json::Value> {
-        let _g = span.enter();
-        let mut conn = pool.get()?;
-        let floor = Utc::now() - Duration::days(7);
-
-        let q = |op: &'static str, stmt: &'static str| {
-            tracing::info_span!(
-                "db.query",
-                otel.kind = "client",
-                db.system = "postgresql",
-                op = op,
-                graph.mode = %mode,
-                db.statement = stmt
-            )
-        };
+    let res = if mode == "roles" {
+        let account = params.account;
+        let span = tracing::info_span!(
+            "db.query",
+            otel.kind = "client",
+            db.system = "postgresql",
+            op = "graph.roles",
+            entity.id = %actor.as_deref().unwrap_or("")
+        );
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Graph> {
+            let _g = span.enter();
+            let mut conn = pool.get()?;
+            roles_graph(&mut conn, actor, account, from, to)
+        })
+        .await
+    } else {
+        let to = to.unwrap_or_else(Utc::now);
+        let from = from.unwrap_or(to - Duration::days(DEFAULT_WINDOW_DAYS));
+        let span = tracing::info_span!(
+            "db.query",
+            otel.kind = "client",
+            db.system = "postgresql",
+            op = "graph.aggregate",
+            graph.mode = %mode,
+            entity.id = %actor.as_deref().unwrap_or("")
+        );
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Graph> {
+            let _g = span.enter();
+            let mut conn = pool.get()?;
+            activity_graph(&mut conn, mode, actor, from, to)
+        })
+        .await
+    };
 
-        // --- actor↔source edges, scoped by mode -----------------------------
-        let edges: Vec<EdgeRow> = match mode.as_str() {
-            "entity" => {
-                let sql = "SELECT aa.actor_id AS actor_id, e.source AS source, count(*) AS weight, \
-                        count(*) FILTER (WHERE e.status = 'failure') AS failures \
-                 FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
-                 WHERE aa.actor_id = $1 AND e.ts >= $2 \
-                 GROUP BY aa.actor_id, e.source";
-                q("graph.edges", sql).in_scope(|| {
-                    diesel::sql_query(sql)
-                        .bind::<Text, _>(actor.as_deref().unwrap_or(""))
-                        .bind::<Timestamptz, _>(floor)
-                        .load(&mut conn)
-                })?
-            }
-            "investigate" => {
-                let sql = "SELECT aa.actor_id AS actor_id, e.source AS source, count(*) AS weight, \
-                        count(*) FILTER (WHERE e.status = 'failure') AS failures \
-                 FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
-                 WHERE e.ts >= $2 AND ($1 = '' OR aa.actor_id ILIKE '%' || $1 || '%') \
-                 GROUP BY aa.actor_id, e.source \
-                 ORDER BY weight DESC LIMIT 400";
-                q("graph.edges", sql).in_scope(|| {
-                    diesel::sql_query(sql)
-                        .bind::<Text, _>(actor.as_deref().unwrap_or(""))
-                        .bind::<Timestamptz, _>(floor)
-                        .load(&mut conn)
-                })?
-            }
-            // surface (default): top actors by risk then activity ↔ sources.
-            _ => {
-                let sql = "WITH top_actors AS ( \
-                    SELECT aa.actor_id AS actor_id, sum(c.n) AS activity \
-                    FROM actor_daily_counts c JOIN actor_aliases aa ON aa.alias = c.actor \
-                    WHERE c.day >= (now() - interval '7 days')::date \
-                    GROUP BY aa.actor_id \
-                 ), chosen AS ( \
-                    SELECT ta.actor_id FROM top_actors ta LEFT JOIN risk_scores r ON r.actor_id = ta.actor_id \
-                    ORDER BY COALESCE(r.score, 0) DESC, ta.activity DESC LIMIT 40 \
-                 ) \
-                 SELECT aa.actor_id AS actor_id, e.source AS source, \
-                        sum(e.cnt)::bigint AS weight, \
-                        sum(e.fails)::bigint AS failures \
-                 FROM chosen c \
-                 JOIN actor_aliases aa ON aa.actor_id = c.actor_id \
-                 CROSS JOIN LATERAL ( \
-                     SELECT ev.source AS source, \
-                            count(*) AS cnt, \
-                            count(*) FILTER (WHERE ev.status = 'failure') AS fails \
-                     FROM ssumgmt_events ev \
-                     WHERE ev.actor = aa.alias AND ev.ts >= now() - interval '7 days' \
-                     GROUP BY ev.source \
-                     OFFSET 0 \
-                 ) e \
-                 GROUP BY aa.actor_id, e.source";
-                q("graph.edges", sql)
-                    .in_scope(|| diesel::sql_query(sql).load(&mut conn))?
-            }
-        };
+    match res {
+        Ok(Ok(g)) => Ok(g),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response()),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response()),
+    }
+}
+
+/// The actor↔source graph of the `surface`, `investigate` and `entity` modes,
+/// over `[from, to]`.
+fn activity_graph(
+    conn: &mut PgConnection,
+    mode: String,
+    actor: Option<String>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> anyhow::Result<Graph> {
+    let q = |op: &'static str, stmt: &'static str| {
+        tracing::info_span!(
+            "db.query",
+            otel.kind = "client",
+            db.system = "postgresql",
+            op = op,
+            graph.mode = %mode,
+            db.statement = stmt
+        )
+    };
 
-        let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
-        let mut out_edges: Vec<Edge> = Vec::new();
-        let mut actor_ids: BTreeSet<String> = BTreeSet::new();
+    // --- actor↔source edges, scoped by mode -----------------------------
+    let edges: Vec<EdgeRow> = match mode.as_str() {
+        "entity" => {
+            let sql = "SELECT aa.actor_id AS actor_id, e.source AS source, count(*) AS weight, \
+                    count(*) FILTER (WHERE e.status = 'failure') AS failures, \
+                    min(e.ts) AS first_seen, max(e.ts) AS last_seen \
+             FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
+             WHERE aa.actor_id = $1 AND e.ts >= $2 AND e.ts <= $3 \
+             GROUP BY aa.actor_id, e.source";
+            q("graph.edges", sql).in_scope(|| {
+                diesel::sql_query(sql)
+                    .bind::<Text, _>(actor.as_deref().unwrap_or(""))
+                    .bind::<Timestamptz, _>(from)
+                    .bind::<Timestamptz, _>(to)
+                    .load(conn)
+            })?
+        }
+        "investigate" => {
+            let sql = "SELECT aa.actor_id AS actor_id, e.source AS source, count(*) AS weight, \
+                    count(*) FILTER (WHERE e.status = 'failure') AS failures, \
+                    min(e.ts) AS first_seen, max(e.ts) AS last_seen \
+             FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
+             WHERE e.ts >= $2 AND e.ts <= $3 AND ($1 = '' OR aa.actor_id ILIKE '%' || $1 || '%') \
+             GROUP BY aa.actor_id, e.source \
+             ORDER BY weight DESC LIMIT 400";
+            q("graph.edges", sql).in_scope(|| {
+                diesel::sql_query(sql)
+                    .bind::<Text, _>(actor.as_deref().unwrap_or(""))
+                    .bind::<Timestamptz, _>(from)
+                    .bind::<Timestamptz, _>(to)
+                    .load(conn)
+            })?
+        }
+        // surface (default): top actors by risk then activity ↔ sources.
+        _ => {
+            let sql = "WITH top_actors AS ( \
+                SELECT aa.actor_id AS actor_id, sum(c.n) AS activity \
+                FROM actor_daily_counts c JOIN actor_aliases aa ON aa.alias = c.actor \
+                WHERE c.day >= $1::date AND c.day <= $2::date \
+                GROUP BY aa.actor_id \
+             ), chosen AS ( \
+                SELECT ta.actor_id FROM top_actors ta LEFT JOIN risk_scores r ON r.actor_id = ta.actor_id \
+                ORDER BY COALESCE(r.score, 0) DESC, ta.activity DESC LIMIT 40 \
+             ) \
+             SELECT aa.actor_id AS actor_id, e.source AS source, \
+                    sum(e.cnt)::bigint AS weight, \
+                    sum(e.fails)::bigint AS failures, \
+                    min(e.first_ts) AS first_seen, max(e.last_ts) AS last_seen \
+             FROM chosen c \
+             JOIN actor_aliases aa ON aa.actor_id = c.actor_id \
+             CROSS JOIN LATERAL ( \
+                 SELECT ev.source AS source, \
+                        count(*) AS cnt, \
+                        count(*) FILTER (WHERE ev.status = 'failure') AS fails, \
+                        min(ev.ts) AS first_ts, max(ev.ts) AS last_ts \
+                 FROM ssumgmt_events ev \
+                 WHERE ev.actor = aa.alias AND ev.ts >= $1 AND ev.ts <= $2 \
+                 GROUP BY ev.source \
+                 OFFSET 0 \
+             ) e \
+             GROUP BY aa.actor_id, e.source";
+            q("graph.edges", sql).in_scope(|| {
+                diesel::sql_query(sql)
+                    .bind::<Timestamptz, _>(from)
+                    .bind::<Timestamptz, _>(to)
+                    .load(conn)
+            })?
+        }
+    };
+
+    let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+    let mut out_edges: Vec<Edge> = Vec::new();
+    let mut actor_ids: BTreeSet<String> = BTreeSet::new();
 
-        // Source nodes + actor↔source edges (respecting the cap by actor weight).
-        let mut actor_weight: BTreeMap<String, i64> = BTreeMap::new();
-        for e in &edges {
-            *actor_weight.entry(e.actor_id.clone()).or_insert(0) += e.weight;
+    // Source nodes + actor↔source edges (respecting the cap by actor weight).
+    let mut actor_weight: BTreeMap<String, i64> = BTreeMap::new();
+    for e in &edges {
+        *actor_weight.entry(e.actor_id.clone()).or_insert(0) += e.weight;
+    }
+    // Choose the heaviest actors first if we exceed the cap.
+    let mut ranked: Vec<(String, i64)> = actor_weight.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1));
+    let total_actors = ranked.len();
+    let keep: BTreeSet<String> = ranked
+        .into_iter()
+        .take(NODE_CAP.saturating_sub(16))
+        .map(|(a, _)| a)
+        .collect();
+
+    for e in &edges {
+        if !keep.contains(&e.actor_id) {
+            continue;
         }
-        // Choose the heaviest actors first if we exceed the cap.
-        let mut ranked: Vec<(String, i64)> = actor_weight.into_iter().collect();
-        ranked.sort_by(|a, b| b.1.cmp(&a.1));
-        let total_actors = ranked.len();
-        let keep: BTreeSet<String> = ranked.into_iter().take(NODE_CAP.saturating_sub(16)).map(|(a, _)| a).collect();
-
-        for e in &edges {
-            if !keep.contains(&e.actor_id) {
-                continue;
+        actor_ids.insert(e.actor_id.clone());
+        let src_id = format!("source:{}", e.source);
+        nodes
+            .entry(src_id.clone())
+            .or_insert_with(|| Node::new(src_id.clone(), "source", e.source.clone()));
+        out_edges.push(Edge {
+            from: format!("actor:{}", e.actor_id),
+            to: src_id,
+            kind: "activity".to_string(),
+            weight: e.weight,
+            failure: e.failures > 0,
+            label: None,
+            first_seen: e.first_seen,
+            last_seen: e.last_seen,
+        });
+    }
+
+    // --- entity mode: add IP nodes + peer actors (shared infrastructure) --
+    if mode == "entity" {
+        let a = actor.as_deref().unwrap_or("");
+        let ips_sql = "SELECT e.source_ip AS ip, count(*) AS weight, \
+                    min(e.ts) AS first_seen, max(e.ts) AS last_seen \
+             FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
+             WHERE aa.actor_id = $1 AND e.source_ip IS NOT NULL AND e.ts >= $2 AND e.ts <= $3 \
+             GROUP BY e.source_ip ORDER BY weight DESC LIMIT 10";
+        let ips: Vec<IpRow> = q("graph.ips", ips_sql).in_scope(|| {
+            diesel::sql_query(ips_sql)
+                .bind::<Text, _>(a)
+                .bind::<Timestamptz, _>(from)
+                .bind::<Timestamptz, _>(to)
+                .load(conn)
+        })?;
+
+        let ip_list: Vec<String> = ips.iter().filter_map(|r| r.ip.clone()).collect();
+        for r in &ips {
+            if let Some(ip) = &r.ip {
+                let ip_id = format!("ip:{}", ip);
+                nodes
+                    .entry(ip_id.clone())
+                    .or_insert_with(|| Node::new(ip_id.clone(), "ip", ip.clone()));
+                out_edges.push(Edge {
+                    from: format!("actor:{}", a),
+                    to: ip_id,
+                    kind: "network".to_string(),
+                    weight: r.weight,
+                    failure: false,
+                    label: None,
+                    first_seen: r.first_seen,
+                    last_seen: r.last_seen,
+                });
             }
-            actor_ids.insert(e.actor_id.clone());
-            let src_id = format!("source:{}", e.source);
-            nodes.entry(src_id.clone()).or_insert_with(|| Node {
-                id: src_id.clone(),
-                node_type: "source".to_string(),
-                label: e.source.clone(),
-                risk: 0,
-            });
-            out_edges.push(Edge {
-                from: format!("actor:{}", e.actor_id),
-                to: src_id,
-                kind: "activity".to_string(),
-                weight: e.weight,
-                failure: e.failures > 0,
-            });
         }
 
-        // --- entity mode: add IP nodes + peer actors (shared infrastructure) --
-        if mode == "entity" {
-            let a = actor.as_deref().unwrap_or("");
-            let ips_sql = "SELECT e.source_ip AS ip, count(*) AS weight \
+        if !ip_list.is_empty() {
+            let peers_sql = "SELECT e.source_ip AS ip, aa.actor_id AS peer, count(*) AS weight, \
+                        min(e.ts) AS first_seen, max(e.ts) AS last_seen \
                  FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
-                 WHERE aa.actor_id = $1 AND e.source_ip IS NOT NULL AND e.ts >= $2 \
-                 GROUP BY e.source_ip ORDER BY weight DESC LIMIT 10";
-            let ips: Vec<IpRow> = q("graph.ips", ips_sql).in_scope(|| {
-                diesel::sql_query(ips_sql)
+                 WHERE e.source_ip = ANY($1) AND aa.actor_id <> $2 AND e.ts >= $3 AND e.ts <= $4 \
+                 GROUP BY e.source_ip, aa.actor_id ORDER BY weight DESC LIMIT 50";
+            let peers: Vec<PeerRow> = q("graph.peers", peers_sql).in_scope(|| {
+                diesel::sql_query(peers_sql)
+                    .bind::<Array<Text>, _>(&ip_list)
                     .bind::<Text, _>(a)
-                    .bind::<Timestamptz, _>(floor)
-                    .load(&mut conn)
+                    .bind::<Timestamptz, _>(from)
+                    .bind::<Timestamptz, _>(to)
+                    .load(conn)
             })?;
-
-            let ip_list: Vec<String> = ips.iter().filter_map(|r| r.ip.clone()).collect();
-            for r in &ips {
-                if let Some(ip) = &r.ip {
-                    let ip_id = format!("ip:{}", ip);
-                    nodes.entry(ip_id.clone()).or_insert_with(|| Node {
-                        id: ip_id.clone(),
-                        node_type: "ip".to_string(),
-                        label: ip.clone(),
-                        risk: 0,
-                    });
+            for p in &peers {
+                if let Some(ip) = &p.ip {
+                    actor_ids.insert(p.peer.clone());
                     out_edges.push(Edge {
-                        from: format!("actor:{}", a),
-                        to: ip_id,
-                        kind: "network".to_string(),
-                        weight: r.weight,
+                        from: format!("ip:{}", ip),
+                        to: format!("actor:{}", p.peer),
+                        kind: "shared-ip".to_string(),
+                        weight: p.weight,
                         failure: false,
+                        label: None,
+                        first_seen: p.first_seen,
+                        last_seen: p.last_seen,
                     });
                 }
             }
-
-            if !ip_list.is_empty() {
-                let peers_sql = "SELECT e.source_ip AS ip, aa.actor_id AS peer, count(*) AS weight \
-                     FROM ssumgmt_events e JOIN actor_aliases aa ON aa.alias = e.actor \
-                     WHERE e.source_ip = ANY($1) AND aa.actor_id <> $2 AND e.ts >= $3 \
-                     GROUP BY e.source_ip, aa.actor_id ORDER BY weight DESC LIMIT 50";
-                let peers: Vec<PeerRow> = q("graph.peers", peers_sql).in_scope(|| {
-                    diesel::sql_query(peers_sql)
-                        .bind::<Array<Text>, _>(&ip_list)
-                        .bind::<Text, _>(a)
-                        .bind::<Timestamptz, _>(floor)
-                        .load(&mut conn)
-                })?;
-                for p in &peers {
-                    if let Some(ip) = &p.ip {
-                        actor_ids.insert(p.peer.clone());
-                        out_edges.push(Edge {
-                            from: format!("ip:{}", ip),
-                            to: format!("actor:{}", p.peer),
-                            kind: "shared-ip".to_string(),
-                            weight: p.weight,
-                            failure: false,
-                        });
-                    }
-                }
-            }
         }
+    }
 
-        // --- actor node metadata (label + risk) -----------------------------
-        let id_vec: Vec<String> = actor_ids.iter().cloned().collect();
-        if !id_vec.is_empty() {
-            let metas_sql = "SELECT a.id AS id, COALESCE(a.display_name, a.id) AS label, a.kind AS kind, COALESCE(r.score, 0) AS score \
-                 FROM actors a LEFT JOIN risk_scores r ON r.actor_id = a.id WHERE a.id = ANY($1)";
-            let metas: Vec<ActorMeta> = q("graph.actor_meta", metas_sql)
-                .in_scope(|| diesel::sql_query(metas_sql).bind::<Array<Text>, _>(&id_vec).load(&mut conn))?;
-            for m in metas {
-                let node_id = format!("actor:{}", m.id);
-                nodes.insert(node_id.clone(), Node {
-                    id: node_id,
-                    node_type: "actor".to_string(),
-                    label: m.label,
-                    risk: m.score,
-                });
+    // --- actor node metadata (label, kind, team, risk) -------------------
+    let id_vec: Vec<String> = actor_ids.iter().cloned().collect();
+    add_actor_nodes(conn, &mut nodes, &id_vec)?;
+
+    Ok(Graph {
+        mode,
+        nodes: nodes.into_values().collect(),
+        edges: out_edges,
+        shown: actor_ids.len() as i64,
+        total: total_actors as i64,
+        from: Some(from),
+        to: Some(to),
+    })
+}
+
+/// Actor nodes (label, kind, team, risk) for `ids`. Any actor referenced by an
+/// edge but missing an actors row (unlikely) still gets a minimal node so the
+/// edge isn't dangling.
+fn add_actor_nodes(
+    conn: &mut PgConnection,
+    nodes: &mut BTreeMap<String, Node>,
+    ids: &[String],
+) -> anyhow::Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "graph.actor_meta",
+        db.statement = ACTOR_META_SQL
+    );
+    let metas: Vec<ActorMeta> = span.in_scope(|| {
+        diesel::sql_query(ACTOR_META_SQL)
+            .bind::<Array<Text>, _>(ids)
+            .load(conn)
+    })?;
+    for m in metas {
+        let node_id = format!("actor:{}", m.id);
+        nodes.insert(
+            node_id.clone(),
+            Node {
+                risk: m.score,
+                kind: Some(m.kind),
+                team: m.team,
+                ..Node::new(node_id, "actor", m.label)
+            },
+        );
+    }
+    for id in ids {
+        let node_id = format!("actor:{}", id);
+        nodes
+            .entry(node_id.clone())
+            .or_insert_with(|| Node::new(node_id, "actor", id.clone()));
+    }
+    Ok(())
+}
+
+/// `mode=roles`: the AWS role-assumption graph. Callers (actors, role sessions
+/// chaining onward, unresolved principals) point at the roles they assumed;
+/// `actor` keeps one actor's hops, `account` one account's.
+fn roles_graph(
+    conn: &mut PgConnection,
+    actor: Option<String>,
+    account: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> anyhow::Result<Graph> {
+    let actor = actor.filter(|a| !a.is_empty());
+    let account = account.filter(|a| !a.is_empty());
+    let total = role_graph::edge_count(conn, actor.as_deref(), account.as_deref(), from, to)?;
+    let edges = role_graph::edges(
+        conn,
+        actor.as_deref(),
+        account.as_deref(),
+        from,
+        to,
+        ROLE_EDGE_CAP,
+    )?;
+
+    let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+    let mut out_edges: Vec<Edge> = Vec::new();
+    let mut actor_ids: BTreeSet<String> = BTreeSet::new();
+    for e in &edges {
+        match e.src_kind.as_str() {
+            "actor" => {
+                actor_ids.insert(e.src.clone());
             }
-            // Any actor referenced by an edge but missing an actors row (unlikely)
-            // still gets a minimal node so the edge isn't dangling.
-            for id in &id_vec {
-                let node_id = format!("actor:{}", id);
-                nodes.entry(node_id.clone()).or_insert_with(|| Node {
-                    id: node_id.clone(),
-                    node_type: "actor".to_string(),
-                    label: id.clone(),
-                    risk: 0,
+            "role" => {
+                nodes
+                    .entry(e.src_node())
+                    .or_insert_with(|| role_node(&e.src, None));
+            }
+            _ => {
+                nodes.entry(e.src_node()).or_insert_with(|| Node {
+                    account: e.src_account.clone(),
+                    ..Node::new(e.src_node(), "principal", e.src.clone())
                 });
             }
         }
+        // The target side knows whether the role is privileged; it wins
+        // over a bare caller-side node for the same role.
+        nodes.insert(e.dst_node(), role_node(&e.dst_role, Some(e)));
+        out_edges.push(Edge {
+            from: e.src_node(),
+            to: e.dst_node(),
+            kind: "assumes".to_string(),
+            weight: e.event_count,
+            failure: false,
+            label: Some(format!(
+                "{} · {}",
+                e.via,
+                e.identity_source.as_deref().unwrap_or("?")
+            )),
+            first_seen: e.first_seen,
+            last_seen: e.last_seen,
+        });
+    }
 
-        let node_vec: Vec<&Node> = nodes.values().collect();
+    let id_vec: Vec<String> = actor_ids.into_iter().collect();
+    add_actor_nodes(conn, &mut nodes, &id_vec)?;
+
+    Ok(Graph {
+        mode: "roles".to_string(),
+        nodes: nodes.into_values().collect(),
+        edges: out_edges,
+        shown: edges.len() as i64,
+        total,
+        from,
+        to,
+    })
+}
+
+/// A role node labelled with the role name; `seen_as_target` carries the
+/// account and privileged flag derived for it.
+fn role_node(arn: &str, seen_as_target: Option<&RoleEdge>) -> Node {
+    Node {
+        account: seen_as_target
+            .and_then(|e| e.dst_account.clone())
+            .or_else(|| arn.split(':').nth(4).map(str::to_string)),
+        privileged: seen_as_target.map(|e| e.privileged),
+        ..Node::new(
+            format!("role:{}", arn),
+            "role",
+            arn.rsplit('/').next().unwrap_or(arn).to_string(),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PathParams {
+    /// The actor whose paths are wanted (required).
+    pub actor: Option<String>,
+    /// Only paths ending in this AWS account.
+    pub account: Option<String>,
+    /// Include paths ending in non-privileged roles too (default false).
+    pub all: Option<bool>,
+    /// Longest chain followed (default and max 6 hops).
+    pub depth: Option<usize>,
+    /// Most paths returned, shortest first (default 100).
+    pub limit: Option<usize>,
+}
+
+/// Every observed path by which an actor reached a (privileged) role,
+/// optionally in one account.
+async fn paths_handler(State(pool): State<DbPool>, Query(params): Query<PathParams>) -> Response {
+    let Some(actor) = params.actor.filter(|a| !a.is_empty()) else {
+        return (StatusCode::BAD_REQUEST, "paths require ?actor=").into_response();
+    };
+    let account = params.account.filter(|a| !a.is_empty());
+    let privileged_only = !params.all.unwrap_or(false);
+    let depth = params.depth.unwrap_or(role_graph::MAX_PATH_DEPTH);
+    let limit = params.limit.unwrap_or(DEFAULT_MAX_PATHS).clamp(1, 1000);
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "graph.paths",
+        entity.id = %actor
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<serde_json::Value> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        let (paths, truncated): (Vec<RolePath>, bool) = role_graph::paths(
+            &mut conn,
+            &actor,
+            account.as_deref(),
+            privileged_only,
+            depth,
+            limit,
+        )?;
         Ok(json!({
-            "nodes": node_vec,
-            "edges": out_edges,
-            "shownOf": { "shown": actor_ids.len(), "total": total_actors },
-            "mode": mode,
+            "actor": actor,
+            "account": account,
+            "privilegedOnly": privileged_only,
+            "paths": paths,
+            "truncated": truncated,
         }))
     })
     .await;
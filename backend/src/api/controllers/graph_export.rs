@@ -0,0 +1,404 @@
+//! `/api/graph/export`: the console graph as a file for link-analysis tools.
+//! GraphML loads into Gephi, yEd and Neo4j (APOC `import.graphml`), GEXF into
+//! Gephi, and Cytoscape JSON into Cytoscape and cytoscape.js. Every format
+//! carries the same attributes: per node `type`, `kind`, `risk`, `team`,
+//! `account` and `privileged`; per edge `kind`, `weight`, `failure`, `label`
+//! and the `first_seen`/`last_seen` range. Unset attributes are left out.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+
+use super::graph::{Graph, Node};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Format {
+    GraphMl,
+    Gexf,
+    Cytoscape,
+}
+
+impl Format {
+    pub(super) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "graphml" => Some(Format::GraphMl),
+            "gexf" => Some(Format::Gexf),
+            "cytoscape" => Some(Format::Cytoscape),
+            _ => None,
+        }
+    }
+
+    pub(super) fn content_type(self) -> &'static str {
+        match self {
+            Format::GraphMl => "application/graphml+xml; charset=utf-8",
+            Format::Gexf => "application/gexf+xml; charset=utf-8",
+            Format::Cytoscape => "application/json",
+        }
+    }
+
+    pub(super) fn extension(self) -> &'static str {
+        match self {
+            Format::GraphMl => "graphml",
+            Format::Gexf => "gexf",
+            Format::Cytoscape => "cyjs",
+        }
+    }
+}
+
+pub(super) fn render(g: &Graph, format: Format) -> String {
+    match format {
+        Format::GraphMl => graphml(g),
+        Format::Gexf => gexf(g),
+        Format::Cytoscape => cytoscape(g).to_string(),
+    }
+}
+
+/// `(attribute, value)` of a node's set attributes (the label aside), in
+/// output order.
+fn node_attrs(n: &Node) -> Vec<(&'static str, String)> {
+    let mut attrs = vec![("type", n.node_type.clone()), ("risk", n.risk.to_string())];
+    if let Some(k) = &n.kind {
+        attrs.push(("kind", k.clone()));
+    }
+    if let Some(t) = &n.team {
+        attrs.push(("team", t.clone()));
+    }
+    if let Some(a) = &n.account {
+        attrs.push(("account", a.clone()));
+    }
+    if let Some(p) = n.privileged {
+        attrs.push(("privileged", p.to_string()));
+    }
+    attrs
+}
+
+fn ts(t: DateTime<Utc>) -> String {
+    t.to_rfc3339()
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Not representable in XML 1.0; event fields are free text.
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn description(g: &Graph) -> String {
+    let bound = |t: Option<DateTime<Utc>>| t.map_or_else(|| "*".to_string(), ts);
+    format!(
+        "{} graph, {} to {}, {} of {} shown",
+        g.mode,
+        bound(g.from),
+        bound(g.to),
+        g.shown,
+        g.total
+    )
+}
+
+const GRAPHML_KEYS: [(&str, &str, &str, &str); 13] = [
+    // (id, for, attr.name, attr.type)
+    ("label", "node", "label", "string"),
+    ("type", "node", "type", "string"),
+    ("kind", "node", "kind", "string"),
+    ("risk", "node", "risk", "int"),
+    ("team", "node", "team", "string"),
+    ("account", "node", "account", "string"),
+    ("privileged", "node", "privileged", "boolean"),
+    ("e_kind", "edge", "kind", "string"),
+    ("weight", "edge", "weight", "long"),
+    ("failure", "edge", "failure", "boolean"),
+    ("e_label", "edge", "label", "string"),
+    ("first_seen", "edge", "first_seen", "string"),
+    ("last_seen", "edge", "last_seen", "string"),
+];
+
+fn graphml(g: &Graph) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+    );
+    for (id, of, name, ty) in GRAPHML_KEYS {
+        out.push_str(&format!(
+            "  <key id=\"{id}\" for=\"{of}\" attr.name=\"{name}\" attr.type=\"{ty}\"/>\n"
+        ));
+    }
+    out.push_str(&format!(
+        "  <graph id=\"{}\" edgedefault=\"directed\">\n    <desc>{}</desc>\n",
+        xml_escape(&g.mode),
+        xml_escape(&description(g))
+    ));
+    for n in &g.nodes {
+        out.push_str(&format!("    <node id=\"{}\">\n", xml_escape(&n.id)));
+        out.push_str(&format!(
+            "      <data key=\"label\">{}</data>\n",
+            xml_escape(&n.label)
+        ));
+        for (k, v) in node_attrs(n) {
+            out.push_str(&format!(
+                "      <data key=\"{k}\">{}</data>\n",
+                xml_escape(&v)
+            ));
+        }
+        out.push_str("    </node>\n");
+    }
+    for (i, e) in g.edges.iter().enumerate() {
+        out.push_str(&format!(
+            "    <edge id=\"e{i}\" source=\"{}\" target=\"{}\">\n",
+            xml_escape(&e.from),
+            xml_escape(&e.to)
+        ));
+        out.push_str(&format!(
+            "      <data key=\"e_kind\">{}</data>\n      <data key=\"weight\">{}</data>\n      \
+             <data key=\"failure\">{}</data>\n",
+            xml_escape(&e.kind),
+            e.weight,
+            e.failure
+        ));
+        if let Some(l) = &e.label {
+            out.push_str(&format!(
+                "      <data key=\"e_label\">{}</data>\n",
+                xml_escape(l)
+            ));
+        }
+        out.push_str(&format!(
+            "      <data key=\"first_seen\">{}</data>\n      <data key=\"last_seen\">{}</data>\n    </edge>\n",
+            ts(e.first_seen),
+            ts(e.last_seen)
+        ));
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+const GEXF_NODE_ATTRS: [(&str, &str); 6] = [
+    ("type", "string"),
+    ("kind", "string"),
+    ("risk", "integer"),
+    ("team", "string"),
+    ("account", "string"),
+    ("privileged", "boolean"),
+];
+
+const GEXF_EDGE_ATTRS: [(&str, &str); 5] = [
+    ("kind", "string"),
+    ("failure", "boolean"),
+    ("label", "string"),
+    ("first_seen", "string"),
+    ("last_seen", "string"),
+];
+
+fn gexf(g: &Graph) -> String {
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n  \
+         <meta lastmodifieddate=\"{}\">\n    <creator>ssu-mgmt</creator>\n    \
+         <description>{}</description>\n  </meta>\n  \
+         <graph defaultedgetype=\"directed\" mode=\"static\">\n",
+        Utc::now().format("%Y-%m-%d"),
+        xml_escape(&description(g))
+    );
+    for (class, attrs) in [
+        ("node", &GEXF_NODE_ATTRS[..]),
+        ("edge", &GEXF_EDGE_ATTRS[..]),
+    ] {
+        out.push_str(&format!("    <attributes class=\"{class}\">\n"));
+        for (id, ty) in attrs {
+            out.push_str(&format!(
+                "      <attribute id=\"{id}\" title=\"{id}\" type=\"{ty}\"/>\n"
+            ));
+        }
+        out.push_str("    </attributes>\n");
+    }
+
+    out.push_str("    <nodes>\n");
+    for n in &g.nodes {
+        out.push_str(&format!(
+            "      <node id=\"{}\" label=\"{}\">\n        <attvalues>\n",
+            xml_escape(&n.id),
+            xml_escape(&n.label)
+        ));
+        for (k, v) in node_attrs(n) {
+            out.push_str(&format!(
+                "          <attvalue for=\"{k}\" value=\"{}\"/>\n",
+                xml_escape(&v)
+            ));
+        }
+        out.push_str("        </attvalues>\n      </node>\n");
+    }
+    out.push_str("    </nodes>\n    <edges>\n");
+    for (i, e) in g.edges.iter().enumerate() {
+        out.push_str(&format!(
+            "      <edge id=\"e{i}\" source=\"{}\" target=\"{}\" weight=\"{}\">\n        <attvalues>\n",
+            xml_escape(&e.from),
+            xml_escape(&e.to),
+            e.weight
+        ));
+        let mut attrs = vec![("kind", e.kind.clone()), ("failure", e.failure.to_string())];
+        if let Some(l) = &e.label {
+            attrs.push(("label", l.clone()));
+        }
+        attrs.push(("first_seen", ts(e.first_seen)));
+        attrs.push(("last_seen", ts(e.last_seen)));
+        for (k, v) in attrs {
+            out.push_str(&format!(
+                "          <attvalue for=\"{k}\" value=\"{}\"/>\n",
+                xml_escape(&v)
+            ));
+        }
+        out.push_str("        </attvalues>\n      </edge>\n");
+    }
+    out.push_str("    </edges>\n  </graph>\n</gexf>\n");
+    out
+}
+
+/// Cytoscape's `elements` JSON. `name` doubles the label, since that is the
+/// column Cytoscape desktop labels nodes by.
+fn cytoscape(g: &Graph) -> Value {
+    let nodes: Vec<Value> = g
+        .nodes
+        .iter()
+        .map(|n| {
+            let mut data = Map::new();
+            data.insert("id".into(), json!(n.id));
+            data.insert("name".into(), json!(n.label));
+            data.insert("label".into(), json!(n.label));
+            data.insert("type".into(), json!(n.node_type));
+            data.insert("risk".into(), json!(n.risk));
+            if let Some(k) = &n.kind {
+                data.insert("kind".into(), json!(k));
+            }
+            if let Some(t) = &n.team {
+                data.insert("team".into(), json!(t));
+            }
+            if let Some(a) = &n.account {
+                data.insert("account".into(), json!(a));
+            }
+            if let Some(p) = n.privileged {
+                data.insert("privileged".into(), json!(p));
+            }
+            json!({ "data": data })
+        })
+        .collect();
+    let edges: Vec<Value> = g
+        .edges
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let mut data = Map::new();
+            data.insert("id".into(), json!(format!("e{i}")));
+            data.insert("source".into(), json!(e.from));
+            data.insert("target".into(), json!(e.to));
+            data.insert("kind".into(), json!(e.kind));
+            data.insert("weight".into(), json!(e.weight));
+            data.insert("failure".into(), json!(e.failure));
+            if let Some(l) = &e.label {
+                data.insert("label".into(), json!(l));
+            }
+            data.insert("first_seen".into(), json!(e.first_seen));
+            data.insert("last_seen".into(), json!(e.last_seen));
+            json!({ "data": data })
+        })
+        .collect();
+
+    json!({
+        "format_version": "1.0",
+        "generated_by": "ssu-mgmt",
+        "target_cytoscapejs_version": "~2.1",
+        "data": {
+            "name": description(g),
+            "mode": g.mode,
+            "from": g.from,
+            "to": g.to,
+            "shown": g.shown,
+            "total": g.total,
+        },
+        "elements": { "nodes": nodes, "edges": edges },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::graph::Edge;
+    use super::*;
+
+    fn sample() -> Graph {
+        let t = DateTime::parse_from_rfc3339("2026-10-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        Graph {
+            mode: "entity".to_string(),
+            nodes: vec![
+                Node {
+                    id: "actor:a&b".to_string(),
+                    node_type: "actor".to_string(),
+                    label: "A <B>".to_string(),
+                    risk: 42,
+                    kind: Some("human".to_string()),
+                    team: Some("platform".to_string()),
+                    account: None,
+                    privileged: None,
+                },
+                Node {
+                    id: "source:github".to_string(),
+                    node_type: "source".to_string(),
+                    label: "github".to_string(),
+                    risk: 0,
+                    kind: None,
+                    team: None,
+                    account: None,
+                    privileged: None,
+                },
+            ],
+            edges: vec![Edge {
+                from: "actor:a&b".to_string(),
+                to: "source:github".to_string(),
+                kind: "activity".to_string(),
+                weight: 7,
+                failure: true,
+                label: None,
+                first_seen: t,
+                last_seen: t,
+            }],
+            shown: 1,
+            total: 1,
+            from: Some(t),
+            to: None,
+        }
+    }
+
+    #[test]
+    fn exports_carry_attributes_and_escape_text() {
+        let g = sample();
+
+        let ml = graphml(&g);
+        assert!(ml.contains("<node id=\"actor:a&amp;b\">"));
+        assert!(ml.contains("<data key=\"label\">A &lt;B&gt;</data>"));
+        assert!(ml.contains("<data key=\"team\">platform</data>"));
+        assert!(ml.contains("source=\"actor:a&amp;b\" target=\"source:github\""));
+        assert!(ml.contains("<data key=\"failure\">true</data>"));
+        assert!(!ml.contains("<data key=\"e_label\">"));
+
+        let gx = gexf(&g);
+        assert!(gx.contains("weight=\"7\""));
+        assert!(gx.contains("<attvalue for=\"risk\" value=\"42\"/>"));
+        assert!(gx.contains("to *"));
+
+        let cy = cytoscape(&g);
+        assert_eq!(cy["elements"]["nodes"][0]["data"]["kind"], "human");
+        assert!(cy["elements"]["nodes"][1]["data"].get("team").is_none());
+        assert_eq!(cy["elements"]["edges"][0]["data"]["weight"], 7);
+
+        assert_eq!(xml_escape("a\u{1}b\n"), "ab\n");
+        assert_eq!(Format::parse("gexf"), Some(Format::Gexf));
+        assert_eq!(Format::parse("dot"), None);
+    }
+}
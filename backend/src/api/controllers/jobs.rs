@@ -0,0 +1,204 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json, Router};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::entity::{db_span, outcome_response};
+use super::query::{compile_params, Compiled, QueryParams, SELECT_COLS};
+use crate::api::auth::principal_of;
+use crate::db::DbPool;
+use crate::service::outcome::Outcome;
+use crate::service::query_jobs::{self, JobPage, JobRequest, JobRunner, QueryJob};
+
+const DEFAULT_PAGE: i64 = 100;
+const MAX_PAGE: i64 = 1_000;
+
+#[derive(Clone)]
+struct JobsState {
+    /// Job bookkeeping is quick and runs on the API pool; only the job
+    /// itself runs on the runner's pool.
+    pool: DbPool,
+    runner: JobRunner,
+}
+
+pub fn routes(pool: DbPool, runner: JobRunner) -> Router {
+    Router::new()
+        .route("/", axum::routing::get(list_handler).post(submit_handler))
+        .route(
+            "/:id",
+            axum::routing::get(get_handler).delete(delete_handler),
+        )
+        .route("/:id/results", axum::routing::get(results_handler))
+        .route("/:id/cancel", axum::routing::post(cancel_handler))
+        .with_state(JobsState { pool, runner })
+}
+
+/// The `/api/query` compiler over a job's spec.
+fn compile(spec: &JobRequest) -> Result<Compiled, String> {
+    compile_params(&QueryParams {
+        ast: spec.ast.as_ref().map(Value::to_string),
+        q: spec.q.clone(),
+        status: spec.status.clone(),
+        source: spec.source.clone(),
+        from: spec.from.clone(),
+        to: spec.to.clone(),
+        order_by: spec.order_by.clone(),
+        order_dir: spec.order_dir.clone(),
+        ..QueryParams::default()
+    })
+}
+
+/// Queue a query for background execution. Answers 202 with the job; poll
+/// `/:id` for status and progress and page rows from `/:id/results`.
+async fn submit_handler(
+    State(state): State<JobsState>,
+    claims: Option<Extension<Value>>,
+    Json(spec): Json<JobRequest>,
+) -> Response {
+    let Compiled {
+        where_sql,
+        binds,
+        order_sql,
+        ..
+    } = match compile(&spec) {
+        Ok(c) => c,
+        Err(msg) => return (StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    let rows_sql =
+        format!("SELECT {SELECT_COLS} FROM ssumgmt_events WHERE {where_sql} ORDER BY {order_sql}");
+
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("query_jobs.submit");
+    let pool = state.pool.clone();
+    let conf = state.runner.conf().clone();
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<QueryJob>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        query_jobs::submit(&mut conn, &conf, &spec, &who)
+    })
+    .await;
+
+    if let Ok(Ok(Outcome::Done(job))) = &res {
+        state.runner.start(job.id.clone(), rows_sql, binds);
+    }
+    outcome_response(res, StatusCode::ACCEPTED)
+}
+
+/// The caller's jobs, newest first.
+async fn list_handler(
+    State(state): State<JobsState>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("query_jobs.list");
+    let pool = state.pool;
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<QueryJob>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        query_jobs::list(&mut conn, &who)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_handler(
+    State(state): State<JobsState>,
+    Path(id): Path<String>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("query_jobs.get");
+    let pool = state.pool;
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<QueryJob>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        Ok(query_jobs::get(&mut conn, &id, &who)?.map_or(Outcome::NotFound, Outcome::Done))
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct PageParams {
+    /// Rows to skip. Default 0.
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// A page of the job's rows. Works while the job is still running, over the
+/// rows materialised so far.
+async fn results_handler(
+    State(state): State<JobsState>,
+    Path(id): Path<String>,
+    Query(params): Query<PageParams>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let offset = params.offset.unwrap_or(0).max(0);
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE);
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("query_jobs.results");
+    let pool = state.pool;
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<JobPage>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        Ok(query_jobs::results(&mut conn, &id, &who, offset, limit)?
+            .map_or(Outcome::NotFound, Outcome::Done))
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+/// Stop a queued or running job; rows already materialised stay readable.
+async fn cancel_handler(
+    State(state): State<JobsState>,
+    Path(id): Path<String>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("query_jobs.cancel");
+    let pool = state.pool;
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<QueryJob>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        query_jobs::cancel(&mut conn, &id, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+/// Drop a finished job and its rows ahead of expiry. 409 while it's active.
+async fn delete_handler(
+    State(state): State<JobsState>,
+    Path(id): Path<String>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("query_jobs.delete");
+    let pool = state.pool;
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<()>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        query_jobs::delete(&mut conn, &id, &who)
+    })
+    .await;
+    match res {
+        Ok(Ok(Outcome::Done(()))) => StatusCode::NO_CONTENT.into_response(),
+        other => outcome_response(other, StatusCode::NO_CONTENT),
+    }
+}
@@ -2,12 +2,23 @@ mod actors;
 mod alerts;
 pub mod auth_config;
 mod entity;
+mod entity_ip;
+mod entity_resource;
 mod graph;
+mod graph_export;
+mod jobs;
 mod meta;
 mod overview;
 pub mod progress;
 mod query;
-mod query_ast;
+mod query_agg;
+pub mod query_ast;
+mod query_facets;
+pub mod query_text;
+mod reviews;
+mod searches;
+pub mod tail;
+mod teams;
 
 use crate::api::WebSharedState;
 use axum::Router;
@@ -37,5 +48,18 @@ pub fn add_controllers(mut router: Router, state: WebSharedState) -> Router {
     let meta_routes = meta::routes(state.db_pool.clone()).layer(role_layer());
     router = router.nest("/meta", meta_routes);
 
+    let reviews_routes = reviews::routes(state.db_pool.clone()).layer(role_layer());
+    router = router.nest("/reviews", reviews_routes);
+
+    let teams_routes = teams::routes(state.db_pool.clone()).layer(role_layer());
+    router = router.nest("/teams", teams_routes);
+
+    let searches_routes = searches::routes(state.db_pool.clone()).layer(role_layer());
+    router = router.nest("/searches", searches_routes);
+
+    let jobs_routes =
+        jobs::routes(state.db_pool.clone(), state.query_jobs.clone()).layer(role_layer());
+    router = router.nest("/jobs", jobs_routes);
+
     router
 }
@@ -24,6 +24,14 @@ pub fn routes(pool: DbPool) -> Router {
             "/actors-by-risk",
             axum::routing::get(actors_by_risk_handler),
         )
+        .route(
+            "/attack-coverage",
+            axum::routing::get(attack_coverage_handler),
+        )
+        .route("/threat-intel", axum::routing::get(threat_intel_handler))
+        .route("/risk-movers", axum::routing::get(risk_movers_handler))
+        .route("/risk-trend", axum::routing::get(risk_trend_handler))
+        .route("/mfa-logins", axum::routing::get(mfa_logins_handler))
         .with_state(pool)
 }
 
@@ -49,6 +57,10 @@ struct KpiRow {
     guardduty_open: i64,
     #[diesel(sql_type = BigInt)]
     anomalies_24h: i64,
+    #[diesel(sql_type = BigInt)]
+    password_logins_7d: i64,
+    #[diesel(sql_type = BigInt)]
+    mfa_logins_7d: i64,
 }
 
 /// Build the overview KPI payload on a pooled connection. Shared by the HTTP
@@ -67,7 +79,11 @@ pub(crate) fn load_kpis(conn: &mut PgConnection) -> anyhow::Result<serde_json::V
            (SELECT count(*) FROM risk_scores WHERE score >= 60) AS high_risk_actors, \
            (SELECT count(*) FROM sessions WHERE status = 'active') AS active_sessions, \
            (SELECT count(*) FROM alerts WHERE source = 'guardduty' AND status IN ('open','acked')) AS guardduty_open, \
-           (SELECT count(*) FROM anomalies WHERE event_time >= now() - interval '24 hours') AS anomalies_24h",
+           (SELECT count(*) FROM anomalies WHERE event_time >= now() - interval '24 hours') AS anomalies_24h, \
+           (SELECT count(*) FROM cloudtrail_events WHERE event_name = 'ConsoleLogin' AND auth_method = 'password' AND error_code IS NULL \
+              AND raw->'responseElements'->>'ConsoleLogin' = 'Success' AND event_time >= now() - interval '7 days') AS password_logins_7d, \
+           (SELECT count(*) FROM cloudtrail_events WHERE event_name = 'ConsoleLogin' AND auth_method = 'password' AND error_code IS NULL \
+              AND raw->'responseElements'->>'ConsoleLogin' = 'Success' AND mfa_used AND event_time >= now() - interval '7 days') AS mfa_logins_7d",
     )
     .get_result(conn)?;
 
@@ -87,6 +103,13 @@ pub(crate) fn load_kpis(conn: &mut PgConnection) -> anyhow::Result<serde_json::V
         "actors_tracked": k.actors_tracked,
         "high_risk_actors": k.high_risk_actors,
         "active_sessions": k.active_sessions,
+        // Federated sign-ins carry no usable MFA flag, so the share covers
+        // successful password logins only; `null` when there were none.
+        "mfa_login_share_7d": if k.password_logins_7d > 0 {
+            json!(k.mfa_logins_7d as f64 / k.password_logins_7d as f64)
+        } else {
+            json!(null)
+        },
     }))
 }
 
@@ -134,6 +157,8 @@ struct CountRow {
 pub struct AlertsParams {
     pub severity: Option<String>,
     pub status: Option<String>,
+    /// Routing target (team or person) stamped from the actor's owner.
+    pub owner: Option<String>,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
 }
@@ -185,6 +210,9 @@ async fn alerts_handler(
         if let Some(s) = &params.status {
             q = q.filter(a::status.eq(s.clone()));
         }
+        if let Some(o) = &params.owner {
+            q = q.filter(a::owner.eq(o.clone()));
+        }
         let rows = q
             .order(a::last_seen.desc())
             .limit(limit)
@@ -200,6 +228,9 @@ async fn alerts_handler(
         if let Some(s) = &params.status {
             cq = cq.filter(a::status.eq(s.clone()));
         }
+        if let Some(o) = &params.owner {
+            cq = cq.filter(a::owner.eq(o.clone()));
+        }
         let total: i64 = cq.count().get_result(&mut conn)?;
 
         Ok(AlertsResponse { rows, total })
@@ -595,3 +626,522 @@ async fn timeline_handler(
             .into_response(),
     }
 }
+
+// --- ATT&CK coverage -------------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct AttackCoverageParams {
+    /// Trailing window (days) for the hit counts. Defaults to 30.
+    pub days: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct TechniqueHitRow {
+    #[diesel(sql_type = Text)]
+    technique: String,
+    #[diesel(sql_type = BigInt)]
+    hits: i64,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    last_hit: Option<DateTime<Utc>>,
+}
+
+#[derive(QueryableByName)]
+struct AnomalyKindHitRow {
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = BigInt)]
+    hits: i64,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    last_hit: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct CoverageDetection {
+    id: &'static str,
+    kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct CoverageTechnique {
+    id: String,
+    name: Option<&'static str>,
+    detections: Vec<CoverageDetection>,
+    covered: bool,
+    hits: i64,
+    last_hit: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct CoverageTactic {
+    id: &'static str,
+    name: &'static str,
+    techniques: Vec<CoverageTechnique>,
+}
+
+#[derive(Serialize)]
+struct CoverageResponse {
+    window_days: i64,
+    tactics: Vec<CoverageTactic>,
+    /// Techniques seen on alerts (GuardDuty's own indicators) that the static
+    /// catalogue doesn't know, so they can't be placed under a tactic.
+    uncatalogued: Vec<CoverageTechnique>,
+    covered: usize,
+    total: usize,
+}
+
+/// ATT&CK matrix (tactic → technique) with the rules, anomaly kinds and GuardDuty
+/// finding types covering each technique, plus alert and anomaly hits over the
+/// trailing window. A technique with no detections is a detection gap.
+async fn attack_coverage_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<AttackCoverageParams>,
+) -> Response {
+    use crate::service::siem::attack;
+    use std::collections::HashMap;
+
+    let days = params.days.unwrap_or(30).clamp(1, 365);
+    let floor = Utc::now() - Duration::days(days);
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "overview.attack_coverage",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(
+        move || -> diesel::QueryResult<(Vec<TechniqueHitRow>, Vec<AnomalyKindHitRow>)> {
+            let _g = span.enter();
+            let mut conn = crate::db::conn(&pool)?;
+            let alerts_sql =
+                "SELECT t AS technique, count(*)::bigint AS hits, max(a.last_seen) AS last_hit \
+                 FROM alerts a, unnest(a.attack_techniques) t \
+                 WHERE a.last_seen >= $1 AND t IS NOT NULL \
+                 GROUP BY t";
+            span.record("db.statement", alerts_sql);
+            let alert_hits = diesel::sql_query(alerts_sql)
+                .bind::<Timestamptz, _>(floor)
+                .load::<TechniqueHitRow>(&mut conn)?;
+            let anomaly_hits = diesel::sql_query(
+                "SELECT kind, count(*)::bigint AS hits, max(event_time) AS last_hit \
+                 FROM anomalies WHERE event_time >= $1 GROUP BY kind",
+            )
+            .bind::<Timestamptz, _>(floor)
+            .load::<AnomalyKindHitRow>(&mut conn)?;
+            Ok((alert_hits, anomaly_hits))
+        },
+    )
+    .await;
+
+    let (alert_hits, anomaly_hits) = match res {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("db error: {}", e),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("task join error: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    // Fold alert hits (tagged per row) and anomaly hits (tagged per kind via the
+    // catalogue) into one per-technique tally.
+    let mut hits: HashMap<String, (i64, Option<DateTime<Utc>>)> = HashMap::new();
+    let mut bump = |technique: &str, n: i64, last: Option<DateTime<Utc>>| {
+        let e = hits.entry(technique.to_string()).or_insert((0, None));
+        e.0 += n;
+        e.1 = e.1.max(last);
+    };
+    for h in &alert_hits {
+        bump(&h.technique, h.hits, h.last_hit);
+    }
+    for h in &anomaly_hits {
+        if let Some(d) = attack::lookup("anomaly", &h.kind) {
+            for t in d.techniques {
+                bump(t, h.hits, h.last_hit);
+            }
+        }
+    }
+
+    let entry_for = |id: &str| -> CoverageTechnique {
+        let detections: Vec<CoverageDetection> = attack::DETECTIONS
+            .iter()
+            .filter(|d| d.techniques.contains(&id))
+            .map(|d| CoverageDetection {
+                id: d.id,
+                kind: d.kind,
+            })
+            .collect();
+        let (n, last) = hits.get(id).cloned().unwrap_or((0, None));
+        CoverageTechnique {
+            id: id.to_string(),
+            name: attack::technique(id).map(|t| t.name),
+            covered: !detections.is_empty(),
+            detections,
+            hits: n,
+            last_hit: last,
+        }
+    };
+
+    let tactics: Vec<CoverageTactic> = attack::TACTICS
+        .iter()
+        .map(|ta| CoverageTactic {
+            id: ta.id,
+            name: ta.name,
+            techniques: attack::TECHNIQUES
+                .iter()
+                .filter(|te| te.tactics.contains(&ta.id))
+                .map(|te| entry_for(te.id))
+                .collect(),
+        })
+        .collect();
+    let mut uncatalogued: Vec<CoverageTechnique> = hits
+        .keys()
+        .filter(|id| attack::technique(id).is_none())
+        .map(|id| entry_for(id))
+        .collect();
+    uncatalogued.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let covered = attack::TECHNIQUES
+        .iter()
+        .filter(|te| {
+            attack::DETECTIONS
+                .iter()
+                .any(|d| d.techniques.contains(&te.id))
+        })
+        .count();
+
+    Json(CoverageResponse {
+        window_days: days,
+        tactics,
+        uncatalogued,
+        covered,
+        total: attack::TECHNIQUES.len(),
+    })
+    .into_response()
+}
+
+// --- Threat-intel feeds ----------------------------------------------------
+
+#[derive(QueryableByName, Serialize)]
+struct FeedStatusRow {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Text)]
+    format: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    confidence: i32,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    ttl_days: i32,
+    #[diesel(sql_type = BigInt)]
+    indicators: i64,
+    #[diesel(sql_type = BigInt)]
+    hits_7d: i64,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    loaded_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    next_expiry: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<Text>)]
+    last_error: Option<String>,
+}
+
+/// Feed health for the threat-intel panel: live indicator count, recent hits,
+/// last successful load and any load error per configured feed.
+async fn threat_intel_handler(State(pool): State<DbPool>) -> Response {
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "overview.threat_intel",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<FeedStatusRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let sql = "SELECT f.name, f.format, f.confidence, f.ttl_days, \
+                   (SELECT count(*) FROM threat_indicators i WHERE i.feed = f.name AND i.expires_at > now()) AS indicators, \
+                   (SELECT count(*) FROM threat_intel_hits h WHERE h.feed = f.name AND h.event_time >= now() - interval '7 days') AS hits_7d, \
+                   f.loaded_at, \
+                   (SELECT min(i.expires_at) FROM threat_indicators i WHERE i.feed = f.name AND i.expires_at > now()) AS next_expiry, \
+                   f.last_error \
+             FROM threat_intel_feeds f ORDER BY f.name";
+        span.record("db.statement", sql);
+        diesel::sql_query(sql).load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+// --- Risk movers / trend ---------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct RiskMoversParams {
+    pub hours: Option<i64>,
+    pub limit: Option<i64>,
+    /// `up` (default), `down` or `both`.
+    pub direction: Option<String>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct RiskMoverRow {
+    #[diesel(sql_type = Text)]
+    id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    display_name: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    email: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    team: Option<String>,
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    score: i32,
+    #[diesel(sql_type = Text)]
+    label: String,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    baseline: i32,
+    #[diesel(sql_type = Timestamptz)]
+    baseline_at: DateTime<Utc>,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    delta: i32,
+}
+
+/// Actors whose score moved most over the last `hours`. The baseline is the
+/// last history point at or before the window start, falling back to the
+/// first point after it for actors first scored inside the window.
+async fn risk_movers_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<RiskMoversParams>,
+) -> Response {
+    let hours = params.hours.unwrap_or(24).clamp(1, 24 * 90);
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let (filter, order) = match params.direction.as_deref().unwrap_or("up") {
+        "up" => ("> 0", "delta DESC"),
+        "down" => ("< 0", "delta ASC"),
+        "both" => ("<> 0", "abs(delta) DESC"),
+        other => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("invalid direction: {}", other),
+            )
+                .into_response()
+        }
+    };
+    let start = Utc::now() - Duration::hours(hours);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "overview.risk_movers",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<RiskMoverRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let sql = format!(
+            "SELECT * FROM ( \
+               SELECT a.id, a.display_name, a.email, a.team, a.kind, r.score, r.label, \
+                 b.score AS baseline, b.bucket AS baseline_at, r.score - b.score AS delta \
+               FROM risk_scores r \
+               JOIN actors a ON a.id = r.actor_id \
+               CROSS JOIN LATERAL ( \
+                 SELECT score, bucket FROM ( \
+                   (SELECT h.score, h.bucket, 0 AS pref FROM risk_score_history h \
+                    WHERE h.actor_id = r.actor_id AND h.bucket <= $1 ORDER BY h.bucket DESC LIMIT 1) \
+                   UNION ALL \
+                   (SELECT h.score, h.bucket, 1 AS pref FROM risk_score_history h \
+                    WHERE h.actor_id = r.actor_id AND h.bucket > $1 ORDER BY h.bucket ASC LIMIT 1) \
+                 ) c ORDER BY pref LIMIT 1 \
+               ) b \
+             ) m WHERE delta {filter} ORDER BY {order}, score DESC LIMIT $2"
+        );
+        span.record("db.statement", sql.as_str());
+        diesel::sql_query(sql)
+            .bind::<Timestamptz, _>(start)
+            .bind::<BigInt, _>(limit)
+            .load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RiskTrendParams {
+    pub team: Option<String>,
+    pub days: Option<i64>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct RiskTrendRow {
+    #[diesel(sql_type = Timestamptz)]
+    day: DateTime<Utc>,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    avg_score: f64,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    max_score: i32,
+    #[diesel(sql_type = BigInt)]
+    actors: i64,
+}
+
+/// Daily risk series for one team (or everyone without `team`): the mean of
+/// each actor's sample-weighted daily score, and the highest score seen.
+async fn risk_trend_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<RiskTrendParams>,
+) -> Response {
+    let days = params.days.unwrap_or(90).clamp(1, 730);
+    let floor = Utc::now() - Duration::days(days);
+    let team = params.team.filter(|t| !t.is_empty());
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "overview.risk_trend",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<RiskTrendRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let sql = "SELECT day, round(avg(actor_avg)::numeric, 1)::float8 AS avg_score, \
+                   max(actor_max) AS max_score, count(*) AS actors \
+             FROM ( \
+               SELECT h.actor_id, date_trunc('day', h.bucket) AS day, \
+                 sum(h.score::float8 * h.samples) / sum(h.samples) AS actor_avg, max(h.max_score) AS actor_max \
+               FROM risk_score_history h JOIN actors a ON a.id = h.actor_id \
+               WHERE h.bucket >= $1 AND ($2::text IS NULL OR a.team = $2) \
+               GROUP BY h.actor_id, date_trunc('day', h.bucket) \
+             ) d GROUP BY day ORDER BY day";
+        span.record("db.statement", sql);
+        diesel::sql_query(sql)
+            .bind::<Timestamptz, _>(floor)
+            .bind::<Nullable<Text>, _>(team)
+            .load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+// --- MFA on console logins -------------------------------------------------
+
+#[derive(Deserialize)]
+pub struct MfaLoginsParams {
+    pub days: Option<i64>,
+}
+
+#[derive(QueryableByName, Serialize)]
+struct MfaLoginRow {
+    #[diesel(sql_type = Nullable<Text>)]
+    account: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    logins: i64,
+    #[diesel(sql_type = BigInt)]
+    mfa: i64,
+    #[diesel(sql_type = BigInt)]
+    no_mfa: i64,
+    /// Federated sign-ins, whose MFA happened (or not) at the IdP.
+    #[diesel(sql_type = BigInt)]
+    unknown: i64,
+    /// `mfa / (mfa + no_mfa)`; `null` for accounts with only federated logins.
+    #[diesel(sql_type = Nullable<diesel::sql_types::Double>)]
+    share: Option<f64>,
+}
+
+/// Successful console logins per account over the last `days` (default 7),
+/// split by MFA outcome; least-covered accounts first.
+async fn mfa_logins_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<MfaLoginsParams>,
+) -> Response {
+    let days = params.days.unwrap_or(7).clamp(1, 90);
+    let floor = Utc::now() - Duration::days(days);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "overview.mfa_logins",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<MfaLoginRow>> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool)?;
+        let sql = "SELECT account, logins, mfa, no_mfa, unknown, \
+                   CASE WHEN mfa + no_mfa > 0 THEN mfa::float8 / (mfa + no_mfa) END AS share \
+             FROM ( \
+               SELECT recipient_account_id AS account, count(*) AS logins, \
+                 count(*) FILTER (WHERE mfa_used) AS mfa, \
+                 count(*) FILTER (WHERE NOT mfa_used) AS no_mfa, \
+                 count(*) FILTER (WHERE mfa_used IS NULL) AS unknown \
+               FROM cloudtrail_events \
+               WHERE event_name = 'ConsoleLogin' AND error_code IS NULL AND event_time >= $1 \
+                 AND COALESCE(raw->'responseElements'->>'ConsoleLogin', 'Success') = 'Success' \
+               GROUP BY recipient_account_id \
+             ) a ORDER BY share ASC NULLS LAST, logins DESC";
+        span.record("db.statement", sql);
+        diesel::sql_query(sql)
+            .bind::<Timestamptz, _>(floor)
+            .load(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
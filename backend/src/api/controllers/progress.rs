@@ -36,6 +36,15 @@ async fn ws_handler(
     State(state): State<WebSharedState>,
     headers: HeaderMap,
 ) -> Response {
+    let authed = authorize(&state, &headers).await;
+    let pool = state.db_pool.clone();
+    ws.protocols(["bearer"])
+        .on_upgrade(move |socket| run(socket, pool, authed))
+}
+
+/// Whether a WS upgrade carries a valid `ce.cloudengineer` token. Shared with
+/// the live tail socket, which authenticates the same way.
+pub(super) async fn authorize(state: &WebSharedState, headers: &HeaderMap) -> bool {
     let conf = load_conf().unwrap();
 
     // Pull the bearer token out of the WS subprotocol header (`bearer, <jwt>`).
@@ -50,19 +59,25 @@ async fn ws_handler(
             }
         });
 
-    let authed = if conf.api_enable_auth {
+    if conf.api_enable_auth {
         match token.as_deref() {
-            Some(t) => validate(&state, t).await,
+            Some(t) => validate(state, t).await,
             None => false,
         }
     } else {
         // Dev parity with `role_check`/`auth_oauth`: when auth is off, accept.
         true
-    };
+    }
+}
 
-    let pool = state.db_pool.clone();
-    ws.protocols(["bearer"])
-        .on_upgrade(move |socket| run(socket, pool, authed))
+/// Close with [`CLOSE_UNAUTHORIZED`] so the client stops reconnecting.
+pub(super) async fn close_unauthorized(socket: &mut WebSocket) {
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: CLOSE_UNAUTHORIZED,
+            reason: "unauthorized".into(),
+        })))
+        .await;
 }
 
 async fn validate(state: &WebSharedState, token: &str) -> bool {
@@ -86,12 +101,7 @@ async fn validate(state: &WebSharedState, token: &str) -> bool {
 
 async fn run(mut socket: WebSocket, pool: DbPool, authed: bool) {
     if !authed {
-        let _ = socket
-            .send(Message::Close(Some(CloseFrame {
-                code: CLOSE_UNAUTHORIZED,
-                reason: "unauthorized".into(),
-            })))
-            .await;
+        close_unauthorized(&mut socket).await;
         return;
     }
 
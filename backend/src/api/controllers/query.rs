@@ -6,10 +6,12 @@ use axum::{Json, Router};
 use axum_extra::extract::Query;
 use diesel::pg::Pg;
 use diesel::prelude::*;
-use diesel::sql_types::{Array, BigInt, Double, Text, Timestamptz};
+use diesel::sql_types::{BigInt, Jsonb};
 use serde::{Deserialize, Serialize};
 
-use super::query_ast::{self, Bind, Node};
+use super::query_ast::{self, apply_binds, Bind, Dataset, Node};
+use super::query_text;
+use super::{query_agg, query_facets};
 use crate::db::model::SsuMgmtEvent;
 use crate::db::DbPool;
 
@@ -22,14 +24,28 @@ pub fn routes(pool: DbPool) -> Router {
     Router::new()
         .route("/", axum::routing::get(query_handler))
         .route("/export.csv", axum::routing::get(export_handler))
+        .route("/parse", axum::routing::get(parse_handler))
+        .route(
+            "/aggregate",
+            axum::routing::get(query_agg::aggregate_handler),
+        )
+        .route("/facets", axum::routing::get(query_facets::facets_handler))
         .with_state(pool)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct QueryParams {
+    /// Which relation to query (`query_ast::DATASETS`): `events` (the
+    /// default), `alerts`, `anomalies`, `sessions` or `grants`. Field names,
+    /// `ts`, the facets and `order_by` are all that dataset's.
+    pub dataset: Option<String>,
     /// URL-encoded JSON of a `query_ast::Node`. Absent/empty → match everything.
     pub ast: Option<String>,
-    /// Direct facets (equality) — only meaningful for sources that populate them.
+    /// The same filter as query text (see `query_text`); mutually exclusive
+    /// with `ast`. A trailing `order by` applies unless `order_by` is given.
+    pub q: Option<String>,
+    /// Direct facets (equality) — only meaningful for sources that populate them,
+    /// and only on datasets with a `status`/`source` field.
     pub status: Option<String>,
     pub source: Option<String>,
     pub from: Option<String>,
@@ -46,9 +62,18 @@ pub struct QueryParams {
     pub count: Option<bool>,
 }
 
+/// Events keep their typed row; the derived tables return each row as an
+/// object of all its columns.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Rows {
+    Events(Vec<SsuMgmtEvent>),
+    Json(Vec<serde_json::Value>),
+}
+
 #[derive(Serialize)]
 pub struct QueryResponse {
-    pub rows: Vec<SsuMgmtEvent>,
+    pub rows: Rows,
     /// `None` (JSON `null`) when the count was skipped (pagination); otherwise
     /// the row count, clamped to the cap when `total_capped`.
     pub total: Option<i64>,
@@ -61,108 +86,129 @@ struct CountRow {
     total: i64,
 }
 
+/// A derived-table row as one object.
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[diesel(sql_type = Jsonb)]
+    data: serde_json::Value,
+}
+
 /// The compiled WHERE clause + the ordered binds it references. Shared by the
 /// rows query and the count query; the rows query appends LIMIT/OFFSET binds.
-struct Compiled {
-    where_sql: String,
-    binds: Vec<Bind>,
+#[derive(Clone)]
+pub(super) struct Compiled {
+    pub(super) dataset: &'static Dataset,
+    pub(super) where_sql: String,
+    pub(super) binds: Vec<Bind>,
+    pub(super) order_sql: String,
 }
 
-fn compile_params(params: &QueryParams) -> Result<Compiled, String> {
+pub(super) fn compile_params(params: &QueryParams) -> Result<Compiled, String> {
+    let ds = match non_empty(params.dataset.as_deref()) {
+        None => &query_ast::EVENTS,
+        Some(name) => {
+            Dataset::by_name(name).ok_or_else(|| format!("unknown dataset `{}`", name))?
+        }
+    };
     let mut binds: Vec<Bind> = Vec::new();
     let mut clauses: Vec<String> = Vec::new();
 
-    if let Some(s) = params.source.as_deref().filter(|s| !s.is_empty()) {
-        binds.push(Bind::Text(s.to_owned()));
-        clauses.push(format!("source = ${}", binds.len()));
-    }
-    if let Some(s) = params.status.as_deref().filter(|s| !s.is_empty()) {
-        binds.push(Bind::Text(s.to_owned()));
-        clauses.push(format!("status = ${}", binds.len()));
+    for (facet, value) in [("source", &params.source), ("status", &params.status)] {
+        if let Some(v) = value.as_deref().filter(|s| !s.is_empty()) {
+            let def = ds
+                .field(facet)
+                .ok_or_else(|| format!("{} have no `{}` facet", ds.name, facet))?;
+            binds.push(Bind::Text(v.to_owned()));
+            clauses.push(format!("{} = ${}", def.column, binds.len()));
+        }
     }
     if let Some(f) = params.from.as_deref().filter(|s| !s.is_empty()) {
         binds.push(Bind::Ts(query_ast::parse_ts(f)?));
-        clauses.push(format!("ts >= ${}", binds.len()));
+        clauses.push(format!("{} >= ${}", ds.ts, binds.len()));
     }
     if let Some(t) = params.to.as_deref().filter(|s| !s.is_empty()) {
         binds.push(Bind::Ts(query_ast::parse_ts(t)?));
-        clauses.push(format!("ts <= ${}", binds.len()));
+        clauses.push(format!("{} <= ${}", ds.ts, binds.len()));
     }
 
-    if let Some(ast) = params
-        .ast
-        .as_deref()
-        .map(str::trim)
-        .filter(|s| !s.is_empty())
-    {
-        let node: Node = serde_json::from_str(ast).map_err(|e| format!("bad ast: {}", e))?;
-        clauses.push(query_ast::compile(&node, &mut binds)?);
+    let mut order_by = params.order_by.clone();
+    let mut order_dir = params.order_dir.clone();
+    match (
+        non_empty(params.ast.as_deref()),
+        non_empty(params.q.as_deref()),
+    ) {
+        (Some(_), Some(_)) => return Err("give either ast or q, not both".to_string()),
+        (Some(ast), None) => {
+            let node: Node = serde_json::from_str(ast).map_err(|e| format!("bad ast: {}", e))?;
+            clauses.push(query_ast::compile_in(ds, &node, &mut binds)?);
+        }
+        (None, Some(q)) => {
+            let parsed = query_text::parse_in(ds, q).map_err(|e| format!("bad q: {}", e))?;
+            if let Some(node) = &parsed.node {
+                clauses.push(query_ast::compile_in(ds, node, &mut binds)?);
+            }
+            if order_by.is_none() {
+                order_by = parsed.order_by;
+                order_dir = parsed.order_dir;
+            }
+        }
+        (None, None) => {}
     }
+    let order_sql = order_by_clause(ds, order_by.as_deref(), order_dir.as_deref())?;
 
     let where_sql = if clauses.is_empty() {
         "TRUE".to_string()
     } else {
         clauses.join(" AND ")
     };
-    Ok(Compiled { where_sql, binds })
+    Ok(Compiled {
+        dataset: ds,
+        where_sql,
+        binds,
+        order_sql,
+    })
+}
+
+fn non_empty(s: Option<&str>) -> Option<&str> {
+    s.map(str::trim).filter(|s| !s.is_empty())
 }
 
-const SELECT_COLS: &str = "source, uid, ts, actor, action, resource, source_ip, \
+pub(super) const SELECT_COLS: &str = "source, uid, ts, actor, action, resource, source_ip, \
                            level, status, raw, role, identity_source, account_id, \
                            caller_account_id";
 
-fn order_column(field: &str) -> Option<&'static str> {
-    Some(match field.to_lowercase().as_str() {
-        "actor" => "actor",
-        "source" => "source",
-        "action" => "action",
-        "resource" => "resource",
-        "ip" => "source_ip",
-        "status" => "status",
-        "level" => "level",
-        "uid" => "uid",
-        "role" => "role",
-        "idsource" => "identity_source",
-        "account" => "account_id",
-        "calleraccount" => "caller_account_id",
-        "ts" => "ts",
-        _ => return None,
-    })
-}
-
-fn order_by_clause(order_by: Option<&str>, order_dir: Option<&str>) -> Result<String, String> {
+/// `ts` or any orderable field of `ds`; ties break on time, then the key.
+fn order_by_clause(
+    ds: &Dataset,
+    order_by: Option<&str>,
+    order_dir: Option<&str>,
+) -> Result<String, String> {
     let Some(field) = order_by.map(str::trim).filter(|s| !s.is_empty()) else {
-        return Ok("ts DESC, uid".to_string());
+        return Ok(format!("{} DESC, {}", ds.ts, ds.key));
+    };
+    let col = if field.eq_ignore_ascii_case("ts") {
+        ds.ts
+    } else {
+        ds.field(field)
+            .filter(|f| f.orderable())
+            .map(|f| f.column)
+            .ok_or_else(|| format!("cannot order by `{}`", field))?
     };
-    let col = order_column(field).ok_or_else(|| format!("cannot order by `{}`", field))?;
     let dir = match order_dir.map(|s| s.trim().to_lowercase()).as_deref() {
         Some("asc") | None => "ASC",
         Some("desc") => "DESC",
         Some(other) => return Err(format!("invalid order direction `{}`", other)),
     };
-    if col == "ts" {
-        Ok(format!("ts {}, uid", dir))
+    if col == ds.ts {
+        Ok(format!("{} {}, {}", col, dir, ds.key))
     } else {
-        Ok(format!("{} {} NULLS LAST, ts DESC, uid", col, dir))
+        Ok(format!(
+            "{} {} NULLS LAST, {} DESC, {}",
+            col, dir, ds.ts, ds.key
+        ))
     }
 }
 
-fn apply_binds<'a>(
-    mut q: diesel::query_builder::BoxedSqlQuery<'a, Pg, diesel::query_builder::SqlQuery>,
-    binds: Vec<Bind>,
-) -> diesel::query_builder::BoxedSqlQuery<'a, Pg, diesel::query_builder::SqlQuery> {
-    for b in binds {
-        q = match b {
-            Bind::Text(s) => q.bind::<Text, _>(s),
-            Bind::Double(d) => q.bind::<Double, _>(d),
-            Bind::BigInt(n) => q.bind::<BigInt, _>(n),
-            Bind::TextArray(a) => q.bind::<Array<Text>, _>(a),
-            Bind::Ts(t) => q.bind::<Timestamptz, _>(t),
-        };
-    }
-    q
-}
-
 async fn query_handler(State(pool): State<DbPool>, Query(params): Query<QueryParams>) -> Response {
     let compiled = match compile_params(&params) {
         Ok(c) => c,
@@ -172,10 +218,6 @@ async fn query_handler(State(pool): State<DbPool>, Query(params): Query<QueryPar
     let offset = params.offset.unwrap_or(0).max(0);
     let count_cap = params.count_cap.unwrap_or(DEFAULT_COUNT_CAP);
     let skip_count = params.count == Some(false);
-    let order_sql = match order_by_clause(params.order_by.as_deref(), params.order_dir.as_deref()) {
-        Ok(o) => o,
-        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
-    };
 
     let span = tracing::info_span!(
         "db.query",
@@ -184,33 +226,52 @@ async fn query_handler(State(pool): State<DbPool>, Query(params): Query<QueryPar
         op = "query.search",
         db.statement = tracing::field::Empty
     );
-    let res = tokio::task::spawn_blocking(
-        move || -> diesel::QueryResult<(Vec<SsuMgmtEvent>, Option<i64>, bool)> {
+    let res =
+        tokio::task::spawn_blocking(move || -> diesel::QueryResult<(Rows, Option<i64>, bool)> {
             let _g = span.enter();
             let mut conn = crate::db::conn(&pool)?;
-            let Compiled { where_sql, binds } = compiled;
+            let Compiled {
+                dataset,
+                where_sql,
+                binds,
+                order_sql,
+            } = compiled;
+            let table = dataset.table;
 
             let mut row_binds = binds.clone();
             row_binds.push(Bind::BigInt(limit));
             let limit_idx = row_binds.len();
             row_binds.push(Bind::BigInt(offset));
             let offset_idx = row_binds.len();
+            let cols = if dataset.table == query_ast::EVENTS.table {
+                SELECT_COLS
+            } else {
+                "to_jsonb(t) AS data"
+            };
             let rows_sql = format!(
-                "SELECT {cols} FROM ssumgmt_events WHERE {where_sql} \
+                "SELECT {cols} FROM {table} t WHERE {where_sql} \
              ORDER BY {order_sql} LIMIT ${limit_idx} OFFSET ${offset_idx}",
-                cols = SELECT_COLS,
             );
             span.record("db.statement", rows_sql.as_str());
-            let rows: Vec<SsuMgmtEvent> =
-                apply_binds(diesel::sql_query(rows_sql).into_boxed::<Pg>(), row_binds)
-                    .load(&mut conn)?;
+            let query = apply_binds(diesel::sql_query(rows_sql).into_boxed::<Pg>(), row_binds);
+            let rows = if dataset.table == query_ast::EVENTS.table {
+                Rows::Events(query.load(&mut conn)?)
+            } else {
+                Rows::Json(
+                    query
+                        .load::<JsonRow>(&mut conn)?
+                        .into_iter()
+                        .map(|r| r.data)
+                        .collect(),
+                )
+            };
 
             let (total, total_capped) = if skip_count {
                 (None, false)
             } else if count_cap > 0 {
                 let count_sql = format!(
                     "SELECT count(*)::bigint AS total FROM \
-                 (SELECT 1 FROM ssumgmt_events WHERE {where_sql} LIMIT {cap}) sub",
+                 (SELECT 1 FROM {table} WHERE {where_sql} LIMIT {cap}) sub",
                     cap = count_cap + 1,
                 );
                 let counted: i64 =
@@ -223,9 +284,8 @@ async fn query_handler(State(pool): State<DbPool>, Query(params): Query<QueryPar
                     (Some(counted), false)
                 }
             } else {
-                let count_sql = format!(
-                    "SELECT count(*)::bigint AS total FROM ssumgmt_events WHERE {where_sql}"
-                );
+                let count_sql =
+                    format!("SELECT count(*)::bigint AS total FROM {table} WHERE {where_sql}");
                 let counted: i64 =
                     apply_binds(diesel::sql_query(count_sql).into_boxed::<Pg>(), binds)
                         .get_result::<CountRow>(&mut conn)?
@@ -234,9 +294,8 @@ async fn query_handler(State(pool): State<DbPool>, Query(params): Query<QueryPar
             };
 
             Ok((rows, total, total_capped))
-        },
-    )
-    .await;
+        })
+        .await;
 
     match res {
         Ok(Ok((rows, total, total_capped))) => Json(QueryResponse {
@@ -265,10 +324,7 @@ async fn export_handler(State(pool): State<DbPool>, Query(params): Query<QueryPa
         Ok(c) => c,
         Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
     };
-    let order_sql = match order_by_clause(params.order_by.as_deref(), params.order_dir.as_deref()) {
-        Ok(o) => o,
-        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
-    };
+    let disposition = format!("attachment; filename=\"{}.csv\"", compiled.dataset.table);
 
     let span = tracing::info_span!(
         "db.query",
@@ -284,7 +340,7 @@ async fn export_handler(State(pool): State<DbPool>, Query(params): Query<QueryPa
 
     tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        if let Err(e) = stream_export(&pool, compiled, &order_sql, &span, &tx) {
+        if let Err(e) = stream_export(&pool, compiled, &span, &tx) {
             // Best-effort: surface the failure mid-stream (truncates the CSV).
             let _ = tx.blocking_send(Err(std::io::Error::other(e.to_string())));
         }
@@ -299,31 +355,12 @@ async fn export_handler(State(pool): State<DbPool>, Query(params): Query<QueryPa
         header::CONTENT_TYPE,
         HeaderValue::from_static("text/csv; charset=utf-8"),
     );
-    headers.insert(
-        header::CONTENT_DISPOSITION,
-        HeaderValue::from_static("attachment; filename=\"ssumgmt_events.csv\""),
-    );
+    if let Ok(v) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
     (StatusCode::OK, headers, Body::from_stream(stream)).into_response()
 }
 
-/// CSV header, written once before the first data batch.
-const EXPORT_HEADER: [&str; 14] = [
-    "source",
-    "uid",
-    "ts",
-    "actor",
-    "action",
-    "resource",
-    "source_ip",
-    "level",
-    "status",
-    "role",
-    "identity_source",
-    "account_id",
-    "caller_account_id",
-    "raw",
-];
-
 /// Encode a slice of rows to a fresh CSV buffer (no header).
 fn encode_rows(rows: Vec<SsuMgmtEvent>) -> anyhow::Result<Vec<u8>> {
     let mut wtr = csv::Writer::from_writer(Vec::new());
@@ -349,20 +386,44 @@ fn encode_rows(rows: Vec<SsuMgmtEvent>) -> anyhow::Result<Vec<u8>> {
     Ok(wtr.into_inner()?)
 }
 
+/// [`encode_rows`] for derived-table rows: `columns` plucked from each object,
+/// nested JSON (arrays, `evidence`) written as JSON text.
+fn encode_json_rows(rows: Vec<JsonRow>, columns: &[&str]) -> anyhow::Result<Vec<u8>> {
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    for r in rows {
+        wtr.write_record(columns.iter().map(|c| match r.data.get(c) {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+        }))?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
 /// Drive the server-side cursor and push CSV chunks to `tx`. Runs on a blocking
 /// thread; `blocking_send` returning `Err` means the client disconnected, which
 /// ends the export cleanly (the transaction rolls back / the cursor is dropped).
 fn stream_export(
     pool: &DbPool,
     compiled: Compiled,
-    order_sql: &str,
     span: &tracing::Span,
     tx: &tokio::sync::mpsc::Sender<Result<Vec<u8>, std::io::Error>>,
 ) -> anyhow::Result<()> {
-    let Compiled { where_sql, binds } = compiled;
+    let Compiled {
+        dataset,
+        where_sql,
+        binds,
+        order_sql,
+    } = compiled;
+    let events = dataset.table == query_ast::EVENTS.table;
     let rows_sql = format!(
-        "SELECT {cols} FROM ssumgmt_events WHERE {where_sql} ORDER BY {order_sql}",
-        cols = SELECT_COLS
+        "SELECT {cols} FROM {table} t WHERE {where_sql} ORDER BY {order_sql}",
+        cols = if events {
+            SELECT_COLS
+        } else {
+            "to_jsonb(t) AS data"
+        },
+        table = dataset.table,
     );
     span.record("db.statement", rows_sql.as_str());
 
@@ -370,7 +431,7 @@ fn stream_export(
 
     // Header first; if the client is already gone, stop.
     let mut hdr = csv::Writer::from_writer(Vec::new());
-    hdr.write_record(EXPORT_HEADER)?;
+    hdr.write_record(dataset.export_columns)?;
     if tx.blocking_send(Ok(hdr.into_inner()?)).is_err() {
         return Ok(());
     }
@@ -381,11 +442,19 @@ fn stream_export(
 
         let fetch_sql = format!("FETCH FORWARD {} FROM ssu_export_cur", EXPORT_FETCH_BATCH);
         loop {
-            let batch: Vec<SsuMgmtEvent> = diesel::sql_query(&fetch_sql).load(conn)?;
-            if batch.is_empty() {
-                break;
-            }
-            let bytes = encode_rows(batch)?;
+            let bytes = if events {
+                let batch: Vec<SsuMgmtEvent> = diesel::sql_query(&fetch_sql).load(conn)?;
+                if batch.is_empty() {
+                    break;
+                }
+                encode_rows(batch)?
+            } else {
+                let batch: Vec<JsonRow> = diesel::sql_query(&fetch_sql).load(conn)?;
+                if batch.is_empty() {
+                    break;
+                }
+                encode_json_rows(batch, dataset.export_columns)?
+            };
             if tx.blocking_send(Ok(bytes)).is_err() {
                 // Client disconnected — abandon the export.
                 break;
@@ -396,3 +465,77 @@ fn stream_export(
         Ok(())
     })
 }
+
+#[derive(Deserialize)]
+pub struct ParseParams {
+    pub q: Option<String>,
+    pub ast: Option<String>,
+    /// Check `q`'s fields against this dataset. Default `events`.
+    pub dataset: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ParseResponse {
+    /// `None` for an empty query.
+    pub ast: Option<Node>,
+    /// The canonical text form of `ast`.
+    pub text: String,
+    pub order_by: Option<String>,
+    pub order_dir: Option<String>,
+}
+
+/// Translate between the two query forms without running anything: `q` →
+/// AST (and canonical text), or `ast` → text. Parse failures are 400 with
+/// `{error, position}`, `position` being the 0-based character offset.
+async fn parse_handler(Query(params): Query<ParseParams>) -> Response {
+    let ds = match non_empty(params.dataset.as_deref()) {
+        None => &query_ast::EVENTS,
+        Some(name) => match Dataset::by_name(name) {
+            Some(ds) => ds,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("unknown dataset `{}`", name),
+                )
+                    .into_response()
+            }
+        },
+    };
+    let parsed = match (
+        non_empty(params.q.as_deref()),
+        non_empty(params.ast.as_deref()),
+    ) {
+        (Some(_), Some(_)) => {
+            return (StatusCode::BAD_REQUEST, "give either ast or q, not both").into_response()
+        }
+        (Some(q), None) => match query_text::parse_in(ds, q) {
+            Ok(p) => p,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": e.to_string(), "position": e.pos })),
+                )
+                    .into_response()
+            }
+        },
+        (None, Some(ast)) => match serde_json::from_str::<Node>(ast) {
+            Ok(node) => query_text::ParsedQuery {
+                node: Some(node),
+                ..Default::default()
+            },
+            Err(e) => return (StatusCode::BAD_REQUEST, format!("bad ast: {}", e)).into_response(),
+        },
+        (None, None) => query_text::ParsedQuery::default(),
+    };
+    Json(ParseResponse {
+        text: parsed
+            .node
+            .as_ref()
+            .map(query_text::print)
+            .unwrap_or_default(),
+        ast: parsed.node,
+        order_by: parsed.order_by,
+        order_dir: parsed.order_dir,
+    })
+    .into_response()
+}
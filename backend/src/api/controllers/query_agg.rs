@@ -0,0 +1,533 @@
+//! `/api/query/aggregate`: group-by, histogram and top-N over the same filter
+//! as the event query (`ast=` or `q=` plus the facet params).
+//!
+//! Nothing user-supplied is interpolated into the SQL: fields, aggregate kinds
+//! and bucket widths go through closed allowlists, and JSON paths and output
+//! key names are bound. Cost is bounded like the count query: `scan_cap`
+//! limits how many matching events are aggregated (the newest ones, reported
+//! back as `capped`), `top` limits the groups returned, and the statement runs
+//! under a `statement_timeout`.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::Query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Jsonb};
+use serde::{Deserialize, Serialize};
+
+use super::query::{compile_params, Compiled, QueryParams};
+use super::query_ast::{apply_binds, Bind, FieldType, EVENTS};
+use super::query_text;
+use crate::db::DbPool;
+
+const DEFAULT_SCAN_CAP: i64 = 100_000;
+const DEFAULT_TOP: i64 = 20;
+const MAX_TOP: i64 = 1_000;
+/// Rows returned with `bucket`: buckets × groups, newest buckets kept.
+const MAX_BUCKET_ROWS: i64 = 5_000;
+const MAX_GROUP_BY: usize = 3;
+const MAX_AGGS: usize = 4;
+const AGG_STATEMENT_TIMEOUT: &str = "30s";
+
+#[derive(Deserialize)]
+pub struct AggParams {
+    /// Filter, as on `/api/query`.
+    pub ast: Option<String>,
+    pub q: Option<String>,
+    pub status: Option<String>,
+    pub source: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Repeatable. A field name (`actor`, `ip`, …) or a payload path
+    /// (`json.requestParameters.roleName`).
+    #[serde(default)]
+    pub group_by: Vec<String>,
+    /// Repeatable: `count` (the default), `distinct:<field or path>`,
+    /// `min_ts`, `max_ts`.
+    #[serde(default)]
+    pub agg: Vec<String>,
+    /// `minute`/`hour`/`day`/`week`: adds a time-bucket key (a histogram).
+    pub bucket: Option<String>,
+    /// Groups returned, ranked by the first aggregate descending. With
+    /// `bucket`, the top groups overall, each with its full series.
+    pub top: Option<i64>,
+    /// Max matching events aggregated, newest first. Defaults to
+    /// `DEFAULT_SCAN_CAP`; `0`/negative → unbounded, as with `count_cap`.
+    pub scan_cap: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct AggResponse {
+    pub group_by: Vec<String>,
+    pub aggs: Vec<String>,
+    pub bucket: Option<String>,
+    /// One object per group: `{"key": {<group_by>: value, …}, "bucket": ts,
+    /// <agg>: value, …}`; `bucket` only when bucketing.
+    pub rows: Vec<serde_json::Value>,
+    /// Matching events that were aggregated.
+    pub scanned: i64,
+    /// The filter matched more than `scan_cap` events; only the newest
+    /// `scan_cap` are aggregated.
+    pub capped: bool,
+    /// More groups (or bucket rows) existed than were returned.
+    pub truncated: bool,
+}
+
+#[derive(QueryableByName)]
+struct AggRow {
+    #[diesel(sql_type = Jsonb)]
+    row: serde_json::Value,
+    #[diesel(sql_type = BigInt)]
+    scanned: i64,
+    #[diesel(sql_type = Bool)]
+    capped: bool,
+    #[diesel(sql_type = BigInt)]
+    n_groups: i64,
+}
+
+/// A group-by key or `distinct:` operand: a view column or a payload path.
+#[derive(Debug, PartialEq)]
+pub(super) enum Operand {
+    Column(&'static str),
+    Path(Vec<String>),
+}
+
+impl Operand {
+    /// Parse a spec, returning its canonical name alongside.
+    pub(super) fn parse(spec: &str) -> Result<(String, Operand), String> {
+        let spec = spec.trim();
+        if let Some(def) = EVENTS.field(spec) {
+            if def.ty == FieldType::ActorKind {
+                return Err(format!("cannot aggregate on `{}`", def.name));
+            }
+            return Ok((def.name.to_string(), Operand::Column(def.column)));
+        }
+        let mut path =
+            query_text::parse_path(spec, 0).map_err(|e| format!("bad path `{}`: {}", spec, e))?;
+        let prefixed = path
+            .first()
+            .is_some_and(|p| p.eq_ignore_ascii_case("json") || p.eq_ignore_ascii_case("raw"));
+        if !prefixed || path.len() < 2 {
+            return Err(format!(
+                "unknown field `{}` (use a field name or json.<path>)",
+                spec
+            ));
+        }
+        path.remove(0);
+        let name = format!("json{}", query_text::print_path(&path));
+        Ok((name, Operand::Path(path)))
+    }
+
+    pub(super) fn sql(&self, binds: &mut Vec<Bind>) -> String {
+        match self {
+            Operand::Column(c) => c.to_string(),
+            Operand::Path(p) => {
+                binds.push(Bind::TextArray(p.clone()));
+                format!("(raw #>> ${})", binds.len())
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Agg {
+    Count,
+    Distinct(Operand),
+    MinTs,
+    MaxTs,
+}
+
+impl Agg {
+    fn parse(spec: &str) -> Result<(String, Agg), String> {
+        let spec = spec.trim();
+        match spec.to_lowercase().as_str() {
+            "count" => return Ok(("count".to_string(), Agg::Count)),
+            "min_ts" => return Ok(("min_ts".to_string(), Agg::MinTs)),
+            "max_ts" => return Ok(("max_ts".to_string(), Agg::MaxTs)),
+            _ => {}
+        }
+        match spec.split_once(':') {
+            Some((kind, operand)) if kind.eq_ignore_ascii_case("distinct") => {
+                let (name, operand) = Operand::parse(operand)?;
+                Ok((format!("distinct:{}", name), Agg::Distinct(operand)))
+            }
+            _ => Err(format!(
+                "invalid agg `{}` (count, distinct:<field>, min_ts, max_ts)",
+                spec
+            )),
+        }
+    }
+}
+
+/// The statement plus the names it reports, built from validated params on
+/// top of the compiled filter.
+#[derive(Debug)]
+struct Plan {
+    sql: String,
+    binds: Vec<Bind>,
+    group_by: Vec<String>,
+    aggs: Vec<String>,
+    bucket: Option<String>,
+    top: i64,
+}
+
+fn plan(params: &AggParams, compiled: Compiled) -> Result<Plan, String> {
+    let Compiled {
+        where_sql,
+        mut binds,
+        ..
+    } = compiled;
+
+    if params.group_by.len() > MAX_GROUP_BY {
+        return Err(format!("at most {} group_by fields", MAX_GROUP_BY));
+    }
+    if params.agg.len() > MAX_AGGS {
+        return Err(format!("at most {} aggs", MAX_AGGS));
+    }
+    let keys = params
+        .group_by
+        .iter()
+        .map(|s| Operand::parse(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut aggs = params
+        .agg
+        .iter()
+        .map(|s| Agg::parse(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    if aggs.is_empty() {
+        aggs.push(("count".to_string(), Agg::Count));
+    }
+    // Bound into date_trunc, but still allowlisted so a typo is a 400.
+    let bucket = match params.bucket.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(b @ ("minute" | "hour" | "day" | "week")) => Some(b.to_string()),
+        Some(other) => return Err(format!("invalid bucket: {}", other)),
+    };
+    let top = params.top.unwrap_or(DEFAULT_TOP).clamp(1, MAX_TOP);
+    let cap = params.scan_cap.unwrap_or(DEFAULT_SCAN_CAP);
+
+    // Project only what the aggregation reads, naming keys k0.., distinct
+    // operands d0.. and the bucket b, so later stages never repeat a bind.
+    let mut cols = vec!["ts".to_string()];
+    for (i, (_, key)) in keys.iter().enumerate() {
+        cols.push(format!("{} AS k{}", key.sql(&mut binds), i));
+    }
+    for (i, (_, agg)) in aggs.iter().enumerate() {
+        if let Agg::Distinct(operand) = agg {
+            cols.push(format!("{} AS d{}", operand.sql(&mut binds), i));
+        }
+    }
+    if let Some(b) = &bucket {
+        binds.push(Bind::Text(b.clone()));
+        cols.push(format!("date_trunc(${}, ts) AS b", binds.len()));
+    }
+    let cols = cols.join(", ");
+
+    let (scope_sql, capped_sql) = if cap > 0 {
+        (
+            format!(
+                "scoped AS (SELECT {cols} FROM ssumgmt_events WHERE {where_sql} \
+                 ORDER BY ts DESC LIMIT {over}), \
+                 kept AS (SELECT * FROM scoped ORDER BY ts DESC LIMIT {cap})",
+                over = cap + 1,
+            ),
+            format!("(SELECT count(*) FROM scoped) > {cap}"),
+        )
+    } else {
+        (
+            format!("kept AS (SELECT {cols} FROM ssumgmt_events WHERE {where_sql})"),
+            "false".to_string(),
+        )
+    };
+
+    let agg_exprs: Vec<String> = aggs
+        .iter()
+        .enumerate()
+        .map(|(i, (_, agg))| match agg {
+            Agg::Count => "count(*)".to_string(),
+            Agg::Distinct(_) => format!("count(DISTINCT d{})", i),
+            Agg::MinTs => "min(ts)".to_string(),
+            Agg::MaxTs => "max(ts)".to_string(),
+        })
+        .collect();
+    let rank = format!("{} DESC NULLS LAST", agg_exprs[0]);
+    let agg_cols = agg_exprs
+        .iter()
+        .enumerate()
+        .map(|(i, e)| format!("{} AS a{}", e, i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let k: Vec<String> = (0..keys.len()).map(|i| format!("k{}", i)).collect();
+    let ek: Vec<String> = k.iter().map(|c| format!("e.{}", c)).collect();
+
+    let (grouped_sql, order_sql, n_groups_sql) = match (&bucket, keys.is_empty()) {
+        (None, true) => (
+            format!("grouped AS (SELECT {agg_cols} FROM kept)"),
+            "a0".to_string(),
+            "0::bigint",
+        ),
+        (None, false) => {
+            let k = k.join(", ");
+            (
+                format!(
+                    "grouped AS (SELECT {k}, {agg_cols} FROM kept GROUP BY {k} \
+                     ORDER BY {rank}, {k} LIMIT {over})",
+                    over = top + 1,
+                ),
+                format!("a0 DESC NULLS LAST, {k}"),
+                "0::bigint",
+            )
+        }
+        (Some(_), true) => (
+            format!(
+                "grouped AS (SELECT b, {agg_cols} FROM kept GROUP BY b \
+                 ORDER BY b DESC LIMIT {over})",
+                over = MAX_BUCKET_ROWS + 1,
+            ),
+            "b".to_string(),
+            "0::bigint",
+        ),
+        (Some(_), false) => {
+            let on = k
+                .iter()
+                .map(|c| format!("e.{c} IS NOT DISTINCT FROM t.{c}"))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let (k, ek) = (k.join(", "), ek.join(", "));
+            (
+                format!(
+                    "top_keys AS (SELECT {k}, \
+                       row_number() OVER (ORDER BY {rank}, {k}) AS r, \
+                       count(*) OVER () AS n_groups \
+                     FROM kept GROUP BY {k} ORDER BY r LIMIT {top}), \
+                     grouped AS (SELECT e.b, {ek}, t.r, t.n_groups, {agg_cols} \
+                     FROM kept e JOIN top_keys t ON {on} \
+                     GROUP BY e.b, {ek}, t.r, t.n_groups \
+                     ORDER BY e.b DESC, t.r LIMIT {over})",
+                    over = MAX_BUCKET_ROWS + 1,
+                ),
+                "b, r".to_string(),
+                "n_groups",
+            )
+        }
+    };
+
+    let mut key_pairs = Vec::new();
+    for (i, (name, _)) in keys.iter().enumerate() {
+        binds.push(Bind::Text(name.clone()));
+        key_pairs.push(format!("${}::text, k{}", binds.len(), i));
+    }
+    let mut obj = vec![format!(
+        "'key', jsonb_build_object({})",
+        key_pairs.join(", ")
+    )];
+    if bucket.is_some() {
+        obj.push("'bucket', b".to_string());
+    }
+    for (i, (name, _)) in aggs.iter().enumerate() {
+        binds.push(Bind::Text(name.clone()));
+        obj.push(format!("${}::text, a{}", binds.len(), i));
+    }
+
+    let sql = format!(
+        "WITH {scope_sql}, {grouped_sql} \
+         SELECT jsonb_build_object({obj}) AS row, \
+           (SELECT count(*) FROM kept) AS scanned, \
+           {capped_sql} AS capped, \
+           {n_groups_sql} AS n_groups \
+         FROM grouped ORDER BY {order_sql}",
+        obj = obj.join(", "),
+    );
+
+    Ok(Plan {
+        sql,
+        binds,
+        group_by: keys.into_iter().map(|(name, _)| name).collect(),
+        aggs: aggs.into_iter().map(|(name, _)| name).collect(),
+        bucket,
+        top,
+    })
+}
+
+pub(super) async fn aggregate_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<AggParams>,
+) -> Response {
+    let filter = QueryParams {
+        ast: params.ast.clone(),
+        q: params.q.clone(),
+        status: params.status.clone(),
+        source: params.source.clone(),
+        from: params.from.clone(),
+        to: params.to.clone(),
+        ..Default::default()
+    };
+    let plan = match compile_params(&filter).and_then(|c| plan(&params, c)) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "query.aggregate",
+        db.statement = tracing::field::Empty
+    );
+    let Plan {
+        sql,
+        binds,
+        group_by,
+        aggs,
+        bucket,
+        top,
+    } = plan;
+    let res = tokio::task::spawn_blocking(move || -> diesel::QueryResult<Vec<AggRow>> {
+        let _g = span.enter();
+        span.record("db.statement", sql.as_str());
+        let mut conn = crate::db::conn(&pool)?;
+        conn.transaction(|conn| {
+            diesel::sql_query(format!(
+                "SET LOCAL statement_timeout = '{AGG_STATEMENT_TIMEOUT}'"
+            ))
+            .execute(conn)?;
+            apply_binds(diesel::sql_query(sql).into_boxed::<Pg>(), binds).load(conn)
+        })
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => {
+            let (scanned, capped, n_groups) = rows
+                .first()
+                .map_or((0, false, 0), |r| (r.scanned, r.capped, r.n_groups));
+            let mut rows: Vec<serde_json::Value> = rows.into_iter().map(|r| r.row).collect();
+            let mut truncated = n_groups > top;
+            let limit = usize::try_from(if bucket.is_some() {
+                MAX_BUCKET_ROWS
+            } else {
+                top
+            })
+            .unwrap_or(usize::MAX);
+            if rows.len() > limit {
+                truncated = true;
+                if bucket.is_some() {
+                    // Bucket rows come oldest first; the extra one is the oldest.
+                    rows.drain(..rows.len() - limit);
+                } else {
+                    rows.truncate(limit);
+                }
+            }
+            Json(AggResponse {
+                group_by,
+                aggs,
+                bucket,
+                rows,
+                scanned,
+                capped,
+                truncated,
+            })
+            .into_response()
+        }
+        Ok(Err(e)) if e.to_string().contains("statement timeout") => (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "aggregation exceeded {}; narrow the filter or lower scan_cap",
+                AGG_STATEMENT_TIMEOUT
+            ),
+        )
+            .into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(group_by: &[&str], agg: &[&str], bucket: Option<&str>) -> AggParams {
+        AggParams {
+            ast: None,
+            q: None,
+            status: None,
+            source: None,
+            from: None,
+            to: None,
+            group_by: group_by.iter().map(|s| s.to_string()).collect(),
+            agg: agg.iter().map(|s| s.to_string()).collect(),
+            bucket: bucket.map(str::to_string),
+            top: None,
+            scan_cap: None,
+        }
+    }
+
+    fn compiled() -> Compiled {
+        compile_params(&QueryParams {
+            q: Some("source=cloudtrail".to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn operands_are_allowlisted_fields_or_bound_paths() {
+        assert_eq!(
+            Operand::parse("IP").unwrap(),
+            ("ip".to_string(), Operand::Column("source_ip"))
+        );
+        assert_eq!(
+            Operand::parse("raw.requestParameters[\"role name\"]").unwrap(),
+            (
+                "json.requestParameters[\"role name\"]".to_string(),
+                Operand::Path(vec![
+                    "requestParameters".to_string(),
+                    "role name".to_string()
+                ])
+            )
+        );
+        assert!(Operand::parse("kind").is_err());
+        assert!(Operand::parse("actor; DROP TABLE alerts").is_err());
+        assert!(Agg::parse("sum:actor").is_err());
+    }
+
+    #[test]
+    fn plan_binds_every_user_string() {
+        let p = plan(
+            &params(
+                &["account", "json.userAgent"],
+                &["count", "distinct:ip"],
+                Some("hour"),
+            ),
+            compiled(),
+        )
+        .unwrap();
+        assert_eq!(p.group_by, ["account", "json.userAgent"]);
+        assert_eq!(p.aggs, ["count", "distinct:ip"]);
+        // filter, path, bucket, two key names, two agg names
+        assert_eq!(p.binds.len(), 7);
+        assert!(!p.sql.contains("userAgent"));
+        assert!(!p.sql.contains("'hour'"));
+        assert!(p.sql.contains("LIMIT 100001"));
+
+        let err = plan(&params(&[], &[], Some("fortnight")), compiled()).unwrap_err();
+        assert_eq!(err, "invalid bucket: fortnight");
+        let err = plan(
+            &params(&["actor", "ip", "role", "uid"], &[], None),
+            compiled(),
+        )
+        .unwrap_err();
+        assert!(err.contains("group_by"), "{err}");
+    }
+}
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use diesel::pg::Pg;
+use diesel::sql_types::{Array, BigInt, Double, Text, Timestamptz};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
 pub enum Bind {
@@ -10,7 +12,24 @@ pub enum Bind {
     Ts(DateTime<Utc>),
 }
 
-#[derive(Deserialize, Debug)]
+/// Bind `binds` onto a boxed `sql_query`, in order (`$1`, `$2`, …).
+pub fn apply_binds<'a>(
+    mut q: diesel::query_builder::BoxedSqlQuery<'a, Pg, diesel::query_builder::SqlQuery>,
+    binds: Vec<Bind>,
+) -> diesel::query_builder::BoxedSqlQuery<'a, Pg, diesel::query_builder::SqlQuery> {
+    for b in binds {
+        q = match b {
+            Bind::Text(s) => q.bind::<Text, _>(s),
+            Bind::Double(d) => q.bind::<Double, _>(d),
+            Bind::BigInt(n) => q.bind::<BigInt, _>(n),
+            Bind::TextArray(a) => q.bind::<Array<Text>, _>(a),
+            Bind::Ts(t) => q.bind::<Timestamptz, _>(t),
+        };
+    }
+    q
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum Node {
     Group {
@@ -20,8 +39,9 @@ pub enum Node {
     Not {
         child: Box<Node>,
     },
+    /// `field` is a field name of the dataset being queried.
     Field {
-        field: Field,
+        field: String,
         op: Op,
         value: String,
     },
@@ -40,14 +60,14 @@ pub enum Node {
     },
 }
 
-#[derive(Deserialize, Clone, Copy, Debug)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum BoolOp {
     And,
     Or,
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Op {
     Eq,
@@ -60,56 +80,309 @@ pub enum Op {
     Lte,
 }
 
-#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ValueType {
     Text,
     Number,
 }
 
-/// The 10 normalized columns on the `ssumgmt_events` view. Tags are lowercased
-/// to match the frontend `EventField` names (`ip`, `idsource`).
-#[derive(Deserialize, Clone, Copy, Debug)]
-#[serde(rename_all = "lowercase")]
-pub enum Field {
-    Actor,
-    Source,
-    Action,
-    Resource,
-    Ip,
-    Status,
-    Level,
-    Uid,
-    Role,
-    IdSource,
-    Account,
-    CallerAccount,
-    /// Pseudo-field: not a view column. Resolves an event's raw `actor` to its
-    /// reconciled actor kind via `actor_aliases`→`actors`. Compiled specially in
-    /// `compile_field`; never reaches `column()`.
-    Kind,
+/// How a dataset field compiles, and so which operators it takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    /// Equality and (ILIKE) substring operators.
+    Text,
+    /// Equality and comparisons against a number.
+    Number,
+    /// Equality and comparisons against a timestamp (see [`parse_ts`]).
+    Time,
+    /// Equality against `true`/`false`.
+    Bool,
+    /// A `text[]` column: equality means "has an element equal to",
+    /// substring "has an element containing".
+    TextArray,
+    /// Events only: the raw `actor` resolved to its reconciled actor kind via
+    /// `actor_aliases`→`actors` (see `compile_actor_kind`).
+    ActorKind,
+    /// Derived tables: the kind of the `actors` row an `actor_id` points at.
+    ActorIdKind,
 }
 
-impl Field {
-    fn column(self) -> &'static str {
-        match self {
-            Field::Actor => "actor",
-            Field::Source => "source",
-            Field::Action => "action",
-            Field::Resource => "resource",
-            Field::Ip => "source_ip",
-            Field::Status => "status",
-            Field::Level => "level",
-            Field::Uid => "uid",
-            Field::Role => "role",
-            Field::IdSource => "identity_source",
-            Field::Account => "account_id",
-            Field::CallerAccount => "caller_account_id",
-            Field::Kind => unreachable!("kind is compiled via compile_actor_kind"),
-        }
+/// One queryable field of a [`Dataset`]: its query-language name (lowercase,
+/// like the frontend field names) and the column it compiles to.
+#[derive(Debug)]
+pub struct FieldDef {
+    pub name: &'static str,
+    pub column: &'static str,
+    pub ty: FieldType,
+}
+
+impl FieldDef {
+    /// Takes `>`/`>=`/`<`/`<=`.
+    pub fn comparable(&self) -> bool {
+        matches!(self.ty, FieldType::Number | FieldType::Time)
+    }
+
+    /// Can be an `order by` key.
+    pub fn orderable(&self) -> bool {
+        !matches!(
+            self.ty,
+            FieldType::TextArray | FieldType::ActorKind | FieldType::ActorIdKind
+        )
+    }
+}
+
+/// A relation the query language can filter: the closed allowlist of its
+/// fields plus the columns the fixed parts of a query refer to.
+#[derive(Debug)]
+pub struct Dataset {
+    /// The `dataset=` value.
+    pub name: &'static str,
+    pub table: &'static str,
+    /// Unique column; the last ordering key, so paging is stable.
+    pub key: &'static str,
+    /// What `ts` and the `from`/`to` facets mean, and the default order.
+    pub ts: &'static str,
+    /// The JSON column behind `json.<path>` and `raw:`, if any.
+    pub json: Option<&'static str>,
+    pub fields: &'static [FieldDef],
+    /// CSV export columns, in order.
+    pub export_columns: &'static [&'static str],
+}
+
+impl Dataset {
+    /// Case-insensitive field lookup.
+    pub fn field(&self, name: &str) -> Option<&'static FieldDef> {
+        self.fields
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Case-insensitive inverse of `name`, over [`DATASETS`].
+    pub fn by_name(name: &str) -> Option<&'static Dataset> {
+        DATASETS
+            .into_iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name))
     }
 }
 
+const fn def(name: &'static str, column: &'static str, ty: FieldType) -> FieldDef {
+    FieldDef { name, column, ty }
+}
+
+/// The normalized columns on the `ssumgmt_events` view. Names match the
+/// frontend `EventField` names (`ip`, `idsource`).
+pub static EVENTS: Dataset = Dataset {
+    name: "events",
+    table: "ssumgmt_events",
+    key: "uid",
+    ts: "ts",
+    json: Some("raw"),
+    fields: &[
+        def("actor", "actor", FieldType::Text),
+        def("source", "source", FieldType::Text),
+        def("action", "action", FieldType::Text),
+        def("resource", "resource", FieldType::Text),
+        def("ip", "source_ip", FieldType::Text),
+        def("status", "status", FieldType::Text),
+        def("level", "level", FieldType::Text),
+        def("uid", "uid", FieldType::Text),
+        def("role", "role", FieldType::Text),
+        def("idsource", "identity_source", FieldType::Text),
+        def("account", "account_id", FieldType::Text),
+        def("calleraccount", "caller_account_id", FieldType::Text),
+        def("kind", "actor", FieldType::ActorKind),
+    ],
+    export_columns: &[
+        "source",
+        "uid",
+        "ts",
+        "actor",
+        "action",
+        "resource",
+        "source_ip",
+        "level",
+        "status",
+        "role",
+        "identity_source",
+        "account_id",
+        "caller_account_id",
+        "raw",
+    ],
+};
+
+pub static ALERTS: Dataset = Dataset {
+    name: "alerts",
+    table: "alerts",
+    key: "id",
+    ts: "last_seen",
+    json: Some("evidence"),
+    fields: &[
+        def("id", "id", FieldType::Number),
+        def("rule", "rule_id", FieldType::Text),
+        def("fingerprint", "fingerprint", FieldType::Text),
+        def("severity", "severity", FieldType::Text),
+        def("title", "title", FieldType::Text),
+        def("description", "description", FieldType::Text),
+        def("actor", "actor_id", FieldType::Text),
+        def("actorkind", "actor_id", FieldType::ActorIdKind),
+        def("source", "source", FieldType::Text),
+        def("status", "status", FieldType::Text),
+        def("owner", "owner", FieldType::Text),
+        def("ownerkind", "owner_kind", FieldType::Text),
+        def("count", "event_count", FieldType::Number),
+        def("firstseen", "first_seen", FieldType::Time),
+        def("lastseen", "last_seen", FieldType::Time),
+        def("ackedby", "acked_by", FieldType::Text),
+        def("ackedat", "acked_at", FieldType::Time),
+        def("resolvedby", "resolved_by", FieldType::Text),
+        def("resolvedat", "resolved_at", FieldType::Time),
+        def("tactic", "attack_tactics", FieldType::TextArray),
+        def("technique", "attack_techniques", FieldType::TextArray),
+    ],
+    export_columns: &[
+        "id",
+        "rule_id",
+        "severity",
+        "status",
+        "title",
+        "actor_id",
+        "source",
+        "owner",
+        "event_count",
+        "first_seen",
+        "last_seen",
+        "acked_by",
+        "resolved_by",
+        "attack_tactics",
+        "attack_techniques",
+        "fingerprint",
+        "evidence",
+    ],
+};
+
+pub static ANOMALIES: Dataset = Dataset {
+    name: "anomalies",
+    table: "anomalies",
+    key: "id",
+    ts: "event_time",
+    json: Some("evidence"),
+    fields: &[
+        def("id", "id", FieldType::Number),
+        def("kind", "kind", FieldType::Text),
+        def("fingerprint", "fingerprint", FieldType::Text),
+        def("actor", "actor_id", FieldType::Text),
+        def("actorkind", "actor_id", FieldType::ActorIdKind),
+        def("severity", "severity", FieldType::Text),
+        def("score", "score", FieldType::Number),
+        def("baseline", "baseline", FieldType::Number),
+        def("observed", "observed", FieldType::Number),
+        def("title", "title", FieldType::Text),
+        def("detail", "detail", FieldType::Text),
+        def("detectedat", "detected_at", FieldType::Time),
+    ],
+    export_columns: &[
+        "id",
+        "kind",
+        "severity",
+        "actor_id",
+        "score",
+        "baseline",
+        "observed",
+        "title",
+        "detail",
+        "event_time",
+        "detected_at",
+        "fingerprint",
+        "evidence",
+    ],
+};
+
+pub static SESSIONS: Dataset = Dataset {
+    name: "sessions",
+    table: "sessions",
+    key: "id",
+    ts: "started_at",
+    json: None,
+    fields: &[
+        def("id", "id", FieldType::Number),
+        def("key", "session_key", FieldType::Text),
+        def("actor", "actor_id", FieldType::Text),
+        def("actorkind", "actor_id", FieldType::ActorIdKind),
+        def("source", "source", FieldType::Text),
+        def("device", "device", FieldType::Text),
+        def("ip", "source_ip", FieldType::Text),
+        def("location", "location", FieldType::Text),
+        def("lastseen", "last_seen_at", FieldType::Time),
+        def("count", "event_count", FieldType::Number),
+        def("status", "status", FieldType::Text),
+        def("flag", "flag_reason", FieldType::Text),
+        def("authmethod", "auth_method", FieldType::Text),
+        def("mfa", "mfa_used", FieldType::Bool),
+        def("asn", "asn", FieldType::Number),
+        def("asorg", "as_org", FieldType::Text),
+        def("network", "network_class", FieldType::Text),
+    ],
+    export_columns: &[
+        "id",
+        "session_key",
+        "actor_id",
+        "source",
+        "status",
+        "started_at",
+        "last_seen_at",
+        "event_count",
+        "source_ip",
+        "location",
+        "device",
+        "auth_method",
+        "mfa_used",
+        "asn",
+        "as_org",
+        "network_class",
+        "flag_reason",
+    ],
+};
+
+pub static GRANTS: Dataset = Dataset {
+    name: "grants",
+    table: "grants",
+    key: "id",
+    ts: "granted_at",
+    json: None,
+    fields: &[
+        def("id", "id", FieldType::Number),
+        def("key", "grant_key", FieldType::Text),
+        def("actor", "actor_id", FieldType::Text),
+        def("actorkind", "actor_id", FieldType::ActorIdKind),
+        def("system", "system", FieldType::Text),
+        def("role", "role", FieldType::Text),
+        def("scope", "scope", FieldType::Text),
+        def("severity", "severity", FieldType::Text),
+        def("privileged", "privileged", FieldType::Bool),
+        def("grantedby", "granted_by", FieldType::Text),
+        def("sourceevent", "source_event", FieldType::Text),
+        def("revokedat", "revoked_at", FieldType::Time),
+        def("updatedat", "updated_at", FieldType::Time),
+    ],
+    export_columns: &[
+        "id",
+        "actor_id",
+        "system",
+        "role",
+        "scope",
+        "severity",
+        "privileged",
+        "granted_at",
+        "granted_by",
+        "revoked_at",
+        "source_event",
+        "grant_key",
+    ],
+};
+
+pub static DATASETS: [&Dataset; 5] = [&EVENTS, &ALERTS, &ANOMALIES, &SESSIONS, &GRANTS];
+
 /// Parse an RFC3339 (or naive `%Y-%m-%dT%H:%M:%S`) timestamp. Shared by the AST
 /// `Ts` node and the `from`/`to` facets.
 pub fn parse_ts(s: &str) -> Result<DateTime<Utc>, String> {
@@ -127,7 +400,14 @@ pub fn parse_ts(s: &str) -> Result<DateTime<Utc>, String> {
         .map_err(|e| format!("invalid timestamp {}: {}", s, e))
 }
 
+/// Compile against [`EVENTS`].
 pub fn compile(node: &Node, binds: &mut Vec<Bind>) -> Result<String, String> {
+    compile_in(&EVENTS, node, binds)
+}
+
+/// Compile a tree to a WHERE-clause fragment over `ds`, pushing the values it
+/// references onto `binds`. Only `ds`'s allowlisted columns are interpolated.
+pub fn compile_in(ds: &Dataset, node: &Node, binds: &mut Vec<Bind>) -> Result<String, String> {
     match node {
         Node::Group { op, children } => {
             if children.is_empty() {
@@ -139,36 +419,53 @@ pub fn compile(node: &Node, binds: &mut Vec<Bind>) -> Result<String, String> {
             };
             let parts = children
                 .iter()
-                .map(|c| compile(c, binds))
+                .map(|c| compile_in(ds, c, binds))
                 .collect::<Result<Vec<_>, _>>()?;
             Ok(format!("({})", parts.join(joiner)))
         }
-        Node::Not { child } => Ok(format!("NOT ({})", compile(child, binds)?)),
-        Node::Field { field, op, value } => compile_field(*field, *op, value, binds),
-        Node::Ts { op, value } => compile_ts(*op, value, binds),
+        Node::Not { child } => Ok(format!("NOT ({})", compile_in(ds, child, binds)?)),
+        Node::Field { field, op, value } => {
+            let def = ds
+                .field(field)
+                .ok_or_else(|| format!("unknown field `{}` for {}", field, ds.name))?;
+            compile_field(def, *op, value, binds)
+        }
+        Node::Ts { op, value } => compile_ts(ds.ts, *op, value, binds),
         Node::JsonPath {
             path,
             op,
             value,
             value_type,
-        } => compile_jsonpath(path, *op, value, *value_type, binds),
+        } => compile_jsonpath(json_column(ds)?, path, *op, value, *value_type, binds),
         Node::Raw { value } => {
+            let col = json_column(ds)?;
             binds.push(Bind::Text(format!("%{}%", value)));
-            Ok(format!("(raw::text ILIKE ${})", binds.len()))
+            Ok(format!("({}::text ILIKE ${})", col, binds.len()))
         }
     }
 }
 
+fn json_column(ds: &Dataset) -> Result<&'static str, String> {
+    ds.json
+        .ok_or_else(|| format!("{} have no payload for json paths or raw:", ds.name))
+}
+
 fn compile_field(
-    field: Field,
+    def: &FieldDef,
     op: Op,
     value: &str,
     binds: &mut Vec<Bind>,
 ) -> Result<String, String> {
-    if let Field::Kind = field {
-        return compile_actor_kind(op, value, binds);
+    let col = def.column;
+    match def.ty {
+        FieldType::Text => {}
+        FieldType::ActorKind => return compile_actor_kind(op, value, binds),
+        FieldType::ActorIdKind => return compile_actor_id_kind(col, op, value, binds),
+        FieldType::Number => return compile_number(def, op, value, binds),
+        FieldType::Time => return compile_ts(col, op, value, binds),
+        FieldType::Bool => return compile_bool(def, op, value),
+        FieldType::TextArray => return compile_text_array(def, op, value, binds),
     }
-    let col = field.column();
     match op {
         Op::Eq => {
             binds.push(Bind::Text(value.to_owned()));
@@ -193,11 +490,122 @@ fn compile_field(
         }
         _ => Err(format!(
             "comparison operators are not allowed on field `{}`",
-            col
+            def.name
+        )),
+    }
+}
+
+/// On typed fields the console's `:` means equals, as it does for `ts`.
+fn exact(op: Op) -> Op {
+    match op {
+        Op::Contains => Op::Eq,
+        Op::NotContains => Op::Ne,
+        op => op,
+    }
+}
+
+fn compile_number(
+    def: &FieldDef,
+    op: Op,
+    value: &str,
+    binds: &mut Vec<Bind>,
+) -> Result<String, String> {
+    let num: f64 = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    let sql_op = comparison_op(exact(op)).ok_or("operator not allowed on a number")?;
+    binds.push(Bind::Double(num));
+    Ok(format!("{} {} ${}", def.column, sql_op, binds.len()))
+}
+
+fn compile_bool(def: &FieldDef, op: Op, value: &str) -> Result<String, String> {
+    let truth = match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" => "TRUE",
+        "false" | "no" => "FALSE",
+        _ => return Err(format!("`{}` takes true or false", def.name)),
+    };
+    match exact(op) {
+        Op::Eq => Ok(format!("{} IS {}", def.column, truth)),
+        Op::Ne => Ok(format!("{} IS NOT {}", def.column, truth)),
+        _ => Err(format!("operator not allowed on `{}`", def.name)),
+    }
+}
+
+fn compile_text_array(
+    def: &FieldDef,
+    op: Op,
+    value: &str,
+    binds: &mut Vec<Bind>,
+) -> Result<String, String> {
+    let col = def.column;
+    match op {
+        Op::Eq | Op::Ne => {
+            binds.push(Bind::Text(value.to_owned()));
+            let any = format!("${} = ANY({})", binds.len(), col);
+            Ok(if op == Op::Eq {
+                any
+            } else {
+                format!("NOT ({})", any)
+            })
+        }
+        Op::Contains | Op::NotContains => {
+            binds.push(Bind::Text(format!("%{}%", value)));
+            let exists = format!(
+                "EXISTS (SELECT 1 FROM unnest({}) AS e(v) WHERE e.v ILIKE ${})",
+                col,
+                binds.len()
+            );
+            Ok(if op == Op::Contains {
+                exists
+            } else {
+                format!("NOT {}", exists)
+            })
+        }
+        _ => Err(format!(
+            "comparison operators are not allowed on field `{}`",
+            def.name
         )),
     }
 }
 
+/// Derived tables key actors by `actors.id`, so no alias resolution is needed.
+fn compile_actor_id_kind(
+    col: &str,
+    op: Op,
+    value: &str,
+    binds: &mut Vec<Bind>,
+) -> Result<String, String> {
+    let negate = match op {
+        Op::Eq | Op::Contains => false,
+        Op::Ne | Op::NotContains => true,
+        _ => return Err("operator not allowed on `actorkind`".to_string()),
+    };
+    let predicate = match value {
+        "person" | "service" => {
+            binds.push(Bind::Text(value.to_owned()));
+            format!(
+                "({col} IN (SELECT a.id FROM actors a WHERE a.kind = ${}))",
+                binds.len()
+            )
+        }
+        "unknown" => format!(
+            "({col} IS NULL OR {col} NOT IN (SELECT a.id FROM actors a \
+             WHERE a.kind IN ('person','service')))"
+        ),
+        other => {
+            return Err(format!(
+                "actorkind must be person|service|unknown (got `{}`)",
+                other
+            ))
+        }
+    };
+    Ok(if negate {
+        format!("NOT {}", predicate)
+    } else {
+        predicate
+    })
+}
+
 fn compile_actor_kind(op: Op, value: &str, binds: &mut Vec<Bind>) -> Result<String, String> {
     let negate = match op {
         Op::Eq | Op::Contains => false,
@@ -230,14 +638,15 @@ fn compile_actor_kind(op: Op, value: &str, binds: &mut Vec<Bind>) -> Result<Stri
     })
 }
 
-fn compile_ts(op: Op, value: &str, binds: &mut Vec<Bind>) -> Result<String, String> {
+fn compile_ts(col: &str, op: Op, value: &str, binds: &mut Vec<Bind>) -> Result<String, String> {
     let ts = parse_ts(value)?;
-    let sql_op = comparison_op(op).ok_or("operator not allowed on `ts`")?;
+    let sql_op = comparison_op(op).ok_or("operator not allowed on a timestamp")?;
     binds.push(Bind::Ts(ts));
-    Ok(format!("ts {} ${}", sql_op, binds.len()))
+    Ok(format!("{} {} ${}", col, sql_op, binds.len()))
 }
 
 fn compile_jsonpath(
+    col: &str,
     path: &[String],
     op: Op,
     value: &str,
@@ -253,24 +662,26 @@ fn compile_jsonpath(
         ValueType::Text => match op {
             Op::Eq => {
                 binds.push(Bind::Text(value.to_owned()));
-                Ok(format!("(raw #>> ${}) = ${}", p, binds.len()))
+                Ok(format!("({} #>> ${}) = ${}", col, p, binds.len()))
             }
             Op::Ne => {
                 binds.push(Bind::Text(value.to_owned()));
                 Ok(format!(
-                    "(raw #>> ${}) IS DISTINCT FROM ${}",
+                    "({} #>> ${}) IS DISTINCT FROM ${}",
+                    col,
                     p,
                     binds.len()
                 ))
             }
             Op::Contains => {
                 binds.push(Bind::Text(format!("%{}%", value)));
-                Ok(format!("(raw #>> ${}) ILIKE ${}", p, binds.len()))
+                Ok(format!("({} #>> ${}) ILIKE ${}", col, p, binds.len()))
             }
             Op::NotContains => {
                 binds.push(Bind::Text(format!("%{}%", value)));
                 Ok(format!(
-                    "((raw #>> ${p}) IS NULL OR (raw #>> ${p}) NOT ILIKE ${v})",
+                    "(({c} #>> ${p}) IS NULL OR ({c} #>> ${p}) NOT ILIKE ${v})",
+                    c = col,
                     p = p,
                     v = binds.len()
                 ))
@@ -288,7 +699,8 @@ fn compile_jsonpath(
             };
             binds.push(Bind::Double(num));
             Ok(format!(
-                "((raw #>> ${p}) ~ '^-?[0-9.]+$' AND (raw #>> ${p})::numeric {op} ${v}::numeric)",
+                "(({c} #>> ${p}) ~ '^-?[0-9.]+$' AND ({c} #>> ${p})::numeric {op} ${v}::numeric)",
+                c = col,
                 p = p,
                 op = sql_op,
                 v = binds.len()
@@ -317,7 +729,7 @@ mod tests {
 
     fn kind_node(op: Op, value: &str) -> Node {
         Node::Field {
-            field: Field::Kind,
+            field: "kind".to_string(),
             op,
             value: value.to_string(),
         }
@@ -377,4 +789,68 @@ mod tests {
         let mut binds = Vec::new();
         assert!(compile(&kind_node(Op::Gt, "person"), &mut binds).is_err());
     }
+
+    fn field(name: &str, op: Op, value: &str) -> Node {
+        Node::Field {
+            field: name.to_string(),
+            op,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn derived_dataset_fields_compile_by_type() {
+        let node = Node::Group {
+            op: BoolOp::And,
+            children: vec![
+                field("Severity", Op::Eq, "high"),
+                field("count", Op::Gte, "5"),
+                field("technique", Op::Contains, "T1098"),
+                field("actorkind", Op::Eq, "service"),
+                Node::Ts {
+                    op: Op::Gt,
+                    value: "2026-10-01".to_string(),
+                },
+                Node::JsonPath {
+                    path: vec!["uids".to_string()],
+                    op: Op::Contains,
+                    value: "abc".to_string(),
+                    value_type: ValueType::Text,
+                },
+            ],
+        };
+        let mut binds = Vec::new();
+        let sql = compile_in(&ALERTS, &node, &mut binds).unwrap();
+        assert_eq!(
+            sql,
+            "(severity = $1 AND event_count >= $2 AND \
+             EXISTS (SELECT 1 FROM unnest(attack_techniques) AS e(v) WHERE e.v ILIKE $3) AND \
+             (actor_id IN (SELECT a.id FROM actors a WHERE a.kind = $4)) AND \
+             last_seen > $5 AND (evidence #>> $6) ILIKE $7)"
+        );
+        assert!(matches!(binds[1], Bind::Double(n) if n == 5.0));
+
+        let mut binds = Vec::new();
+        let sql = compile_in(
+            &GRANTS,
+            &field("privileged", Op::Contains, "true"),
+            &mut binds,
+        );
+        assert_eq!(sql.unwrap(), "privileged IS TRUE");
+        assert!(binds.is_empty());
+    }
+
+    #[test]
+    fn fields_are_checked_against_the_dataset() {
+        let mut binds = Vec::new();
+        let err = compile_in(&SESSIONS, &field("rule", Op::Eq, "x"), &mut binds).unwrap_err();
+        assert_eq!(err, "unknown field `rule` for sessions");
+        let raw = Node::Raw {
+            value: "x".to_string(),
+        };
+        assert!(compile_in(&SESSIONS, &raw, &mut binds).is_err());
+        assert!(compile_in(&ANOMALIES, &field("score", Op::Gt, "high"), &mut binds).is_err());
+        assert!(compile(&field("severity", Op::Eq, "high"), &mut binds).is_err());
+        assert_eq!(Dataset::by_name("Alerts").map(|d| d.table), Some("alerts"));
+    }
 }
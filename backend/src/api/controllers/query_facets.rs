@@ -0,0 +1,265 @@
+//! `/api/query/facets`: the most frequent values of one field (or payload
+//! path), for building filters and autocompleting values in the query UI.
+//!
+//! Without an `ast`/`q` filter the answer comes from the value dictionary
+//! (`service::field_values`), which honours `source`/`status` and counts whole
+//! UTC days of `from`/`to`; it trails live ingest by a few minutes. With a
+//! filter, or for a field the dictionary doesn't count, the newest `scan_cap`
+//! matching events are counted instead, as `/aggregate` does.
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::Query;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Text};
+use serde::{Deserialize, Serialize};
+
+use super::query::{compile_params, Compiled, QueryParams};
+use super::query_agg::Operand;
+use super::query_ast::{apply_binds, parse_ts, Bind};
+use crate::db::DbPool;
+use crate::service::field_values::{self, FacetValue, Lookup};
+
+const DEFAULT_TOP: i64 = 20;
+const MAX_TOP: i64 = 100;
+/// Lower than `/aggregate`'s: this runs on every keystroke.
+const DEFAULT_SCAN_CAP: i64 = 20_000;
+const FACETS_STATEMENT_TIMEOUT: &str = "10s";
+/// As the dictionary stores them.
+const MAX_VALUE_CHARS: i64 = 256;
+
+#[derive(Deserialize)]
+pub struct FacetParams {
+    /// A field name (`actor`, `idsource`, …) or a payload path
+    /// (`json.requestParameters.roleName`).
+    pub field: String,
+    /// Case-insensitive value prefix, for autocomplete.
+    pub prefix: Option<String>,
+    /// Values returned. Default `DEFAULT_TOP`, at most `MAX_TOP`.
+    pub top: Option<i64>,
+    /// Filter, as on `/api/query`.
+    pub ast: Option<String>,
+    pub q: Option<String>,
+    pub status: Option<String>,
+    pub source: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Max matching events counted when scanning, newest first;
+    /// `0`/negative → unbounded. Ignored when served from the dictionary.
+    pub scan_cap: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct FacetResponse {
+    /// Canonical name of the field.
+    pub field: String,
+    /// Most frequent first.
+    pub values: Vec<FacetValue>,
+    /// Served from the value dictionary rather than by scanning events.
+    pub from_dictionary: bool,
+    /// Matching events counted when scanning.
+    pub scanned: Option<i64>,
+    /// The scan stopped at `scan_cap`; counts cover the newest events only.
+    pub capped: bool,
+}
+
+#[derive(QueryableByName)]
+struct ScanRow {
+    #[diesel(sql_type = Text)]
+    value: String,
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+    #[diesel(sql_type = BigInt)]
+    scanned: i64,
+    #[diesel(sql_type = Bool)]
+    capped: bool,
+}
+
+fn non_empty(s: Option<&str>) -> Option<&str> {
+    s.map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Count the values of `operand` over the newest `cap` events matching the
+/// compiled filter (and the prefix).
+fn scan_sql(
+    operand: &Operand,
+    prefix: Option<&str>,
+    compiled: Compiled,
+    top: i64,
+    cap: i64,
+) -> (String, Vec<Bind>) {
+    let Compiled {
+        where_sql,
+        mut binds,
+        ..
+    } = compiled;
+    let v = operand.sql(&mut binds);
+    let mut filter = format!("{where_sql} AND {v} <> ''");
+    if let Some(p) = prefix {
+        binds.push(Bind::Text(format!("{}%", field_values::like_escape(p))));
+        filter.push_str(&format!(
+            " AND lower({v}) LIKE lower(${}) ESCAPE '\\'",
+            binds.len()
+        ));
+    }
+    let (scope_sql, capped_sql) = if cap > 0 {
+        (
+            format!(
+                "scoped AS (SELECT ts, {v} AS v FROM ssumgmt_events WHERE {filter} \
+                 ORDER BY ts DESC LIMIT {over}), \
+                 kept AS (SELECT v FROM scoped ORDER BY ts DESC LIMIT {cap})",
+                over = cap + 1,
+            ),
+            format!("(SELECT count(*) FROM scoped) > {cap}"),
+        )
+    } else {
+        (
+            format!("kept AS (SELECT {v} AS v FROM ssumgmt_events WHERE {filter})"),
+            "false".to_string(),
+        )
+    };
+    let sql = format!(
+        "WITH {scope_sql} \
+         SELECT left(v, {MAX_VALUE_CHARS}) AS value, count(*)::bigint AS count, \
+           (SELECT count(*) FROM kept) AS scanned, {capped_sql} AS capped \
+         FROM kept GROUP BY 1 ORDER BY count DESC, value LIMIT {top}"
+    );
+    (sql, binds)
+}
+
+pub(super) async fn facets_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<FacetParams>,
+) -> Response {
+    let (field, operand) = match Operand::parse(&params.field) {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let top = params.top.unwrap_or(DEFAULT_TOP).clamp(1, MAX_TOP);
+    let prefix = non_empty(params.prefix.as_deref()).map(str::to_owned);
+    let filter = QueryParams {
+        ast: params.ast.clone(),
+        q: params.q.clone(),
+        status: params.status.clone(),
+        source: params.source.clone(),
+        from: params.from.clone(),
+        to: params.to.clone(),
+        ..Default::default()
+    };
+    let compiled = match compile_params(&filter) {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let unfiltered =
+        non_empty(params.ast.as_deref()).is_none() && non_empty(params.q.as_deref()).is_none();
+    // Already validated by `compile_params`.
+    let from = non_empty(params.from.as_deref()).and_then(|s| parse_ts(s).ok());
+    let to = non_empty(params.to.as_deref()).and_then(|s| parse_ts(s).ok());
+    let cap = params.scan_cap.unwrap_or(DEFAULT_SCAN_CAP);
+    let (scan, binds) = scan_sql(&operand, prefix.as_deref(), compiled, top, cap);
+
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "query.facets",
+        db.statement = tracing::field::Empty
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<FacetResponse> {
+        let _g = span.enter();
+        let mut conn = crate::db::conn(&pool).map_err(anyhow::Error::from)?;
+        if unfiltered && field_values::has_field(&mut conn, &field)? {
+            let values = field_values::lookup(
+                &mut conn,
+                &Lookup {
+                    field: &field,
+                    prefix: prefix.as_deref(),
+                    source: non_empty(params.source.as_deref()),
+                    status: non_empty(params.status.as_deref()),
+                    from,
+                    to,
+                    top,
+                },
+            )?;
+            return Ok(FacetResponse {
+                field,
+                values,
+                from_dictionary: true,
+                scanned: None,
+                capped: false,
+            });
+        }
+
+        span.record("db.statement", scan.as_str());
+        let rows: Vec<ScanRow> = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::sql_query(format!(
+                "SET LOCAL statement_timeout = '{FACETS_STATEMENT_TIMEOUT}'"
+            ))
+            .execute(conn)?;
+            apply_binds(diesel::sql_query(scan).into_boxed::<Pg>(), binds).load(conn)
+        })?;
+        let (scanned, capped) = rows.first().map_or((0, false), |r| (r.scanned, r.capped));
+        Ok(FacetResponse {
+            field,
+            values: rows
+                .into_iter()
+                .map(|r| FacetValue {
+                    value: r.value,
+                    count: r.count,
+                })
+                .collect(),
+            from_dictionary: false,
+            scanned: Some(scanned),
+            capped,
+        })
+    })
+    .await;
+
+    match res {
+        Ok(Ok(body)) => Json(body).into_response(),
+        Ok(Err(e)) if format!("{:#}", e).contains("statement timeout") => (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "facet scan exceeded {}; narrow the filter or lower scan_cap",
+                FACETS_STATEMENT_TIMEOUT
+            ),
+        )
+            .into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_binds_path_and_prefix() {
+        let compiled = compile_params(&QueryParams {
+            q: Some("source=cloudtrail".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let (_, operand) = Operand::parse("json.requestParameters.roleName").unwrap();
+        let (sql, binds) = scan_sql(&operand, Some("Admin_%"), compiled, 20, 500);
+        // filter, path, prefix
+        assert_eq!(binds.len(), 3);
+        assert!(matches!(&binds[2], Bind::Text(p) if p == "Admin\\_\\%%"));
+        assert!(!sql.contains("roleName"));
+        assert!(!sql.contains("Admin"));
+        assert!(sql.contains("LIMIT 501"));
+        assert!(sql.ends_with("LIMIT 20"));
+    }
+}
@@ -0,0 +1,1014 @@
+//! The text form of a [`Node`] tree: the parser behind `q=` and the printer
+//! back from a tree.
+//!
+//! ```text
+//! query     := [or] [order by <field> [asc|desc]]
+//! or        := and (OR and)*
+//! and       := unary ([AND] unary)*          -- whitespace is an implicit AND
+//! unary     := NOT unary | '-' unary | primary
+//! primary   := '(' [or] ')' | predicate
+//! predicate := lhs op value
+//! lhs       := <field> | ts | raw | json.<path> | raw.<path>
+//! op        := ':' '~' (substring) | '=' '!=' | '!~' | '>' '>=' '<' '<='
+//! value     := "quoted \"text\"" | bare text up to whitespace or a paren
+//! ```
+//!
+//! This is the console query box's grammar, so a query copied out of the UI
+//! means the same thing here. `-` glued to a predicate flips its operator
+//! (`-actor:bob` is `actor!~bob`); `NOT` and a free-standing `-` wrap their
+//! operand in a `not` node. Unquoted substring values may carry `*` at either
+//! end (`action:Attach*`), which is a no-op since substring matches are
+//! unanchored; quoting keeps a `*` literal. Paths split on `.`, with `["…"]`
+//! for keys holding dots or spaces.
+//!
+//! [`print`] renders a tree so that parsing the text gives the same tree back,
+//! for every tree [`parse`] can produce.
+
+use serde::Serialize;
+use thiserror::Error;
+
+use super::query_ast::{self, BoolOp, Dataset, FieldType, Node, Op, ValueType, EVENTS};
+
+/// A `q=` that doesn't parse. `pos` is the 0-based character offset of the
+/// offending input.
+#[derive(Error, Debug, Clone, PartialEq, Serialize)]
+#[error("{message} at column {col}", col = .pos + 1)]
+pub struct ParseError {
+    pub pos: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedQuery {
+    /// `None` for an empty query, which matches everything.
+    pub node: Option<Node>,
+    /// A trailing `order by <field> [asc|desc]`, lifted out of the filter.
+    pub order_by: Option<String>,
+    pub order_dir: Option<String>,
+}
+
+/// Operator spellings, longest first so `>=` wins over `>`.
+const OPS: [(&str, Op); 9] = [
+    (">=", Op::Gte),
+    ("<=", Op::Lte),
+    ("!=", Op::Ne),
+    ("!~", Op::NotContains),
+    (">", Op::Gt),
+    ("<", Op::Lt),
+    ("=", Op::Eq),
+    ("~", Op::Contains),
+    (":", Op::Contains),
+];
+
+/// Characters that end a left-hand side (an operator starts there).
+const OP_CHARS: [char; 6] = [':', '~', '=', '!', '<', '>'];
+
+const KEYWORDS: [&str; 3] = ["AND", "OR", "NOT"];
+
+/// Longest `q=` accepted, in characters.
+pub const MAX_LEN: usize = 4096;
+
+/// Deepest nesting of `(` and `NOT`/`-` accepted. The parser, `compile_in` and
+/// dropping a [`Node`] all recurse once per level, so this bounds their stack;
+/// a JSON `ast=` is bounded the same way by serde_json's recursion limit.
+pub const MAX_DEPTH: usize = 64;
+
+/// Parse against [`EVENTS`].
+pub fn parse(text: &str) -> Result<ParsedQuery, ParseError> {
+    parse_in(&EVENTS, text)
+}
+
+/// Parse a query over `ds`: field names are checked against its allowlist and
+/// `ts`, `json.` and `raw` refer to its time and payload columns.
+pub fn parse_in(ds: &'static Dataset, text: &str) -> Result<ParsedQuery, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() > MAX_LEN {
+        return Err(err_at(
+            MAX_LEN,
+            format!("query is longer than {MAX_LEN} characters"),
+        ));
+    }
+    let (end, order_by, order_dir) = split_order(&chars);
+    let mut p = Parser {
+        ds,
+        s: &chars[..end],
+        pos: 0,
+        depth: 0,
+    };
+    p.skip_ws();
+    let node = if p.at_end() {
+        None
+    } else {
+        Some(p.parse_or()?)
+    };
+    p.skip_ws();
+    if let Some(c) = p.peek() {
+        return Err(p.err(format!("unexpected `{c}`")));
+    }
+    Ok(ParsedQuery {
+        node,
+        order_by,
+        order_dir,
+    })
+}
+
+/// Find a trailing `order by <field> [asc|desc]` outside quotes and brackets.
+/// Returns where the filter text ends plus the clause's field and direction.
+/// The field is checked against the orderable columns by the caller.
+fn split_order(s: &[char]) -> (usize, Option<String>, Option<String>) {
+    // Start offsets of the last four whitespace-separated words, last first.
+    let mut starts = Vec::with_capacity(4);
+    let mut i = s.len();
+    while starts.len() < 4 {
+        while i > 0 && s[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        if i == 0 {
+            break;
+        }
+        while i > 0 && !s[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        starts.push(i);
+    }
+    for n in [4, 3] {
+        let Some(&i) = starts.get(n - 1) else {
+            continue;
+        };
+        let rest: String = s[i..].iter().collect();
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let is_order = words[0].eq_ignore_ascii_case("order")
+            && words[1].eq_ignore_ascii_case("by")
+            && words[2]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let dir = words.get(3).map(|d| d.to_ascii_lowercase());
+        if is_order
+            && matches!(dir.as_deref(), None | Some("asc" | "desc"))
+            && outside_quotes(&s[..i])
+        {
+            return (i, Some(words[2].to_ascii_lowercase()), dir);
+        }
+    }
+    (s.len(), None, None)
+}
+
+/// Does `s` end outside any `"…"` string and `[…]` path segment?
+fn outside_quotes(s: &[char]) -> bool {
+    let mut quoted = false;
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < s.len() {
+        let c = s[i];
+        if quoted {
+            match c {
+                '\\' => i += 1,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c == '[' {
+            depth += 1;
+        } else if c == ']' {
+            depth = depth.saturating_sub(1);
+        }
+        i += 1;
+    }
+    !quoted && depth == 0
+}
+
+struct Parser<'a> {
+    ds: &'static Dataset,
+    s: &'a [char],
+    pos: usize,
+    /// Open `(` and `NOT`/`-` levels at the cursor; capped at [`MAX_DEPTH`].
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn starts_with(&self, pat: &str) -> bool {
+        let n = pat.chars().count();
+        self.s
+            .get(self.pos..self.pos + n)
+            .is_some_and(|w| w.iter().copied().eq(pat.chars()))
+    }
+
+    fn err(&self, message: impl Into<String>) -> ParseError {
+        err_at(self.pos, message)
+    }
+
+    /// Run `f` one nesting level down for the `(`/`NOT`/`-` at `open`,
+    /// refusing to go past [`MAX_DEPTH`].
+    fn nested(
+        &mut self,
+        open: usize,
+        f: impl FnOnce(&mut Self) -> Result<Node, ParseError>,
+    ) -> Result<Node, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(err_at(
+                open,
+                format!("nested more than {MAX_DEPTH} levels deep"),
+            ));
+        }
+        self.depth += 1;
+        let node = f(self);
+        self.depth -= 1;
+        node
+    }
+
+    /// The word at the cursor, if it is a keyword (`AND`/`OR`/`NOT`, any case)
+    /// standing on its own.
+    fn keyword(&self) -> Option<&'static str> {
+        let end = (self.pos..self.s.len())
+            .find(|&i| self.s[i].is_whitespace() || matches!(self.s[i], '(' | ')'))
+            .unwrap_or(self.s.len());
+        let word: String = self.s[self.pos..end].iter().collect();
+        KEYWORDS.into_iter().find(|k| k.eq_ignore_ascii_case(&word))
+    }
+
+    fn parse_or(&mut self) -> Result<Node, ParseError> {
+        let mut children = vec![self.parse_and()?];
+        loop {
+            self.skip_ws();
+            if self.keyword() != Some("OR") {
+                break;
+            }
+            self.pos += 2;
+            children.push(self.parse_and()?);
+        }
+        Ok(group(BoolOp::Or, children))
+    }
+
+    fn parse_and(&mut self) -> Result<Node, ParseError> {
+        let mut children = vec![self.parse_unary()?];
+        loop {
+            self.skip_ws();
+            if matches!(self.peek(), None | Some(')')) {
+                break;
+            }
+            match self.keyword() {
+                Some("OR") => break,
+                Some("AND") => self.pos += 3,
+                _ => {}
+            }
+            children.push(self.parse_unary()?);
+        }
+        Ok(group(BoolOp::And, children))
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, ParseError> {
+        self.skip_ws();
+        match self.peek() {
+            None => Err(self.err("expected a term")),
+            Some('-') => {
+                let open = self.pos;
+                self.pos += 1;
+                if self.peek().is_some_and(|c| !c.is_whitespace() && c != '(') {
+                    Ok(negate(self.parse_predicate()?))
+                } else {
+                    Ok(not(self.nested(open, Self::parse_unary)?))
+                }
+            }
+            Some(_) if self.keyword() == Some("NOT") => {
+                let open = self.pos;
+                self.pos += 3;
+                Ok(not(self.nested(open, Self::parse_unary)?))
+            }
+            Some(_) => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, ParseError> {
+        match self.peek() {
+            Some('(') => {
+                let open = self.pos;
+                self.pos += 1;
+                self.skip_ws();
+                let inner = if self.peek() == Some(')') {
+                    group(BoolOp::And, Vec::new())
+                } else {
+                    self.nested(open, Self::parse_or)?
+                };
+                self.skip_ws();
+                if self.peek() != Some(')') {
+                    return Err(err_at(open, "unclosed `(`"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(')') => Err(self.err("unexpected `)`")),
+            _ => self.parse_predicate(),
+        }
+    }
+
+    fn parse_predicate(&mut self) -> Result<Node, ParseError> {
+        if let Some(kw) = self.keyword() {
+            return Err(self.err(format!("expected a term, found `{kw}`")));
+        }
+        let lhs_pos = self.pos;
+        let mut bracket = None;
+        while let Some(c) = self.peek() {
+            if bracket.is_some() {
+                match c {
+                    ']' => bracket = None,
+                    '"' => {
+                        self.quoted()?;
+                        continue;
+                    }
+                    _ => {}
+                }
+            } else if c == '[' {
+                bracket = Some(self.pos);
+            } else if c.is_whitespace() || matches!(c, '(' | ')' | '"') || OP_CHARS.contains(&c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if let Some(open) = bracket {
+            return Err(err_at(open, "unclosed `[`"));
+        }
+        let lhs: String = self.s[lhs_pos..self.pos].iter().collect();
+        if lhs.is_empty() {
+            return Err(match self.peek() {
+                Some('"') => self.err("expected field:value, found a quoted string"),
+                Some(c) => self.err(format!("missing field before `{c}`")),
+                None => self.err("expected a term"),
+            });
+        }
+
+        self.skip_ws();
+        let op_pos = self.pos;
+        let Some((op_text, op)) = OPS.into_iter().find(|(t, _)| self.starts_with(t)) else {
+            return Err(err_at(
+                lhs_pos,
+                format!("bare term `{lhs}`: use field:value, json.<path>, ts or raw:"),
+            ));
+        };
+        self.pos += op_text.len();
+        self.skip_ws();
+        let value_pos = self.pos;
+        let (value, quoted) = match self.peek() {
+            Some('"') => (self.quoted()?, true),
+            Some(c) if !matches!(c, '(' | ')') && self.keyword().is_none() => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+                {
+                    self.pos += 1;
+                }
+                (self.s[start..self.pos].iter().collect(), false)
+            }
+            _ => return Err(self.err(format!("missing value after `{lhs}{op_text}`"))),
+        };
+
+        Predicate {
+            ds: self.ds,
+            lhs,
+            lhs_pos,
+            op_text,
+            op,
+            op_pos,
+            value,
+            quoted,
+            value_pos,
+        }
+        .build()
+    }
+
+    /// A `"…"` string at the cursor; `\"` and `\\` are the only escapes, any
+    /// other backslash is kept as is.
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => match self.peek() {
+                    Some(e @ ('"' | '\\')) => {
+                        out.push(e);
+                        self.pos += 1;
+                    }
+                    _ => out.push('\\'),
+                },
+                _ => out.push(c),
+            }
+        }
+        Err(err_at(open, "unterminated quote"))
+    }
+}
+
+struct Predicate {
+    ds: &'static Dataset,
+    lhs: String,
+    lhs_pos: usize,
+    op_text: &'static str,
+    op: Op,
+    op_pos: usize,
+    value: String,
+    quoted: bool,
+    value_pos: usize,
+}
+
+impl Predicate {
+    fn build(self) -> Result<Node, ParseError> {
+        let substring = matches!(self.op, Op::Contains | Op::NotContains);
+        let compare = matches!(self.op, Op::Gt | Op::Gte | Op::Lt | Op::Lte);
+        let value = if self.quoted {
+            self.value.clone()
+        } else {
+            self.unwild(substring)?
+        };
+        let lower = self.lhs.to_ascii_lowercase();
+
+        let no_payload = || {
+            err_at(
+                self.lhs_pos,
+                format!("{} have no payload for json paths or raw:", self.ds.name),
+            )
+        };
+
+        if lower == "raw" {
+            if self.ds.json.is_none() {
+                return Err(no_payload());
+            }
+            if self.op != Op::Contains {
+                return Err(err_at(
+                    self.op_pos,
+                    "raw supports substring matching only (raw:term)",
+                ));
+            }
+            return Ok(Node::Raw { value });
+        }
+
+        if lower == "ts" {
+            let op = match self.op {
+                Op::NotContains => return Err(err_at(self.op_pos, "ts does not support `!~`")),
+                // `ts:` / `ts~` mean equals, as in the console.
+                Op::Contains => Op::Eq,
+                op => op,
+            };
+            query_ast::parse_ts(&value).map_err(|e| err_at(self.value_pos, e))?;
+            return Ok(Node::Ts { op, value });
+        }
+
+        if let Some(def) = self.ds.field(&self.lhs) {
+            if compare && !def.comparable() {
+                return Err(err_at(
+                    self.op_pos,
+                    format!("comparison operators are not allowed on `{}`", def.name),
+                ));
+            }
+            match def.ty {
+                FieldType::Time => {
+                    query_ast::parse_ts(&value).map_err(|e| err_at(self.value_pos, e))?;
+                }
+                FieldType::Number if !is_number(&value) => {
+                    return Err(err_at(self.value_pos, format!("`{value}` is not a number")));
+                }
+                _ => {}
+            }
+            return Ok(Node::Field {
+                field: def.name.to_string(),
+                op: self.op,
+                value,
+            });
+        }
+
+        let mut path = parse_path(&self.lhs, self.lhs_pos)?;
+        let prefixed = path
+            .first()
+            .is_some_and(|p| p.eq_ignore_ascii_case("json") || p.eq_ignore_ascii_case("raw"));
+        if !prefixed || path.len() < 2 {
+            return Err(err_at(
+                self.lhs_pos,
+                if self.ds.json.is_some() {
+                    format!(
+                        "unknown field `{}`: prefix payload paths with json. (e.g. json.requestParameters.roleName)",
+                        self.lhs
+                    )
+                } else {
+                    format!("unknown field `{}` for {}", self.lhs, self.ds.name)
+                },
+            ));
+        }
+        if self.ds.json.is_none() {
+            return Err(no_payload());
+        }
+        path.remove(0);
+        let value_type = if compare {
+            if !is_number(&value) {
+                return Err(err_at(self.value_pos, format!("`{value}` is not a number")));
+            }
+            ValueType::Number
+        } else {
+            ValueType::Text
+        };
+        Ok(Node::JsonPath {
+            path,
+            op: self.op,
+            value,
+            value_type,
+        })
+    }
+
+    /// Strip the no-op `*` from either end of an unquoted substring value;
+    /// anywhere else a bare `*` is refused rather than matched literally.
+    fn unwild(&self, substring: bool) -> Result<String, ParseError> {
+        let value = if substring {
+            self.value.trim_matches('*')
+        } else {
+            self.value.as_str()
+        };
+        match value.find('*') {
+            None => Ok(value.to_string()),
+            Some(i) => {
+                let lead = self.value.len() - self.value.trim_start_matches('*').len();
+                let at = self.value_pos
+                    + self.value[..if substring { lead + i } else { i }]
+                        .chars()
+                        .count();
+                let hint = if substring {
+                    "`*` only works at the start or end of a substring match"
+                } else {
+                    "`*` is a wildcard only with `:`, `~` or `!~`"
+                };
+                Err(err_at(
+                    at,
+                    format!(
+                        "{hint}; quote the value to match it literally (after `{}{}`)",
+                        self.lhs, self.op_text
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Split `a.b["c.d"].e` into `[a, b, c.d, e]`. `base` is the character
+/// offset of `s` in the query, for error positions.
+pub fn parse_path(s: &str, base: usize) -> Result<Vec<String>, ParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut after_bracket = false;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                if cur.is_empty() && !after_bracket {
+                    return Err(err_at(base + i, "empty path segment"));
+                }
+                if !cur.is_empty() {
+                    out.push(std::mem::take(&mut cur));
+                }
+                after_bracket = false;
+                i += 1;
+            }
+            '[' => {
+                if !cur.is_empty() {
+                    out.push(std::mem::take(&mut cur));
+                }
+                let open = i;
+                i += 1;
+                let mut seg = String::new();
+                if chars.get(i) == Some(&'"') {
+                    i += 1;
+                    loop {
+                        match chars.get(i) {
+                            None => return Err(err_at(base + open, "unterminated quote in path")),
+                            Some('"') => {
+                                i += 1;
+                                break;
+                            }
+                            Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                                seg.push(chars[i + 1]);
+                                i += 2;
+                            }
+                            Some(&c) => {
+                                seg.push(c);
+                                i += 1;
+                            }
+                        }
+                    }
+                } else {
+                    while i < chars.len() && chars[i] != ']' {
+                        seg.push(chars[i]);
+                        i += 1;
+                    }
+                }
+                if chars.get(i) != Some(&']') {
+                    return Err(err_at(base + open, "unclosed `[`"));
+                }
+                if seg.is_empty() {
+                    return Err(err_at(base + open, "empty path segment"));
+                }
+                i += 1;
+                out.push(seg);
+                after_bracket = true;
+            }
+            c => {
+                cur.push(c);
+                after_bracket = false;
+                i += 1;
+            }
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    } else if chars.last() == Some(&'.') {
+        return Err(err_at(base + chars.len(), "empty path segment"));
+    }
+    Ok(out)
+}
+
+fn err_at(pos: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        pos,
+        message: message.into(),
+    }
+}
+
+fn is_number(v: &str) -> bool {
+    let digits = v.strip_prefix('-').unwrap_or(v);
+    let mut parts = digits.splitn(2, '.');
+    let int = parts.next().unwrap_or("");
+    let all_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    all_digits(int) && parts.next().into_iter().all(all_digits)
+}
+
+fn group(op: BoolOp, mut children: Vec<Node>) -> Node {
+    if children.len() == 1 {
+        children.pop().unwrap()
+    } else {
+        Node::Group { op, children }
+    }
+}
+
+fn not(child: Node) -> Node {
+    Node::Not {
+        child: Box::new(child),
+    }
+}
+
+/// `-` glued to a predicate: flip an (in)equality or substring operator,
+/// wrap anything else in `not`.
+fn negate(node: Node) -> Node {
+    let flip = |op: Op| match op {
+        Op::Eq => Some(Op::Ne),
+        Op::Ne => Some(Op::Eq),
+        Op::Contains => Some(Op::NotContains),
+        Op::NotContains => Some(Op::Contains),
+        _ => None,
+    };
+    match node {
+        Node::Field { field, op, value } if flip(op).is_some() => Node::Field {
+            field,
+            op: flip(op).unwrap(),
+            value,
+        },
+        Node::Ts { op, value } if flip(op).is_some() => Node::Ts {
+            op: flip(op).unwrap(),
+            value,
+        },
+        Node::JsonPath {
+            path,
+            op,
+            value,
+            value_type,
+        } if flip(op).is_some() => Node::JsonPath {
+            path,
+            op: flip(op).unwrap(),
+            value,
+            value_type,
+        },
+        other => not(other),
+    }
+}
+
+/// Render a tree as query text. An empty top-level group prints as an empty string.
+pub fn print(node: &Node) -> String {
+    match node {
+        Node::Group { op, children } => {
+            let joiner = match op {
+                BoolOp::And => " AND ",
+                BoolOp::Or => " OR ",
+            };
+            children
+                .iter()
+                .map(print_operand)
+                .collect::<Vec<_>>()
+                .join(joiner)
+        }
+        Node::Not { child } => format!("NOT {}", print_operand(child)),
+        Node::Field { field, op, value } => {
+            format!("{}{}{}", field, op_text(*op), print_value(value))
+        }
+        Node::Ts { op, value } => format!("ts{}{}", op_text(*op), print_value(value)),
+        Node::JsonPath {
+            path, op, value, ..
+        } => format!(
+            "json{}{}{}",
+            print_path(path),
+            op_text(*op),
+            print_value(value)
+        ),
+        Node::Raw { value } => format!("raw:{}", print_value(value)),
+    }
+}
+
+/// Groups under a group or `NOT` are always parenthesised, so the nesting
+/// survives a round trip.
+fn print_operand(node: &Node) -> String {
+    match node {
+        Node::Group { .. } => format!("({})", print(node)),
+        _ => print(node),
+    }
+}
+
+fn op_text(op: Op) -> &'static str {
+    match op {
+        Op::Eq => "=",
+        Op::Ne => "!=",
+        Op::Contains => ":",
+        Op::NotContains => "!~",
+        Op::Gt => ">",
+        Op::Gte => ">=",
+        Op::Lt => "<",
+        Op::Lte => "<=",
+    }
+}
+
+fn print_value(v: &str) -> String {
+    let needs_quotes = v.is_empty()
+        || v.chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | '\\' | '*'))
+        || KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(v));
+    if needs_quotes {
+        quote(v)
+    } else {
+        v.to_string()
+    }
+}
+
+pub fn print_path(path: &[String]) -> String {
+    path.iter()
+        .map(|seg| {
+            let plain = !seg.is_empty()
+                && seg
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '$' | '@'));
+            if plain {
+                format!(".{seg}")
+            } else {
+                format!("[{}]", quote(seg))
+            }
+        })
+        .collect()
+}
+
+fn quote(v: &str) -> String {
+    format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(field: &str, op: Op, value: &str) -> Node {
+        Node::Field {
+            field: field.to_string(),
+            op,
+            value: value.to_string(),
+        }
+    }
+
+    fn parse_node(q: &str) -> Node {
+        parse(q).unwrap().node.unwrap()
+    }
+
+    #[test]
+    fn parses_fields_wildcards_timestamps_and_payload_paths() {
+        let node = parse_node(
+            "actor:alice@dfds.com AND action:Attach* AND ts >= 2026-06-20 \
+             AND raw.requestParameters.roleName != \"x\"",
+        );
+        assert_eq!(
+            node,
+            Node::Group {
+                op: BoolOp::And,
+                children: vec![
+                    field("actor", Op::Contains, "alice@dfds.com"),
+                    field("action", Op::Contains, "Attach"),
+                    Node::Ts {
+                        op: Op::Gte,
+                        value: "2026-06-20".to_string()
+                    },
+                    Node::JsonPath {
+                        path: vec!["requestParameters".to_string(), "roleName".to_string()],
+                        op: Op::Ne,
+                        value: "x".to_string(),
+                        value_type: ValueType::Text,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or_and_minus_flips_the_operator() {
+        let node = parse_node("-actor:bob source=github OR NOT (kind:service)");
+        assert_eq!(
+            node,
+            Node::Group {
+                op: BoolOp::Or,
+                children: vec![
+                    Node::Group {
+                        op: BoolOp::And,
+                        children: vec![
+                            field("actor", Op::NotContains, "bob"),
+                            field("source", Op::Eq, "github"),
+                        ],
+                    },
+                    not(field("kind", Op::Contains, "service")),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_character() {
+        let cases = [
+            ("actor:alice )", 12, "unexpected `)`"),
+            ("(actor:alice", 0, "unclosed `(`"),
+            ("actor", 0, "bare term `actor`"),
+            ("actor:", 6, "missing value"),
+            ("ip>1", 2, "comparison operators are not allowed on `ip`"),
+            ("json.a.b > abc", 11, "`abc` is not a number"),
+            ("action:At*ach", 9, "`*` only works"),
+            ("actor:\"bob", 6, "unterminated quote"),
+            ("ts>=yesterday", 4, "invalid timestamp"),
+            ("source=x AND", 12, "expected a term"),
+            ("json..a:x", 5, "empty path segment"),
+        ];
+        for (q, pos, msg) in cases {
+            let err = parse(q).unwrap_err();
+            assert_eq!(err.pos, pos, "{q}: {err}");
+            assert!(err.message.contains(msg), "{q}: {err}");
+        }
+    }
+
+    #[test]
+    fn order_by_is_lifted_off_the_end_but_not_out_of_quotes() {
+        let parsed = parse("source=cloudtrail order by actor DESC").unwrap();
+        assert_eq!(parsed.node, Some(field("source", Op::Eq, "cloudtrail")));
+        assert_eq!(parsed.order_by.as_deref(), Some("actor"));
+        assert_eq!(parsed.order_dir.as_deref(), Some("desc"));
+
+        let parsed = parse("raw:\"order by actor\"").unwrap();
+        assert_eq!(parsed.order_by, None);
+        assert_eq!(
+            parsed.node,
+            Some(Node::Raw {
+                value: "order by actor".to_string()
+            })
+        );
+
+        assert_eq!(parse("  ").unwrap().node, None);
+        assert_eq!(
+            parse("order by ts asc").unwrap().order_by.as_deref(),
+            Some("ts")
+        );
+        let parsed = parse("actor:\"x order by ts\" order by ts").unwrap();
+        assert_eq!(
+            parsed.node,
+            Some(field("actor", Op::Contains, "x order by ts"))
+        );
+        assert_eq!(parsed.order_by.as_deref(), Some("ts"));
+    }
+
+    #[test]
+    fn nesting_and_length_are_capped() {
+        let ok = format!("{}a:x{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert_eq!(
+            parse(&ok).unwrap().node,
+            Some(field("a", Op::Contains, "x"))
+        );
+
+        let deep = format!(
+            "{}a:x{}",
+            "(".repeat(MAX_DEPTH + 1),
+            ")".repeat(MAX_DEPTH + 1)
+        );
+        let err = parse(&deep).unwrap_err();
+        assert_eq!(err.pos, MAX_DEPTH);
+        assert!(err.message.contains("nested"), "{err}");
+        let err = parse(&format!("{}a:x", "NOT ".repeat(MAX_DEPTH + 1))).unwrap_err();
+        assert_eq!(err.pos, 4 * MAX_DEPTH);
+        let err = parse(&format!("{}a:x", "- ".repeat(MAX_DEPTH + 1))).unwrap_err();
+        assert_eq!(err.pos, 2 * MAX_DEPTH);
+
+        let err = parse(&"(".repeat(1_000_000)).unwrap_err();
+        assert_eq!(err.pos, MAX_LEN);
+    }
+
+    #[test]
+    fn printed_queries_parse_back_to_the_same_tree() {
+        let queries = [
+            "source=cloudtrail action:Console status=failure",
+            "(source=cloudtrail -action:AssumeRole) AND (source=selfservice actor:john)",
+            "actor:\"john doe\" OR idsource~oidc",
+            "json.requestParameters.maxSessionDuration >= 1500",
+            "json.responseElements.tags[\"dfds.cost.centre\"] = \"ti-cae\"",
+            "NOT (actor:b OR NOT uid:d) ts<2026-06-20T10:00:00Z ip=2001:db8::1",
+            "resource=\"arn:aws:s3:::bucket/*\" actor=\"say \\\"or\\\" \\\\ twice\"",
+            "raw:\"(x)\" OR () OR json.a[\"b c\"].d!~\"\"",
+        ];
+        for q in queries {
+            let node = parse_node(q);
+            let text = print(&node);
+            assert_eq!(parse_node(&text), node, "{q} printed as {text}");
+        }
+
+        let tricky = Node::Group {
+            op: BoolOp::And,
+            children: vec![
+                field("role", Op::Eq, "OR"),
+                field("resource", Op::Contains, "*star*"),
+                field("actor", Op::Ne, ""),
+                Node::Group {
+                    op: BoolOp::And,
+                    children: vec![
+                        field("uid", Op::Eq, "a\\\"b"),
+                        not(not(Node::Raw {
+                            value: "two words".to_string(),
+                        })),
+                    ],
+                },
+                Node::JsonPath {
+                    path: vec!["tags".to_string(), "dfds.cost.centre".to_string()],
+                    op: Op::Lt,
+                    value: "-1.5".to_string(),
+                    value_type: ValueType::Number,
+                },
+            ],
+        };
+        let text = print(&tricky);
+        assert_eq!(parse_node(&text), tricky, "printed as {text}");
+    }
+
+    #[test]
+    fn other_datasets_check_their_own_fields() {
+        let node = parse_in(
+            &query_ast::ALERTS,
+            "severity=high count>=5 lastseen>2026-10-01",
+        )
+        .unwrap()
+        .node
+        .unwrap();
+        assert_eq!(
+            node,
+            Node::Group {
+                op: BoolOp::And,
+                children: vec![
+                    field("severity", Op::Eq, "high"),
+                    field("count", Op::Gte, "5"),
+                    field("lastseen", Op::Gt, "2026-10-01"),
+                ],
+            }
+        );
+        assert_eq!(
+            print(&node),
+            "severity=high AND count>=5 AND lastseen>2026-10-01"
+        );
+
+        let cases = [
+            ("action:x", 0, "unknown field `action` for sessions"),
+            ("json.a:x", 0, "sessions have no payload"),
+            ("count>many", 6, "`many` is not a number"),
+            ("lastseen>=soon", 10, "invalid timestamp"),
+        ];
+        for (q, pos, msg) in cases {
+            let err = parse_in(&query_ast::SESSIONS, q).unwrap_err();
+            assert_eq!(err.pos, pos, "{q}: {err}");
+            assert!(err.message.contains(msg), "{q}: {err}");
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json, Router};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::entity::{db_span, outcome_response};
+use crate::api::auth::{has_role, principal_of};
+use crate::db::DbPool;
+use crate::service::access_review::{
+    self, Campaign, Decision, Item, ItemEvent, NewCampaign, TeamLead,
+};
+use crate::service::outcome::Outcome;
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route(
+            "/campaigns",
+            axum::routing::get(list_handler).post(create_handler),
+        )
+        .route("/campaigns/:id", axum::routing::get(detail_handler))
+        .route("/campaigns/:id/close", axum::routing::post(close_handler))
+        .route("/campaigns/:id/export", axum::routing::get(export_handler))
+        .route("/items/:id/decision", axum::routing::post(decision_handler))
+        .route("/items/:id/reviewer", axum::routing::put(reviewer_handler))
+        .route("/team-leads", axum::routing::get(team_leads_handler))
+        .route(
+            "/team-leads/:team",
+            axum::routing::put(set_team_lead_handler).delete(delete_team_lead_handler),
+        )
+        .with_state(pool)
+}
+
+/// App role that may decide any review item, not just those assigned to them.
+const REVIEW_ADMIN_ROLE: &str = "ce.reviewadmin";
+
+/// With auth disabled there are no roles to check (see `role_check`).
+fn is_review_admin(claims: Option<&Value>) -> bool {
+    let auth_disabled = crate::misc::config::load_conf().is_ok_and(|c| !c.api_enable_auth);
+    auth_disabled || has_role(claims, REVIEW_ADMIN_ROLE)
+}
+
+async fn list_handler(State(pool): State<DbPool>) -> Response {
+    let span = db_span("reviews.campaigns.list");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<Campaign>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::list(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Snapshot the grants in scope and assign reviewers.
+async fn create_handler(
+    State(pool): State<DbPool>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<NewCampaign>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("reviews.campaigns.create");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<Campaign>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::create(&mut conn, body, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::CREATED)
+}
+
+#[derive(Deserialize)]
+pub struct DetailParams {
+    /// Only items assigned to this reviewer; `me` is the caller.
+    pub reviewer: Option<String>,
+    /// Only undecided items.
+    pub pending: Option<bool>,
+}
+
+async fn detail_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(params): Query<DetailParams>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let reviewer = match params.reviewer.as_deref() {
+        Some("me") => Some(principal_of(claims.as_ref().map(|e| &e.0))),
+        Some("") | None => None,
+        Some(r) => Some(r.to_string()),
+    };
+    let span = db_span("reviews.campaigns.detail");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<Value>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        let Some(campaign) = access_review::get(&mut conn, id)? else {
+            return Ok(None);
+        };
+        let items = access_review::items(
+            &mut conn,
+            id,
+            reviewer.as_deref(),
+            params.pending.unwrap_or(false),
+        )?;
+        Ok(Some(json!({ "campaign": campaign, "items": items })))
+    })
+    .await;
+
+    match res {
+        Ok(Ok(Some(v))) => Json(v).into_response(),
+        Ok(Ok(None)) => (StatusCode::NOT_FOUND, "campaign not found").into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Sign-off: only a campaign with every item decided can be closed.
+async fn close_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("reviews.campaigns.close");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<Campaign>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::close(&mut conn, id, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    /// `csv` (default) or `json`.
+    pub format: Option<String>,
+}
+
+/// Review evidence: every item with its reviewer, decision, decider and
+/// timestamps; JSON also carries the full action log.
+async fn export_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    Query(params): Query<ExportParams>,
+) -> Response {
+    let as_json = match params.format.as_deref() {
+        None | Some("csv") => false,
+        Some("json") => true,
+        Some(other) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("unknown format '{other}' (csv | json)"),
+            )
+                .into_response()
+        }
+    };
+    let span = db_span("reviews.campaigns.export");
+    let res = tokio::task::spawn_blocking(
+        move || -> anyhow::Result<Option<(Campaign, Vec<Item>, Vec<ItemEvent>)>> {
+            let _g = span.enter();
+            let mut conn = pool.get()?;
+            let Some(campaign) = access_review::get(&mut conn, id)? else {
+                return Ok(None);
+            };
+            let items = access_review::items(&mut conn, id, None, false)?;
+            let events = if as_json {
+                access_review::events(&mut conn, id)?
+            } else {
+                Vec::new()
+            };
+            Ok(Some((campaign, items, events)))
+        },
+    )
+    .await;
+
+    let (campaign, items, events) = match res {
+        Ok(Ok(Some(v))) => v,
+        Ok(Ok(None)) => return (StatusCode::NOT_FOUND, "campaign not found").into_response(),
+        Ok(Err(e)) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("db error: {:#}", e),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("task join error: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let filename = format!(
+        "attachment; filename=\"access_review_{}.{}\"",
+        campaign.id,
+        if as_json { "json" } else { "csv" }
+    );
+    let mut headers = HeaderMap::new();
+    if let Ok(v) = HeaderValue::from_str(&filename) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    if as_json {
+        let body = json!({ "campaign": campaign, "items": items, "events": events });
+        return (StatusCode::OK, headers, Json(body)).into_response();
+    }
+    match access_review::export_csv(&campaign, &items) {
+        Ok(bytes) => {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            );
+            (StatusCode::OK, headers, bytes).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("csv error: {:#}", e),
+        )
+            .into_response(),
+    }
+}
+
+/// Approve, revoke or comment on one item as the caller, who must be its
+/// reviewer or hold `ce.reviewadmin`.
+async fn decision_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<Decision>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let admin = is_review_admin(claims.as_ref().map(|e| &e.0));
+    let span = db_span("reviews.items.decide");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<Item>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::decide(&mut conn, id, body, &who, admin)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct AssignReviewer {
+    pub reviewer: String,
+}
+
+async fn reviewer_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<AssignReviewer>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("reviews.items.assign");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<Item>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::assign(&mut conn, id, &body.reviewer, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+// --- Team leads -----------------------------------------------------------
+
+async fn team_leads_handler(State(pool): State<DbPool>) -> Response {
+    let span = db_span("reviews.team_leads.list");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<TeamLead>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::team_leads(&mut conn)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetTeamLead {
+    pub lead: String,
+}
+
+/// Set who reviews for a team. Applies to campaigns created afterwards.
+async fn set_team_lead_handler(
+    State(pool): State<DbPool>,
+    Path(team): Path<String>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<SetTeamLead>,
+) -> Response {
+    let lead = body.lead.trim().to_string();
+    if lead.is_empty() {
+        return (StatusCode::BAD_REQUEST, "lead is required").into_response();
+    }
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("reviews.team_leads.set");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::set_team_lead(&mut conn, &team, &lead, &who)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(_)) => Json(json!({ "ok": true })).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn delete_team_lead_handler(
+    State(pool): State<DbPool>,
+    Path(team): Path<String>,
+) -> Response {
+    let span = db_span("reviews.team_leads.delete");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        access_review::delete_team_lead(&mut conn, &team)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => (StatusCode::NOT_FOUND, "team lead not found").into_response(),
+        Ok(Ok(_)) => Json(json!({ "ok": true })).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,157 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json, Router};
+use serde_json::Value;
+
+use super::entity::{db_span, outcome_response};
+use crate::api::auth::principal_of;
+use crate::db::DbPool;
+use crate::service::outcome::Outcome;
+use crate::service::saved_search::{self, NewSearch, SavedSearch, Schedule};
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/", axum::routing::get(list_handler).post(create_handler))
+        .route(
+            "/:id",
+            axum::routing::get(get_handler)
+                .put(update_handler)
+                .delete(delete_handler),
+        )
+        .route(
+            "/:id/schedule",
+            axum::routing::put(schedule_handler).delete(unschedule_handler),
+        )
+        .with_state(pool)
+}
+
+/// The caller's searches and everyone's shared ones.
+async fn list_handler(State(pool): State<DbPool>, claims: Option<Extension<Value>>) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.list");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<SavedSearch>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        saved_search::list(&mut conn, &who)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.get");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<SavedSearch>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        Ok(saved_search::get(&mut conn, id, &who)?.map_or(Outcome::NotFound, Outcome::Done))
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+/// Save a filter (`ast` or `q`) under a name, owned by the caller.
+async fn create_handler(
+    State(pool): State<DbPool>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<NewSearch>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.create");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<SavedSearch>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        saved_search::create(&mut conn, body, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::CREATED)
+}
+
+async fn update_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<NewSearch>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.update");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<SavedSearch>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        saved_search::update(&mut conn, id, body, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+async fn delete_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.delete");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<()>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        saved_search::delete(&mut conn, id, &who)
+    })
+    .await;
+    match res {
+        Ok(Ok(Outcome::Done(()))) => StatusCode::NO_CONTENT.into_response(),
+        other => outcome_response(other, StatusCode::NO_CONTENT),
+    }
+}
+
+/// Promote to a scheduled search: run every `every_mins`, alerting when a
+/// slice matches more than `threshold` events.
+async fn schedule_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+    Json(body): Json<Schedule>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.schedule");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<SavedSearch>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        saved_search::set_schedule(&mut conn, id, body, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
+
+async fn unschedule_handler(
+    State(pool): State<DbPool>,
+    Path(id): Path<i64>,
+    claims: Option<Extension<Value>>,
+) -> Response {
+    let who = principal_of(claims.as_ref().map(|e| &e.0));
+    let span = db_span("searches.unschedule");
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Outcome<SavedSearch>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        saved_search::clear_schedule(&mut conn, id, &who)
+    })
+    .await;
+    outcome_response(res, StatusCode::OK)
+}
@@ -0,0 +1,445 @@
+//! `/api/tail/ws`: live tail of newly ingested events matching a filter.
+//!
+//! The filter is given on the upgrade request (`ast=` or `q=`, plus the
+//! `source`/`status` facets) and compiled like `/api/query`, so a bad filter
+//! is a 400 before any socket exists. Authentication is the progress socket's
+//! (bearer token in the WS subprotocol).
+//!
+//! Each connection follows `ssumgmt_events.ingested_at` with its own cursor,
+//! woken by ingest progress signals (after `settle_secs`, so a batch that is
+//! still committing isn't skipped past) or every `poll_secs`. `ingested_at` is
+//! stamped when an ingest run starts but only visible once it commits, so every
+//! poll re-reads `overlap_secs` behind the cursor and drops the `(source, uid)`s
+//! it already sent. Frames: `{"type":"events","payload":[…]}` in ingest order
+//! (late commits from the overlap first), `{"type":"rate_limited",
+//! "payload":{"from":…,"to":…}}` when matches in that ingest window exceeded
+//! the connection's rate cap and were skipped, and `{"type":"error",…}` for a
+//! failed poll (the socket stays open). A client that stops reading is
+//! dropped after `send_timeout_secs`; nothing is queued for it meanwhile.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Timestamptz;
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+use super::progress::{authorize, close_unauthorized};
+use super::query::{compile_params, Compiled, QueryParams, SELECT_COLS};
+use super::query_ast::{apply_binds, Bind};
+use crate::api::WebSharedState;
+use crate::db::model::SsuMgmtEvent;
+use crate::db::DbPool;
+use crate::misc::config::{load_conf, TailConfig};
+use crate::service::ingest::progress_subscribe;
+
+/// Keep-alive ping cadence, as on the progress socket.
+const PING_EVERY: Duration = Duration::from_secs(30);
+
+pub fn routes(state: WebSharedState) -> Router {
+    Router::new()
+        .route("/ws", axum::routing::get(ws_handler))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+pub struct TailParams {
+    /// Filter, as on `/api/query`; empty tails everything.
+    pub ast: Option<String>,
+    pub q: Option<String>,
+    pub status: Option<String>,
+    pub source: Option<String>,
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<WebSharedState>,
+    headers: HeaderMap,
+    Query(params): Query<TailParams>,
+) -> Response {
+    let filter = match compile_params(&QueryParams {
+        ast: params.ast,
+        q: params.q,
+        status: params.status,
+        source: params.source,
+        ..Default::default()
+    }) {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let authed = authorize(&state, &headers).await;
+    let conf = load_conf().unwrap().tail;
+    let pool = state.db_pool.clone();
+    ws.protocols(["bearer"])
+        .on_upgrade(move |socket| run(socket, pool, conf, filter, authed))
+}
+
+/// Token bucket behind the per-connection rate cap.
+struct RateCap {
+    tokens: f64,
+    per_sec: f64,
+    burst: f64,
+    last: Instant,
+}
+
+impl RateCap {
+    fn new(conf: &TailConfig) -> Self {
+        let burst = f64::from(conf.burst.max(1));
+        Self {
+            tokens: burst,
+            per_sec: f64::from(conf.max_rows_per_sec.max(1)),
+            burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// Rows that may be sent now.
+    fn available(&mut self, now: Instant) -> i64 {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.burst);
+        self.last = now;
+        self.tokens.floor() as i64
+    }
+
+    fn take(&mut self, n: usize) {
+        self.tokens = (self.tokens - n as f64).max(0.0);
+    }
+}
+
+/// One poll's result.
+struct Batch {
+    /// The window read ended here.
+    hi: DateTime<Utc>,
+    rows: Vec<SsuMgmtEvent>,
+    /// Matches beyond `limit` existed in the window.
+    more: bool,
+}
+
+/// A connection's position in `ingested_at`.
+#[derive(Default)]
+struct Cursor {
+    /// End of the last window read; `None` until the first poll.
+    hi: Option<DateTime<Utc>>,
+    /// Nothing at or before this is re-read: the end of a rate-limited
+    /// window, whose overflow the client was told was skipped.
+    floor: Option<DateTime<Utc>>,
+    /// `(source, uid)` of rows sent from windows the overlap still covers,
+    /// with the end of the window each was sent from.
+    sent: HashMap<(String, String), DateTime<Utc>>,
+}
+
+impl Cursor {
+    /// Start of the next window: `overlap` behind `hi`, never below `floor`.
+    fn lo(&self, overlap: chrono::Duration) -> Option<DateTime<Utc>> {
+        let lo = self.hi? - overlap;
+        Some(self.floor.map_or(lo, |f| lo.max(f)))
+    }
+
+    fn is_sent(&self, e: &SsuMgmtEvent) -> bool {
+        self.sent.contains_key(&(e.source.clone(), e.uid.clone()))
+    }
+
+    /// Move past `batch`, forgetting rows the next window can no longer reach.
+    fn advance(&mut self, batch: &Batch, overlap: chrono::Duration) {
+        self.hi = Some(batch.hi);
+        if batch.more {
+            self.floor = Some(batch.hi);
+        }
+        for e in &batch.rows {
+            self.sent
+                .insert((e.source.clone(), e.uid.clone()), batch.hi);
+        }
+        if let Some(lo) = self.lo(overlap) {
+            self.sent.retain(|_, hi| *hi > lo);
+        }
+    }
+}
+
+/// Read up to `limit` unsent matches ingested in `(cursor.lo, now - settle]`.
+/// Without a cursor only the window end is returned, so a new connection
+/// starts at now.
+fn fetch(
+    conn: &mut PgConnection,
+    conf: &TailConfig,
+    filter: &Compiled,
+    cursor: &Cursor,
+    limit: i64,
+) -> diesel::QueryResult<Batch> {
+    #[derive(QueryableByName)]
+    struct Hi {
+        #[diesel(sql_type = Timestamptz)]
+        hi: DateTime<Utc>,
+    }
+
+    conn.transaction(|conn| {
+        diesel::sql_query(format!(
+            "SET LOCAL statement_timeout = '{}s'",
+            conf.statement_timeout_secs.max(1)
+        ))
+        .execute(conn)?;
+        let Hi { hi } = diesel::sql_query(format!(
+            "SELECT now() - interval '{} seconds' AS hi",
+            conf.settle_secs
+        ))
+        .get_result(conn)?;
+        let overlap = chrono::Duration::seconds(conf.overlap_secs as i64);
+        let lo = match (cursor.lo(overlap), cursor.hi) {
+            (Some(lo), Some(prev)) if prev < hi => lo,
+            _ => {
+                return Ok(Batch {
+                    hi: cursor.hi.map_or(hi, |c| c.max(hi)),
+                    rows: Vec::new(),
+                    more: false,
+                })
+            }
+        };
+
+        let mut binds = filter.binds.clone();
+        binds.push(Bind::Ts(lo));
+        binds.push(Bind::Ts(hi));
+        let sql = format!(
+            "SELECT {SELECT_COLS} FROM ssumgmt_events \
+             WHERE ingested_at > ${lo} AND ingested_at <= ${hi} AND ({where_sql}) \
+             ORDER BY ingested_at, uid LIMIT {over}",
+            lo = binds.len() - 1,
+            hi = binds.len(),
+            where_sql = filter.where_sql,
+            // Rows already sent still count against the LIMIT.
+            over = limit + 1 + cursor.sent.len() as i64,
+        );
+        let mut rows: Vec<SsuMgmtEvent> =
+            apply_binds(diesel::sql_query(sql).into_boxed::<Pg>(), binds).load(conn)?;
+        rows.retain(|e| !cursor.is_sent(e));
+        let more = rows.len() as i64 > limit;
+        rows.truncate(usize::try_from(limit).unwrap_or(0));
+        Ok(Batch { hi, rows, more })
+    })
+}
+
+async fn run(
+    mut socket: WebSocket,
+    pool: DbPool,
+    conf: TailConfig,
+    filter: Compiled,
+    authed: bool,
+) {
+    if !authed {
+        close_unauthorized(&mut socket).await;
+        return;
+    }
+    info!("live tail opened (filter: {})", filter.where_sql);
+
+    let mut rx = progress_subscribe();
+    let mut cap = RateCap::new(&conf);
+    let mut cursor = Cursor::default();
+    let overlap = chrono::Duration::seconds(conf.overlap_secs as i64);
+    let poll = Duration::from_secs(conf.poll_secs.max(1));
+    let settle = Duration::from_secs(conf.settle_secs);
+    let send_timeout = Duration::from_secs(conf.send_timeout_secs.max(1));
+
+    // First wake immediately, to fix the starting cursor.
+    let wake = tokio::time::sleep(Duration::ZERO);
+    tokio::pin!(wake);
+    let mut ping = tokio::time::interval(PING_EVERY);
+    ping.tick().await; // consume the immediate first tick
+
+    loop {
+        let recv = async {
+            match rx.as_mut() {
+                Some(r) => r.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            signal = recv => {
+                match signal {
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        if let Some(r) = rx.as_mut() {
+                            loop {
+                                match r.try_recv() {
+                                    Ok(_) | Err(TryRecvError::Lagged(_)) => continue,
+                                    Err(_) => break, // Empty or Closed
+                                }
+                            }
+                        }
+                        // Rows just committed are inside the settle window;
+                        // look once it has passed, unless a poll comes sooner.
+                        let at = tokio::time::Instant::now() + settle;
+                        if at < wake.deadline() {
+                            wake.as_mut().reset(at);
+                        }
+                    }
+                    Err(RecvError::Closed) => rx = None,
+                }
+            }
+            _ = &mut wake => {
+                let limit = cap.available(Instant::now());
+                if limit > 0 || cursor.hi.is_none() {
+                    // The cursor rides along to the blocking task and back; a
+                    // panicked task loses it, and the tail restarts at now.
+                    let res = {
+                        let pool = pool.clone();
+                        let conf = conf.clone();
+                        let filter = filter.clone();
+                        let cur = std::mem::take(&mut cursor);
+                        tokio::task::spawn_blocking(move || {
+                            let batch = crate::db::conn(&pool)
+                                .and_then(|mut conn| fetch(&mut conn, &conf, &filter, &cur, limit));
+                            (cur, batch)
+                        })
+                        .await
+                    };
+                    let res = res.map(|(cur, batch)| {
+                        cursor = cur;
+                        batch
+                    });
+                    let frames = match res {
+                        Ok(Ok(batch)) => {
+                            let from = cursor.lo(overlap);
+                            cursor.advance(&batch, overlap);
+                            cap.take(batch.rows.len());
+                            let mut frames = Vec::new();
+                            if !batch.rows.is_empty() {
+                                frames.push(json!({ "type": "events", "payload": batch.rows }));
+                            }
+                            if batch.more {
+                                frames.push(json!({
+                                    "type": "rate_limited",
+                                    "payload": { "from": from, "to": batch.hi },
+                                }));
+                            }
+                            frames
+                        }
+                        // Keep the cursor: the window is retried on the next wake.
+                        Ok(Err(e)) => vec![json!({ "type": "error", "payload": format!("{:#}", e) })],
+                        Err(e) => vec![json!({ "type": "error", "payload": e.to_string() })],
+                    };
+                    let mut gone = false;
+                    for frame in frames {
+                        let sent = tokio::time::timeout(
+                            send_timeout,
+                            socket.send(Message::Text(frame.to_string())),
+                        )
+                        .await;
+                        if !matches!(sent, Ok(Ok(()))) {
+                            gone = true;
+                            break;
+                        }
+                    }
+                    if gone {
+                        break;
+                    }
+                }
+                wake.as_mut().reset(tokio::time::Instant::now() + poll);
+            }
+            _ = ping.tick() => {
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            inbound = socket.recv() => {
+                match inbound {
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    Some(Ok(_)) => {} // pong / stray frame — ignore
+                }
+            }
+        }
+    }
+    info!("live tail closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_cap_refills_up_to_burst() {
+        let conf = TailConfig {
+            max_rows_per_sec: 10,
+            burst: 20,
+            ..TailConfig::default()
+        };
+        let mut cap = RateCap::new(&conf);
+        let t0 = cap.last;
+        assert_eq!(cap.available(t0), 20);
+        cap.take(20);
+        assert_eq!(cap.available(t0), 0);
+        assert_eq!(cap.available(t0 + Duration::from_millis(500)), 5);
+        assert_eq!(cap.available(t0 + Duration::from_secs(60)), 20);
+    }
+
+    fn event(uid: &str) -> SsuMgmtEvent {
+        SsuMgmtEvent {
+            source: "cloudtrail".into(),
+            uid: uid.into(),
+            ts: Utc::now(),
+            actor: None,
+            action: "ConsoleLogin".into(),
+            resource: None,
+            source_ip: None,
+            level: "info".into(),
+            status: "success".into(),
+            raw: None,
+            role: None,
+            identity_source: None,
+            account_id: None,
+            caller_account_id: None,
+        }
+    }
+
+    #[test]
+    fn cursor_overlaps_until_rows_are_out_of_reach() {
+        let overlap = chrono::Duration::seconds(300);
+        let t0 = Utc::now();
+        let at = |secs: i64| t0 + chrono::Duration::seconds(secs);
+        let mut cursor = Cursor::default();
+        assert_eq!(cursor.lo(overlap), None);
+
+        cursor.advance(
+            &Batch {
+                hi: at(0),
+                rows: vec![event("a")],
+                more: false,
+            },
+            overlap,
+        );
+        assert_eq!(cursor.lo(overlap), Some(at(-300)));
+        assert!(cursor.is_sent(&event("a")));
+        assert!(!cursor.is_sent(&event("b")));
+
+        // Still inside the next window: kept, so a re-read drops it.
+        cursor.advance(
+            &Batch {
+                hi: at(60),
+                rows: Vec::new(),
+                more: false,
+            },
+            overlap,
+        );
+        assert!(cursor.is_sent(&event("a")));
+
+        // A rate-limited window is never re-read, so nothing before it is kept.
+        cursor.advance(
+            &Batch {
+                hi: at(90),
+                rows: vec![event("b")],
+                more: true,
+            },
+            overlap,
+        );
+        assert_eq!(cursor.lo(overlap), Some(at(90)));
+        assert!(cursor.sent.is_empty());
+    }
+}
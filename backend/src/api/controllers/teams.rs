@@ -0,0 +1,129 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use axum_extra::extract::Query;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::db::DbPool;
+use crate::service::siem::posture::{self, TeamPosture};
+
+const DEFAULT_HISTORY_DAYS: i64 = 90;
+const DEFAULT_MEMBERS: i64 = 50;
+const MAX_MEMBERS: i64 = 500;
+
+pub fn routes(pool: DbPool) -> Router {
+    Router::new()
+        .route("/", axum::routing::get(leaderboard_handler))
+        .route("/:team", axum::routing::get(team_handler))
+        .with_state(pool)
+}
+
+#[derive(Deserialize)]
+pub struct LeaderboardParams {
+    /// `risk_avg` (default), `risk_max`, `open_alerts`, `privileged_grants`,
+    /// `dormant`, `anomaly_rate` or `members`; worst first.
+    pub sort: Option<String>,
+}
+
+/// Every team's current posture, as of the last SIEM pass.
+async fn leaderboard_handler(
+    State(pool): State<DbPool>,
+    Query(params): Query<LeaderboardParams>,
+) -> Response {
+    let sort = params.sort.unwrap_or_default();
+    let Some(sort) = posture::sort_column(&sort) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("unknown sort '{sort}' ({})", posture::SORTS.join(" | ")),
+        )
+            .into_response();
+    };
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "teams.leaderboard"
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<TeamPosture>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        posture::leaderboard(&mut conn, sort)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Json(rows).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TeamParams {
+    /// Days of daily history (default 90).
+    pub days: Option<i64>,
+    /// Members listed, riskiest first (default 50).
+    pub limit: Option<i64>,
+}
+
+/// One team: current posture, daily history and its members.
+async fn team_handler(
+    State(pool): State<DbPool>,
+    Path(team): Path<String>,
+    Query(params): Query<TeamParams>,
+) -> Response {
+    let days = params.days.unwrap_or(DEFAULT_HISTORY_DAYS);
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_MEMBERS)
+        .clamp(1, MAX_MEMBERS);
+    let span = tracing::info_span!(
+        "db.query",
+        otel.kind = "client",
+        db.system = "postgresql",
+        op = "teams.detail"
+    );
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<Option<Value>> {
+        let _g = span.enter();
+        let mut conn = pool.get()?;
+        let current = posture::get(&mut conn, &team)?;
+        let history = posture::history(&mut conn, &team, days)?;
+        // A team that lost its last member still has its history.
+        if current.is_none() && history.is_empty() {
+            return Ok(None);
+        }
+        let members = posture::members(&mut conn, &team, limit)?;
+        Ok(Some(json!({
+            "team": team,
+            "posture": current,
+            "history": history,
+            "members": members,
+        })))
+    })
+    .await;
+
+    match res {
+        Ok(Ok(Some(v))) => Json(v).into_response(),
+        Ok(Ok(None)) => (StatusCode::NOT_FOUND, "team not found").into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("db error: {:#}", e),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("task join error: {}", e),
+        )
+            .into_response(),
+    }
+}
@@ -1,5 +1,5 @@
 mod auth;
-mod controllers;
+pub mod controllers;
 mod static_files;
 
 use crate::api::controllers::add_controllers;
@@ -72,10 +72,17 @@ pub fn start_server(
                 .map(|s| s.trim().to_string())
                 .filter(|s| !s.is_empty())
                 .collect();
+            // Async query jobs get their own connection budget (see
+            // `service::query_jobs`), built here since only the API runs them.
+            let query_jobs = crate::service::query_jobs::JobRunner::new(
+                crate::db::build_jobs_pool(&conf.db),
+                conf.query_jobs.clone(),
+            );
             let web_state = Arc::new(WebState::new(
                 x,
                 conf.cache_implementation,
                 db_pool,
+                query_jobs,
                 audit_tx,
                 conf.audit.enabled,
                 audit_exclude,
@@ -133,6 +140,7 @@ pub fn api_router(state: WebSharedState) -> axum::Router {
         .fallback(axum::routing::any(api_fallback));
 
     routes = routes.nest("/progress", controllers::progress::routes(state.clone()));
+    routes = routes.nest("/tail", controllers::tail::routes(state.clone()));
 
     routes = add_controllers(routes, state.clone());
 
@@ -163,10 +171,17 @@ fn action_for(method: &str, template: &str) -> String {
     let action = match (method, t) {
         ("GET", "/query") => "query.search",
         ("GET", "/query/export.csv") => "query.export_csv",
+        ("POST", "/jobs") => "query.job_submit",
+        ("POST", "/jobs/:id/cancel") => "query.job_cancel",
         ("GET", "/entity/:id") => "entity.inspect",
         ("GET", "/entity/:id/activity") => "entity.activity",
         ("GET", "/entity/:id/timeline") => "entity.timeline",
+        ("GET", "/entity/resource/:id") => "entity.resource_inspect",
+        ("GET", "/entity/resource/:id/activity") => "entity.resource_activity",
+        ("GET", "/entity/resource/:id/timeline") => "entity.resource_timeline",
+        ("GET", "/entity/ip/:ip") => "entity.ip_inspect",
         ("GET", "/graph") => "graph.view",
+        ("GET", "/graph/export") => "graph.export",
         ("GET", "/actors") => "actors.list",
         ("POST", "/alerts/:id/ack") => "alert.ack",
         ("POST", "/alerts/:id/resolve") => "alert.resolve",
@@ -286,6 +301,8 @@ pub type WebSharedState = Arc<WebState>;
 pub struct WebState {
     pub jwt_validator: AuthorizationLayer<Value>,
     pub db_pool: crate::db::DbPool,
+    /// Runs async query jobs on the separate jobs pool.
+    pub query_jobs: crate::service::query_jobs::JobRunner,
     /// Channel to the bg batch writer for self-audit rows (source `ssu-mgmt`).
     pub audit_tx: Sender<Message>,
     /// Master switch for self-audit (`SSU__AUDIT__ENABLED`).
@@ -300,6 +317,7 @@ impl WebState {
         layer: AuthorizationLayer<Value>,
         _cache_implementation: String,
         db_pool: crate::db::DbPool,
+        query_jobs: crate::service::query_jobs::JobRunner,
         audit_tx: Sender<Message>,
         audit_enabled: bool,
         audit_exclude: Vec<String>,
@@ -307,6 +325,7 @@ impl WebState {
         Self {
             jwt_validator: layer,
             db_pool,
+            query_jobs,
             audit_tx,
             audit_enabled,
             audit_exclude: Arc::new(audit_exclude),
@@ -28,6 +28,8 @@ pub struct Config {
     pub pool_min_idle: Option<u32>,
     pub worker_pool_max_size: Option<u32>,
     pub worker_pool_min_idle: Option<u32>,
+    /// Async query jobs (`service::query_jobs`); each running job holds two.
+    pub jobs_pool_max_size: Option<u32>,
 }
 
 impl Config {
@@ -72,6 +74,19 @@ pub fn build_worker_pool(conf: &Config) -> DbPool {
     )
 }
 
+/// Build the **query jobs** r2d2 connection pool — serves async query jobs on
+/// the API replicas. A job can scan for many minutes, so jobs get their own
+/// budget rather than borrowing API-pool connections that interactive queries
+/// need. No idle floor: connections open only while jobs run.
+pub fn build_jobs_pool(conf: &Config) -> DbPool {
+    build_pool_inner(
+        conf,
+        conf.jobs_pool_max_size.unwrap_or(8),
+        Some(0),
+        std::time::Duration::from_secs(30),
+    )
+}
+
 pub fn build_leader_pool(conf: &Config) -> DbPool {
     build_pool_inner(conf, 2, Some(1), std::time::Duration::from_secs(5))
 }
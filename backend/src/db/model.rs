@@ -73,6 +73,8 @@ pub struct CloudtrailEventInsert {
     pub principal_name: Option<String>,
     pub assumed_role_arn: Option<String>,
     pub identity_source: Option<String>,
+    pub auth_method: Option<String>,
+    pub mfa_used: Option<bool>,
     pub source_ip: Option<String>,
     pub user_agent: Option<String>,
     pub error_code: Option<String>,
@@ -198,9 +200,17 @@ pub struct Alert {
     pub resolved_by: Option<String>,
     pub resolved_at: Option<chrono::DateTime<chrono::Utc>>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub attack_tactics: Vec<Option<String>>,
+    pub attack_techniques: Vec<Option<String>>,
+    /// Routing target: the actor's effective owner (team or person).
+    pub owner: Option<String>,
+    pub owner_kind: Option<String>,
 }
 
-/// Derived session row (AWS-only in v1; `location` via GeoLite2 when available).
+/// Derived session row, stitched per person across sources (`source` opened
+/// it, `source_counts` per source, `chain` links SSO logins to the role
+/// sessions they spawned); `location`, `asn`/`as_org` via GeoLite2 when
+/// available; `network_class` marks trusted egress and hosting/VPN ranges.
 #[derive(Queryable, Selectable, QueryableByName, Serialize, Clone)]
 #[diesel(table_name = sessions)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -217,6 +227,13 @@ pub struct Session {
     pub event_count: i64,
     pub status: String,
     pub flag_reason: Option<String>,
+    pub asn: Option<i64>,
+    pub as_org: Option<String>,
+    pub network_class: Option<String>,
+    pub source_counts: serde_json::Value,
+    pub chain: serde_json::Value,
+    pub auth_method: Option<String>,
+    pub mfa_used: Option<bool>,
 }
 
 #[derive(Queryable, Selectable, QueryableByName, Serialize, Clone)]
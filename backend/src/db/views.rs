@@ -19,6 +19,8 @@ diesel::table! {
     ///
     /// Mirrors the `ssumgmt_events` view defined in the migration; keep the two
     /// in sync (column set + types).
+    /// The trailing `ingested_at` column is only read by the live tail's raw
+    /// SQL and deliberately not mapped.
     ssumgmt_events (uid) {
         source -> Text,
         uid -> Text,
@@ -22,6 +22,8 @@ pub struct Config {
     pub enable_siem_derivation: bool,
     pub enable_guardduty: bool,
     pub enable_retention: bool,
+    pub enable_threat_intel: bool,
+    pub enable_ownership: bool,
     pub auth: Auth,
     pub auth_jwks_url: Option<String>,
     pub cache_implementation: String,
@@ -34,9 +36,16 @@ pub struct Config {
     pub risk: RiskConfig,
     pub geoip: GeoipConfig,
     pub guardduty: GuarddutyConfig,
+    pub threat_intel: ThreatIntelConfig,
+    pub ownership: OwnershipConfig,
     pub worker: WorkerConfig,
     pub runtime: RuntimeConfig,
     pub timeline: TimelineConfig,
+    pub searches: SearchConfig,
+    pub query_jobs: QueryJobsConfig,
+    pub facets: FacetsConfig,
+    pub tail: TailConfig,
+    pub resources: ResourcesConfig,
     pub retention: RetentionConfig,
     pub audit: AuditConfig,
     pub tracing: TracingConfig,
@@ -57,6 +66,151 @@ impl Default for TimelineConfig {
     }
 }
 
+/// Scheduled saved searches (`service::saved_search`). The scheduler runs on the
+/// worker leader; each search still runs only every `every_mins`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchConfig {
+    /// How often the scheduler looks for due searches.
+    pub interval_secs: u64,
+    /// Slices (of `ingested_at`) end this far behind now. Ingesters stamp
+    /// `ingested_at` when a run starts and commit when it ends, so this covers
+    /// a run's length and no row commits into a slice that already ran.
+    pub lag_mins: i64,
+    /// Matching `uid`s kept in an alert's evidence.
+    pub evidence_uids: i64,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            lag_mins: 15,
+            evidence_uids: 50,
+        }
+    }
+}
+
+/// The facet value dictionary (`service::field_values`), refreshed on the
+/// worker leader.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FacetsConfig {
+    pub interval_secs: u64,
+    /// The dictionary trails now by this much, so late-delivered events are
+    /// still counted.
+    pub lag_mins: i64,
+    /// Event time folded per pass at most, so a cold start catches up in steps.
+    pub step_hours: i64,
+    /// Where a cold start begins, and how many days of counts are kept.
+    pub retention_days: i64,
+    /// Comma-separated payload paths (`requestParameters.roleName`) counted
+    /// alongside the fields, offered as `json.<path>` facets.
+    pub json_paths: String,
+}
+
+impl Default for FacetsConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            lag_mins: 15,
+            step_hours: 6,
+            retention_days: 30,
+            json_paths: "eventSource,userIdentity.type,errorCode,requestParameters.roleName"
+                .to_owned(),
+        }
+    }
+}
+
+/// Resource entities (`service::resources`), maintained on the worker leader.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResourcesConfig {
+    pub interval_secs: u64,
+    /// Ingest history folded on a cold start.
+    pub backfill_days: i64,
+}
+
+impl Default for ResourcesConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 120,
+            backfill_days: 30,
+        }
+    }
+}
+
+/// Live tail over WebSocket (`/api/tail/ws`).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TailConfig {
+    /// Poll cadence when no ingest progress signal arrives (the self-audit
+    /// writer doesn't signal, and the relay may be down).
+    pub poll_secs: u64,
+    /// Rows ingested within this many seconds of now are left for the next
+    /// poll, so a batch still committing isn't skipped past.
+    pub settle_secs: u64,
+    /// Each poll re-reads this far behind its cursor, skipping rows already
+    /// sent. Ingesters stamp `ingested_at` when a run starts and commit when it
+    /// ends, so a row can appear well behind the cursor; like the web-identity
+    /// watermark lag, this covers a run's length.
+    pub overlap_secs: u64,
+    /// Per-connection rate cap: sustained rows per second, and the burst
+    /// allowed on top. Rows over the cap are skipped, and the client told.
+    pub max_rows_per_sec: u32,
+    pub burst: u32,
+    /// A client that doesn't take a frame within this long is disconnected.
+    pub send_timeout_secs: u64,
+    pub statement_timeout_secs: u64,
+}
+
+impl Default for TailConfig {
+    fn default() -> Self {
+        Self {
+            poll_secs: 5,
+            settle_secs: 5,
+            overlap_secs: 300,
+            max_rows_per_sec: 50,
+            burst: 500,
+            send_timeout_secs: 10,
+            statement_timeout_secs: 5,
+        }
+    }
+}
+
+/// Async query jobs (`service::query_jobs`). Jobs run on the API replica that
+/// accepted them, on the separate `db.jobs_pool_max_size` pool; the cleanup
+/// sweep runs on the worker leader.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueryJobsConfig {
+    /// Queued + running jobs one user may have at a time.
+    pub max_per_user: i64,
+    /// Jobs running at once per replica (further ones wait queued), also capped
+    /// at half the jobs pool.
+    pub max_concurrent: usize,
+    /// Rows materialised per job; the job ends `truncated` past this.
+    pub max_rows: i64,
+    pub fetch_batch: i64,
+    pub statement_timeout_secs: u64,
+    /// How long a finished job and its rows are kept.
+    pub ttl_mins: i64,
+    /// A queued/running job not heard from for this long lost its runner
+    /// (replica restart) and is failed by the cleanup sweep.
+    pub stale_mins: i64,
+    pub cleanup_interval_secs: u64,
+}
+
+impl Default for QueryJobsConfig {
+    fn default() -> Self {
+        Self {
+            max_per_user: 2,
+            max_concurrent: 4,
+            max_rows: 500_000,
+            fetch_batch: 2_000,
+            statement_timeout_secs: 900,
+            ttl_mins: 1_440,
+            stale_mins: 30,
+            cleanup_interval_secs: 300,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TracingConfig {
     pub enable: bool,
@@ -201,12 +355,16 @@ pub struct RetentionConfig {
     pub github_days: i64,
     /// Keep self-service audit records for this many days. `<= 0` → keep forever.
     pub selfservice_days: i64,
-    /// Keep derived `sessions`/`anomalies` + **resolved** `alerts` for this many
-    /// days (open/acked alerts are never pruned). `<= 0` → keep forever.
+    /// Keep derived `sessions`/`anomalies`/`threat_intel_hits`/`resources` and
+    /// **resolved** `alerts` for this many days (open/acked alerts are never
+    /// pruned). `<= 0` → keep forever.
     pub derived_days: i64,
     /// Keep the service's own self-audit rows (`ssumgmt_audit`) for this many days.
     /// `<= 0` → keep forever.
     pub ssumgmt_days: i64,
+    /// Keep daily `risk_score_history` buckets for this many days. `<= 0` → keep
+    /// forever.
+    pub risk_history_days: i64,
     /// Rows deleted per chunk. Small + index-driven so each chunk is quick and
     /// cancellation is observed promptly between chunks (shutdown-wedge guard).
     pub batch_size: i64,
@@ -221,6 +379,7 @@ impl Default for RetentionConfig {
             selfservice_days: 365,
             derived_days: 90,
             ssumgmt_days: 365,
+            risk_history_days: 730,
             batch_size: 5_000,
         }
     }
@@ -236,7 +395,7 @@ pub struct AuditConfig {
     pub enabled: bool,
     /// Comma-separated matched-path template **prefixes** to exclude from self-audit
     /// (matched against the full `/api/...` template). Defaults exclude the dashboard
-    /// polling endpoints plus the auth-config and progress-WS bypasses.
+    /// polling endpoints plus the auth-config and progress/tail WS bypasses.
     pub exclude_prefixes: String,
 }
 
@@ -244,7 +403,8 @@ impl Default for AuditConfig {
     fn default() -> Self {
         Self {
             enabled: true,
-            exclude_prefixes: "/api/overview/,/api/auth/config,/api/progress/".to_owned(),
+            exclude_prefixes: "/api/overview/,/api/auth/config,/api/progress/,/api/tail/"
+                .to_owned(),
         }
     }
 }
@@ -280,6 +440,25 @@ pub struct SiemConfig {
     /// Speed (km/h) between two consecutive logins above which travel is deemed
     /// physically impossible (faster than a commercial flight + airport overhead).
     pub impossible_travel_kmh: f64,
+    /// Score rise (points) within `risk_jump_window_hours` that trips `risk_jump`.
+    pub risk_jump_delta: i32,
+    pub risk_jump_window_hours: i64,
+    /// A roster fetch that drops more than this percentage of the last snapshot's
+    /// members in one go is taken as a possibly broken/partial response: the
+    /// diff is held back and `roster_suspect_diff` raised instead of recording
+    /// a wave of leavers.
+    pub roster_max_leave_pct: i64,
+    /// Consecutive fetches with the same held-back leaver set after which it is
+    /// accepted as a real reorganisation. Acking the alert accepts it sooner.
+    pub roster_confirm_fetches: i64,
+    /// Days of daily `team_posture_history` rows to keep.
+    pub team_posture_history_days: i64,
+    /// Days a role-assumption edge is kept after it was last seen. Until it
+    /// ages out, reusing it is not a newly observed path.
+    pub role_edge_retention_days: i64,
+    /// An access key minted for a freshly created IAM user within this many
+    /// minutes of `CreateUser` trips `new_user_with_access_key`.
+    pub new_user_key_window_mins: i64,
 }
 
 impl Default for SiemConfig {
@@ -297,6 +476,13 @@ impl Default for SiemConfig {
             anomaly_min_history_days: 3,
             off_hours_spike_min: 5,
             impossible_travel_kmh: 900.0,
+            risk_jump_delta: 30,
+            risk_jump_window_hours: 24,
+            roster_max_leave_pct: 20,
+            roster_confirm_fetches: 3,
+            team_posture_history_days: 365,
+            role_edge_retention_days: 180,
+            new_user_key_window_mins: 60,
         }
     }
 }
@@ -312,8 +498,14 @@ pub struct SelfserviceConfig {
     pub token_scope: String,
 }
 
-/// Risk-model weights (`SSU__RISK__W_*`). Each factor is
-/// saturating so no single signal dominates; `score = clamp(0..100, Σ w·f(x))`.
+/// Risk-model weights (`SSU__RISK__W_*`) and saturation caps (`SSU__RISK__CAP_*`,
+/// the raw value at which a factor is fully on). Each factor is saturating so
+/// no single signal dominates; `score = clamp(0..100, Σ w·f(x))`. The factors
+/// themselves live in `siem::risk::FACTORS`. Factors added after the initial
+/// model (`w_open_alerts` onwards) default to weight 0 so an upgrade does not
+/// shift every score and trip `risk_jump`; they still report their raw value
+/// and refs in `components` until a weight is set.
+/// Every pass also appends to `risk_score_history`, compacted as it ages.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RiskConfig {
     pub w_failed_auth: f64,
@@ -323,6 +515,26 @@ pub struct RiskConfig {
     pub w_flagged_sessions: f64,
     pub w_source_diversity: f64,
     pub w_anomalies: f64,
+    pub w_open_alerts: f64,
+    pub w_threat_intel: f64,
+    pub w_github_admin: f64,
+    pub w_mfa_less_logins: f64,
+    pub w_priv_mfa_less_logins: f64,
+    pub cap_failed_auth: f64,
+    pub cap_priv_grants: f64,
+    pub cap_flagged_sessions: f64,
+    pub cap_source_diversity: f64,
+    pub cap_anomalies: f64,
+    pub cap_open_alerts: f64,
+    pub cap_threat_intel: f64,
+    pub cap_github_admin: f64,
+    pub cap_mfa_less_logins: f64,
+    /// Keep per-pass history samples this long before folding them into
+    /// hourly buckets.
+    pub history_raw_hours: i64,
+    /// Keep hourly buckets this long before folding them into daily buckets.
+    /// Daily buckets are pruned by `retention.risk_history_days`.
+    pub history_hourly_days: i64,
 }
 
 impl Default for RiskConfig {
@@ -335,14 +547,43 @@ impl Default for RiskConfig {
             w_flagged_sessions: 25.0,
             w_source_diversity: 10.0,
             w_anomalies: 20.0,
+            w_open_alerts: 0.0,
+            w_threat_intel: 0.0,
+            w_github_admin: 0.0,
+            w_mfa_less_logins: 0.0,
+            w_priv_mfa_less_logins: 0.0,
+            cap_failed_auth: 10.0,
+            cap_priv_grants: 5.0,
+            cap_flagged_sessions: 3.0,
+            cap_source_diversity: 2.0,
+            cap_anomalies: 3.0,
+            cap_open_alerts: 2.0,
+            cap_threat_intel: 10.0,
+            cap_github_admin: 3.0,
+            cap_mfa_less_logins: 5.0,
+            history_raw_hours: 48,
+            history_hourly_days: 30,
         }
     }
 }
 
+/// GeoLite2 enrichment (`SSU__GEOIP__*`). `db_path` (City) drives session
+/// location and impossible travel; `asn_db_path` (ASN) adds the network owner.
+/// Logins from trusted egress or hosting/VPN networks are kept out of the
+/// geo-based detections, since their location isn't the person's.
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct GeoipConfig {
     pub license_key: String,
     pub db_path: String,
+    /// GeoLite2-ASN (or compatible) mmdb. Empty → no ASN/org enrichment; trusted
+    /// CIDRs still apply.
+    pub asn_db_path: String,
+    /// Comma-separated CIDRs we egress from (corporate VPN, NAT gateways, CI).
+    pub trusted_cidrs: String,
+    /// Comma-separated ASNs treated as trusted egress (`AS` prefix optional).
+    pub trusted_asns: String,
+    /// Extra hosting/VPN-provider ASNs on top of the built-in cloud list.
+    pub hosting_asns: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -375,6 +616,73 @@ impl Default for GuarddutyConfig {
     }
 }
 
+/// Threat-intel feed loader knobs (`SSU__THREAT_INTEL__*`). The worker (a leader
+/// singleton) re-reads the feed manifest at `manifest_path` every `interval_secs`
+/// and reloads any feed file that changed, so feeds are managed on disk without a
+/// restart. Matching against events runs in the SIEM pass.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreatIntelConfig {
+    /// Reload cadence. Clamped to ≥60s.
+    pub interval_secs: u64,
+    /// YAML/JSON manifest listing the feeds (`name`, `path`, `format`, optional
+    /// `confidence`/`ttl_days`). Empty → no feeds.
+    pub manifest_path: String,
+    /// Confidence (0–100) for feeds and indicators that don't carry their own.
+    pub default_confidence: i32,
+    /// Lifetime of an indicator without its own expiry, counted from the load
+    /// that last saw it.
+    pub default_ttl_days: i64,
+}
+
+impl Default for ThreatIntelConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 900,
+            manifest_path: String::new(),
+            default_confidence: 50,
+            default_ttl_days: 30,
+        }
+    }
+}
+
+/// Non-human actor ownership lookups (`SSU__OWNERSHIP__*`). The worker (a
+/// leader singleton) refreshes owner hints from IAM role tags, the self-service
+/// capability roster (k8s namespace → capability) and GitHub CODEOWNERS / repo
+/// admin teams every `interval_secs`; the SIEM pass maps them onto actors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OwnershipConfig {
+    /// Refresh cadence. Clamped to ≥300s.
+    pub interval_secs: u64,
+    /// Comma-separated role ARNs, one per AWS account, assumed to list IAM
+    /// roles and their tags. Empty → IAM lookup skipped.
+    pub aws_role_arns: String,
+    /// STS session name used when assuming `aws_role_arns`.
+    pub aws_session_name: String,
+    /// Comma-separated role tag keys (case-insensitive) naming an owner, in
+    /// preference order. An `@` in the value makes it a person, else a team.
+    pub owner_tag_keys: String,
+    /// Comma-separated role tag keys naming a team; used when no owner tag is set.
+    pub team_tag_keys: String,
+    /// Token for CODEOWNERS / repo team lookups. Empty → `github.audit_pat`.
+    pub github_token: String,
+    /// Repos (the busiest for non-human GitHub actors) looked up per refresh.
+    pub github_max_repos: i64,
+}
+
+impl Default for OwnershipConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 3600,
+            aws_role_arns: String::new(),
+            aws_session_name: "ssu-mgmt-ownership".to_owned(),
+            owner_tag_keys: "owner".to_owned(),
+            team_tag_keys: "team,capability".to_owned(),
+            github_token: String::new(),
+            github_max_repos: 200,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct CloudtrailConfig {
     /// S3 bucket holding the org trail, e.g. `dfds-audit`.
@@ -387,7 +695,7 @@ pub struct CloudtrailConfig {
     /// Trailing look-back window in days. Older data stays in Athena.
     pub window_days: i64,
     /// Comma-separated `eventName` allowlist. Empty → the built-in SIEM set
-    /// (see `cloudtrail::default_allowlist`).
+    /// (see `cloudtrail::default_allowlist`). Root calls are kept regardless.
     pub event_allowlist: String,
     /// Drop non-management events (`eventCategory != "Management"`) before insert.
     pub management_events_only: bool,
@@ -655,6 +963,20 @@ fn set_defaults(builder: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultSt
         .unwrap()
         .set_default("siem.impossible_travel_kmh", 900.0)
         .unwrap()
+        .set_default("siem.risk_jump_delta", 30)
+        .unwrap()
+        .set_default("siem.risk_jump_window_hours", 24)
+        .unwrap()
+        .set_default("siem.roster_max_leave_pct", 20)
+        .unwrap()
+        .set_default("siem.roster_confirm_fetches", 3)
+        .unwrap()
+        .set_default("siem.team_posture_history_days", 365)
+        .unwrap()
+        .set_default("siem.role_edge_retention_days", 180)
+        .unwrap()
+        .set_default("siem.new_user_key_window_mins", 60)
+        .unwrap()
         .set_default("selfservice.base_url", "")
         .unwrap()
         .set_default("selfservice.token", "")
@@ -673,10 +995,50 @@ fn set_defaults(builder: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultSt
         .unwrap()
         .set_default("risk.w_anomalies", 20.0)
         .unwrap()
+        .set_default("risk.w_open_alerts", 0.0)
+        .unwrap()
+        .set_default("risk.w_threat_intel", 0.0)
+        .unwrap()
+        .set_default("risk.w_github_admin", 0.0)
+        .unwrap()
+        .set_default("risk.w_mfa_less_logins", 0.0)
+        .unwrap()
+        .set_default("risk.w_priv_mfa_less_logins", 0.0)
+        .unwrap()
+        .set_default("risk.cap_failed_auth", 10.0)
+        .unwrap()
+        .set_default("risk.cap_priv_grants", 5.0)
+        .unwrap()
+        .set_default("risk.cap_flagged_sessions", 3.0)
+        .unwrap()
+        .set_default("risk.cap_source_diversity", 2.0)
+        .unwrap()
+        .set_default("risk.cap_anomalies", 3.0)
+        .unwrap()
+        .set_default("risk.cap_open_alerts", 2.0)
+        .unwrap()
+        .set_default("risk.cap_threat_intel", 10.0)
+        .unwrap()
+        .set_default("risk.cap_github_admin", 3.0)
+        .unwrap()
+        .set_default("risk.cap_mfa_less_logins", 5.0)
+        .unwrap()
+        .set_default("risk.history_raw_hours", 48)
+        .unwrap()
+        .set_default("risk.history_hourly_days", 30)
+        .unwrap()
         .set_default("geoip.license_key", "")
         .unwrap()
         .set_default("geoip.db_path", "")
         .unwrap()
+        .set_default("geoip.asn_db_path", "")
+        .unwrap()
+        .set_default("geoip.trusted_cidrs", "")
+        .unwrap()
+        .set_default("geoip.trusted_asns", "")
+        .unwrap()
+        .set_default("geoip.hosting_asns", "")
+        .unwrap()
         .set_default("guardduty.regions", "eu-west-1")
         .unwrap()
         .set_default("guardduty.interval_secs", 900)
@@ -687,6 +1049,34 @@ fn set_defaults(builder: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultSt
         .unwrap()
         .set_default("guardduty.backfill_window_days", 30)
         .unwrap()
+        // Threat-intel feed loader — off until a manifest is configured.
+        .set_default("enable_threat_intel", "false")
+        .unwrap()
+        .set_default("threat_intel.interval_secs", 900)
+        .unwrap()
+        .set_default("threat_intel.manifest_path", "")
+        .unwrap()
+        .set_default("threat_intel.default_confidence", 50)
+        .unwrap()
+        .set_default("threat_intel.default_ttl_days", 30)
+        .unwrap()
+        // Ownership lookups — off until at least one source is configured.
+        .set_default("enable_ownership", "false")
+        .unwrap()
+        .set_default("ownership.interval_secs", 3600)
+        .unwrap()
+        .set_default("ownership.aws_role_arns", "")
+        .unwrap()
+        .set_default("ownership.aws_session_name", "ssu-mgmt-ownership")
+        .unwrap()
+        .set_default("ownership.owner_tag_keys", "owner")
+        .unwrap()
+        .set_default("ownership.team_tag_keys", "team,capability")
+        .unwrap()
+        .set_default("ownership.github_token", "")
+        .unwrap()
+        .set_default("ownership.github_max_repos", 200)
+        .unwrap()
         // Leader election for the singleton background workers.
         .set_default("worker.leader_election", "true")
         .unwrap()
@@ -711,6 +1101,59 @@ fn set_defaults(builder: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultSt
         .unwrap()
         .set_default("timeline.rollup_interval_secs", 300)
         .unwrap()
+        .set_default("searches.interval_secs", 60)
+        .unwrap()
+        .set_default("searches.lag_mins", 15)
+        .unwrap()
+        .set_default("searches.evidence_uids", 50)
+        .unwrap()
+        .set_default("facets.interval_secs", 300)
+        .unwrap()
+        .set_default("facets.lag_mins", 15)
+        .unwrap()
+        .set_default("facets.step_hours", 6)
+        .unwrap()
+        .set_default("facets.retention_days", 30)
+        .unwrap()
+        .set_default(
+            "facets.json_paths",
+            "eventSource,userIdentity.type,errorCode,requestParameters.roleName",
+        )
+        .unwrap()
+        .set_default("resources.interval_secs", 120)
+        .unwrap()
+        .set_default("resources.backfill_days", 30)
+        .unwrap()
+        .set_default("tail.poll_secs", 5)
+        .unwrap()
+        .set_default("tail.settle_secs", 5)
+        .unwrap()
+        .set_default("tail.overlap_secs", 300)
+        .unwrap()
+        .set_default("tail.max_rows_per_sec", 50)
+        .unwrap()
+        .set_default("tail.burst", 500)
+        .unwrap()
+        .set_default("tail.send_timeout_secs", 10)
+        .unwrap()
+        .set_default("tail.statement_timeout_secs", 5)
+        .unwrap()
+        .set_default("query_jobs.max_per_user", 2)
+        .unwrap()
+        .set_default("query_jobs.max_concurrent", 4)
+        .unwrap()
+        .set_default("query_jobs.max_rows", 500_000)
+        .unwrap()
+        .set_default("query_jobs.fetch_batch", 2_000)
+        .unwrap()
+        .set_default("query_jobs.statement_timeout_secs", 900)
+        .unwrap()
+        .set_default("query_jobs.ttl_mins", 1_440)
+        .unwrap()
+        .set_default("query_jobs.stale_mins", 30)
+        .unwrap()
+        .set_default("query_jobs.cleanup_interval_secs", 300)
+        .unwrap()
         // Retention prune worker — off by default (destructive deletes).
         .set_default("enable_retention", "false")
         .unwrap()
@@ -726,6 +1169,8 @@ fn set_defaults(builder: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultSt
         .unwrap()
         .set_default("retention.ssumgmt_days", 365)
         .unwrap()
+        .set_default("retention.risk_history_days", 730)
+        .unwrap()
         .set_default("retention.batch_size", 5_000)
         .unwrap()
         // Self-audit: record the service's own API usage as source `ssu-mgmt`.
@@ -733,7 +1178,7 @@ fn set_defaults(builder: ConfigBuilder<DefaultState>) -> ConfigBuilder<DefaultSt
         .unwrap()
         .set_default(
             "audit.exclude_prefixes",
-            "/api/overview/,/api/auth/config,/api/progress/",
+            "/api/overview/,/api/auth/config,/api/progress/,/api/tail/",
         )
         .unwrap()
         .set_default("tracing.enable", "false")
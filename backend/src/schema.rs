@@ -45,6 +45,10 @@ diesel::table! {
         resolved_by -> Nullable<Text>,
         resolved_at -> Nullable<Timestamptz>,
         updated_at -> Timestamptz,
+        attack_tactics -> Array<Nullable<Text>>,
+        attack_techniques -> Array<Nullable<Text>>,
+        owner -> Nullable<Text>,
+        owner_kind -> Nullable<Text>,
     }
 }
 
@@ -106,6 +110,8 @@ diesel::table! {
         assumed_role_arn -> Nullable<Text>,
         identity_source -> Nullable<Text>,
         user_identity_account_id -> Nullable<Text>,
+        auth_method -> Nullable<Text>,
+        mfa_used -> Nullable<Bool>,
     }
 }
 
@@ -181,6 +187,13 @@ diesel::table! {
         event_count -> Int8,
         status -> Text,
         flag_reason -> Nullable<Text>,
+        asn -> Nullable<Int8>,
+        as_org -> Nullable<Text>,
+        network_class -> Nullable<Text>,
+        source_counts -> Jsonb,
+        chain -> Jsonb,
+        auth_method -> Nullable<Text>,
+        mfa_used -> Nullable<Bool>,
     }
 }
 
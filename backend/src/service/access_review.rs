@@ -0,0 +1,655 @@
+//! Access-review campaigns over `grants`.
+//!
+//! [`create`] snapshots every unrevoked grant in a campaign's scope (system,
+//! privileged-only, team) into `access_review_items` and assigns each a
+//! reviewer: the actor's effective owner when that is a person, else the lead
+//! of the actor's team (`team_leads`), else nobody until someone [`assign`]s
+//! one. The assigned reviewer (or a review admin) [`decide`]s per item —
+//! approve, revoke or comment — and every action lands in
+//! `access_review_events` too. [`close`] is the sign-off: only a
+//! fully decided campaign can be closed. A revoke is a recorded decision for
+//! follow-up; nothing here touches the grant itself.
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::service::outcome::Outcome;
+
+#[derive(Deserialize, Debug)]
+pub struct NewCampaign {
+    pub name: String,
+    pub description: Option<String>,
+    /// `aws`, … — the `grants.system` value.
+    pub system: Option<String>,
+    /// Only privileged grants.
+    pub privileged: Option<bool>,
+    /// The grant actor's team (effective owner team, else roster team).
+    pub team: Option<String>,
+    pub due_at: Option<DateTime<Utc>>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct Campaign {
+    #[diesel(sql_type = BigInt)]
+    pub id: i64,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub description: Option<String>,
+    #[diesel(sql_type = Jsonb)]
+    pub scope: Value,
+    #[diesel(sql_type = Text)]
+    pub status: String,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub due_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Text)]
+    pub created_by: String,
+    #[diesel(sql_type = Timestamptz)]
+    pub created_at: DateTime<Utc>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub closed_by: Option<String>,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub closed_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = BigInt)]
+    pub total: i64,
+    #[diesel(sql_type = BigInt)]
+    pub approved: i64,
+    #[diesel(sql_type = BigInt)]
+    pub revoked: i64,
+    #[diesel(sql_type = BigInt)]
+    pub pending: i64,
+    #[diesel(sql_type = BigInt)]
+    pub unassigned: i64,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct Item {
+    #[diesel(sql_type = BigInt)]
+    pub id: i64,
+    #[diesel(sql_type = BigInt)]
+    pub campaign_id: i64,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub grant_id: Option<i64>,
+    #[diesel(sql_type = Text)]
+    pub grant_key: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub actor_id: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub team: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub system: String,
+    #[diesel(sql_type = Text)]
+    pub role: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub scope: Option<String>,
+    #[diesel(sql_type = Bool)]
+    pub privileged: bool,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub granted_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub reviewer: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub reviewer_source: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub decision: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub comment: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub decided_by: Option<String>,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct ItemEvent {
+    #[diesel(sql_type = BigInt)]
+    pub item_id: i64,
+    #[diesel(sql_type = Text)]
+    pub action: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub comment: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub actor: String,
+    #[diesel(sql_type = Timestamptz)]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct TeamLead {
+    #[diesel(sql_type = Text)]
+    pub team: String,
+    #[diesel(sql_type = Text)]
+    pub lead: String,
+    #[diesel(sql_type = Text)]
+    pub updated_by: String,
+    #[diesel(sql_type = Timestamptz)]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Approve,
+    Revoke,
+    Comment,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Approve => "approve",
+            Action::Revoke => "revoke",
+            Action::Comment => "comment",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Decision {
+    pub action: Action,
+    pub comment: Option<String>,
+}
+
+const CAMPAIGN_SELECT: &str = "SELECT c.id, c.name, c.description, c.scope, c.status, c.due_at, \
+       c.created_by, c.created_at, c.closed_by, c.closed_at, \
+       count(i.id) AS total, \
+       count(i.id) FILTER (WHERE i.decision = 'approve') AS approved, \
+       count(i.id) FILTER (WHERE i.decision = 'revoke') AS revoked, \
+       count(i.id) FILTER (WHERE i.decision IS NULL) AS pending, \
+       count(i.id) FILTER (WHERE i.reviewer IS NULL) AS unassigned \
+     FROM access_review_campaigns c LEFT JOIN access_review_items i ON i.campaign_id = c.id";
+
+const ITEM_COLUMNS: &str = "i.id, i.campaign_id, i.grant_id, i.grant_key, i.actor_id, i.team, \
+       i.system, i.role, i.scope, i.privileged, i.granted_at, i.reviewer, i.reviewer_source, \
+       i.decision, i.comment, i.decided_by, i.decided_at";
+
+/// Campaigns with their progress, newest first.
+pub fn list(conn: &mut PgConnection) -> anyhow::Result<Vec<Campaign>> {
+    diesel::sql_query(format!(
+        "{CAMPAIGN_SELECT} GROUP BY c.id ORDER BY c.created_at DESC"
+    ))
+    .load(conn)
+    .context("list campaigns")
+}
+
+pub fn get(conn: &mut PgConnection, id: i64) -> anyhow::Result<Option<Campaign>> {
+    diesel::sql_query(format!("{CAMPAIGN_SELECT} WHERE c.id = $1 GROUP BY c.id"))
+        .bind::<BigInt, _>(id)
+        .get_result(conn)
+        .optional()
+        .context("load campaign")
+}
+
+/// Items of a campaign, optionally only one reviewer's and/or undecided ones.
+pub fn items(
+    conn: &mut PgConnection,
+    campaign_id: i64,
+    reviewer: Option<&str>,
+    pending_only: bool,
+) -> anyhow::Result<Vec<Item>> {
+    diesel::sql_query(format!(
+        "SELECT {ITEM_COLUMNS} FROM access_review_items i \
+         WHERE i.campaign_id = $1 AND ($2::text IS NULL OR i.reviewer = $2) \
+           AND (NOT $3 OR i.decision IS NULL) \
+         ORDER BY i.privileged DESC, i.team NULLS LAST, i.actor_id, i.grant_key"
+    ))
+    .bind::<BigInt, _>(campaign_id)
+    .bind::<Nullable<Text>, _>(reviewer)
+    .bind::<Bool, _>(pending_only)
+    .load(conn)
+    .context("load campaign items")
+}
+
+/// Open a campaign over the grants in scope. Rejected when nothing matches.
+pub fn create(
+    conn: &mut PgConnection,
+    new: NewCampaign,
+    who: &str,
+) -> anyhow::Result<Outcome<Campaign>> {
+    let name = new.name.trim();
+    if name.is_empty() {
+        return Ok(Outcome::Rejected("name is required".into()));
+    }
+    let system = new.system.as_deref().filter(|s| !s.is_empty());
+    let team = new.team.as_deref().filter(|s| !s.is_empty());
+    let privileged = new.privileged.unwrap_or(false);
+    let scope = json!({ "system": system, "privileged": privileged, "team": team });
+
+    let created = conn.transaction::<_, anyhow::Error, _>(|conn| {
+        #[derive(QueryableByName)]
+        struct Id {
+            #[diesel(sql_type = BigInt)]
+            id: i64,
+        }
+        let id = diesel::sql_query(
+            "INSERT INTO access_review_campaigns (name, description, scope, due_at, created_by) \
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind::<Text, _>(name)
+        .bind::<Nullable<Text>, _>(new.description.as_deref())
+        .bind::<Jsonb, _>(&scope)
+        .bind::<Nullable<Timestamptz>, _>(new.due_at)
+        .bind::<Text, _>(who)
+        .get_result::<Id>(conn)
+        .context("insert campaign")?
+        .id;
+
+        // Reviewer: a person owner, else the team's lead — never the grantee.
+        let n = diesel::sql_query(
+            "INSERT INTO access_review_items \
+               (campaign_id, grant_id, grant_key, actor_id, team, system, role, scope, privileged, granted_at, reviewer, reviewer_source) \
+             SELECT $1, g.id, g.grant_key, g.actor_id, t.team, g.system, g.role, g.scope, g.privileged, g.granted_at, \
+                    CASE WHEN po.owner IS NOT NULL THEN po.owner ELSE tl.lead END, \
+                    CASE WHEN po.owner IS NOT NULL THEN 'owner' WHEN tl.lead IS NOT NULL THEN 'team_lead' ELSE 'unassigned' END \
+             FROM grants g \
+             LEFT JOIN actors a ON a.id = g.actor_id \
+             LEFT JOIN actor_owner_effective e ON e.actor_id = g.actor_id \
+             CROSS JOIN LATERAL ( \
+               SELECT CASE WHEN e.owner_kind = 'team' THEN e.owner ELSE a.team END AS team \
+             ) t \
+             LEFT JOIN LATERAL ( \
+               SELECT e.owner WHERE e.owner_kind = 'person' AND e.owner IS DISTINCT FROM g.actor_id \
+             ) po ON true \
+             LEFT JOIN team_leads tl ON tl.team = t.team AND tl.lead IS DISTINCT FROM g.actor_id \
+             WHERE g.revoked_at IS NULL \
+               AND ($2::text IS NULL OR g.system = $2) \
+               AND (NOT $3 OR g.privileged) \
+               AND ($4::text IS NULL OR t.team = $4)",
+        )
+        .bind::<BigInt, _>(id)
+        .bind::<Nullable<Text>, _>(system)
+        .bind::<Bool, _>(privileged)
+        .bind::<Nullable<Text>, _>(team)
+        .execute(conn)
+        .context("snapshot grants into campaign")?;
+        if n == 0 {
+            // Rolls the campaign row back with it.
+            anyhow::bail!(NoGrants);
+        }
+        Ok(id)
+    });
+    match created {
+        Ok(id) => Ok(get(conn, id)?.map_or(Outcome::NotFound, Outcome::Done)),
+        Err(e) if e.is::<NoGrants>() => Ok(Outcome::Rejected(e.to_string())),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug)]
+struct NoGrants;
+
+impl std::fmt::Display for NoGrants {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no active grants in scope")
+    }
+}
+
+impl std::error::Error for NoGrants {}
+
+#[derive(QueryableByName)]
+struct ItemState {
+    #[diesel(sql_type = Nullable<Text>)]
+    actor_id: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    reviewer: Option<String>,
+    #[diesel(sql_type = Text)]
+    status: String,
+}
+
+/// Locks the item, and the campaign against a concurrent [`close`].
+fn item_state(conn: &mut PgConnection, item_id: i64) -> anyhow::Result<Option<ItemState>> {
+    diesel::sql_query(
+        "SELECT i.actor_id, i.reviewer, c.status FROM access_review_items i \
+         JOIN access_review_campaigns c ON c.id = i.campaign_id WHERE i.id = $1 \
+         FOR UPDATE OF i FOR SHARE OF c",
+    )
+    .bind::<BigInt, _>(item_id)
+    .get_result(conn)
+    .optional()
+    .context("load review item")
+}
+
+fn record_event(
+    conn: &mut PgConnection,
+    item_id: i64,
+    action: &str,
+    comment: Option<&str>,
+    who: &str,
+) -> anyhow::Result<()> {
+    diesel::sql_query(
+        "INSERT INTO access_review_events (item_id, action, comment, actor) VALUES ($1, $2, $3, $4)",
+    )
+    .bind::<BigInt, _>(item_id)
+    .bind::<Text, _>(action)
+    .bind::<Nullable<Text>, _>(comment)
+    .bind::<Text, _>(who)
+    .execute(conn)
+    .context("record review event")?;
+    Ok(())
+}
+
+/// Only the item's reviewer may decide it; a review admin may decide any
+/// item, including unassigned ones.
+pub fn may_decide(reviewer: Option<&str>, who: &str, admin: bool) -> bool {
+    admin || reviewer.is_some_and(|r| r.eq_ignore_ascii_case(who))
+}
+
+/// Approve, revoke or comment on one item. A later decision replaces an earlier
+/// one (the event log keeps both); a comment leaves the decision alone.
+pub fn decide(
+    conn: &mut PgConnection,
+    item_id: i64,
+    d: Decision,
+    who: &str,
+    admin: bool,
+) -> anyhow::Result<Outcome<Item>> {
+    let comment = d
+        .comment
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    if d.action == Action::Comment && comment.is_none() {
+        return Ok(Outcome::Rejected("comment is required".into()));
+    }
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let Some(state) = item_state(conn, item_id)? else {
+            return Ok(Outcome::NotFound);
+        };
+        if state.status != "open" {
+            return Ok(Outcome::Rejected("campaign is closed".into()));
+        }
+        if state
+            .actor_id
+            .as_deref()
+            .is_some_and(|a| a.eq_ignore_ascii_case(who))
+        {
+            return Ok(Outcome::Rejected("cannot review your own access".into()));
+        }
+        if !may_decide(state.reviewer.as_deref(), who, admin) {
+            return Ok(Outcome::Forbidden(match state.reviewer {
+                Some(r) => format!("item is assigned to {r}"),
+                None => "item has no reviewer; assign one first".into(),
+            }));
+        }
+        match d.action {
+            Action::Approve | Action::Revoke => diesel::sql_query(
+                "UPDATE access_review_items SET decision = $2, comment = COALESCE($3, comment), \
+                   decided_by = $4, decided_at = now() WHERE id = $1",
+            )
+            .bind::<BigInt, _>(item_id)
+            .bind::<Text, _>(d.action.as_str())
+            .bind::<Nullable<Text>, _>(comment)
+            .bind::<Text, _>(who)
+            .execute(conn),
+            Action::Comment => {
+                diesel::sql_query("UPDATE access_review_items SET comment = $2 WHERE id = $1")
+                    .bind::<BigInt, _>(item_id)
+                    .bind::<Nullable<Text>, _>(comment)
+                    .execute(conn)
+            }
+        }
+        .context("update review item")?;
+        record_event(conn, item_id, d.action.as_str(), comment, who)?;
+        load_item(conn, item_id)
+    })
+}
+
+fn load_item(conn: &mut PgConnection, item_id: i64) -> anyhow::Result<Outcome<Item>> {
+    let item = diesel::sql_query(format!(
+        "SELECT {ITEM_COLUMNS} FROM access_review_items i WHERE i.id = $1"
+    ))
+    .bind::<BigInt, _>(item_id)
+    .get_result(conn)
+    .optional()
+    .context("load review item")?;
+    Ok(item.map_or(Outcome::NotFound, Outcome::Done))
+}
+
+/// Hand an item to a named reviewer (unassigned items, or re-routing).
+pub fn assign(
+    conn: &mut PgConnection,
+    item_id: i64,
+    reviewer: &str,
+    who: &str,
+) -> anyhow::Result<Outcome<Item>> {
+    let reviewer = reviewer.trim();
+    if reviewer.is_empty() {
+        return Ok(Outcome::Rejected("reviewer is required".into()));
+    }
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let Some(state) = item_state(conn, item_id)? else {
+            return Ok(Outcome::NotFound);
+        };
+        if state.status != "open" {
+            return Ok(Outcome::Rejected("campaign is closed".into()));
+        }
+        if state
+            .actor_id
+            .as_deref()
+            .is_some_and(|a| a.eq_ignore_ascii_case(reviewer))
+        {
+            return Ok(Outcome::Rejected("reviewer cannot review their own access".into()));
+        }
+        diesel::sql_query(
+            "UPDATE access_review_items SET reviewer = $2, reviewer_source = 'manual' WHERE id = $1",
+        )
+        .bind::<BigInt, _>(item_id)
+        .bind::<Text, _>(reviewer)
+        .execute(conn)
+        .context("assign reviewer")?;
+        record_event(conn, item_id, "assign", Some(reviewer), who)?;
+        load_item(conn, item_id)
+    })
+}
+
+#[derive(QueryableByName)]
+struct CampaignStatus {
+    #[diesel(sql_type = Text)]
+    status: String,
+}
+
+/// Sign off a campaign. Every item must carry a decision first. The campaign
+/// row is locked for the check so no decision lands between count and close.
+pub fn close(conn: &mut PgConnection, id: i64, who: &str) -> anyhow::Result<Outcome<Campaign>> {
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let locked: Option<CampaignStatus> = diesel::sql_query(
+            "SELECT status FROM access_review_campaigns WHERE id = $1 FOR UPDATE",
+        )
+        .bind::<BigInt, _>(id)
+        .get_result(conn)
+        .optional()
+        .context("lock campaign")?;
+        let Some(locked) = locked else {
+            return Ok(Outcome::NotFound);
+        };
+        if locked.status != "open" {
+            return Ok(Outcome::Rejected("campaign is already closed".into()));
+        }
+        let Some(c) = get(conn, id)? else {
+            return Ok(Outcome::NotFound);
+        };
+        if c.pending > 0 {
+            return Ok(Outcome::Rejected(format!(
+                "{} of {} items still undecided",
+                c.pending, c.total
+            )));
+        }
+        diesel::sql_query(
+            "UPDATE access_review_campaigns SET status = 'closed', closed_by = $2, closed_at = now() \
+             WHERE id = $1",
+        )
+        .bind::<BigInt, _>(id)
+        .bind::<Text, _>(who)
+        .execute(conn)
+        .context("close campaign")?;
+        Ok(get(conn, id)?.map_or(Outcome::NotFound, Outcome::Done))
+    })
+}
+
+/// Full action log of a campaign, oldest first.
+pub fn events(conn: &mut PgConnection, campaign_id: i64) -> anyhow::Result<Vec<ItemEvent>> {
+    diesel::sql_query(
+        "SELECT e.item_id, e.action, e.comment, e.actor, e.created_at \
+         FROM access_review_events e JOIN access_review_items i ON i.id = e.item_id \
+         WHERE i.campaign_id = $1 ORDER BY e.created_at, e.id",
+    )
+    .bind::<BigInt, _>(campaign_id)
+    .load(conn)
+    .context("load review events")
+}
+
+pub fn team_leads(conn: &mut PgConnection) -> anyhow::Result<Vec<TeamLead>> {
+    diesel::sql_query("SELECT team, lead, updated_by, updated_at FROM team_leads ORDER BY team")
+        .load(conn)
+        .context("list team leads")
+}
+
+/// Set a team's lead. Applies to campaigns created afterwards.
+pub fn set_team_lead(
+    conn: &mut PgConnection,
+    team: &str,
+    lead: &str,
+    who: &str,
+) -> anyhow::Result<usize> {
+    diesel::sql_query(
+        "INSERT INTO team_leads (team, lead, updated_by, updated_at) VALUES ($1, $2, $3, now()) \
+         ON CONFLICT (team) DO UPDATE SET lead = EXCLUDED.lead, updated_by = EXCLUDED.updated_by, updated_at = now()",
+    )
+    .bind::<Text, _>(team)
+    .bind::<Text, _>(lead)
+    .bind::<Text, _>(who)
+    .execute(conn)
+    .context("set team lead")
+}
+
+pub fn delete_team_lead(conn: &mut PgConnection, team: &str) -> anyhow::Result<usize> {
+    diesel::sql_query("DELETE FROM team_leads WHERE team = $1")
+        .bind::<Text, _>(team)
+        .execute(conn)
+        .context("delete team lead")
+}
+
+/// Evidence CSV header, one row per item.
+pub const EXPORT_HEADER: [&str; 20] = [
+    "campaign_id",
+    "campaign",
+    "campaign_status",
+    "signed_off_by",
+    "signed_off_at",
+    "item_id",
+    "grant_key",
+    "actor_id",
+    "team",
+    "system",
+    "role",
+    "scope",
+    "privileged",
+    "granted_at",
+    "reviewer",
+    "reviewer_source",
+    "decision",
+    "comment",
+    "decided_by",
+    "decided_at",
+];
+
+pub fn export_csv(c: &Campaign, items: &[Item]) -> anyhow::Result<Vec<u8>> {
+    let ts = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+    let mut wtr = csv::Writer::from_writer(Vec::new());
+    wtr.write_record(EXPORT_HEADER)?;
+    for i in items {
+        wtr.write_record([
+            c.id.to_string(),
+            c.name.clone(),
+            c.status.clone(),
+            c.closed_by.clone().unwrap_or_default(),
+            ts(c.closed_at),
+            i.id.to_string(),
+            i.grant_key.clone(),
+            i.actor_id.clone().unwrap_or_default(),
+            i.team.clone().unwrap_or_default(),
+            i.system.clone(),
+            i.role.clone(),
+            i.scope.clone().unwrap_or_default(),
+            i.privileged.to_string(),
+            ts(i.granted_at),
+            i.reviewer.clone().unwrap_or_default(),
+            i.reviewer_source.clone(),
+            i.decision.clone().unwrap_or_default(),
+            i.comment.clone().unwrap_or_default(),
+            i.decided_by.clone().unwrap_or_default(),
+            ts(i.decided_at),
+        ])?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_reviewer_or_an_admin_decides() {
+        assert!(may_decide(Some("Lead@dfds.com"), "lead@dfds.com", false));
+        assert!(!may_decide(Some("lead@dfds.com"), "bob@dfds.com", false));
+        assert!(!may_decide(None, "someone@dfds.com", false));
+        assert!(may_decide(None, "someone@dfds.com", true));
+        assert!(may_decide(Some("lead@dfds.com"), "admin@dfds.com", true));
+    }
+
+    #[test]
+    fn csv_export_has_one_row_per_item() {
+        let at = DateTime::parse_from_rfc3339("2026-10-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let c = Campaign {
+            id: 7,
+            name: "Q4 privileged, cloud".into(),
+            description: None,
+            scope: json!({}),
+            status: "closed".into(),
+            due_at: None,
+            created_by: "auditor@dfds.com".into(),
+            created_at: at,
+            closed_by: Some("lead@dfds.com".into()),
+            closed_at: Some(at),
+            total: 1,
+            approved: 1,
+            revoked: 0,
+            pending: 0,
+            unassigned: 0,
+        };
+        let item = Item {
+            id: 1,
+            campaign_id: 7,
+            grant_id: Some(3),
+            grant_key: "aws:alice:AdministratorAccess:".into(),
+            actor_id: Some("alice@dfds.com".into()),
+            team: Some("cloud".into()),
+            system: "aws".into(),
+            role: "AdministratorAccess".into(),
+            scope: None,
+            privileged: true,
+            granted_at: None,
+            reviewer: Some("lead@dfds.com".into()),
+            reviewer_source: "team_lead".into(),
+            decision: Some("approve".into()),
+            comment: Some("on-call, still needed".into()),
+            decided_by: Some("lead@dfds.com".into()),
+            decided_at: Some(at),
+        };
+        let out = String::from_utf8(export_csv(&c, &[item]).unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("campaign_id,campaign,"));
+        assert!(lines[1].starts_with("7,\"Q4 privileged, cloud\",closed,lead@dfds.com,"));
+        assert!(lines[1].ends_with(
+            ",approve,\"on-call, still needed\",lead@dfds.com,2026-10-01T09:00:00+00:00"
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+//! The value dictionary behind `/api/query/facets`: per-UTC-day counts of each
+//! value of the events' text fields and of the configured payload paths
+//! (`facets.json_paths`), kept per `source`/`status` so those facets and a
+//! day-granular time range are answered from `field_value_daily` alone.
+//!
+//! The worker leader folds it forward by event time, trailing now by
+//! `lag_mins` (watermark `field_values`). Events that arrive later than that
+//! are not counted, and a path added to `json_paths` counts from the current
+//! watermark onward; both only affect suggestions, never query results.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Integer, Text, Timestamptz};
+use diesel::PgConnection;
+use log::{error, info, warn};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::controllers::query_ast::{apply_binds, Bind, FieldType, EVENTS};
+use crate::api::controllers::query_text;
+use crate::db::DbPool;
+use crate::misc::config::FacetsConfig;
+use crate::service::ingest::get_watermark;
+
+pub const FIELD_VALUES_WATERMARK_SOURCE: &str = "field_values";
+/// Longer values are stored (and suggested) cut to this many characters.
+const MAX_VALUE_CHARS: i64 = 256;
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct FacetValue {
+    #[diesel(sql_type = Text)]
+    pub value: String,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+/// What a dictionary entry counts: a view column or a payload path.
+enum Source {
+    Column(&'static str),
+    Path(Vec<String>),
+}
+
+/// The dictionary's fields under their facet names: the events' text fields
+/// except `uid` (unique, so useless as a suggestion), then the configured
+/// paths as `json.<path>`. Unparseable paths are logged and skipped.
+fn fields(conf: &FacetsConfig) -> Vec<(String, Source)> {
+    let mut out: Vec<(String, Source)> = EVENTS
+        .fields
+        .iter()
+        .filter(|f| f.ty == FieldType::Text && f.name != "uid")
+        .map(|f| (f.name.to_string(), Source::Column(f.column)))
+        .collect();
+    for spec in conf.json_paths.split(',').map(str::trim) {
+        if spec.is_empty() {
+            continue;
+        }
+        match query_text::parse_path(spec, 0) {
+            Ok(path) if !path.is_empty() => {
+                out.push((
+                    format!("json{}", query_text::print_path(&path)),
+                    Source::Path(path),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("facets.json_paths: skipping `{spec}`: {e}"),
+        }
+    }
+    out
+}
+
+#[derive(QueryableByName)]
+struct Exists {
+    #[diesel(sql_type = Bool)]
+    exists: bool,
+}
+
+/// Whether the dictionary counts `field` (a canonical facet name, as
+/// `query_agg` reports group-by keys). Decided by the table rather than the
+/// config, so API replicas needn't agree with the leader's `json_paths`.
+pub fn has_field(conn: &mut PgConnection, field: &str) -> anyhow::Result<bool> {
+    let row: Exists = diesel::sql_query(
+        "SELECT EXISTS (SELECT 1 FROM field_value_daily WHERE field = $1) AS exists",
+    )
+    .bind::<Text, _>(field)
+    .get_result(conn)
+    .context("check field value dictionary")?;
+    Ok(row.exists)
+}
+
+/// The filter a dictionary lookup takes.
+#[derive(Debug, Default)]
+pub struct Lookup<'a> {
+    pub field: &'a str,
+    /// Case-insensitive value prefix.
+    pub prefix: Option<&'a str>,
+    pub source: Option<&'a str>,
+    pub status: Option<&'a str>,
+    /// Counts whole UTC days overlapping `from..=to`.
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub top: i64,
+}
+
+/// The most frequent values of `l.field`, most frequent first.
+pub fn lookup(conn: &mut PgConnection, l: &Lookup) -> anyhow::Result<Vec<FacetValue>> {
+    let mut binds = vec![Bind::Text(l.field.to_owned())];
+    let mut clauses = vec!["field = $1".to_string()];
+    for (col, v) in [("source", l.source), ("status", l.status)] {
+        if let Some(v) = v {
+            binds.push(Bind::Text(v.to_owned()));
+            clauses.push(format!("{col} = ${}", binds.len()));
+        }
+    }
+    for (op, ts) in [(">=", l.from), ("<=", l.to)] {
+        if let Some(ts) = ts {
+            binds.push(Bind::Ts(ts));
+            clauses.push(format!(
+                "day {op} (${}::timestamptz AT TIME ZONE 'UTC')::date",
+                binds.len()
+            ));
+        }
+    }
+    if let Some(p) = l.prefix.filter(|p| !p.is_empty()) {
+        binds.push(Bind::Text(format!("{}%", like_escape(p))));
+        clauses.push(format!(
+            "lower(value) LIKE lower(${}) ESCAPE '\\'",
+            binds.len()
+        ));
+    }
+    let sql = format!(
+        "SELECT value, sum(count)::bigint AS count FROM field_value_daily \
+         WHERE {} GROUP BY value ORDER BY count DESC, value LIMIT {}",
+        clauses.join(" AND "),
+        l.top
+    );
+    apply_binds(diesel::sql_query(sql).into_boxed::<Pg>(), binds)
+        .load(conn)
+        .context("look up field values")
+}
+
+/// Escape `LIKE` metacharacters so `s` matches literally (with `ESCAPE '\'`).
+pub fn like_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub async fn run(cancel: CancellationToken, conf: FacetsConfig, pool: DbPool) {
+    info!(
+        "field value dictionary started (interval={}s, lag={}m)",
+        conf.interval_secs, conf.lag_mins
+    );
+    let interval = std::time::Duration::from_secs(conf.interval_secs.max(1));
+
+    loop {
+        tick(&pool, &conf).await;
+
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                info!("field value dictionary stopping");
+                return;
+            }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+#[tracing::instrument(name = "field_values.refresh", skip_all)]
+async fn tick(pool: &DbPool, conf: &FacetsConfig) {
+    let pool = pool.clone();
+    let conf = conf.clone();
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let mut conn = crate::db::conn(&pool).map_err(anyhow::Error::from)?;
+        refresh(&mut conn, &conf)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => {}
+        Ok(Ok(steps)) => info!("field value dictionary folded {steps} step(s)"),
+        Ok(Err(e)) => error!("field value dictionary refresh failed: {e:#}"),
+        Err(e) => error!("field value dictionary task join error: {e}"),
+    }
+}
+
+/// Fold every step up to `now - lag_mins`, each in its own transaction so a
+/// long catch-up keeps its progress, then drop days past retention. Returns
+/// the steps folded.
+fn refresh(conn: &mut PgConnection, conf: &FacetsConfig) -> anyhow::Result<usize> {
+    let fields = fields(conf);
+    let target = Utc::now() - Duration::minutes(conf.lag_mins.max(0));
+    let step = Duration::hours(conf.step_hours.max(1));
+
+    let mut w = get_watermark(conn, FIELD_VALUES_WATERMARK_SOURCE)
+        .context("read field value watermark")?
+        .and_then(|wm| wm.last_event_at)
+        .unwrap_or_else(|| target - Duration::days(conf.retention_days.max(1)));
+    let mut steps = 0;
+    while w < target {
+        let boundary = target.min(w + step);
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            fold(conn, &fields, w, boundary)?;
+            diesel::sql_query(
+                "INSERT INTO ingest_watermarks \
+                   (source, last_event_at, last_run_at, objects_scanned, events_applied) \
+                 VALUES ($1, $2, now(), 0, 0) \
+                 ON CONFLICT (source) DO UPDATE SET \
+                   last_event_at = GREATEST(EXCLUDED.last_event_at, ingest_watermarks.last_event_at), \
+                   last_run_at   = now()",
+            )
+            .bind::<Text, _>(FIELD_VALUES_WATERMARK_SOURCE)
+            .bind::<Timestamptz, _>(boundary)
+            .execute(conn)
+            .context("advance field value watermark")?;
+            Ok(())
+        })?;
+        w = boundary;
+        steps += 1;
+    }
+
+    diesel::sql_query(
+        "DELETE FROM field_value_daily \
+         WHERE day < ((now() AT TIME ZONE 'UTC')::date - $1::int)",
+    )
+    .bind::<Integer, _>(i32::try_from(conf.retention_days).unwrap_or(i32::MAX))
+    .execute(conn)
+    .context("prune field value dictionary")?;
+    Ok(steps)
+}
+
+/// Add the counts of events with `from < ts <= to`.
+fn fold(
+    conn: &mut PgConnection,
+    fields: &[(String, Source)],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let mut binds = vec![Bind::Ts(from), Bind::Ts(to)];
+    let mut rows = Vec::with_capacity(fields.len());
+    for (name, source) in fields {
+        binds.push(Bind::Text(name.clone()));
+        let name_ref = binds.len();
+        let value = match source {
+            Source::Column(c) => format!("e.{c}"),
+            Source::Path(p) => {
+                binds.push(Bind::TextArray(p.clone()));
+                format!("e.raw #>> ${}", binds.len())
+            }
+        };
+        rows.push(format!("(${name_ref}::text, {value})"));
+    }
+    let sql = format!(
+        "INSERT INTO field_value_daily (field, source, status, value, day, count) \
+         SELECT f.field, e.source, e.status, left(f.value, {MAX_VALUE_CHARS}), \
+                (e.ts AT TIME ZONE 'UTC')::date, count(*)::bigint \
+           FROM ssumgmt_events e \
+           CROSS JOIN LATERAL (VALUES {}) f(field, value) \
+          WHERE e.ts > $1 AND e.ts <= $2 AND f.value <> '' \
+          GROUP BY 1, 2, 3, 4, 5 \
+         ON CONFLICT (field, value, day, source, status) DO UPDATE SET \
+           count = field_value_daily.count + EXCLUDED.count",
+        rows.join(", ")
+    );
+    apply_binds(diesel::sql_query(sql).into_boxed::<Pg>(), binds)
+        .execute(conn)
+        .context("fold field values")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dictionary_covers_text_fields_and_configured_paths() {
+        let conf = FacetsConfig {
+            json_paths: "eventSource, requestParameters[\"role name\"],,a..b".to_string(),
+            ..FacetsConfig::default()
+        };
+        let names: Vec<String> = fields(&conf).into_iter().map(|(n, _)| n).collect();
+        assert!(names.contains(&"actor".to_string()));
+        assert!(names.contains(&"idsource".to_string()));
+        assert!(!names.contains(&"uid".to_string()));
+        assert!(!names.contains(&"kind".to_string()));
+        assert!(names.contains(&"json.eventSource".to_string()));
+        assert!(names.contains(&"json.requestParameters[\"role name\"]".to_string()));
+        assert!(!names.iter().any(|n| n.contains("a..b")));
+        assert_eq!(like_escape("50%_off\\"), "50\\%\\_off\\\\");
+    }
+}
@@ -91,8 +91,16 @@ const WEBID_DRAIN_BUDGET_SECS: i64 = 90;
 /// for 49 minutes the way the old unbounded full-window UPDATE did.
 const WEBID_STATEMENT_TIMEOUT: &str = "60s";
 
+/// Does the record come from the account root user?
+pub(crate) fn is_root(rec: &Value) -> bool {
+    rec.get("userIdentity")
+        .and_then(|u| u.get("type"))
+        .and_then(Value::as_str)
+        == Some("Root")
+}
+
 /// Built-in SIEM-relevant `eventName` allowlist used when
-/// `SSU__CLOUDTRAIL__EVENT_ALLOWLIST` is empty.
+/// `SSU__CLOUDTRAIL__EVENT_ALLOWLIST` is empty. Root calls bypass it.
 pub fn default_allowlist() -> Vec<String> {
     [
         "ConsoleLogin",
@@ -100,6 +108,7 @@ pub fn default_allowlist() -> Vec<String> {
         "AssumeRoleWithSAML",
         "AssumeRoleWithWebIdentity",
         "GetSessionToken",
+        "GetRoleCredentials",
         "CreateAccessKey",
         "DeleteAccessKey",
         "UpdateAccessKey",
@@ -124,6 +133,9 @@ pub fn default_allowlist() -> Vec<String> {
         "StopLogging",
         "DeleteTrail",
         "UpdateTrail",
+        "DeleteDetector",
+        "UpdateDetector",
+        "ModifySnapshotAttribute",
     ]
     .iter()
     .map(|s| s.to_string())
@@ -988,7 +1000,7 @@ impl<'a, 'b, 'de> serde::de::Visitor<'de> for RecordsSeq<'a, 'b> {
     }
 }
 
-fn map_record(
+pub(crate) fn map_record(
     rec: Value,
     allowlist: &[String],
     management_only: bool,
@@ -997,7 +1009,9 @@ fn map_record(
     key: &str,
 ) -> Option<CloudtrailEventInsert> {
     let event_name = rec.get("eventName").and_then(Value::as_str).unwrap_or("");
-    if !allowlist.iter().any(|a| a == event_name) {
+    // Any root call is worth keeping, listed or not: `root_account_usage`
+    // must see a root `GetCallerIdentity` as much as a root `CreateRole`.
+    if !is_root(&rec) && !allowlist.iter().any(|a| a == event_name) {
         return None;
     }
     let category = rec.get("eventCategory").and_then(Value::as_str);
@@ -1023,6 +1037,7 @@ fn map_record(
     let event_name = event_name.to_string();
 
     let identity = derive_identity(&rec);
+    let (auth_method, mfa_used) = login_auth(&rec);
     Some(CloudtrailEventInsert {
         event_id,
         event_time,
@@ -1045,6 +1060,8 @@ fn map_record(
         principal_name: identity.principal_name,
         assumed_role_arn: identity.assumed_role_arn,
         identity_source: identity.identity_source,
+        auth_method,
+        mfa_used,
         source_ip: str_field(&rec, "sourceIPAddress"),
         user_agent: str_field(&rec, "userAgent"),
         error_code: str_field(&rec, "errorCode"),
@@ -1279,6 +1296,54 @@ fn identity_source(rec: &Value) -> Option<String> {
     ty.map(|t| t.to_lowercase())
 }
 
+/// Authentication strength of a console sign-in: `(auth_method, mfa_used)`.
+/// IAM users and root sign in with a `password`, and `additionalEventData.MFAUsed`
+/// says whether a second factor followed. Federated sign-ins (`sso` for IAM
+/// Identity Center, else `federated`) authenticate at the IdP, so their
+/// `MFAUsed` is always "No"; only a session flagged `mfaAuthenticated` proves
+/// MFA, anything else is unknown rather than MFA-less. Kept in step with the
+/// backfill in the `login_auth_strength` migration.
+pub(crate) fn login_auth(rec: &Value) -> (Option<String>, Option<bool>) {
+    if rec.get("eventName").and_then(Value::as_str) != Some("ConsoleLogin") {
+        return (None, None);
+    }
+    let Some(ui) = rec.get("userIdentity") else {
+        return (None, None);
+    };
+    let ty = ui.get("type").and_then(Value::as_str).unwrap_or("");
+    if matches!(ty, "IAMUser" | "Root") {
+        let mfa = rec
+            .get("additionalEventData")
+            .and_then(|d| d.get("MFAUsed"))
+            .and_then(Value::as_str)
+            .map(|v| v == "Yes");
+        return (Some("password".to_string()), mfa);
+    }
+    let session = ui.get("sessionContext");
+    let issuer = session
+        .and_then(|s| s.get("sessionIssuer"))
+        .and_then(|s| s.get("userName"))
+        .and_then(Value::as_str)
+        .unwrap_or("");
+    let method = if issuer.starts_with("AWSReservedSSO_") {
+        "sso"
+    } else if matches!(
+        ty,
+        "AssumedRole" | "FederatedUser" | "SAMLUser" | "WebIdentityUser"
+    ) {
+        "federated"
+    } else {
+        return (None, None);
+    };
+    let mfa = session
+        .and_then(|s| s.get("attributes"))
+        .and_then(|a| a.get("mfaAuthenticated"))
+        .and_then(Value::as_str)
+        .filter(|v| *v == "true")
+        .map(|_| true);
+    (Some(method.to_string()), mfa)
+}
+
 /// Extract `host[/tenant]` from an OIDC-provider ARN such as
 /// `arn:aws:iam::123:oidc-provider/sts.windows.net/<tenant>/`.
 fn oidc_host(provider_arn: &str) -> Option<String> {
@@ -1965,6 +2030,34 @@ mod tests {
         assert_eq!(id.identity_source.as_deref(), Some("aws-sso"));
     }
 
+    #[test]
+    fn login_auth_reads_mfa_for_passwords_and_only_proof_for_federation() {
+        let iam = |mfa: &str| {
+            json!({
+                "eventName": "ConsoleLogin",
+                "userIdentity": { "type": "IAMUser", "userName": "dave" },
+                "additionalEventData": { "MFAUsed": mfa }
+            })
+        };
+        assert_eq!(
+            login_auth(&iam("Yes")),
+            (Some("password".into()), Some(true))
+        );
+        assert_eq!(
+            login_auth(&iam("No")),
+            (Some("password".into()), Some(false))
+        );
+
+        // SSO always reports MFAUsed=No; the IdP's MFA is unknown, not absent.
+        let mut sso = sso_login();
+        sso["additionalEventData"] = json!({ "MFAUsed": "No" });
+        assert_eq!(login_auth(&sso), (Some("sso".into()), None));
+        sso["userIdentity"]["sessionContext"]["attributes"] = json!({ "mfaAuthenticated": "true" });
+        assert_eq!(login_auth(&sso), (Some("sso".into()), Some(true)));
+
+        assert_eq!(login_auth(&web_identity()), (None, None));
+    }
+
     fn irsa_chained_assume_role() -> Value {
         json!({
             "eventName": "AssumeRole",
@@ -94,7 +94,7 @@ pub async fn run(cancel: CancellationToken, conf: GithubConfig, pool: DbPool) {
     }
 }
 
-fn build_client(conf: &GithubConfig) -> anyhow::Result<reqwest::Client> {
+pub(crate) fn build_client(conf: &GithubConfig) -> anyhow::Result<reqwest::Client> {
     let mut headers = HeaderMap::new();
     headers.insert(
         ACCEPT,
@@ -199,11 +199,57 @@ fn spawn_singleton_workers(
         info!("Retention prune disabled");
     }
 
+    if conf.enable_threat_intel {
+        info!("Threat-intel feed loader enabled");
+        rt.spawn(crate::service::siem::threat_intel::run(
+            cancel.clone(),
+            conf.threat_intel.clone(),
+            pool.clone(),
+        ));
+    } else {
+        info!("Threat-intel feed loader disabled");
+    }
+
+    if conf.enable_ownership {
+        info!("Ownership lookups enabled");
+        rt.spawn(crate::service::siem::ownership::run(
+            cancel.clone(),
+            conf.clone(),
+            pool.clone(),
+        ));
+    } else {
+        info!("Ownership lookups disabled");
+    }
+
     rt.spawn(crate::service::timeline::run(
         cancel.clone(),
         conf.timeline.rollup_interval_secs,
         pool.clone(),
     ));
+
+    rt.spawn(crate::service::resources::run(
+        cancel.clone(),
+        conf.resources.clone(),
+        pool.clone(),
+    ));
+
+    rt.spawn(crate::service::field_values::run(
+        cancel.clone(),
+        conf.facets.clone(),
+        pool.clone(),
+    ));
+
+    rt.spawn(crate::service::saved_search::run(
+        cancel.clone(),
+        conf.searches.clone(),
+        pool.clone(),
+    ));
+
+    rt.spawn(crate::service::query_jobs::run_cleanup(
+        cancel.clone(),
+        conf.query_jobs.clone(),
+        pool.clone(),
+    ));
 }
 
 fn try_acquire(pool: &DbPool, holder: &str, ttl_secs: u64) -> Result<Option<i64>> {
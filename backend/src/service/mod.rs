@@ -1,7 +1,13 @@
+pub mod access_review;
 pub mod bg;
+pub mod field_values;
 pub mod ingest;
 pub mod leader;
+pub mod outcome;
 pub mod progress_relay;
+pub mod query_jobs;
+pub mod resources;
 pub mod retention;
+pub mod saved_search;
 pub mod siem;
 pub mod timeline;
@@ -0,0 +1,18 @@
+//! The result of a service call that the API maps onto a status code (see
+//! `outcome_response` in the controllers).
+
+/// `Done` is a success; every other variant is an answer the caller gets
+/// instead, its message shown as is.
+pub enum Outcome<T> {
+    Done(T),
+    /// Missing, or not visible to the caller.
+    NotFound,
+    /// Visible, but the caller may not act on it (403).
+    Forbidden(String),
+    /// Well-formed but not applicable (400): a bad field, a closed campaign.
+    Rejected(String),
+    /// Not in a state that allows it (409): deleting a job still running.
+    Conflict(String),
+    /// A per-user limit is reached (429).
+    Busy(String),
+}
@@ -0,0 +1,526 @@
+//! Async query jobs: event queries too slow for a request.
+//!
+//! A job is a compiled event query submitted by one user and run in the
+//! background on the replica that accepted it, on the jobs pool — a connection
+//! budget of its own, so a multi-minute scan never holds an API-pool
+//! connection. The runner walks a server-side cursor on one connection and
+//! appends each batch to `query_job_rows` on another, so rows can be paged and
+//! `rows_done` reports progress while the job is still running.
+//!
+//! A cancel sets `cancel_requested` (seen by the runner between batches, on
+//! whichever replica it runs) and `pg_cancel_backend`s the reader, which is
+//! found by its `application_name` rather than a stored pid so a pooled
+//! connection that has since moved on to other work is never hit. Finished jobs
+//! expire after `ttl_mins`; the leader's [`run_cleanup`] deletes them (rows
+//! cascade) and fails jobs whose runner went away with its replica.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Bool, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::controllers::query_ast::{apply_binds, Bind};
+use crate::db::model::SsuMgmtEvent;
+use crate::db::DbPool;
+use crate::misc::config::QueryJobsConfig;
+use crate::service::outcome::Outcome;
+
+/// What a job runs: the `/api/query` filter parameters, with `ast` as JSON
+/// rather than a URL-encoded string. Stored on the job as its `spec`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct JobRequest {
+    pub ast: Option<Value>,
+    pub q: Option<String>,
+    pub status: Option<String>,
+    pub source: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub order_by: Option<String>,
+    pub order_dir: Option<String>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct QueryJob {
+    #[diesel(sql_type = Text)]
+    pub id: String,
+    #[diesel(sql_type = Text)]
+    pub owner: String,
+    #[diesel(sql_type = Jsonb)]
+    pub spec: Value,
+    /// `queued` / `running` / `done` / `failed` / `cancelled`.
+    #[diesel(sql_type = Text)]
+    pub status: String,
+    #[diesel(sql_type = Bool)]
+    pub cancel_requested: bool,
+    /// Rows materialised so far.
+    #[diesel(sql_type = BigInt)]
+    pub rows_done: i64,
+    /// More rows matched than `max_rows`; only the first ones were kept.
+    #[diesel(sql_type = Bool)]
+    pub truncated: bool,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub error: Option<String>,
+    #[diesel(sql_type = Timestamptz)]
+    pub created_at: DateTime<Utc>,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Timestamptz)]
+    pub expires_at: DateTime<Utc>,
+}
+
+const JOB_COLUMNS: &str = "id, owner, spec, status, cancel_requested, rows_done, truncated, \
+                           error, created_at, started_at, finished_at, expires_at";
+
+/// A page of a job's rows, in result order. Rows are the `/api/query` row
+/// shape; `next_offset` is `None` once the page reaches the end of what the job
+/// has materialised so far.
+#[derive(Serialize)]
+pub struct JobPage {
+    pub job: QueryJob,
+    pub rows: Vec<Value>,
+    pub offset: i64,
+    pub next_offset: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct DataRow {
+    #[diesel(sql_type = Jsonb)]
+    data: Value,
+}
+
+/// Runs jobs on the jobs pool, at most `permits` at a time on this replica.
+#[derive(Clone)]
+pub struct JobRunner {
+    pool: DbPool,
+    permits: Arc<Semaphore>,
+    conf: QueryJobsConfig,
+}
+
+impl JobRunner {
+    pub fn new(pool: DbPool, conf: QueryJobsConfig) -> Self {
+        // Each running job holds a reader and a writer connection.
+        let slots = conf.max_concurrent.min(pool.max_size() as usize / 2).max(1);
+        Self {
+            pool,
+            permits: Arc::new(Semaphore::new(slots)),
+            conf,
+        }
+    }
+
+    pub fn conf(&self) -> &QueryJobsConfig {
+        &self.conf
+    }
+
+    /// Run a submitted job in the background. `rows_sql` is the full ordered
+    /// SELECT over `ssumgmt_events`, with `binds` for its placeholders.
+    pub fn start(&self, id: String, rows_sql: String, binds: Vec<Bind>) {
+        let runner = self.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = runner.permits.clone().acquire_owned().await else {
+                return;
+            };
+            let span = tracing::info_span!(
+                "db.query",
+                otel.kind = "client",
+                db.system = "postgresql",
+                op = "query_jobs.run",
+                job = id.as_str()
+            );
+            let res = tokio::task::spawn_blocking(move || {
+                let _g = span.enter();
+                execute(&runner.pool, &runner.conf, &id, &rows_sql, binds)
+            })
+            .await;
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("query job failed to record its outcome: {e:#}"),
+                Err(e) => error!("query job task join error: {e}"),
+            }
+        });
+    }
+}
+
+/// The reader's `application_name`, which is how a cancel finds it.
+fn backend_name(id: &str) -> String {
+    format!("ssu-query-job:{id}")
+}
+
+/// Create a queued job unless `who` already has `max_per_user` active ones.
+/// A per-owner advisory lock serialises the count and the insert, so parallel
+/// submits can't all pass the check.
+pub fn submit(
+    conn: &mut PgConnection,
+    conf: &QueryJobsConfig,
+    spec: &JobRequest,
+    who: &str,
+) -> anyhow::Result<Outcome<QueryJob>> {
+    let spec = serde_json::to_value(spec).context("encode job spec")?;
+    let created = conn.transaction::<_, anyhow::Error, _>(|conn| {
+        diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext('query_jobs:' || $1))")
+            .bind::<Text, _>(who)
+            .execute(conn)
+            .context("lock owner's query jobs")?;
+        diesel::sql_query(format!(
+            "INSERT INTO query_jobs (id, owner, spec, expires_at) \
+             SELECT $1, $2, $3, now() + make_interval(mins => $4::int) \
+             WHERE (SELECT count(*) FROM query_jobs \
+                    WHERE owner = $2 AND status IN ('queued', 'running')) < $5 \
+             RETURNING {JOB_COLUMNS}"
+        ))
+        .bind::<Text, _>(uuid::Uuid::new_v4().to_string())
+        .bind::<Text, _>(who)
+        .bind::<Jsonb, _>(&spec)
+        .bind::<BigInt, _>(conf.ttl_mins)
+        .bind::<BigInt, _>(conf.max_per_user)
+        .get_result::<QueryJob>(conn)
+        .optional()
+        .context("insert query job")
+    })?;
+    Ok(match created {
+        Some(job) => Outcome::Done(job),
+        None => Outcome::Busy(format!(
+            "at most {} query jobs may be queued or running at once",
+            conf.max_per_user
+        )),
+    })
+}
+
+/// The caller's jobs, newest first.
+pub fn list(conn: &mut PgConnection, who: &str) -> anyhow::Result<Vec<QueryJob>> {
+    diesel::sql_query(format!(
+        "SELECT {JOB_COLUMNS} FROM query_jobs WHERE owner = $1 ORDER BY created_at DESC, id"
+    ))
+    .bind::<Text, _>(who)
+    .load(conn)
+    .context("list query jobs")
+}
+
+pub fn get(conn: &mut PgConnection, id: &str, who: &str) -> anyhow::Result<Option<QueryJob>> {
+    diesel::sql_query(format!(
+        "SELECT {JOB_COLUMNS} FROM query_jobs WHERE id = $1 AND owner = $2"
+    ))
+    .bind::<Text, _>(id)
+    .bind::<Text, _>(who)
+    .get_result(conn)
+    .optional()
+    .context("load query job")
+}
+
+/// Rows `offset+1 ..= offset+limit` of a job, whatever its status.
+pub fn results(
+    conn: &mut PgConnection,
+    id: &str,
+    who: &str,
+    offset: i64,
+    limit: i64,
+) -> anyhow::Result<Option<JobPage>> {
+    let Some(job) = get(conn, id, who)? else {
+        return Ok(None);
+    };
+    let rows: Vec<DataRow> = diesel::sql_query(
+        "SELECT data FROM query_job_rows WHERE job_id = $1 AND seq > $2 ORDER BY seq LIMIT $3",
+    )
+    .bind::<Text, _>(id)
+    .bind::<BigInt, _>(offset)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+    .context("load query job rows")?;
+    let end = offset + rows.len() as i64;
+    let next_offset = (end < job.rows_done).then_some(end);
+    Ok(Some(JobPage {
+        job,
+        rows: rows.into_iter().map(|r| r.data).collect(),
+        offset,
+        next_offset,
+    }))
+}
+
+/// Cancel an active job. A queued one is cancelled outright; a running one is
+/// flagged and its reader's statement cancelled, and the runner records the
+/// `cancelled` status. A finished job is returned unchanged.
+pub fn cancel(conn: &mut PgConnection, id: &str, who: &str) -> anyhow::Result<Outcome<QueryJob>> {
+    let flagged: Option<QueryJob> = diesel::sql_query(format!(
+        "UPDATE query_jobs SET cancel_requested = true, updated_at = now(), \
+           status = CASE WHEN status = 'queued' THEN 'cancelled' ELSE status END, \
+           finished_at = CASE WHEN status = 'queued' THEN now() ELSE finished_at END \
+         WHERE id = $1 AND owner = $2 AND status IN ('queued', 'running') \
+         RETURNING {JOB_COLUMNS}"
+    ))
+    .bind::<Text, _>(id)
+    .bind::<Text, _>(who)
+    .get_result(conn)
+    .optional()
+    .context("flag query job cancelled")?;
+
+    let Some(job) = flagged else {
+        return Ok(get(conn, id, who)?.map_or(Outcome::NotFound, Outcome::Done));
+    };
+    if job.status == "running" {
+        diesel::sql_query(
+            "SELECT pg_cancel_backend(pid) FROM pg_stat_activity WHERE application_name = $1",
+        )
+        .bind::<Text, _>(backend_name(id))
+        .execute(conn)
+        .context("cancel query job backend")?;
+    }
+    Ok(Outcome::Done(job))
+}
+
+/// Delete a finished job and its rows now rather than at expiry.
+pub fn delete(conn: &mut PgConnection, id: &str, who: &str) -> anyhow::Result<Outcome<()>> {
+    let Some(job) = get(conn, id, who)? else {
+        return Ok(Outcome::NotFound);
+    };
+    if matches!(job.status.as_str(), "queued" | "running") {
+        return Ok(Outcome::Conflict(
+            "cancel the job before deleting it".into(),
+        ));
+    }
+    diesel::sql_query("DELETE FROM query_jobs WHERE id = $1 AND owner = $2")
+        .bind::<Text, _>(id)
+        .bind::<Text, _>(who)
+        .execute(conn)
+        .context("delete query job")?;
+    Ok(Outcome::Done(()))
+}
+
+#[derive(QueryableByName)]
+struct Progress {
+    #[diesel(sql_type = BigInt)]
+    rows_done: i64,
+    #[diesel(sql_type = Bool)]
+    cancel_requested: bool,
+}
+
+/// How a run ended, short of an error.
+enum Finish {
+    Done { truncated: bool },
+    Cancelled,
+}
+
+/// Run one job to completion and record how it ended. Errors only when the
+/// outcome itself can't be recorded (the cleanup sweep fails the job later).
+fn execute(
+    pool: &DbPool,
+    conf: &QueryJobsConfig,
+    id: &str,
+    rows_sql: &str,
+    binds: Vec<Bind>,
+) -> anyhow::Result<()> {
+    let mut writer = crate::db::conn(pool).map_err(anyhow::Error::from)?;
+    let started = diesel::sql_query(
+        "UPDATE query_jobs SET status = 'running', started_at = now(), updated_at = now() \
+         WHERE id = $1 AND status = 'queued' AND NOT cancel_requested",
+    )
+    .bind::<Text, _>(id)
+    .execute(&mut writer)
+    .context("start query job")?;
+    if started == 0 {
+        // Cancelled (or failed as stale) while it waited for a slot.
+        return Ok(());
+    }
+
+    let outcome = materialise(pool, &mut writer, conf, id, rows_sql, binds);
+    let (status, truncated, err) = match outcome {
+        Ok(Finish::Done { truncated }) => ("done", truncated, None),
+        Ok(Finish::Cancelled) => ("cancelled", false, None),
+        Err(e) => {
+            // A cancel interrupts the reader with an error; that's not a failure.
+            let cancelled = diesel::sql_query(
+                "SELECT rows_done, cancel_requested FROM query_jobs WHERE id = $1",
+            )
+            .bind::<Text, _>(id)
+            .get_result::<Progress>(&mut writer)
+            .is_ok_and(|p| p.cancel_requested);
+            if cancelled {
+                ("cancelled", false, None)
+            } else {
+                warn!("query job {id} failed: {e:#}");
+                ("failed", false, Some(format!("{e:#}")))
+            }
+        }
+    };
+    diesel::sql_query(
+        "UPDATE query_jobs SET status = $2, truncated = $3, error = $4, finished_at = now(), \
+           updated_at = now(), expires_at = now() + make_interval(mins => $5::int) \
+         WHERE id = $1",
+    )
+    .bind::<Text, _>(id)
+    .bind::<Text, _>(status)
+    .bind::<Bool, _>(truncated)
+    .bind::<Nullable<Text>, _>(err)
+    .bind::<BigInt, _>(conf.ttl_mins)
+    .execute(&mut writer)
+    .context("finish query job")?;
+    Ok(())
+}
+
+/// Walk the query's cursor on a reader connection, appending each batch to
+/// `query_job_rows` on `writer` (autocommit, so pages are readable at once).
+fn materialise(
+    pool: &DbPool,
+    writer: &mut PgConnection,
+    conf: &QueryJobsConfig,
+    id: &str,
+    rows_sql: &str,
+    binds: Vec<Bind>,
+) -> anyhow::Result<Finish> {
+    let mut reader = crate::db::conn(pool).map_err(anyhow::Error::from)?;
+    let max_rows = conf.max_rows.max(1);
+    let batch = conf.fetch_batch.clamp(1, max_rows);
+
+    reader.transaction::<_, anyhow::Error, _>(|reader| {
+        diesel::sql_query(format!(
+            "SET LOCAL statement_timeout = '{}s'",
+            conf.statement_timeout_secs.max(1)
+        ))
+        .execute(reader)
+        .context("set statement_timeout")?;
+        diesel::sql_query("SELECT set_config('application_name', $1, true)")
+            .bind::<Text, _>(backend_name(id))
+            .execute(reader)
+            .context("set application_name")?;
+        let declare_sql = format!("DECLARE ssu_job_cur NO SCROLL CURSOR FOR {rows_sql}");
+        apply_binds(diesel::sql_query(declare_sql).into_boxed::<Pg>(), binds)
+            .execute(reader)
+            .context("declare query job cursor")?;
+
+        let mut done = 0i64;
+        loop {
+            let want = batch.min(max_rows - done);
+            if want == 0 {
+                let more: Vec<SsuMgmtEvent> = diesel::sql_query("FETCH FORWARD 1 FROM ssu_job_cur")
+                    .load(reader)
+                    .context("probe query job cursor")?;
+                return Ok(Finish::Done {
+                    truncated: !more.is_empty(),
+                });
+            }
+            let rows: Vec<SsuMgmtEvent> =
+                diesel::sql_query(format!("FETCH FORWARD {want} FROM ssu_job_cur"))
+                    .load(reader)
+                    .context("fetch query job rows")?;
+            if rows.is_empty() {
+                return Ok(Finish::Done { truncated: false });
+            }
+            let data = rows
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<Value>, _>>()
+                .context("encode query job rows")?;
+
+            diesel::sql_query(
+                "INSERT INTO query_job_rows (job_id, seq, data) \
+                 SELECT $1, $2 + t.ord, t.data FROM unnest($3::jsonb[]) WITH ORDINALITY AS t(data, ord)",
+            )
+            .bind::<Text, _>(id)
+            .bind::<BigInt, _>(done)
+            .bind::<Array<Jsonb>, _>(&data)
+            .execute(writer)
+            .context("store query job rows")?;
+            let progress: Progress = diesel::sql_query(
+                "UPDATE query_jobs SET rows_done = rows_done + $2, updated_at = now() \
+                 WHERE id = $1 RETURNING rows_done, cancel_requested",
+            )
+            .bind::<Text, _>(id)
+            .bind::<BigInt, _>(data.len() as i64)
+            .get_result(writer)
+            .context("record query job progress")?;
+            if progress.cancel_requested {
+                return Ok(Finish::Cancelled);
+            }
+            done = progress.rows_done;
+        }
+    })
+}
+
+pub async fn run_cleanup(cancel: CancellationToken, conf: QueryJobsConfig, pool: DbPool) {
+    info!(
+        "query job cleanup started (interval={}s, ttl={}m)",
+        conf.cleanup_interval_secs, conf.ttl_mins
+    );
+    let interval = std::time::Duration::from_secs(conf.cleanup_interval_secs.max(1));
+
+    loop {
+        cleanup_tick(&pool, &conf).await;
+
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                info!("query job cleanup stopping");
+                return;
+            }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+#[tracing::instrument(name = "query_jobs.cleanup", skip_all)]
+async fn cleanup_tick(pool: &DbPool, conf: &QueryJobsConfig) {
+    let pool = pool.clone();
+    let conf = conf.clone();
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<(usize, usize)> {
+        let mut conn = crate::db::conn(&pool).map_err(anyhow::Error::from)?;
+        sweep(&mut conn, &conf)
+    })
+    .await;
+
+    match res {
+        Ok(Ok((0, 0))) => {}
+        Ok(Ok((stale, expired))) => {
+            info!("query jobs: failed {stale} stale, deleted {expired} expired")
+        }
+        Ok(Err(e)) => error!("query job cleanup failed: {e:#}"),
+        Err(e) => error!("query job cleanup task join error: {e}"),
+    }
+}
+
+/// Fail jobs whose runner stopped reporting, then delete expired jobs (their
+/// rows cascade). Returns (stale, expired).
+pub fn sweep(conn: &mut PgConnection, conf: &QueryJobsConfig) -> anyhow::Result<(usize, usize)> {
+    let stale = diesel::sql_query(
+        "UPDATE query_jobs SET status = 'failed', error = 'the job runner went away', \
+           finished_at = now(), updated_at = now(), \
+           expires_at = now() + make_interval(mins => $2::int) \
+         WHERE status IN ('queued', 'running') \
+           AND updated_at < now() - make_interval(mins => $1::int)",
+    )
+    .bind::<BigInt, _>(conf.stale_mins.max(1))
+    .bind::<BigInt, _>(conf.ttl_mins)
+    .execute(conn)
+    .context("fail stale query jobs")?;
+    let expired = diesel::sql_query(
+        "DELETE FROM query_jobs WHERE expires_at < now() AND status NOT IN ('queued', 'running')",
+    )
+    .execute(conn)
+    .context("delete expired query jobs")?;
+    Ok((stale, expired))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_round_trips_with_ast_as_json() {
+        let spec: JobRequest = serde_json::from_value(serde_json::json!({
+            "ast": {"kind": "field", "field": "actor", "op": "eq", "value": "alice"},
+            "from": "2026-10-01T00:00:00Z"
+        }))
+        .unwrap();
+        assert!(spec.ast.as_ref().is_some_and(Value::is_object));
+        let back = serde_json::to_value(&spec).unwrap();
+        assert_eq!(back["from"], "2026-10-01T00:00:00Z");
+        assert!(back["q"].is_null());
+    }
+}
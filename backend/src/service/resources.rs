@@ -0,0 +1,252 @@
+//! Resource entities (`resources`, `resource_touches`): the buckets, IAM
+//! roles, accounts, repos, orgs and self-service paths events touched, so the
+//! resource inspect pages (`/api/entity/resource/:id`) read one indexed table
+//! instead of JSON-path scans of the event view.
+//!
+//! Folded forward by ingest time like the timeline rollup, reading the source
+//! tables directly so each branch uses its `created_at` index. A cold start
+//! backfills `backfill_days` of ingest in `MAX_STEP_HOURS` steps.
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Text, Timestamptz};
+use diesel::PgConnection;
+use log::{error, info};
+use tokio_util::sync::CancellationToken;
+
+use crate::db::DbPool;
+use crate::misc::config::ResourcesConfig;
+use crate::service::ingest::get_watermark;
+
+pub const RESOURCES_WATERMARK_SOURCE: &str = "resources";
+const RESOURCES_SAFETY_MARGIN_MINS: i64 = 5;
+const MAX_STEP_HOURS: i64 = 6;
+
+/// Kinds, as the `<kind>:` prefix of a resource id.
+pub const RESOURCE_KINDS: [&str; 6] = ["bucket", "role", "account", "repo", "org", "path"];
+
+/// `(resource_id, source, uid, ts, actor, action, status)` for everything
+/// ingested in `($1, $2]`. An IAM role is `<account>/<name>` whether it came
+/// as a name (in the call's account) or an ARN (path stripped).
+const TOUCHES_SQL: &str = "\
+    SELECT r.kind || ':' || r.name AS resource_id, 'cloudtrail'::text AS source, \
+           c.event_id AS uid, c.event_time AS ts, \
+           COALESCE(c.principal_name, c.principal_arn) AS actor, c.event_name AS action, \
+           CASE WHEN c.error_code IS NOT NULL THEN 'failure' ELSE 'success' END AS status \
+      FROM cloudtrail_events c \
+      CROSS JOIN LATERAL ( \
+          SELECT 'bucket', c.raw #>> '{requestParameters,bucketName}' \
+          UNION ALL \
+          SELECT 'role', c.recipient_account_id || '/' || (c.raw #>> '{requestParameters,roleName}') \
+          UNION ALL \
+          SELECT 'role', split_part(a, ':', 5) || '/' || \
+                 regexp_replace(split_part(a, ':', 6), '^role/(.*/)?', '') \
+            FROM (SELECT c.raw #>> '{requestParameters,roleArn}' AS a) ra \
+           WHERE a LIKE 'arn:%:iam::%:role/%' \
+          UNION ALL \
+          SELECT 'account', c.recipient_account_id \
+          UNION ALL \
+          SELECT CASE x ->> 'type' WHEN 'AWS::S3::Bucket' THEN 'bucket' ELSE 'role' END, \
+                 CASE x ->> 'type' \
+                   WHEN 'AWS::S3::Bucket' THEN split_part(x ->> 'ARN', ':::', 2) \
+                   ELSE split_part(x ->> 'ARN', ':', 5) || '/' || \
+                        regexp_replace(split_part(x ->> 'ARN', ':', 6), '^role/(.*/)?', '') \
+                 END \
+            FROM jsonb_array_elements(CASE WHEN jsonb_typeof(c.raw -> 'resources') = 'array' \
+                                           THEN c.raw -> 'resources' ELSE '[]'::jsonb END) x \
+           WHERE x ->> 'type' IN ('AWS::S3::Bucket', 'AWS::IAM::Role') \
+      ) r(kind, name) \
+     WHERE c.created_at > $1 AND c.created_at <= $2 AND r.name <> '' \
+    UNION ALL \
+    SELECT r.kind || ':' || r.name, 'github', g.document_id, g.event_time, g.actor, g.action, 'success' \
+      FROM github_audit_events g \
+      CROSS JOIN LATERAL (VALUES ('repo', g.repo), ('org', g.org)) r(kind, name) \
+     WHERE g.created_at > $1 AND g.created_at <= $2 AND r.name <> '' \
+    UNION ALL \
+    SELECT 'path:' || s.path, 'selfservice', s.id::text, s.timestamp AT TIME ZONE 'UTC', \
+           s.principal, s.action, 'success' \
+      FROM audit_records_selfservice s \
+     WHERE s.created_at > $1 AT TIME ZONE 'UTC' AND s.created_at <= $2 AT TIME ZONE 'UTC' \
+       AND s.path <> ''";
+
+pub async fn run(cancel: CancellationToken, conf: ResourcesConfig, pool: DbPool) {
+    info!(
+        "resource entity maintainer started (interval={}s)",
+        conf.interval_secs
+    );
+    let interval = std::time::Duration::from_secs(conf.interval_secs.max(1));
+
+    loop {
+        maintain(&pool, &conf).await;
+
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                info!("resource entity maintainer stopping");
+                return;
+            }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+#[tracing::instrument(name = "resources.maintain", skip_all)]
+async fn maintain(pool: &DbPool, conf: &ResourcesConfig) {
+    let pool = pool.clone();
+    let backfill_days = conf.backfill_days;
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+        let mut conn = crate::db::conn(&pool).map_err(anyhow::Error::from)?;
+        maintain_blocking(&mut conn, backfill_days)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(0)) => {}
+        Ok(Ok(steps)) => info!("resource entities folded {steps} step(s)"),
+        Ok(Err(e)) => error!("resource entity maintenance failed: {e:#}"),
+        Err(e) => error!("resource entity maintenance task join error: {e}"),
+    }
+}
+
+/// Fold up to `now - margin`, one transaction per step so a backfill keeps
+/// its progress. Returns the steps folded.
+fn maintain_blocking(conn: &mut PgConnection, backfill_days: i64) -> anyhow::Result<usize> {
+    let target = Utc::now() - Duration::minutes(RESOURCES_SAFETY_MARGIN_MINS);
+    let mut w = get_watermark(conn, RESOURCES_WATERMARK_SOURCE)
+        .context("read resources watermark")?
+        .and_then(|wm| wm.last_event_at)
+        .unwrap_or_else(|| target - Duration::days(backfill_days.max(0)));
+
+    let mut steps = 0;
+    while w < target {
+        let boundary = target.min(w + Duration::hours(MAX_STEP_HOURS));
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            // Touches already folded (a re-run window) are skipped, so the
+            // resource counters only see new ones.
+            diesel::sql_query(format!(
+                "WITH ins AS ( \
+                   INSERT INTO resource_touches \
+                     (resource_id, source, uid, ts, actor, action, status) \
+                   {TOUCHES_SQL} \
+                   ON CONFLICT (resource_id, source, uid) DO NOTHING \
+                   RETURNING resource_id, ts \
+                 ) \
+                 INSERT INTO resources (id, kind, name, first_seen, last_seen, events) \
+                 SELECT resource_id, split_part(resource_id, ':', 1), \
+                        substr(resource_id, strpos(resource_id, ':') + 1), \
+                        min(ts), max(ts), count(*) \
+                   FROM ins GROUP BY resource_id \
+                 ON CONFLICT (id) DO UPDATE SET \
+                   first_seen = LEAST(resources.first_seen, EXCLUDED.first_seen), \
+                   last_seen  = GREATEST(resources.last_seen, EXCLUDED.last_seen), \
+                   events     = resources.events + EXCLUDED.events"
+            ))
+            .bind::<Timestamptz, _>(w)
+            .bind::<Timestamptz, _>(boundary)
+            .execute(conn)
+            .context("fold resource touches")?;
+
+            diesel::sql_query(
+                "INSERT INTO ingest_watermarks \
+                   (source, last_event_at, last_run_at, objects_scanned, events_applied) \
+                 VALUES ($1, $2, now(), 0, 0) \
+                 ON CONFLICT (source) DO UPDATE SET \
+                   last_event_at = GREATEST(EXCLUDED.last_event_at, ingest_watermarks.last_event_at), \
+                   last_run_at   = now()",
+            )
+            .bind::<Text, _>(RESOURCES_WATERMARK_SOURCE)
+            .bind::<Timestamptz, _>(boundary)
+            .execute(conn)
+            .context("advance resources watermark")?;
+            Ok(())
+        })?;
+        w = boundary;
+        steps += 1;
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(QueryableByName)]
+    struct Touch {
+        #[diesel(sql_type = Text)]
+        resource_id: String,
+    }
+
+    /// Runs `TOUCHES_SQL` over temp tables shadowing the source tables. Needs
+    /// `SSU_TEST_DATABASE_URL`, like the SIEM rule fixtures; skipped without it.
+    #[test]
+    fn touches_build_resource_ids() {
+        let Ok(url) = std::env::var("SSU_TEST_DATABASE_URL") else {
+            eprintln!("SSU_TEST_DATABASE_URL unset — skipping resource id test");
+            return;
+        };
+        let mut conn = PgConnection::establish(&url).expect("connect to test database");
+        conn.test_transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::sql_query(
+                "CREATE TEMP TABLE cloudtrail_events ( \
+                   event_id text, event_time timestamptz, principal_name text, principal_arn text, \
+                   event_name text, error_code text, raw jsonb, recipient_account_id text, \
+                   created_at timestamptz DEFAULT now())",
+            )
+            .execute(conn)?;
+            diesel::sql_query(
+                "CREATE TEMP TABLE github_audit_events ( \
+                   document_id text, event_time timestamptz, actor text, action text, \
+                   repo text, org text, created_at timestamptz DEFAULT now())",
+            )
+            .execute(conn)?;
+            diesel::sql_query(
+                "CREATE TEMP TABLE audit_records_selfservice ( \
+                   id bigint, timestamp timestamp, principal text, action text, path text, \
+                   created_at timestamp DEFAULT now() AT TIME ZONE 'UTC')",
+            )
+            .execute(conn)?;
+            diesel::sql_query(
+                r#"INSERT INTO cloudtrail_events (event_id, event_time, principal_name, event_name, raw, recipient_account_id) VALUES
+                   ('e1', now(), 'alice', 'AssumeRole',
+                    '{"requestParameters": {"roleArn": "arn:aws:iam::210987654321:role/team/ops/deploy"}}', '123456789012'),
+                   ('e2', now(), 'alice', 'PutBucketPolicy',
+                    '{"requestParameters": {"bucketName": ""},
+                      "resources": [{"type": "AWS::S3::Bucket", "ARN": "arn:aws:s3:::logs-bucket"}]}', '123456789012'),
+                   ('e3', now(), 'alice', 'GetRole',
+                    '{"requestParameters": {"roleName": "reader"}}', '123456789012')"#,
+            )
+            .execute(conn)?;
+            diesel::sql_query(
+                "INSERT INTO github_audit_events (document_id, event_time, actor, action, repo, org) \
+                 VALUES ('g1', now(), 'alice', 'org.update_member', '', 'dfds')",
+            )
+            .execute(conn)?;
+
+            let mut ids: Vec<String> = diesel::sql_query(format!(
+                "SELECT DISTINCT resource_id FROM ({TOUCHES_SQL}) t"
+            ))
+            .bind::<Timestamptz, _>(Utc::now() - Duration::hours(1))
+            .bind::<Timestamptz, _>(Utc::now() + Duration::hours(1))
+            .load::<Touch>(conn)?
+            .into_iter()
+            .map(|t| t.resource_id)
+            .collect();
+            ids.sort();
+            assert_eq!(
+                ids,
+                [
+                    "account:123456789012",
+                    // Bucket ARN → name; the empty bucketName is dropped.
+                    "bucket:logs-bucket",
+                    // Empty repo dropped; the org still counts.
+                    "org:dfds",
+                    // A role name lives in the call's account.
+                    "role:123456789012/reader",
+                    // A role ARN keeps its account and loses its path.
+                    "role:210987654321/deploy",
+                ]
+            );
+            Ok(())
+        });
+    }
+}
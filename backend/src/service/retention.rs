@@ -15,13 +15,14 @@ struct PruneTarget {
 pub async fn run(cancel: CancellationToken, conf: RetentionConfig, pool: DbPool) {
     let interval = std::time::Duration::from_secs(conf.interval_secs.max(60));
     info!(
-        "retention prune worker starting :: interval={}s cloudtrail={}d github={}d selfservice={}d derived={}d ssumgmt={}d batch={}",
+        "retention prune worker starting :: interval={}s cloudtrail={}d github={}d selfservice={}d derived={}d ssumgmt={}d risk_history={}d batch={}",
         interval.as_secs(),
         conf.cloudtrail_days,
         conf.github_days,
         conf.selfservice_days,
         conf.derived_days,
         conf.ssumgmt_days,
+        conf.risk_history_days,
         conf.batch_size,
     );
 
@@ -102,6 +103,33 @@ async fn sweep(cancel: &CancellationToken, conf: &RetentionConfig, pool: &DbPool
                   ) c WHERE t.ctid = c.ctid",
             days: conf.derived_days,
         },
+        PruneTarget {
+            label: "threat_intel_hits",
+            sql: "DELETE FROM threat_intel_hits AS t USING ( \
+                    SELECT ctid FROM threat_intel_hits \
+                    WHERE event_time < now() - make_interval(days => $1::int) \
+                    ORDER BY event_time LIMIT $2 \
+                  ) c WHERE t.ctid = c.ctid",
+            days: conf.derived_days,
+        },
+        PruneTarget {
+            label: "resource_touches",
+            sql: "DELETE FROM resource_touches AS t USING ( \
+                    SELECT ctid FROM resource_touches \
+                    WHERE ts < now() - make_interval(days => $1::int) \
+                    ORDER BY ts LIMIT $2 \
+                  ) c WHERE t.ctid = c.ctid",
+            days: conf.derived_days,
+        },
+        PruneTarget {
+            label: "resources",
+            sql: "DELETE FROM resources AS t USING ( \
+                    SELECT ctid FROM resources \
+                    WHERE last_seen < now() - make_interval(days => $1::int) \
+                    ORDER BY last_seen LIMIT $2 \
+                  ) c WHERE t.ctid = c.ctid",
+            days: conf.derived_days,
+        },
         PruneTarget {
             label: "actor_identity_context",
             sql: "DELETE FROM actor_identity_context AS t USING ( \
@@ -111,6 +139,16 @@ async fn sweep(cancel: &CancellationToken, conf: &RetentionConfig, pool: &DbPool
                   ) c WHERE t.ctid = c.ctid",
             days: conf.derived_days,
         },
+        PruneTarget {
+            label: "risk_score_history(day)",
+            sql: "DELETE FROM risk_score_history AS t USING ( \
+                    SELECT ctid FROM risk_score_history \
+                    WHERE resolution = 'day' \
+                      AND bucket < now() - make_interval(days => $1::int) \
+                    ORDER BY bucket LIMIT $2 \
+                  ) c WHERE t.ctid = c.ctid",
+            days: conf.risk_history_days,
+        },
     ];
 
     for target in targets {
@@ -0,0 +1,580 @@
+//! Saved searches, and the scheduler that turns them into detections.
+//!
+//! A saved search is a named `query_ast` tree with an owner and a visibility:
+//! `private` to the owner or `shared` with every console user. Only the owner
+//! edits, schedules or deletes it, and only a shared one can be scheduled: its
+//! alert carries the name, owner and query for every console user to see, so a
+//! scheduled search stays shared until unscheduled. Giving one a schedule
+//! promotes it: the
+//! leader's [`run`] loop evaluates it every `every_mins` over the events
+//! ingested since its previous run (slicing on `ingested_at`, not event time,
+//! so a CloudTrail event delivered hours late still lands in a slice that
+//! hasn't run yet) and, when the slice holds more than
+//! `threshold` matching events, upserts one alert per search (rule_id and
+//! fingerprint `search:<id>`) with the matching `uid`s in its evidence. A slice
+//! only advances after a successful run, so a failed run is retried over the
+//! same (grown) slice rather than skipped.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+
+use crate::api::controllers::query_ast::{self, apply_binds, Bind, Node};
+use crate::api::controllers::query_text;
+use crate::db::DbPool;
+use crate::misc::config::SearchConfig;
+use crate::service::outcome::Outcome;
+
+const SEARCH_STATEMENT_TIMEOUT: &str = "60s";
+
+/// Body of a create or (full) update. The filter is `ast` (a `query_ast::Node`)
+/// or `q` (query text); it is stored as the AST either way.
+#[derive(Deserialize, Debug)]
+pub struct NewSearch {
+    pub name: String,
+    pub description: Option<String>,
+    pub ast: Option<Value>,
+    pub q: Option<String>,
+    /// `private` (default) or `shared`.
+    pub visibility: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Schedule {
+    /// Minutes between runs, and the width of each slice (1–1440).
+    pub every_mins: i32,
+    /// Alert when a slice matches more than this many events. Default 0.
+    pub threshold: Option<i64>,
+    /// `low` / `medium` (default) / `high` / `critical`.
+    pub severity: Option<String>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct SavedSearch {
+    #[diesel(sql_type = BigInt)]
+    pub id: i64,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub description: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub owner: String,
+    #[diesel(sql_type = Jsonb)]
+    pub ast: Value,
+    #[diesel(sql_type = Text)]
+    pub visibility: String,
+    /// `None` = not scheduled.
+    #[diesel(sql_type = Nullable<Integer>)]
+    pub every_mins: Option<i32>,
+    #[diesel(sql_type = BigInt)]
+    pub threshold: i64,
+    #[diesel(sql_type = Text)]
+    pub severity: String,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// End of the last evaluated slice; the next slice starts here.
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub last_run_to: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    pub last_match_count: Option<i64>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub last_error: Option<String>,
+    #[diesel(sql_type = Timestamptz)]
+    pub created_at: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    pub updated_at: DateTime<Utc>,
+}
+
+const SEARCH_COLUMNS: &str =
+    "id, name, description, owner, ast, visibility, every_mins, threshold, \
+       severity, last_run_at, last_run_to, last_match_count, last_error, created_at, updated_at";
+
+/// The caller's own searches plus everyone's shared ones, by name.
+pub fn list(conn: &mut PgConnection, who: &str) -> anyhow::Result<Vec<SavedSearch>> {
+    diesel::sql_query(format!(
+        "SELECT {SEARCH_COLUMNS} FROM saved_searches \
+         WHERE owner = $1 OR visibility = 'shared' ORDER BY lower(name), id"
+    ))
+    .bind::<Text, _>(who)
+    .load(conn)
+    .context("list saved searches")
+}
+
+/// A search the caller can see.
+pub fn get(conn: &mut PgConnection, id: i64, who: &str) -> anyhow::Result<Option<SavedSearch>> {
+    diesel::sql_query(format!(
+        "SELECT {SEARCH_COLUMNS} FROM saved_searches \
+         WHERE id = $1 AND (owner = $2 OR visibility = 'shared')"
+    ))
+    .bind::<BigInt, _>(id)
+    .bind::<Text, _>(who)
+    .get_result(conn)
+    .optional()
+    .context("load saved search")
+}
+
+/// Validate a body into (name, AST JSON, visibility).
+fn validate(new: &NewSearch) -> Result<(String, Value, String), String> {
+    let name = new.name.trim();
+    if name.is_empty() {
+        return Err("name is required".into());
+    }
+    let visibility = match new.visibility.as_deref().map(str::trim) {
+        None | Some("") | Some("private") => "private",
+        Some("shared") => "shared",
+        Some(other) => return Err(format!("invalid visibility `{}`", other)),
+    };
+    let q = new.q.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let node = match (&new.ast, q) {
+        (Some(_), Some(_)) => return Err("give either ast or q, not both".into()),
+        (Some(ast), None) => Some(
+            serde_json::from_value::<Node>(ast.clone()).map_err(|e| format!("bad ast: {}", e))?,
+        ),
+        (None, Some(q)) => {
+            query_text::parse(q)
+                .map_err(|e| format!("bad q: {}", e))?
+                .node
+        }
+        (None, None) => None,
+    };
+    let Some(node) = node else {
+        return Err("a saved search needs a filter".into());
+    };
+    // Compile once now so a search that can never run is refused up front.
+    query_ast::compile(&node, &mut Vec::new())?;
+    let ast = serde_json::to_value(&node).map_err(|e| e.to_string())?;
+    Ok((name.to_string(), ast, visibility.to_string()))
+}
+
+pub fn create(
+    conn: &mut PgConnection,
+    new: NewSearch,
+    who: &str,
+) -> anyhow::Result<Outcome<SavedSearch>> {
+    let (name, ast, visibility) = match validate(&new) {
+        Ok(v) => v,
+        Err(msg) => return Ok(Outcome::Rejected(msg)),
+    };
+    let created = diesel::sql_query(format!(
+        "INSERT INTO saved_searches (name, description, owner, ast, visibility) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING {SEARCH_COLUMNS}"
+    ))
+    .bind::<Text, _>(&name)
+    .bind::<Nullable<Text>, _>(new.description.as_deref())
+    .bind::<Text, _>(who)
+    .bind::<Jsonb, _>(&ast)
+    .bind::<Text, _>(&visibility)
+    .get_result(conn)
+    .context("insert saved search")?;
+    Ok(Outcome::Done(created))
+}
+
+/// The search when `who` owns `id`; otherwise the outcome to answer with:
+/// not found when it is missing or private to someone else, forbidden when it
+/// is shared but someone else's.
+fn owned<T>(
+    conn: &mut PgConnection,
+    id: i64,
+    who: &str,
+) -> anyhow::Result<Result<SavedSearch, Outcome<T>>> {
+    Ok(match get(conn, id, who)? {
+        None => Err(Outcome::NotFound),
+        Some(s) if s.owner != who => Err(Outcome::Forbidden(
+            "only the owner can change a saved search".into(),
+        )),
+        Some(s) => Ok(s),
+    })
+}
+
+/// `None` when `who` owns `id`; otherwise the outcome to answer with.
+fn deny<T>(conn: &mut PgConnection, id: i64, who: &str) -> anyhow::Result<Option<Outcome<T>>> {
+    Ok(owned(conn, id, who)?.err())
+}
+
+/// Scheduled searches raise alerts every console user reads, so they must be
+/// shared (see the module docs).
+fn check_schedulable(scheduled: bool, visibility: &str) -> Result<(), String> {
+    if scheduled && visibility != "shared" {
+        return Err("only shared searches can be scheduled".into());
+    }
+    Ok(())
+}
+
+/// Replace name/description/filter/visibility. A scheduled search keeps its
+/// schedule and slice position.
+pub fn update(
+    conn: &mut PgConnection,
+    id: i64,
+    new: NewSearch,
+    who: &str,
+) -> anyhow::Result<Outcome<SavedSearch>> {
+    let (name, ast, visibility) = match validate(&new) {
+        Ok(v) => v,
+        Err(msg) => return Ok(Outcome::Rejected(msg)),
+    };
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let current = match owned(conn, id, who)? {
+            Ok(s) => s,
+            Err(denied) => return Ok(denied),
+        };
+        if let Err(msg) = check_schedulable(current.every_mins.is_some(), &visibility) {
+            return Ok(Outcome::Rejected(msg));
+        }
+        let updated = diesel::sql_query(format!(
+            "UPDATE saved_searches SET name = $2, description = $3, ast = $4, visibility = $5, \
+               last_error = NULL, updated_at = now() \
+             WHERE id = $1 RETURNING {SEARCH_COLUMNS}"
+        ))
+        .bind::<BigInt, _>(id)
+        .bind::<Text, _>(&name)
+        .bind::<Nullable<Text>, _>(new.description.as_deref())
+        .bind::<Jsonb, _>(&ast)
+        .bind::<Text, _>(&visibility)
+        .get_result(conn)
+        .context("update saved search")?;
+        Ok(Outcome::Done(updated))
+    })
+}
+
+/// Delete a search. Alerts it raised stay, as any resolved detection's do.
+pub fn delete(conn: &mut PgConnection, id: i64, who: &str) -> anyhow::Result<Outcome<()>> {
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        if let Some(denied) = deny(conn, id, who)? {
+            return Ok(denied);
+        }
+        diesel::sql_query("DELETE FROM saved_searches WHERE id = $1")
+            .bind::<BigInt, _>(id)
+            .execute(conn)
+            .context("delete saved search")?;
+        Ok(Outcome::Done(()))
+    })
+}
+
+/// Promote to (or re-time) a scheduled search. The first slice ends at the
+/// first run and is `every_mins` wide; re-scheduling keeps the slice position.
+pub fn set_schedule(
+    conn: &mut PgConnection,
+    id: i64,
+    schedule: Schedule,
+    who: &str,
+) -> anyhow::Result<Outcome<SavedSearch>> {
+    if !(1..=1440).contains(&schedule.every_mins) {
+        return Ok(Outcome::Rejected("every_mins must be 1–1440".into()));
+    }
+    let threshold = schedule.threshold.unwrap_or(0);
+    if threshold < 0 {
+        return Ok(Outcome::Rejected("threshold must be >= 0".into()));
+    }
+    let severity = schedule.severity.as_deref().unwrap_or("medium");
+    if !matches!(severity, "low" | "medium" | "high" | "critical") {
+        return Ok(Outcome::Rejected(format!(
+            "invalid severity `{}`",
+            severity
+        )));
+    }
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let current = match owned(conn, id, who)? {
+            Ok(s) => s,
+            Err(denied) => return Ok(denied),
+        };
+        if let Err(msg) = check_schedulable(true, &current.visibility) {
+            return Ok(Outcome::Rejected(msg));
+        }
+        let updated = diesel::sql_query(format!(
+            "UPDATE saved_searches SET every_mins = $2, threshold = $3, severity = $4, updated_at = now() \
+             WHERE id = $1 RETURNING {SEARCH_COLUMNS}"
+        ))
+        .bind::<BigInt, _>(id)
+        .bind::<Integer, _>(schedule.every_mins)
+        .bind::<BigInt, _>(threshold)
+        .bind::<Text, _>(severity)
+        .get_result(conn)
+        .context("schedule saved search")?;
+        Ok(Outcome::Done(updated))
+    })
+}
+
+/// Demote back to a plain saved search. Re-promoting later starts afresh
+/// rather than back-filling the unscheduled gap.
+pub fn clear_schedule(
+    conn: &mut PgConnection,
+    id: i64,
+    who: &str,
+) -> anyhow::Result<Outcome<SavedSearch>> {
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        if let Some(denied) = deny(conn, id, who)? {
+            return Ok(denied);
+        }
+        let updated = diesel::sql_query(format!(
+            "UPDATE saved_searches SET every_mins = NULL, last_run_at = NULL, last_run_to = NULL, \
+               last_match_count = NULL, last_error = NULL, updated_at = now() \
+             WHERE id = $1 RETURNING {SEARCH_COLUMNS}"
+        ))
+        .bind::<BigInt, _>(id)
+        .get_result(conn)
+        .context("unschedule saved search")?;
+        Ok(Outcome::Done(updated))
+    })
+}
+
+pub async fn run(cancel: CancellationToken, conf: SearchConfig, pool: DbPool) {
+    info!(
+        "scheduled search runner started (interval={}s, lag={}m)",
+        conf.interval_secs, conf.lag_mins
+    );
+    let interval = std::time::Duration::from_secs(conf.interval_secs.max(1));
+
+    loop {
+        tick(&pool, &conf).await;
+
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                info!("scheduled search runner stopping");
+                return;
+            }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+#[tracing::instrument(name = "searches.tick", skip_all)]
+async fn tick(pool: &DbPool, conf: &SearchConfig) {
+    let pool = pool.clone();
+    let conf = conf.clone();
+    let res = tokio::task::spawn_blocking(move || -> anyhow::Result<(usize, usize)> {
+        let mut conn = crate::db::conn(&pool).map_err(anyhow::Error::from)?;
+        run_due(&mut conn, &conf)
+    })
+    .await;
+
+    match res {
+        Ok(Ok((0, _))) => {}
+        Ok(Ok((ran, alerted))) => info!("scheduled searches: ran {ran}, alerted {alerted}"),
+        Ok(Err(e)) => error!("scheduled searches failed: {e:#}"),
+        Err(e) => error!("scheduled searches task join error: {e}"),
+    }
+}
+
+/// Run every due scheduled search once. Returns (searches run, alerts raised).
+/// One search failing is recorded on its row and doesn't stop the others.
+pub fn run_due(conn: &mut PgConnection, conf: &SearchConfig) -> anyhow::Result<(usize, usize)> {
+    let due: Vec<SavedSearch> = diesel::sql_query(format!(
+        "SELECT {SEARCH_COLUMNS} FROM saved_searches \
+         WHERE every_mins IS NOT NULL AND visibility = 'shared' \
+           AND (last_run_at IS NULL OR last_run_at + make_interval(mins => every_mins) <= now()) \
+         ORDER BY last_run_at NULLS FIRST, id"
+    ))
+    .load(conn)
+    .context("load due saved searches")?;
+
+    let to = Utc::now() - Duration::minutes(conf.lag_mins.max(0));
+    let (mut ran, mut alerted) = (0, 0);
+    for search in &due {
+        match run_one(conn, search, to, conf.evidence_uids.max(1)) {
+            Ok(hit) => {
+                ran += 1;
+                alerted += usize::from(hit);
+            }
+            Err(e) => {
+                warn!(
+                    "scheduled search {} ({}) failed: {e:#}",
+                    search.id, search.name
+                );
+                diesel::sql_query(
+                    "UPDATE saved_searches SET last_run_at = now(), last_error = $2 WHERE id = $1",
+                )
+                .bind::<BigInt, _>(search.id)
+                .bind::<Text, _>(format!("{e:#}"))
+                .execute(conn)
+                .context("record saved search failure")?;
+            }
+        }
+    }
+    Ok((ran, alerted))
+}
+
+#[derive(QueryableByName)]
+struct MatchRow {
+    #[diesel(sql_type = Text)]
+    uid: String,
+    #[diesel(sql_type = Timestamptz)]
+    ts: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    first_ts: DateTime<Utc>,
+    #[diesel(sql_type = BigInt)]
+    total: i64,
+}
+
+/// Evaluate one search over events ingested in `(last_run_to, to]` and
+/// advance it. Returns whether the slice crossed the threshold.
+fn run_one(
+    conn: &mut PgConnection,
+    search: &SavedSearch,
+    to: DateTime<Utc>,
+    evidence_uids: i64,
+) -> anyhow::Result<bool> {
+    let every = Duration::minutes(search.every_mins.unwrap_or(1).into());
+    let from = search.last_run_to.unwrap_or(to - every);
+    if from >= to {
+        return Ok(false);
+    }
+    let node: Node = serde_json::from_value(search.ast.clone()).context("stored ast")?;
+    let mut binds = Vec::new();
+    let where_sql = query_ast::compile(&node, &mut binds).map_err(anyhow::Error::msg)?;
+    binds.push(Bind::Ts(from));
+    let from_idx = binds.len();
+    binds.push(Bind::Ts(to));
+    let to_idx = binds.len();
+    binds.push(Bind::BigInt(evidence_uids));
+    let limit_idx = binds.len();
+    let sql = format!(
+        "SELECT uid, ts, min(ts) OVER () AS first_ts, count(*) OVER () AS total \
+         FROM ssumgmt_events \
+         WHERE ({where_sql}) AND ingested_at > ${from_idx} AND ingested_at <= ${to_idx} \
+         ORDER BY ts DESC, uid LIMIT ${limit_idx}"
+    );
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        diesel::sql_query(format!(
+            "SET LOCAL statement_timeout = '{SEARCH_STATEMENT_TIMEOUT}'"
+        ))
+        .execute(conn)
+        .context("set statement_timeout")?;
+        let rows: Vec<MatchRow> = apply_binds(diesel::sql_query(sql).into_boxed::<Pg>(), binds)
+            .load(conn)
+            .context("run saved search")?;
+        let total = rows.first().map_or(0, |r| r.total);
+
+        // Rows are newest first; every row carries the slice's earliest match.
+        let hit = total > search.threshold;
+        if let (true, Some(newest)) = (hit, rows.first()) {
+            let seen = (newest.first_ts, newest.ts);
+            raise_alert(conn, search, &node, (from, to), total, &rows, seen)?;
+        }
+
+        diesel::sql_query(
+            "UPDATE saved_searches SET last_run_at = now(), last_run_to = $2, \
+               last_match_count = $3, last_error = NULL \
+             WHERE id = $1",
+        )
+        .bind::<BigInt, _>(search.id)
+        .bind::<Timestamptz, _>(to)
+        .bind::<BigInt, _>(total)
+        .execute(conn)
+        .context("advance saved search")?;
+        Ok(hit)
+    })
+}
+
+/// Upsert the search's single alert. A later slice that crosses the threshold
+/// again adds to its count and reopens it if it was resolved.
+fn raise_alert(
+    conn: &mut PgConnection,
+    search: &SavedSearch,
+    node: &Node,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    total: i64,
+    rows: &[MatchRow],
+    (first_seen, last_seen): (DateTime<Utc>, DateTime<Utc>),
+) -> anyhow::Result<()> {
+    let rule_id = format!("search:{}", search.id);
+    let uids: Vec<&str> = rows.iter().map(|r| r.uid.as_str()).collect();
+    let evidence = json!({
+        "search_id": search.id,
+        "name": search.name,
+        "owner": search.owner,
+        "query": query_text::print(node),
+        "from": from,
+        "to": to,
+        "count": total,
+        "threshold": search.threshold,
+        "uids": uids,
+        "uids_truncated": total > uids.len() as i64,
+    });
+    let description = format!(
+        "{} events ingested between {} and {} matched saved search \"{}\" (threshold {})",
+        total,
+        from.format("%Y-%m-%d %H:%M"),
+        to.format("%Y-%m-%d %H:%M UTC"),
+        search.name,
+        search.threshold
+    );
+    diesel::sql_query(
+        "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+         VALUES ($1, $1, $2, $3, $4, NULL, 'search', $5, $6, $7, 'open', $8, now()) \
+         ON CONFLICT (fingerprint) DO UPDATE SET \
+           last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), \
+           event_count = alerts.event_count + EXCLUDED.event_count, \
+           severity = EXCLUDED.severity, title = EXCLUDED.title, \
+           description = EXCLUDED.description, evidence = EXCLUDED.evidence, \
+           status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, \
+           updated_at = now()",
+    )
+    .bind::<Text, _>(&rule_id)
+    .bind::<Text, _>(&search.severity)
+    .bind::<Text, _>(format!("Saved search: {}", search.name))
+    .bind::<Text, _>(&description)
+    .bind::<Timestamptz, _>(first_seen)
+    .bind::<Timestamptz, _>(last_seen)
+    .bind::<BigInt, _>(total)
+    .bind::<Jsonb, _>(&evidence)
+    .execute(conn)
+    .context("upsert saved search alert")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(ast: Option<Value>, q: Option<&str>) -> NewSearch {
+        NewSearch {
+            name: " Root console logins ".into(),
+            description: None,
+            ast,
+            q: q.map(str::to_string),
+            visibility: None,
+        }
+    }
+
+    #[test]
+    fn text_and_ast_filters_are_stored_as_the_same_ast() {
+        let (name, from_q, visibility) = validate(&body(
+            None,
+            Some("action=ConsoleLogin json.userIdentity.type=Root"),
+        ))
+        .unwrap();
+        assert_eq!(name, "Root console logins");
+        assert_eq!(visibility, "private");
+        let (_, from_ast, _) = validate(&body(Some(from_q.clone()), None)).unwrap();
+        assert_eq!(from_q, from_ast);
+        assert_eq!(from_q["kind"], "group");
+    }
+
+    #[test]
+    fn unusable_filters_are_refused() {
+        let refused = |b: NewSearch| validate(&b).unwrap_err();
+        assert_eq!(refused(body(None, None)), "a saved search needs a filter");
+        assert_eq!(
+            refused(body(None, Some("  "))),
+            "a saved search needs a filter"
+        );
+        assert!(refused(body(None, Some("actor"))).starts_with("bad q: bare term"));
+        assert!(refused(body(Some(json!({ "kind": "nope" })), None)).starts_with("bad ast"));
+        assert!(refused(body(Some(json!({})), Some("actor:x"))).contains("not both"));
+    }
+
+    #[test]
+    fn only_shared_searches_are_scheduled() {
+        assert!(check_schedulable(true, "shared").is_ok());
+        assert!(check_schedulable(false, "private").is_ok());
+        assert!(check_schedulable(true, "private").is_err());
+    }
+}
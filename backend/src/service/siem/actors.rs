@@ -9,6 +9,7 @@ use log::{info, warn};
 use serde_json::Value;
 
 use crate::misc::config::SelfserviceConfig;
+use crate::service::siem::overrides::{self, Overrides};
 
 /// A roster member as resolved from selfservice-api. Tolerant of field naming
 /// across the API surface.
@@ -18,6 +19,8 @@ pub struct RosterMember {
     pub upn: Option<String>,
     pub display_name: Option<String>,
     pub team: Option<String>,
+    /// Every capability the member is in (`team` is the first of them).
+    pub capabilities: Vec<String>,
     /// Azure AD object id — present only for service principals (the roster's
     /// `UserId` when it isn't an email-like UPN). Lets a federated/web-identity
     /// GUID actor stitch to the named SP.
@@ -51,6 +54,13 @@ pub async fn fetch_roster(conf: &SelfserviceConfig) -> Vec<RosterMember> {
 }
 
 async fn fetch_roster_inner(conf: &SelfserviceConfig) -> anyhow::Result<Vec<RosterMember>> {
+    let body = fetch_capabilities_json(conf).await?;
+    Ok(parse_capability_roster(&body))
+}
+
+/// The raw capability listing (capabilities with their members) from
+/// selfservice-api. Also read by the ownership worker for namespace owners.
+pub(crate) async fn fetch_capabilities_json(conf: &SelfserviceConfig) -> anyhow::Result<Value> {
     let url = format!(
         "{}/system/legacy/aad-aws-sync",
         conf.base_url.trim_end_matches('/')
@@ -83,17 +93,20 @@ async fn fetch_roster_inner(conf: &SelfserviceConfig) -> anyhow::Result<Vec<Rost
     if !resp.status().is_success() {
         anyhow::bail!("roster endpoint {} returned {}", url, resp.status());
     }
-    let body: Value = resp.json().await.context("parse roster json")?;
-    Ok(parse_capability_roster(&body))
+    resp.json().await.context("parse roster json")
 }
 
-fn parse_capability_roster(body: &Value) -> Vec<RosterMember> {
-    let caps = body
-        .as_array()
+/// The capability array out of a listing, whichever envelope it came in.
+pub(crate) fn capability_list(body: &Value) -> Vec<Value> {
+    body.as_array()
         .cloned()
         .or_else(|| body.get("items").and_then(|v| v.as_array()).cloned())
         .or_else(|| body.get("capabilities").and_then(|v| v.as_array()).cloned())
-        .unwrap_or_default();
+        .unwrap_or_default()
+}
+
+fn parse_capability_roster(body: &Value) -> Vec<RosterMember> {
+    let caps = capability_list(body);
 
     // Keyed by canonical id: lowercased email for people, lowercased object id for
     // service principals.
@@ -125,12 +138,14 @@ fn parse_capability_roster(body: &Value) -> Vec<RosterMember> {
                             if existing.upn.is_none() {
                                 existing.upn = upn.clone();
                             }
+                            push_capability(existing, &team);
                         })
                         .or_insert_with(|| RosterMember {
                             email,
                             upn,
                             display_name: None,
                             team: team.clone(),
+                            capabilities: team.iter().cloned().collect(),
                             object_id: None,
                         });
                 }
@@ -151,12 +166,14 @@ fn parse_capability_roster(body: &Value) -> Vec<RosterMember> {
                             if existing.display_name.is_none() {
                                 existing.display_name = name.clone();
                             }
+                            push_capability(existing, &team);
                         })
                         .or_insert_with(|| RosterMember {
                             email: String::new(),
                             upn: None,
                             display_name: name,
                             team: team.clone(),
+                            capabilities: team.iter().cloned().collect(),
                             object_id: Some(object_id),
                         });
                 }
@@ -166,6 +183,14 @@ fn parse_capability_roster(body: &Value) -> Vec<RosterMember> {
     by_key.into_values().collect()
 }
 
+fn push_capability(m: &mut RosterMember, team: &Option<String>) {
+    if let Some(t) = team {
+        if !m.capabilities.contains(t) {
+            m.capabilities.push(t.clone());
+        }
+    }
+}
+
 #[derive(serde::Deserialize)]
 struct TokenResponse {
     access_token: String,
@@ -200,7 +225,7 @@ async fn fetch_access_token(
     Ok(tok.access_token)
 }
 
-fn first_str(v: &Value, keys: &[&str]) -> Option<String> {
+pub(crate) fn first_str(v: &Value, keys: &[&str]) -> Option<String> {
     for k in keys {
         if let Some(s) = v.get(*k).and_then(Value::as_str) {
             if !s.is_empty() {
@@ -219,6 +244,14 @@ enum Kind {
 }
 
 impl Kind {
+    fn parse(s: &str) -> Option<Kind> {
+        match s {
+            "person" => Some(Kind::Person),
+            "service" => Some(Kind::Service),
+            "unresolved" => Some(Kind::Unresolved),
+            _ => None,
+        }
+    }
     fn as_str(self) -> &'static str {
         match self {
             Kind::Person => "person",
@@ -383,7 +416,38 @@ fn origins_for(source: &str, actor: &str, resolved_via_roster: bool) -> Vec<&'st
     out
 }
 
+/// `classify` with analyst overrides applied first: a merged alias resolves to
+/// its target (picking up the target's roster entry when it has one), a split
+/// alias is classified as if the roster didn't exist and keyed on the alias
+/// itself, so it can't fold back into the actor it was split from.
+fn classify_with_overrides(
+    source: &str,
+    actor: &str,
+    roster: &HashMap<String, RosterMember>,
+    by_object_id: &HashMap<String, RosterMember>,
+    ov: &Overrides,
+) -> (String, Kind, Option<RosterMember>, String) {
+    let lower = actor.to_lowercase();
+    if let Some(target) = ov.merges.get(&lower) {
+        let (_, kind, _, alias_kind) = classify(source, actor, &HashMap::new(), &HashMap::new());
+        let member = roster.get(&target.to_lowercase()).cloned();
+        let kind = if member.is_some() { Kind::Person } else { kind };
+        return (target.clone(), kind, member, alias_kind);
+    }
+    if ov.splits.contains(&lower) {
+        let (_, kind, _, alias_kind) = classify(source, actor, &HashMap::new(), &HashMap::new());
+        let id = if actor.contains('@') {
+            lower
+        } else {
+            actor.to_string()
+        };
+        return (id, kind, None, alias_kind);
+    }
+    classify(source, actor, roster, by_object_id)
+}
+
 /// Reconcile actors + aliases from the roster and the windowed union view.
+/// Analyst overrides (`actor_overrides`) are layered on top every pass.
 /// Runs inside `spawn_blocking`. Returns the number of canonical actors upserted.
 pub fn reconcile(
     conn: &mut PgConnection,
@@ -422,12 +486,21 @@ pub fn reconcile(
     .bind::<Timestamptz, _>(floor)
     .load(conn)
     .context("load actor activity")?;
+    let ov = overrides::load(conn)?;
+    if !ov.is_empty() {
+        info!(
+            "siem/actors: applying overrides :: merges={} splits={} kinds={}",
+            ov.merges.len(),
+            ov.splits.len(),
+            ov.kinds.len()
+        );
+    }
 
     // Aggregate per canonical id.
     let mut by_id: HashMap<String, Resolved> = HashMap::new();
     for a in &activity {
         let (id, kind, member, alias_kind) =
-            classify(&a.source, &a.actor, &roster_map, &by_object_id);
+            classify_with_overrides(&a.source, &a.actor, &roster_map, &by_object_id, &ov);
         let member_email = member
             .as_ref()
             .map(|m| m.email.clone())
@@ -496,8 +569,16 @@ pub fn reconcile(
         });
     }
 
+    // A kind override wins over whatever the aliases voted for.
+    for r in by_id.values_mut() {
+        if let Some(k) = ov.kinds.get(&r.id).and_then(|k| Kind::parse(k)) {
+            r.kind = k;
+        }
+    }
+
     let count = by_id.len();
     conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let mut moved: Vec<(String, String)> = Vec::new();
         for r in by_id.values() {
             diesel::sql_query(
                 "INSERT INTO actors (id, email, display_name, team, kind, first_seen, last_active, sources, origins, updated_at) \
@@ -526,36 +607,58 @@ pub fn reconcile(
             .context("upsert actor")?;
 
             for (alias, alias_kind) in &r.aliases {
-                diesel::sql_query(
-                    "INSERT INTO actor_aliases (alias, actor_id, kind) VALUES ($1, $2, $3) \
-                     ON CONFLICT (alias) DO UPDATE SET actor_id = EXCLUDED.actor_id, kind = EXCLUDED.kind",
-                )
-                .bind::<Text, _>(alias)
-                .bind::<Text, _>(&r.id)
-                .bind::<Text, _>(alias_kind)
-                .execute(conn)
-                .context("upsert alias")?;
+                upsert_alias(conn, alias, &r.id, alias_kind, &mut moved)?;
             }
             // Roster aliases (email + upn) for people seeded without events.
             if r.kind == Kind::Person {
                 if let Some(email) = &r.email {
-                    diesel::sql_query(
-                        "INSERT INTO actor_aliases (alias, actor_id, kind) VALUES ($1, $2, 'email') \
-                         ON CONFLICT (alias) DO UPDATE SET actor_id = EXCLUDED.actor_id, kind = EXCLUDED.kind",
-                    )
-                    .bind::<Text, _>(email.to_lowercase())
-                    .bind::<Text, _>(&r.id)
-                    .execute(conn)
-                    .context("upsert email alias")?;
+                    upsert_alias(conn, &email.to_lowercase(), &r.id, "email", &mut moved)?;
                 }
             }
         }
+        let removed = overrides::apply(conn, &moved)?;
+        if removed > 0 {
+            info!("siem/actors: removed {} actors emptied by merges", removed);
+        }
         Ok(())
     })?;
 
     Ok(count)
 }
 
+#[derive(QueryableByName)]
+struct PrevOwner {
+    #[diesel(sql_type = Nullable<Text>)]
+    prev_id: Option<String>,
+}
+
+/// Point `alias` at `actor_id`, recording `(previous owner, actor_id)` in
+/// `moved` when the alias changed hands.
+fn upsert_alias(
+    conn: &mut PgConnection,
+    alias: &str,
+    actor_id: &str,
+    kind: &str,
+    moved: &mut Vec<(String, String)>,
+) -> anyhow::Result<()> {
+    // The CTE reads the pre-statement snapshot, so `prev_id` is the old owner.
+    let prev = diesel::sql_query(
+        "WITH prev AS (SELECT actor_id FROM actor_aliases WHERE alias = $1) \
+         INSERT INTO actor_aliases (alias, actor_id, kind) VALUES ($1, $2, $3) \
+         ON CONFLICT (alias) DO UPDATE SET actor_id = EXCLUDED.actor_id, kind = EXCLUDED.kind \
+         RETURNING (SELECT actor_id FROM prev) AS prev_id",
+    )
+    .bind::<Text, _>(alias)
+    .bind::<Text, _>(actor_id)
+    .bind::<Text, _>(kind)
+    .get_result::<PrevOwner>(conn)
+    .context("upsert alias")?;
+    if let Some(old) = prev.prev_id.filter(|p| p != actor_id) {
+        moved.push((old, actor_id.to_string()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -578,6 +681,40 @@ mod tests {
         assert_eq!(alias, "k8s");
     }
 
+    #[test]
+    fn merge_override_folds_alias_into_target() {
+        let mut ov = Overrides::default();
+        ov.merges
+            .insert("alice-gh".to_string(), "alice@dfds.com".to_string());
+        let (id, kind, _, alias) =
+            classify_with_overrides("github", "Alice-GH", &empty(), &empty(), &ov);
+        assert_eq!(id, "alice@dfds.com");
+        assert!(kind == Kind::Unresolved);
+        assert_eq!(alias, "github");
+    }
+
+    #[test]
+    fn split_override_ignores_roster() {
+        let mut roster = empty();
+        roster.insert(
+            "shared@dfds.com".to_string(),
+            RosterMember {
+                email: "alice@dfds.com".to_string(),
+                upn: Some("shared@dfds.com".to_string()),
+                display_name: None,
+                team: None,
+                capabilities: Vec::new(),
+                object_id: None,
+            },
+        );
+        let mut ov = Overrides::default();
+        ov.splits.insert("shared@dfds.com".to_string());
+        let (id, _, member, _) =
+            classify_with_overrides("cloudtrail", "Shared@dfds.com", &roster, &empty(), &ov);
+        assert_eq!(id, "shared@dfds.com");
+        assert!(member.is_none());
+    }
+
     #[test]
     fn origins_kubernetes_supersedes_feed() {
         // k8s SA seen via CloudTrail → kubernetes only, not aws.
@@ -4,6 +4,27 @@ use diesel::prelude::*;
 use diesel::sql_types::{BigInt, Double, Text, Timestamptz};
 use diesel::PgConnection;
 
+/// `WHERE` condition over `cloudtrail_events c` and its `actor_aliases aa`: a
+/// successful console sign-in with a password and no MFA, by root, into a
+/// privileged role or by someone holding a privileged grant. Federated
+/// sign-ins never match (their MFA is the IdP's, `mfa_used` is NULL). Shared
+/// by `privileged_login_without_mfa` and the `priv_mfa_less_logins` risk factor.
+macro_rules! privileged_mfa_less_login {
+    () => {
+        concat!(
+            "c.event_name = 'ConsoleLogin' AND c.error_code IS NULL \
+             AND c.raw->'responseElements'->>'ConsoleLogin' = 'Success' \
+             AND c.mfa_used = false \
+             AND (c.principal_type = 'Root' \
+               OR regexp_replace(COALESCE(c.assumed_role_arn, ''), '^.*/', '') ~* '",
+            crate::service::siem::role_graph::privileged_role_pattern!(),
+            "' OR EXISTS (SELECT 1 FROM grants g \
+                          WHERE g.actor_id = aa.actor_id AND g.privileged AND g.revoked_at IS NULL))"
+        )
+    };
+}
+pub(crate) use privileged_mfa_less_login;
+
 /// Run every detection rule + post-processing (session flagging, auto-resolve).
 /// Returns the number of alert rows inserted/updated by the rules.
 pub fn evaluate(
@@ -137,6 +158,342 @@ pub fn evaluate(
         .execute(conn)
         .context("rule github_secret_scanning")?;
 
+        // Rule: threat_intel_match — one alert per indicator, actor and day; severity
+        // follows the strongest feed confidence behind it.
+        touched += diesel::sql_query(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'threat_intel_match:' || h.kind || ':' || h.indicator || ':' || COALESCE(h.actor_id, h.actor, '?') || ':' || to_char(date_trunc('day', h.event_time), 'YYYY-MM-DD'), \
+               'threat_intel_match', \
+               CASE WHEN max(h.confidence) >= 85 THEN 'high' WHEN max(h.confidence) >= 50 THEN 'medium' ELSE 'low' END, \
+               'Threat-intel indicator match', \
+               COALESCE(min(h.actor), '?') || ' matched ' || h.kind || ' indicator ' || h.indicator || ' (' || string_agg(DISTINCT h.feed, ', ') || ')', \
+               h.actor_id, min(h.source), min(h.event_time), max(h.event_time), count(*), 'open', \
+               jsonb_build_object('indicator', h.indicator, 'kind', h.kind, 'feeds', array_agg(DISTINCT h.feed), \
+                 'confidence', max(h.confidence), 'ips', array_agg(DISTINCT h.source_ip) FILTER (WHERE h.source_ip IS NOT NULL), \
+                 'user_agents', array_agg(DISTINCT h.user_agent) FILTER (WHERE h.user_agent IS NOT NULL), \
+                 'sources', array_agg(DISTINCT h.source)), now() \
+             FROM threat_intel_hits h \
+             WHERE h.event_time >= $1 \
+             GROUP BY h.kind, h.indicator, h.actor_id, COALESCE(h.actor_id, h.actor, '?'), date_trunc('day', h.event_time) \
+             ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), event_count = EXCLUDED.event_count, \
+               description = EXCLUDED.description, evidence = EXCLUDED.evidence, severity = EXCLUDED.severity, \
+               status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()",
+        )
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule threat_intel_match")?;
+
+        // Rule: risk_jump — current score vs the lowest point of the trailing
+        // window in `risk_score_history`; one alert per actor and day.
+        touched += diesel::sql_query(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'risk_jump:' || r.actor_id || ':' || to_char(date_trunc('day', r.computed_at), 'YYYY-MM-DD'), \
+               'risk_jump', CASE WHEN r.score >= 80 THEN 'high' ELSE 'medium' END, 'Sudden risk score increase', \
+               COALESCE(a.display_name, r.actor_id) || ' risk rose from ' || lo.min_score || ' to ' || r.score || ' within ' || $2 || 'h', \
+               r.actor_id, 'siem', r.computed_at, r.computed_at, 1, 'open', \
+               jsonb_build_object('from', lo.min_score, 'from_at', lo.bucket, 'to', r.score, 'delta', r.score - lo.min_score, \
+                 'window_hours', $2, 'components', r.components), now() \
+             FROM risk_scores r \
+             JOIN actors a ON a.id = r.actor_id \
+             CROSS JOIN LATERAL ( \
+               SELECT h.min_score, h.bucket FROM risk_score_history h \
+               WHERE h.actor_id = r.actor_id AND h.bucket >= $1 \
+               ORDER BY h.min_score ASC, h.bucket ASC LIMIT 1 \
+             ) lo \
+             WHERE r.score - lo.min_score >= $3 \
+             ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), description = EXCLUDED.description, \
+               evidence = EXCLUDED.evidence, severity = EXCLUDED.severity, \
+               status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()",
+        )
+        .bind::<Timestamptz, _>(now - Duration::hours(siem.risk_jump_window_hours.max(1)))
+        .bind::<BigInt, _>(siem.risk_jump_window_hours.max(1))
+        .bind::<diesel::sql_types::Integer, _>(siem.risk_jump_delta.max(1))
+        .execute(conn)
+        .context("rule risk_jump")?;
+
+        // Rule: activity_after_leave — identities of someone who left the roster
+        // still acting; one alert per leave, kept fresh while it continues.
+        touched += diesel::sql_query(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'activity_after_leave:' || l.actor_id || ':' || to_char(l.left_at, 'YYYY-MM-DD\"T\"HH24:MI'), \
+               'activity_after_leave', 'high', 'Activity after leaving the roster', \
+               COALESCE(l.display_name, l.actor_id) || ' left the roster on ' || to_char(l.left_at, 'YYYY-MM-DD') || \
+                 ' but has ' || count(*) || ' events since (' || string_agg(DISTINCT e.source, ', ') || ')', \
+               l.actor_id, CASE WHEN count(DISTINCT e.source) = 1 THEN min(e.source) ELSE 'siem' END, \
+               min(e.ts), max(e.ts), count(*), 'open', \
+               jsonb_build_object('left_at', l.left_at, 'member_key', l.member_key, \
+                 'sources', array_agg(DISTINCT e.source), 'identities', array_agg(DISTINCT e.actor), \
+                 'last_action', (array_agg(e.action ORDER BY e.ts DESC))[1]), now() \
+             FROM roster_leavers l \
+             JOIN actor_aliases aa ON aa.actor_id = l.actor_id \
+             JOIN ssumgmt_events e ON e.actor = aa.alias AND e.ts > l.left_at AND e.ts >= $1 \
+             WHERE e.source <> 'ssu-mgmt' \
+             GROUP BY l.actor_id, l.member_key, l.display_name, l.left_at \
+             ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), description = EXCLUDED.description, \
+               event_count = EXCLUDED.event_count, evidence = EXCLUDED.evidence, \
+               status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()",
+        )
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule activity_after_leave")?;
+
+        // Rule: new_privileged_role_path — a hop into a privileged role nobody
+        // took this way before (from this caller by this STS call, whichever
+        // actor) inside the role-edge retention; one alert per edge.
+        touched += diesel::sql_query(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'new_privileged_role_path:' || r.edge_key, \
+               'new_privileged_role_path', 'high', 'New path into a privileged role', \
+               COALESCE(r.actor_id, r.src) || ' assumed ' || regexp_replace(r.dst_role, '^.*/', '') || \
+                 ' in ' || COALESCE(r.dst_account, '?') || ' via ' || r.via || \
+                 CASE WHEN r.src_kind = 'role' THEN ' from ' || regexp_replace(r.src, '^.*/', '') ELSE '' END || \
+                 ' for the first time', \
+               r.actor_id, 'cloudtrail', r.first_seen, r.last_seen, r.event_count, 'open', \
+               jsonb_build_object('src_kind', r.src_kind, 'src', r.src, 'role', r.dst_role, \
+                 'account', r.dst_account, 'src_account', r.src_account, 'via', r.via, \
+                 'identity_source', r.identity_source), now() \
+             FROM role_edges r \
+             WHERE r.privileged AND r.first_seen >= $1 \
+             ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), description = EXCLUDED.description, \
+               event_count = EXCLUDED.event_count, evidence = EXCLUDED.evidence, \
+               status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()",
+        )
+        .bind::<Timestamptz, _>(h24)
+        .execute(conn)
+        .context("rule new_privileged_role_path")?;
+
+        // Rule: privileged_login_without_mfa — a password-only console sign-in
+        // by root, into a privileged role, or by someone holding a privileged
+        // grant. Federated sign-ins never match: their MFA is the IdP's.
+        touched += diesel::sql_query(concat!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'privileged_login_without_mfa:' || c.event_id, 'privileged_login_without_mfa', \
+               CASE WHEN c.principal_type = 'Root' THEN 'critical' ELSE 'high' END, \
+               'Privileged console login without MFA', \
+               COALESCE(c.principal_name, '?') || ' signed in to the console of ' || COALESCE(c.recipient_account_id, '?') || \
+                 ' with a password and no MFA', \
+               aa.actor_id, 'cloudtrail', c.event_time, c.event_time, 1, 'open', \
+               jsonb_build_object('event_id', c.event_id, 'account', c.recipient_account_id, 'source_ip', c.source_ip, \
+                 'auth_method', c.auth_method, 'principal_type', c.principal_type, 'role', c.assumed_role_arn), now() \
+             FROM cloudtrail_events c \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             WHERE c.event_time >= $1 AND ",
+            privileged_mfa_less_login!(),
+            " ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), description = EXCLUDED.description, \
+               evidence = EXCLUDED.evidence, status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()",
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule privileged_login_without_mfa")?;
+
+        // --- AWS defense evasion / persistence --------------------------------
+
+        // ON CONFLICT tail shared by the single-event rules below.
+        const REFRESH: &str = "ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), description = EXCLUDED.description, \
+               evidence = EXCLUDED.evidence, status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()";
+
+        // Rule: cloudtrail_tampering — a trail stopped or deleted, or updated
+        // to log less or somewhere else (bucket, region scope, global events,
+        // digest validation).
+        touched += diesel::sql_query(format!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'cloudtrail_tampering:' || c.event_id, 'cloudtrail_tampering', \
+               CASE WHEN c.event_name = 'UpdateTrail' THEN 'high' ELSE 'critical' END, 'CloudTrail logging tampered with', \
+               COALESCE(c.principal_name, '?') || ' called ' || c.event_name || ' on ' || COALESCE(c.raw->'requestParameters'->>'name', '?') || \
+                 ' in ' || COALESCE(c.recipient_account_id, '?'), \
+               aa.actor_id, 'cloudtrail', c.event_time, c.event_time, 1, 'open', \
+               jsonb_build_object('event_id', c.event_id, 'action', c.event_name, 'trail', c.raw->'requestParameters'->>'name', \
+                 'account', c.recipient_account_id, 'region', c.aws_region, 'request', c.raw->'requestParameters'), now() \
+             FROM cloudtrail_events c \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             WHERE c.error_code IS NULL AND c.event_time >= $1 \
+               AND (c.event_name IN ('StopLogging','DeleteTrail') \
+                 OR (c.event_name = 'UpdateTrail' AND ( \
+                   c.raw->'requestParameters' ? 's3BucketName' \
+                   OR c.raw->'requestParameters'->>'isMultiRegionTrail' = 'false' \
+                   OR c.raw->'requestParameters'->>'includeGlobalServiceEvents' = 'false' \
+                   OR c.raw->'requestParameters'->>'enableLogFileValidation' = 'false'))) \
+             {REFRESH}"
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule cloudtrail_tampering")?;
+
+        // Rule: guardduty_tampering — a detector deleted or switched off.
+        touched += diesel::sql_query(format!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'guardduty_tampering:' || c.event_id, 'guardduty_tampering', \
+               CASE WHEN c.event_name = 'DeleteDetector' THEN 'critical' ELSE 'high' END, 'GuardDuty detector disabled', \
+               COALESCE(c.principal_name, '?') || CASE WHEN c.event_name = 'DeleteDetector' THEN ' deleted' ELSE ' disabled' END || \
+                 ' the GuardDuty detector in ' || COALESCE(c.recipient_account_id, '?') || '/' || COALESCE(c.aws_region, '?'), \
+               aa.actor_id, 'cloudtrail', c.event_time, c.event_time, 1, 'open', \
+               jsonb_build_object('event_id', c.event_id, 'action', c.event_name, 'detector_id', c.raw->'requestParameters'->>'detectorId', \
+                 'account', c.recipient_account_id, 'region', c.aws_region), now() \
+             FROM cloudtrail_events c \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             WHERE c.error_code IS NULL AND c.event_time >= $1 \
+               AND (c.event_name = 'DeleteDetector' \
+                 OR (c.event_name = 'UpdateDetector' AND c.raw->'requestParameters'->>'enable' = 'false')) \
+             {REFRESH}"
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule guardduty_tampering")?;
+
+        // Rule: s3_bucket_made_public — a bucket policy with an unconditioned
+        // Allow for everyone (`"*"` or `{"AWS": "*"}`).
+        touched += diesel::sql_query(format!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               's3_bucket_made_public:' || c.event_id, 's3_bucket_made_public', 'high', 'S3 bucket policy made public', \
+               COALESCE(c.principal_name, '?') || ' opened ' || COALESCE(c.raw->'requestParameters'->>'bucketName', '?') || \
+                 ' to everyone with a bucket policy', \
+               aa.actor_id, 'cloudtrail', c.event_time, c.event_time, 1, 'open', \
+               jsonb_build_object('event_id', c.event_id, 'bucket', c.raw->'requestParameters'->>'bucketName', \
+                 'account', c.recipient_account_id, 'statements', p.statements), now() \
+             FROM cloudtrail_events c \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             CROSS JOIN LATERAL ( \
+               SELECT jsonb_agg(st) AS statements \
+               FROM jsonb_array_elements(CASE jsonb_typeof(c.raw->'requestParameters'->'bucketPolicy'->'Statement') \
+                      WHEN 'array' THEN c.raw->'requestParameters'->'bucketPolicy'->'Statement' \
+                      WHEN 'object' THEN jsonb_build_array(c.raw->'requestParameters'->'bucketPolicy'->'Statement') \
+                      ELSE '[]'::jsonb END) st \
+               WHERE st->>'Effect' = 'Allow' AND st->'Condition' IS NULL \
+                 AND (st->'Principal' = '\"*\"'::jsonb OR st->'Principal'->'AWS' = '\"*\"'::jsonb \
+                   OR st->'Principal'->'AWS' @> '[\"*\"]'::jsonb) \
+             ) p \
+             WHERE c.event_name = 'PutBucketPolicy' AND c.error_code IS NULL AND c.event_time >= $1 \
+               AND p.statements IS NOT NULL \
+             {REFRESH}"
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule s3_bucket_made_public")?;
+
+        // Rule: login_profile_for_other_user — a console password set on an
+        // IAM user by anyone but that user.
+        touched += diesel::sql_query(format!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'login_profile_for_other_user:' || c.event_id, 'login_profile_for_other_user', 'high', \
+               'Console password set for another user', \
+               COALESCE(c.principal_name, '?') || CASE WHEN c.event_name = 'CreateLoginProfile' THEN ' created' ELSE ' changed' END || \
+                 ' the console password of ' || (c.raw->'requestParameters'->>'userName'), \
+               aa.actor_id, 'cloudtrail', c.event_time, c.event_time, 1, 'open', \
+               jsonb_build_object('event_id', c.event_id, 'action', c.event_name, 'target_user', c.raw->'requestParameters'->>'userName', \
+                 'account', c.recipient_account_id, 'reset_required', c.raw->'requestParameters'->'passwordResetRequired'), now() \
+             FROM cloudtrail_events c \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             WHERE c.event_name IN ('CreateLoginProfile','UpdateLoginProfile') AND c.error_code IS NULL AND c.event_time >= $1 \
+               AND c.raw->'requestParameters'->>'userName' IS NOT NULL \
+               AND NOT (c.principal_type = 'IAMUser' AND c.principal_name = c.raw->'requestParameters'->>'userName') \
+             {REFRESH}"
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule login_profile_for_other_user")?;
+
+        // Rule: root_account_usage — one alert per account, reopened when root
+        // acts again after it was resolved.
+        touched += diesel::sql_query(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'root_account_usage:' || r.account, 'root_account_usage', 'high', 'Root account used', \
+               'The root user of ' || r.account || ' made ' || r.n || ' calls (' || array_to_string(r.actions, ', ') || ')', \
+               r.actor_id, 'cloudtrail', r.first_ts, r.last_ts, r.n, 'open', \
+               jsonb_build_object('account', r.account, 'actions', r.actions, 'source_ips', r.ips, 'last_event_id', r.last_event_id), now() \
+             FROM ( \
+               SELECT COALESCE(c.user_identity_account_id, c.recipient_account_id) AS account, min(aa.actor_id) AS actor_id, \
+                 count(*) AS n, min(c.event_time) AS first_ts, max(c.event_time) AS last_ts, \
+                 array_agg(DISTINCT c.event_name) AS actions, array_agg(DISTINCT c.source_ip) FILTER (WHERE c.source_ip IS NOT NULL) AS ips, \
+                 (array_agg(c.event_id ORDER BY c.event_time DESC))[1] AS last_event_id \
+               FROM cloudtrail_events c \
+               LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+               WHERE c.principal_type = 'Root' AND c.error_code IS NULL AND c.event_time >= $1 \
+               GROUP BY 1 \
+             ) r \
+             WHERE r.account IS NOT NULL \
+             ON CONFLICT (fingerprint) DO UPDATE SET \
+               last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), event_count = EXCLUDED.event_count, \
+               description = EXCLUDED.description, evidence = EXCLUDED.evidence, \
+               status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, updated_at = now()",
+        )
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule root_account_usage")?;
+
+        // Rule: snapshot_shared — an EBS snapshot made public, or shared with
+        // an account we don't collect CloudTrail from.
+        touched += diesel::sql_query(format!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'snapshot_shared:' || c.event_id, 'snapshot_shared', \
+               CASE WHEN s.public THEN 'critical' ELSE 'high' END, \
+               CASE WHEN s.public THEN 'EBS snapshot made public' ELSE 'EBS snapshot shared externally' END, \
+               COALESCE(c.principal_name, '?') || ' shared ' || COALESCE(c.raw->'requestParameters'->>'snapshotId', '?') || ' with ' || \
+                 CASE WHEN s.public THEN 'everyone' ELSE array_to_string(s.external, ', ') END, \
+               aa.actor_id, 'cloudtrail', c.event_time, c.event_time, 1, 'open', \
+               jsonb_build_object('event_id', c.event_id, 'snapshot_id', c.raw->'requestParameters'->>'snapshotId', \
+                 'account', c.recipient_account_id, 'public', s.public, 'shared_with', s.external), now() \
+             FROM cloudtrail_events c \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             CROSS JOIN LATERAL ( \
+               SELECT bool_or(i->>'group' = 'all') AS public, \
+                 array_agg(i->>'userId') FILTER (WHERE i ? 'userId' AND NOT EXISTS ( \
+                   SELECT 1 FROM cloudtrail_events o WHERE o.recipient_account_id = i->>'userId')) AS external \
+               FROM jsonb_array_elements(COALESCE(c.raw->'requestParameters'->'createVolumePermission'->'add'->'items', '[]'::jsonb)) i \
+             ) s \
+             WHERE c.event_name = 'ModifySnapshotAttribute' AND c.error_code IS NULL AND c.event_time >= $1 \
+               AND (s.public OR s.external IS NOT NULL) \
+             {REFRESH}"
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("rule snapshot_shared")?;
+
+        // Rule: new_user_with_access_key — an IAM user created and handed an
+        // access key within `siem.new_user_key_window_mins`; one alert per key.
+        touched += diesel::sql_query(format!(
+            "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+             SELECT \
+               'new_user_with_access_key:' || k.event_id, 'new_user_with_access_key', 'high', \
+               'Access key minted for a new IAM user', \
+               COALESCE(u.principal_name, '?') || ' created IAM user ' || (u.raw->'requestParameters'->>'userName') || \
+                 ' and ' || COALESCE(k.principal_name, '?') || ' gave it an access key ' || \
+                 round(EXTRACT(epoch FROM k.event_time - u.event_time) / 60) || ' minutes later', \
+               aa.actor_id, 'cloudtrail', u.event_time, k.event_time, 2, 'open', \
+               jsonb_build_object('user', u.raw->'requestParameters'->>'userName', 'account', u.recipient_account_id, \
+                 'created_by', u.principal_name, 'create_event_id', u.event_id, \
+                 'key_created_by', k.principal_name, 'key_event_id', k.event_id, \
+                 'access_key_id', k.raw->'responseElements'->'accessKey'->>'accessKeyId'), now() \
+             FROM cloudtrail_events u \
+             JOIN cloudtrail_events k ON k.event_name = 'CreateAccessKey' AND k.error_code IS NULL \
+               AND k.recipient_account_id IS NOT DISTINCT FROM u.recipient_account_id \
+               AND COALESCE(k.raw->'requestParameters'->>'userName', k.principal_name) = u.raw->'requestParameters'->>'userName' \
+               AND k.event_time >= u.event_time AND k.event_time <= u.event_time + make_interval(mins => $2) \
+             LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(u.principal_name, u.principal_arn) \
+             WHERE u.event_name = 'CreateUser' AND u.error_code IS NULL AND u.event_time >= $1 \
+             {REFRESH}"
+        ))
+        .bind::<Timestamptz, _>(window_floor)
+        .bind::<diesel::sql_types::Integer, _>(siem.new_user_key_window_mins.clamp(1, 7 * 24 * 60) as i32)
+        .execute(conn)
+        .context("rule new_user_with_access_key")?;
+
         // Flag sessions tied to an open/acked high+ alert for the same actor.
         diesel::sql_query(
             "UPDATE sessions s SET status = 'flagged', flag_reason = 'linked to ' || a.rule_id \
@@ -147,6 +504,17 @@ pub fn evaluate(
         .execute(conn)
         .context("flag sessions")?;
 
+        // Flag sessions whose source IP is on a live threat-intel feed.
+        diesel::sql_query(
+            "UPDATE sessions s SET status = 'flagged', flag_reason = 'threat intel: ' || i.feed \
+             FROM threat_indicators i \
+             WHERE i.kind = 'ip' AND i.expires_at > now() AND s.status <> 'flagged' AND s.last_seen_at >= $1 \
+               AND s.source_ip ~ '^[0-9A-Fa-f:.]+$' AND i.network >>= try_inet(s.source_ip)",
+        )
+        .bind::<Timestamptz, _>(window_floor)
+        .execute(conn)
+        .context("flag threat-intel sessions")?;
+
         diesel::sql_query(
             "UPDATE alerts SET status = 'resolved', resolved_by = 'auto', resolved_at = now(), updated_at = now() \
              WHERE status = 'open' AND source <> 'guardduty' AND last_seen < now() - interval '24 hours'",
@@ -216,28 +216,35 @@ fn new_source(conn: &mut PgConnection, h24: DateTime<Utc>) -> anyhow::Result<usi
     .context("detector new_source")
 }
 
+/// Trusted-egress sessions carry no real country, so they neither raise this
+/// nor count as history for it. Hosting/VPN sessions still do, but a country
+/// reached only through hosting networks is flagged and down-ranked to `low`.
 fn new_country(conn: &mut PgConnection, h24: DateTime<Utc>) -> anyhow::Result<usize> {
     diesel::sql_query(
         "INSERT INTO anomalies (fingerprint, kind, actor_id, severity, score, observed, title, detail, evidence, event_time, updated_at) \
-         SELECT 'new_country:' || s.actor_id || ':' || country, 'new_country', s.actor_id, 'medium', \
+         SELECT 'new_country:' || s.actor_id || ':' || country, 'new_country', s.actor_id, \
+           CASE WHEN bool_and(s.network_class = 'hosting') THEN 'low' ELSE 'medium' END, \
            1, count(*)::float8, 'Login from new country', \
            s.actor_id || ' logged in from ' || country || ' for the first time', \
-           jsonb_build_object('country', country), max(s.last_seen_at), now() \
+           jsonb_build_object('country', country, 'hosting', bool_and(s.network_class = 'hosting'), \
+             'asns', COALESCE(jsonb_agg(DISTINCT s.asn) FILTER (WHERE s.asn IS NOT NULL), '[]'::jsonb), \
+             'orgs', COALESCE(jsonb_agg(DISTINCT s.as_org) FILTER (WHERE s.as_org IS NOT NULL), '[]'::jsonb)), \
+           max(s.last_seen_at), now() \
          FROM ( \
-           SELECT actor_id, last_seen_at, \
+           SELECT actor_id, last_seen_at, asn, as_org, network_class, \
              CASE WHEN position(', ' in location) > 0 \
                   THEN substring(location from position(', ' in location) + 2) \
                   ELSE location END AS country \
-           FROM sessions WHERE actor_id IS NOT NULL AND location IS NOT NULL AND last_seen_at >= $1 \
+           FROM sessions WHERE actor_id IS NOT NULL AND location IS NOT NULL AND network_class IS DISTINCT FROM 'trusted' AND last_seen_at >= $1 \
          ) s \
          WHERE NOT EXISTS ( \
-           SELECT 1 FROM sessions s2 WHERE s2.actor_id = s.actor_id AND s2.location IS NOT NULL AND s2.last_seen_at < $1 \
+           SELECT 1 FROM sessions s2 WHERE s2.actor_id = s.actor_id AND s2.location IS NOT NULL AND s2.network_class IS DISTINCT FROM 'trusted' AND s2.last_seen_at < $1 \
              AND (CASE WHEN position(', ' in s2.location) > 0 \
                        THEN substring(s2.location from position(', ' in s2.location) + 2) \
                        ELSE s2.location END) = s.country) \
          GROUP BY s.actor_id, country \
          ON CONFLICT (fingerprint) DO UPDATE SET \
-           observed = EXCLUDED.observed, detail = EXCLUDED.detail, evidence = EXCLUDED.evidence, \
+           observed = EXCLUDED.observed, severity = EXCLUDED.severity, detail = EXCLUDED.detail, evidence = EXCLUDED.evidence, \
            event_time = GREATEST(anomalies.event_time, EXCLUDED.event_time), updated_at = now()",
     )
     .bind::<Timestamptz, _>(h24)
@@ -0,0 +1,277 @@
+use anyhow::Context;
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Text};
+use diesel::PgConnection;
+
+/// An ATT&CK (Enterprise, cloud-relevant subset) tactic.
+pub struct Tactic {
+    pub id: &'static str,
+    pub name: &'static str,
+}
+
+/// An ATT&CK technique or sub-technique and the tactics it serves.
+pub struct Technique {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub tactics: &'static [&'static str],
+}
+
+/// One of our detections and the ATT&CK context it claims. `kind` is `alert`
+/// (a SQL rule's `rule_id`), `anomaly` (an anomaly `kind`) or `guardduty` (a
+/// GuardDuty finding-type prefix, `ThreatPurpose:ResourceType/ThreatFamily`).
+pub struct Detection {
+    pub id: &'static str,
+    pub kind: &'static str,
+    pub tactics: &'static [&'static str],
+    pub techniques: &'static [&'static str],
+}
+
+#[rustfmt::skip]
+pub const TACTICS: &[Tactic] = &[
+    Tactic { id: "TA0043", name: "Reconnaissance" },
+    Tactic { id: "TA0001", name: "Initial Access" },
+    Tactic { id: "TA0002", name: "Execution" },
+    Tactic { id: "TA0003", name: "Persistence" },
+    Tactic { id: "TA0004", name: "Privilege Escalation" },
+    Tactic { id: "TA0005", name: "Defense Evasion" },
+    Tactic { id: "TA0006", name: "Credential Access" },
+    Tactic { id: "TA0007", name: "Discovery" },
+    Tactic { id: "TA0008", name: "Lateral Movement" },
+    Tactic { id: "TA0009", name: "Collection" },
+    Tactic { id: "TA0011", name: "Command and Control" },
+    Tactic { id: "TA0010", name: "Exfiltration" },
+    Tactic { id: "TA0040", name: "Impact" },
+];
+
+#[rustfmt::skip]
+pub const TECHNIQUES: &[Technique] = &[
+    Technique { id: "T1595", name: "Active Scanning", tactics: &["TA0043"] },
+    Technique { id: "T1078.004", name: "Valid Accounts: Cloud Accounts", tactics: &["TA0001", "TA0003", "TA0004", "TA0005"] },
+    Technique { id: "T1190", name: "Exploit Public-Facing Application", tactics: &["TA0001"] },
+    Technique { id: "T1651", name: "Cloud Administration Command", tactics: &["TA0002"] },
+    Technique { id: "T1098.001", name: "Account Manipulation: Additional Cloud Credentials", tactics: &["TA0003", "TA0004"] },
+    Technique { id: "T1098.003", name: "Account Manipulation: Additional Cloud Roles", tactics: &["TA0003", "TA0004"] },
+    Technique { id: "T1136.003", name: "Create Account: Cloud Account", tactics: &["TA0003"] },
+    Technique { id: "T1548.005", name: "Temporary Elevated Cloud Access", tactics: &["TA0004", "TA0005"] },
+    Technique { id: "T1562.001", name: "Impair Defenses: Disable or Modify Tools", tactics: &["TA0005"] },
+    Technique { id: "T1562.008", name: "Impair Defenses: Disable or Modify Cloud Logs", tactics: &["TA0005"] },
+    Technique { id: "T1535", name: "Unused/Unsupported Cloud Regions", tactics: &["TA0005"] },
+    Technique { id: "T1110", name: "Brute Force", tactics: &["TA0006"] },
+    Technique { id: "T1552.001", name: "Unsecured Credentials: Credentials In Files", tactics: &["TA0006"] },
+    Technique { id: "T1552.005", name: "Unsecured Credentials: Cloud Instance Metadata API", tactics: &["TA0006"] },
+    Technique { id: "T1526", name: "Cloud Service Discovery", tactics: &["TA0007"] },
+    Technique { id: "T1580", name: "Cloud Infrastructure Discovery", tactics: &["TA0007"] },
+    Technique { id: "T1550.001", name: "Use Alternate Authentication Material: Application Access Token", tactics: &["TA0005", "TA0008"] },
+    Technique { id: "T1530", name: "Data from Cloud Storage", tactics: &["TA0009"] },
+    Technique { id: "T1071", name: "Application Layer Protocol", tactics: &["TA0011"] },
+    Technique { id: "T1537", name: "Transfer Data to Cloud Account", tactics: &["TA0010"] },
+    Technique { id: "T1485", name: "Data Destruction", tactics: &["TA0040"] },
+    Technique { id: "T1496", name: "Resource Hijacking", tactics: &["TA0040"] },
+];
+
+#[rustfmt::skip]
+pub const DETECTIONS: &[Detection] = &[
+    // SQL rules (`alerts::evaluate`, `travel::detect`).
+    Detection { id: "console_login_bruteforce", kind: "alert", tactics: &["TA0006"], techniques: &["T1110"] },
+    Detection { id: "off_hours_key_creation", kind: "alert", tactics: &["TA0003"], techniques: &["T1098.001"] },
+    Detection { id: "priv_role_self_assign", kind: "alert", tactics: &["TA0004", "TA0003"], techniques: &["T1098.003"] },
+    Detection { id: "dormant_principal_active", kind: "alert", tactics: &["TA0001", "TA0003"], techniques: &["T1078.004"] },
+    Detection { id: "github_secret_scanning", kind: "alert", tactics: &["TA0006"], techniques: &["T1552.001"] },
+    Detection { id: "impossible_travel", kind: "alert", tactics: &["TA0001"], techniques: &["T1078.004"] },
+    Detection { id: "threat_intel_match", kind: "alert", tactics: &["TA0001", "TA0011"], techniques: &["T1078.004", "T1071"] },
+    Detection { id: "risk_jump", kind: "alert", tactics: &["TA0001"], techniques: &["T1078.004"] },
+    Detection { id: "activity_after_leave", kind: "alert", tactics: &["TA0001", "TA0003"], techniques: &["T1078.004"] },
+    Detection { id: "new_privileged_role_path", kind: "alert", tactics: &["TA0004", "TA0008"], techniques: &["T1078.004", "T1550.001"] },
+    Detection { id: "privileged_login_without_mfa", kind: "alert", tactics: &["TA0001", "TA0006"], techniques: &["T1078.004", "T1110"] },
+    Detection { id: "cloudtrail_tampering", kind: "alert", tactics: &["TA0005"], techniques: &["T1562.008"] },
+    Detection { id: "guardduty_tampering", kind: "alert", tactics: &["TA0005"], techniques: &["T1562.001"] },
+    Detection { id: "s3_bucket_made_public", kind: "alert", tactics: &["TA0009", "TA0010"], techniques: &["T1530", "T1537"] },
+    Detection { id: "login_profile_for_other_user", kind: "alert", tactics: &["TA0003", "TA0004"], techniques: &["T1098.001"] },
+    Detection { id: "root_account_usage", kind: "alert", tactics: &["TA0001", "TA0004"], techniques: &["T1078.004"] },
+    Detection { id: "snapshot_shared", kind: "alert", tactics: &["TA0010"], techniques: &["T1537"] },
+    Detection { id: "new_user_with_access_key", kind: "alert", tactics: &["TA0003"], techniques: &["T1136.003", "T1098.001"] },
+    // Anomaly detectors (`anomalies::detect`).
+    Detection { id: "volume_spike", kind: "anomaly", tactics: &["TA0007", "TA0009"], techniques: &["T1526", "T1530"] },
+    Detection { id: "new_source", kind: "anomaly", tactics: &["TA0001"], techniques: &["T1078.004"] },
+    Detection { id: "new_country", kind: "anomaly", tactics: &["TA0001"], techniques: &["T1078.004"] },
+    Detection { id: "off_hours_spike", kind: "anomaly", tactics: &["TA0001"], techniques: &["T1078.004"] },
+    // GuardDuty finding types, most specific first (`for_guardduty` takes the
+    // first prefix match, then falls back to the threat purpose alone).
+    Detection { id: "UnauthorizedAccess:IAMUser/InstanceCredentialExfiltration", kind: "guardduty", tactics: &["TA0006"], techniques: &["T1552.005"] },
+    Detection { id: "UnauthorizedAccess:IAMUser/ConsoleLoginSuccess", kind: "guardduty", tactics: &["TA0001"], techniques: &["T1078.004"] },
+    Detection { id: "Stealth:IAMUser/CloudTrailLoggingDisabled", kind: "guardduty", tactics: &["TA0005"], techniques: &["T1562.008"] },
+    Detection { id: "Stealth:S3/ServerAccessLoggingDisabled", kind: "guardduty", tactics: &["TA0005"], techniques: &["T1562.008"] },
+    Detection { id: "Policy:IAMUser/RootCredentialUsage", kind: "guardduty", tactics: &["TA0001", "TA0004"], techniques: &["T1078.004"] },
+    Detection { id: "Persistence:IAMUser/AnomalousBehavior", kind: "guardduty", tactics: &["TA0003"], techniques: &["T1098.001", "T1136.003"] },
+    Detection { id: "PrivilegeEscalation:IAMUser/AnomalousBehavior", kind: "guardduty", tactics: &["TA0004"], techniques: &["T1098.003"] },
+    Detection { id: "DefenseEvasion:IAMUser/AnomalousBehavior", kind: "guardduty", tactics: &["TA0005"], techniques: &["T1562.001"] },
+    Detection { id: "Exfiltration:S3", kind: "guardduty", tactics: &["TA0010", "TA0009"], techniques: &["T1530", "T1537"] },
+    Detection { id: "Impact:S3", kind: "guardduty", tactics: &["TA0040"], techniques: &["T1485"] },
+    Detection { id: "Backdoor", kind: "guardduty", tactics: &["TA0011"], techniques: &["T1071"] },
+    Detection { id: "CredentialAccess", kind: "guardduty", tactics: &["TA0006"], techniques: &["T1110"] },
+    Detection { id: "CryptoCurrency", kind: "guardduty", tactics: &["TA0040"], techniques: &["T1496"] },
+    Detection { id: "DefenseEvasion", kind: "guardduty", tactics: &["TA0005"], techniques: &["T1562.001"] },
+    Detection { id: "Discovery", kind: "guardduty", tactics: &["TA0007"], techniques: &["T1580", "T1526"] },
+    Detection { id: "Execution", kind: "guardduty", tactics: &["TA0002"], techniques: &["T1651"] },
+    Detection { id: "Exfiltration", kind: "guardduty", tactics: &["TA0010"], techniques: &["T1537"] },
+    Detection { id: "Impact", kind: "guardduty", tactics: &["TA0040"], techniques: &["T1496"] },
+    Detection { id: "InitialAccess", kind: "guardduty", tactics: &["TA0001"], techniques: &["T1190"] },
+    Detection { id: "PenTest", kind: "guardduty", tactics: &["TA0007"], techniques: &["T1580"] },
+    Detection { id: "Persistence", kind: "guardduty", tactics: &["TA0003"], techniques: &["T1098.001"] },
+    Detection { id: "Policy", kind: "guardduty", tactics: &["TA0005"], techniques: &["T1078.004"] },
+    Detection { id: "PrivilegeEscalation", kind: "guardduty", tactics: &["TA0004"], techniques: &["T1548.005"] },
+    Detection { id: "Recon", kind: "guardduty", tactics: &["TA0043", "TA0007"], techniques: &["T1595", "T1580"] },
+    Detection { id: "Stealth", kind: "guardduty", tactics: &["TA0005"], techniques: &["T1562.008"] },
+    Detection { id: "Trojan", kind: "guardduty", tactics: &["TA0011"], techniques: &["T1071"] },
+    Detection { id: "UnauthorizedAccess", kind: "guardduty", tactics: &["TA0001", "TA0006"], techniques: &["T1078.004"] },
+];
+
+pub fn tactic(id: &str) -> Option<&'static Tactic> {
+    TACTICS.iter().find(|t| t.id == id)
+}
+
+pub fn technique(id: &str) -> Option<&'static Technique> {
+    TECHNIQUES.iter().find(|t| t.id == id)
+}
+
+/// The mapping for a SQL rule or anomaly kind, by its exact id.
+pub fn lookup(kind: &str, id: &str) -> Option<&'static Detection> {
+    DETECTIONS.iter().find(|d| d.kind == kind && d.id == id)
+}
+
+/// Tactic + technique ids for a GuardDuty finding. The finding type picks a
+/// catalogue entry (longest prefix first, then the bare threat purpose); any
+/// ATT&CK indicators GuardDuty attached itself (attack-sequence findings carry
+/// `ATTACK_TACTIC` / `ATTACK_TECHNIQUE`) are unioned on top. Tactic indicators
+/// arrive as names, technique indicators as names with the id embedded, so both
+/// are resolved back to ids against the catalogue; unknown values are kept as-is.
+pub fn for_guardduty(
+    finding_type: &str,
+    tactic_indicators: &[String],
+    technique_indicators: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let purpose = finding_type.split(':').next().unwrap_or(finding_type);
+    let base = DETECTIONS
+        .iter()
+        .filter(|d| d.kind == "guardduty" && d.id.contains(':'))
+        .find(|d| finding_type.starts_with(d.id))
+        .or_else(|| {
+            DETECTIONS
+                .iter()
+                .find(|d| d.kind == "guardduty" && d.id == purpose)
+        });
+
+    let mut tactics: Vec<String> = base
+        .map(|d| d.tactics.iter().map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+    let mut techniques: Vec<String> = base
+        .map(|d| d.techniques.iter().map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+
+    for v in tactic_indicators {
+        let id = TACTICS
+            .iter()
+            .find(|t| t.id == v || t.name.eq_ignore_ascii_case(v))
+            .map(|t| t.id.to_string())
+            .unwrap_or_else(|| v.clone());
+        if !tactics.contains(&id) {
+            tactics.push(id);
+        }
+    }
+    for v in technique_indicators {
+        let id = technique_id_in(v).unwrap_or_else(|| v.clone());
+        if !techniques.contains(&id) {
+            techniques.push(id);
+        }
+    }
+    (tactics, techniques)
+}
+
+/// Pull a `T1234` / `T1234.001` id out of free text.
+fn technique_id_in(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    for (i, _) in s.match_indices('T') {
+        let digits = &bytes[i + 1..];
+        if digits.len() >= 4 && digits[..4].iter().all(u8::is_ascii_digit) {
+            let mut end = i + 5;
+            if bytes.len() >= end + 4
+                && bytes[end] == b'.'
+                && bytes[end + 1..end + 4].iter().all(u8::is_ascii_digit)
+            {
+                end += 4;
+            }
+            return Some(s[i..end].to_string());
+        }
+    }
+    None
+}
+
+/// Stamp every derived alert with its rule's ATT&CK tags. Runs after the rule
+/// stages each pass, so a re-mapped rule retags its history too; rows already
+/// carrying the current tags are left untouched. GuardDuty findings are tagged at
+/// ingest (`guardduty::sweep_detector`) and never match here.
+pub fn tag_alerts(conn: &mut PgConnection) -> anyhow::Result<usize> {
+    let mut touched = 0usize;
+    for d in DETECTIONS.iter().filter(|d| d.kind == "alert") {
+        touched += diesel::sql_query(
+            "UPDATE alerts SET attack_tactics = $2, attack_techniques = $3 \
+             WHERE rule_id = $1 \
+               AND (attack_tactics IS DISTINCT FROM $2 OR attack_techniques IS DISTINCT FROM $3)",
+        )
+        .bind::<Text, _>(d.id)
+        .bind::<Array<Text>, _>(d.tactics)
+        .bind::<Array<Text>, _>(d.techniques)
+        .execute(conn)
+        .with_context(|| format!("tag {} alerts", d.id))?;
+    }
+    Ok(touched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogue_references_are_defined() {
+        for t in TECHNIQUES {
+            for ta in t.tactics {
+                assert!(tactic(ta).is_some(), "{} names unknown tactic {}", t.id, ta);
+            }
+        }
+        for d in DETECTIONS {
+            for ta in d.tactics {
+                assert!(tactic(ta).is_some(), "{} names unknown tactic {}", d.id, ta);
+            }
+            for te in d.techniques {
+                assert!(
+                    technique(te).is_some(),
+                    "{} names unknown technique {}",
+                    d.id,
+                    te
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn guardduty_prefers_specific_type_then_purpose() {
+        let (ta, te) = for_guardduty("Stealth:IAMUser/CloudTrailLoggingDisabled", &[], &[]);
+        assert_eq!(ta, vec!["TA0005"]);
+        assert_eq!(te, vec!["T1562.008"]);
+
+        let (ta, te) = for_guardduty("Recon:EC2/PortProbeUnprotectedPort", &[], &[]);
+        assert_eq!(ta, vec!["TA0043", "TA0007"]);
+        assert_eq!(te, vec!["T1595", "T1580"]);
+    }
+
+    #[test]
+    fn guardduty_indicators_union_onto_type_mapping() {
+        let (ta, te) = for_guardduty(
+            "AttackSequence:IAM/CompromisedCredentials",
+            &["Defense Evasion".to_string(), "Discovery".to_string()],
+            &["Impair Defenses: Disable or Modify Cloud Logs (T1562.008)".to_string()],
+        );
+        assert_eq!(ta, vec!["TA0005", "TA0007"]);
+        assert_eq!(te, vec!["T1562.008"]);
+    }
+}
@@ -1,12 +1,145 @@
+use std::collections::HashSet;
 use std::net::IpAddr;
 use std::sync::Arc;
 
 use log::{info, warn};
 use maxminddb::{geoip2, Reader};
 
+use crate::misc::config::GeoipConfig;
+
+/// Cloud, hosting and commercial-VPN networks. A login from one of these says
+/// where a proxy, runner or exit node sits, not where the person is. Extended
+/// (not replaced) by `SSU__GEOIP__HOSTING_ASNS`.
+#[rustfmt::skip]
+const HOSTING_ASNS: &[u32] = &[
+    16509, 14618, 8987,   // Amazon / AWS
+    15169, 396982, 19527, // Google / GCP
+    8075, 8068,           // Microsoft / Azure
+    13335, 209242,        // Cloudflare (incl. WARP)
+    14061,                // DigitalOcean
+    16276,                // OVH
+    24940,                // Hetzner
+    63949,                // Akamai / Linode
+    20473,                // Vultr
+    31898,                // Oracle Cloud
+    9009,                 // M247 (VPN exit hosting)
+    60068, 212238,        // Datacamp / CDN77 (VPN exit hosting)
+];
+
 #[derive(Clone, Default)]
 pub struct GeoIp {
     reader: Option<Arc<Reader<Vec<u8>>>>,
+    asn_reader: Option<Arc<Reader<Vec<u8>>>>,
+    egress: Arc<EgressPolicy>,
+}
+
+/// Trusted egress (corporate VPN, NAT gateways, CI runners we own) and hosting
+/// ASNs, parsed once from config.
+#[derive(Default)]
+struct EgressPolicy {
+    trusted_cidrs: Vec<Cidr>,
+    trusted_asns: HashSet<u32>,
+    hosting_asns: HashSet<u32>,
+}
+
+/// How a source network should be read. `Trusted` and `Hosting` addresses say
+/// nothing about where a person is, so geo-based detections skip them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkClass {
+    Trusted,
+    Hosting,
+    Public,
+}
+
+impl NetworkClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NetworkClass::Trusted => "trusted",
+            NetworkClass::Hosting => "hosting",
+            NetworkClass::Public => "public",
+        }
+    }
+}
+
+/// ASN/org behind an IP (when the ASN DB is loaded) and its egress class.
+#[derive(Clone, Debug)]
+pub struct NetworkInfo {
+    pub asn: Option<u32>,
+    pub org: Option<String>,
+    pub class: NetworkClass,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cidr {
+    net: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let (addr, prefix) = match raw.split_once('/') {
+            Some((a, p)) => (a.parse::<IpAddr>().ok()?, p.parse::<u8>().ok()?),
+            None => {
+                let a = raw.parse::<IpAddr>().ok()?;
+                (a, if a.is_ipv4() { 32 } else { 128 })
+            }
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        (prefix <= max).then_some(Self { net: addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.net, ip) {
+            (IpAddr::V4(n), IpAddr::V4(a)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(n) & mask == u32::from(a) & mask
+            }
+            (IpAddr::V6(n), IpAddr::V6(a)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(n) & mask == u128::from(a) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Comma-separated ASNs, with or without the `AS` prefix.
+fn parse_asns(raw: &str) -> HashSet<u32> {
+    raw.split(',')
+        .filter_map(|s| {
+            let s = s.trim();
+            let s = s
+                .strip_prefix("AS")
+                .or_else(|| s.strip_prefix("as"))
+                .unwrap_or(s);
+            s.parse().ok()
+        })
+        .collect()
+}
+
+impl EgressPolicy {
+    fn from_config(conf: &GeoipConfig) -> Self {
+        let trusted_cidrs: Vec<Cidr> = conf
+            .trusted_cidrs
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .filter_map(|s| {
+                let c = Cidr::parse(s);
+                if c.is_none() {
+                    warn!("geoip: ignoring invalid trusted CIDR {:?}", s.trim());
+                }
+                c
+            })
+            .collect();
+        let mut hosting_asns: HashSet<u32> = HOSTING_ASNS.iter().copied().collect();
+        hosting_asns.extend(parse_asns(&conf.hosting_asns));
+        Self {
+            trusted_cidrs,
+            trusted_asns: parse_asns(&conf.trusted_asns),
+            hosting_asns,
+        }
+    }
 }
 
 /// A resolved geo point: coordinates (for distance maths) plus the human label.
@@ -18,27 +151,14 @@ pub struct GeoPoint {
 }
 
 impl GeoIp {
-    /// Load the GeoLite2 City DB from `db_path`. An empty path or any read error
-    /// yields a disabled (no-op) instance — geo enrichment degrades gracefully.
-    pub fn load(db_path: &str) -> Self {
-        if db_path.is_empty() {
-            info!("geoip: no db_path configured — session location disabled");
-            return Self::default();
-        }
-        match Reader::open_readfile(db_path) {
-            Ok(r) => {
-                info!("geoip: loaded GeoLite2 db from {}", db_path);
-                Self {
-                    reader: Some(Arc::new(r)),
-                }
-            }
-            Err(e) => {
-                warn!(
-                    "geoip: failed to open {}: {} — session location disabled",
-                    db_path, e
-                );
-                Self::default()
-            }
+    /// Load the GeoLite2 City DB from `db_path` and, if set, the GeoLite2 ASN DB
+    /// from `asn_db_path`. An empty path or any read error leaves that DB disabled
+    /// — geo enrichment degrades gracefully. Trusted CIDRs apply without any DB.
+    pub fn load(conf: &GeoipConfig) -> Self {
+        Self {
+            reader: open_db(&conf.db_path, "City", "session location"),
+            asn_reader: open_db(&conf.asn_db_path, "ASN", "ASN enrichment"),
+            egress: Arc::new(EgressPolicy::from_config(conf)),
         }
     }
 
@@ -46,6 +166,39 @@ impl GeoIp {
         self.reader.is_some()
     }
 
+    pub fn asn_enabled(&self) -> bool {
+        self.asn_reader.is_some()
+    }
+
+    /// ASN/org and egress class for an IP. Trusted CIDRs are checked first, then
+    /// trusted and hosting ASNs. `None` only for an unparseable IP.
+    pub fn network(&self, ip: &str) -> Option<NetworkInfo> {
+        let addr: IpAddr = ip.parse().ok()?;
+        let (asn, org) = self
+            .asn_reader
+            .as_ref()
+            .and_then(|r| r.lookup::<geoip2::Asn>(addr).ok())
+            .map(|a| {
+                (
+                    a.autonomous_system_number,
+                    a.autonomous_system_organization.map(str::to_string),
+                )
+            })
+            .unwrap_or((None, None));
+
+        let policy = &self.egress;
+        let class = if policy.trusted_cidrs.iter().any(|c| c.contains(addr))
+            || asn.is_some_and(|n| policy.trusted_asns.contains(&n))
+        {
+            NetworkClass::Trusted
+        } else if asn.is_some_and(|n| policy.hosting_asns.contains(&n)) {
+            NetworkClass::Hosting
+        } else {
+            NetworkClass::Public
+        };
+        Some(NetworkInfo { asn, org, class })
+    }
+
     /// Resolve an IP string to `"City, Country"` (or whichever parts are known).
     /// Returns `None` for an unparseable/private IP or when geo is disabled.
     pub fn lookup(&self, ip: &str) -> Option<String> {
@@ -90,6 +243,29 @@ impl GeoIp {
     }
 }
 
+fn open_db(path: &str, kind: &str, feature: &str) -> Option<Arc<Reader<Vec<u8>>>> {
+    if path.is_empty() {
+        info!(
+            "geoip: no GeoLite2 {} db configured — {} disabled",
+            kind, feature
+        );
+        return None;
+    }
+    match Reader::open_readfile(path) {
+        Ok(r) => {
+            info!("geoip: loaded GeoLite2 {} db from {}", kind, path);
+            Some(Arc::new(r))
+        }
+        Err(e) => {
+            warn!(
+                "geoip: failed to open {}: {} — {} disabled",
+                path, e, feature
+            );
+            None
+        }
+    }
+}
+
 /// Great-circle distance between two points in kilometres (haversine).
 pub fn haversine_km(a: &GeoPoint, b: &GeoPoint) -> f64 {
     const R: f64 = 6371.0;
@@ -99,3 +275,41 @@ pub fn haversine_km(a: &GeoPoint, b: &GeoPoint) -> f64 {
     let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
     2.0 * R * h.sqrt().asin()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_policy(trusted_cidrs: &str, trusted_asns: &str) -> GeoIp {
+        GeoIp::load(&GeoipConfig {
+            trusted_cidrs: trusted_cidrs.to_string(),
+            trusted_asns: trusted_asns.to_string(),
+            ..GeoipConfig::default()
+        })
+    }
+
+    #[test]
+    fn trusted_cidrs_classify_without_any_db() {
+        let g = with_policy("192.0.2.0/24, 2001:db8::/32, not-a-cidr", "");
+        let class = |ip: &str| g.network(ip).map(|n| n.class);
+        assert_eq!(class("192.0.2.77"), Some(NetworkClass::Trusted));
+        assert_eq!(class("2001:db8:1::5"), Some(NetworkClass::Trusted));
+        assert_eq!(class("198.51.100.1"), Some(NetworkClass::Public));
+        assert_eq!(class("cloudformation.amazonaws.com"), None);
+    }
+
+    #[test]
+    fn cidr_edges() {
+        let c = Cidr::parse("10.0.0.0/8").unwrap();
+        assert!(c.contains("10.255.255.255".parse().unwrap()));
+        assert!(!c.contains("11.0.0.0".parse().unwrap()));
+        assert!(Cidr::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("203.0.113.1".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/40").is_none());
+        assert_eq!(
+            parse_asns("AS16509, 64512 ,x"),
+            [16509, 64512].into_iter().collect()
+        );
+    }
+}
@@ -6,7 +6,7 @@ use aws_sdk_guardduty::types::{Condition, FindingCriteria, OrderBy, SortCriteria
 use aws_sdk_guardduty::Client;
 use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
-use diesel::sql_types::{BigInt, Nullable, Text, Timestamptz};
+use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
 use log::{error, info};
 use tokio_util::sync::CancellationToken;
 use tracing::Instrument;
@@ -16,6 +16,7 @@ use crate::misc::config::GuarddutyConfig;
 use crate::service::ingest::{
     advance_watermark, get_watermark, record_run_error, SOURCE_GUARDDUTY,
 };
+use crate::service::siem::attack;
 
 /// Entry point: initial sweep then poll on the configured interval.
 pub async fn run(cancel: CancellationToken, conf: GuarddutyConfig, pool: DbPool) {
@@ -249,6 +250,9 @@ async fn sweep_detector(
                 let archived = f.service().and_then(|s| s.archived()).unwrap_or(false);
                 let status = if archived { "resolved" } else { "open" };
                 let (first_seen, last_seen) = finding_window(f);
+                let (tactic_ind, technique_ind) = attack_indicators(f);
+                let (attack_tactics, attack_techniques) =
+                    attack::for_guardduty(f.r#type().unwrap_or(""), &tactic_ind, &technique_ind);
                 let updated_at = f
                     .updated_at()
                     .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
@@ -269,7 +273,13 @@ async fn sweep_detector(
                         "region": region,
                         "account_id": f.account_id(),
                         "archived": archived,
+                        "attack_indicators": {
+                            "tactics": tactic_ind,
+                            "techniques": technique_ind,
+                        },
                     }),
+                    attack_tactics,
+                    attack_techniques,
                 });
             }
         }
@@ -292,13 +302,14 @@ async fn sweep_detector(
         conn.transaction::<_, anyhow::Error, _>(|conn| {
             for r in &rows {
                 diesel::sql_query(
-                    "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, resolved_by, resolved_at, updated_at) \
+                    "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, resolved_by, resolved_at, attack_tactics, attack_techniques, updated_at) \
                      VALUES ($1, 'guardduty', $2, $3, $4, NULL, 'guardduty', $5, $6, $7, $8, $9, \
                              CASE WHEN $8 = 'resolved' THEN 'guardduty' ELSE NULL END, \
-                             CASE WHEN $8 = 'resolved' THEN now() ELSE NULL END, now()) \
+                             CASE WHEN $8 = 'resolved' THEN now() ELSE NULL END, $10, $11, now()) \
                      ON CONFLICT (fingerprint) DO UPDATE SET \
                        last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), event_count = EXCLUDED.event_count, \
                        severity = EXCLUDED.severity, description = EXCLUDED.description, evidence = EXCLUDED.evidence, \
+                       attack_tactics = EXCLUDED.attack_tactics, attack_techniques = EXCLUDED.attack_techniques, \
                        status = CASE \
                                   WHEN $8 = 'resolved' THEN 'resolved' \
                                   WHEN alerts.status = 'resolved' AND alerts.resolved_by IN ('auto','guardduty') THEN 'open' \
@@ -322,6 +333,8 @@ async fn sweep_detector(
                 .bind::<BigInt, _>(r.event_count)
                 .bind::<Text, _>(r.status)
                 .bind::<diesel::sql_types::Jsonb, _>(&r.evidence)
+                .bind::<Array<Text>, _>(&r.attack_tactics)
+                .bind::<Array<Text>, _>(&r.attack_techniques)
                 .execute(conn)
                 .context("upsert guardduty alert")?;
             }
@@ -351,6 +364,8 @@ struct FindingRow {
     event_count: i64,
     status: &'static str,
     evidence: serde_json::Value,
+    attack_tactics: Vec<String>,
+    attack_techniques: Vec<String>,
 }
 
 /// GuardDuty severity (0–10) → our 3-tier label.
@@ -380,3 +395,25 @@ fn finding_window(f: &aws_sdk_guardduty::types::Finding) -> (DateTime<Utc>, Date
         .unwrap_or_else(Utc::now);
     (first, last)
 }
+
+/// ATT&CK tactic / technique indicator values GuardDuty attached to the finding.
+/// Only attack-sequence findings (Extended Threat Detection) carry these; every
+/// other type yields two empty lists and is mapped from its type alone.
+fn attack_indicators(f: &aws_sdk_guardduty::types::Finding) -> (Vec<String>, Vec<String>) {
+    let mut tactics = Vec::new();
+    let mut techniques = Vec::new();
+    let indicators = f
+        .service()
+        .and_then(|s| s.detection())
+        .and_then(|d| d.sequence())
+        .map(|seq| seq.indicators())
+        .unwrap_or_default();
+    for ind in indicators {
+        match ind.key().as_str() {
+            "ATTACK_TACTIC" => tactics.extend(ind.values().iter().cloned()),
+            "ATTACK_TECHNIQUE" => techniques.extend(ind.values().iter().cloned()),
+            _ => {}
+        }
+    }
+    (tactics, techniques)
+}
@@ -1,14 +1,25 @@
 pub mod actors;
 pub mod alerts;
 pub mod anomalies;
+pub mod attack;
 pub mod geoip;
 pub mod grants;
 pub mod guardduty;
+pub mod overrides;
+pub mod ownership;
+pub mod posture;
 pub mod risk;
+pub mod role_graph;
+pub mod roster;
 pub mod sessions;
+pub mod threat_intel;
 pub mod travel;
 
+#[cfg(test)]
+mod rule_harness;
+
 use anyhow::Context;
+use diesel::PgConnection;
 use log::{error, info};
 use tokio_util::sync::CancellationToken;
 
@@ -20,13 +31,14 @@ use crate::service::siem::geoip::GeoIp;
 /// Entry point: an initial pass, then recompute on the configured interval until
 /// cancelled. Spawned onto the shared `async_worker` runtime.
 pub async fn run(cancel: CancellationToken, conf: Config, pool: DbPool) {
-    let geoip = GeoIp::load(&conf.geoip.db_path);
+    let geoip = GeoIp::load(&conf.geoip);
     let interval = std::time::Duration::from_secs(conf.siem.interval_secs.max(60));
     info!(
-        "siem derivation starting :: interval={}s window_days={} geoip={} roster={}",
+        "siem derivation starting :: interval={}s window_days={} geoip={} asn={} roster={}",
         interval.as_secs(),
         conf.siem.window_days,
         geoip.enabled(),
+        geoip.asn_enabled(),
         !conf.selfservice.base_url.is_empty(),
     );
 
@@ -67,56 +79,108 @@ async fn run_pass(
     tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
         let _pass = pass_span.enter();
         let mut conn = pool.get().context("pool get")?;
+        derive_all(&mut conn, &conf, &roster, &geoip, &cancel)
+    })
+    .await
+    .context("join")?
+}
 
-        macro_rules! bail_if_cancelled {
-            () => {
-                if cancel.is_cancelled() {
-                    info!("siem pass interrupted by shutdown — abandoning remaining stages");
-                    return Ok(());
-                }
-            };
-        }
+/// Every derivation stage of one pass, in dependency order, on a single
+/// connection. Split out of `run_pass` so the rule harness drives exactly the
+/// production stage sequence against its scratch schema.
+pub(crate) fn derive_all(
+    conn: &mut PgConnection,
+    conf: &Config,
+    roster: &[actors::RosterMember],
+    geoip: &GeoIp,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    macro_rules! bail_if_cancelled {
+        () => {
+            if cancel.is_cancelled() {
+                info!("siem pass interrupted by shutdown — abandoning remaining stages");
+                return Ok(());
+            }
+        };
+    }
 
-        let n_actors = tracing::info_span!("siem.actors")
-            .in_scope(|| actors::reconcile(&mut conn, &roster, conf.siem.window_days))
-            .context("reconcile actors")?;
-        bail_if_cancelled!();
-        let n_grants = tracing::info_span!("siem.grants")
-            .in_scope(|| grants::derive(&mut conn, conf.siem.window_days))
-            .context("derive grants")?;
-        bail_if_cancelled!();
-        let n_sessions = tracing::info_span!("siem.sessions")
-            .in_scope(|| sessions::derive(&mut conn, &geoip, conf.siem.window_days))
-            .context("derive sessions")?;
-        bail_if_cancelled!();
-        // Anomalies feed the risk `w_anomalies` factor, so detect before scoring.
-        let n_anomalies = tracing::info_span!("siem.anomalies")
-            .in_scope(|| anomalies::detect(&mut conn, &conf.siem))
-            .context("detect anomalies")?;
-        bail_if_cancelled!();
-        let n_risk = tracing::info_span!("siem.risk")
-            .in_scope(|| risk::compute(&mut conn, &conf.risk, &conf.siem))
-            .context("compute risk")?;
-        bail_if_cancelled!();
-        let n_alerts = tracing::info_span!("siem.alerts")
-            .in_scope(|| alerts::evaluate(&mut conn, &conf.siem))
-            .context("evaluate alerts")?;
-        bail_if_cancelled!();
-        // Impossible-travel is a geo-correlated alert; no-op without GeoLite2.
-        let n_travel = tracing::info_span!("siem.travel")
-            .in_scope(|| travel::detect(&mut conn, &geoip, &conf.siem))
-            .context("detect impossible travel")?;
+    let n_actors = tracing::info_span!("siem.actors")
+        .in_scope(|| actors::reconcile(conn, roster, conf.siem.window_days))
+        .context("reconcile actors")?;
+    // Leaves feed the `activity_after_leave` rule, so diff before alerting.
+    let n_roster = tracing::info_span!("siem.roster")
+        .in_scope(|| roster::snapshot(conn, roster, chrono::Utc::now(), &conf.siem))
+        .context("diff roster snapshot")?;
+    bail_if_cancelled!();
+    let n_grants = tracing::info_span!("siem.grants")
+        .in_scope(|| grants::derive(conn, conf.siem.window_days))
+        .context("derive grants")?;
+    bail_if_cancelled!();
+    // Edges feed the `new_privileged_role_path` rule, so derive before alerting.
+    let n_role_edges = tracing::info_span!("siem.role_graph")
+        .in_scope(|| role_graph::derive(conn, &conf.siem))
+        .context("derive role graph")?;
+    bail_if_cancelled!();
+    let n_sessions = tracing::info_span!("siem.sessions")
+        .in_scope(|| sessions::derive(conn, geoip, &conf.siem))
+        .context("derive sessions")?;
+    bail_if_cancelled!();
+    // Hits feed the `threat_intel_match` rule, so match before alerting.
+    let n_ti_hits = tracing::info_span!("siem.threat_intel")
+        .in_scope(|| threat_intel::match_events(conn, conf.siem.window_days))
+        .context("match threat intel")?;
+    bail_if_cancelled!();
+    // Anomalies feed the risk `w_anomalies` factor, so detect before scoring.
+    let n_anomalies = tracing::info_span!("siem.anomalies")
+        .in_scope(|| anomalies::detect(conn, &conf.siem))
+        .context("detect anomalies")?;
+    bail_if_cancelled!();
+    let n_risk = tracing::info_span!("siem.risk")
+        .in_scope(|| risk::compute(conn, &conf.risk, &conf.siem))
+        .context("compute risk")?;
+    let n_history = tracing::info_span!("siem.risk_history")
+        .in_scope(|| risk::compact_history(conn, &conf.risk))
+        .context("compact risk history")?;
+    bail_if_cancelled!();
+    let n_alerts = tracing::info_span!("siem.alerts")
+        .in_scope(|| alerts::evaluate(conn, &conf.siem))
+        .context("evaluate alerts")?;
+    bail_if_cancelled!();
+    // Impossible-travel is a geo-correlated alert; no-op without GeoLite2.
+    let n_travel = tracing::info_span!("siem.travel")
+        .in_scope(|| travel::detect(conn, geoip, &conf.siem))
+        .context("detect impossible travel")?;
+    bail_if_cancelled!();
+    // ATT&CK tags key off `rule_id`, so stamp once every rule stage has written.
+    let n_tagged = tracing::info_span!("siem.attack")
+        .in_scope(|| attack::tag_alerts(conn))
+        .context("tag alerts with att&ck")?;
+    bail_if_cancelled!();
+    // Owners route alerts, so stamp after every rule stage has written too.
+    let n_owned = tracing::info_span!("siem.ownership")
+        .in_scope(|| ownership::derive(conn, conf.siem.window_days))
+        .context("derive actor owners")?;
+    bail_if_cancelled!();
+    // Team rollups read risk, alerts and owners, so aggregate last.
+    let n_teams = tracing::info_span!("siem.posture")
+        .in_scope(|| posture::derive(conn, &conf.siem))
+        .context("derive team posture")?;
 
-        info!(
-            "siem pass complete :: actors={} grants={} sessions={} anomalies={} risk_scored={} alerts={} travel={}",
-            n_actors, n_grants, n_sessions, n_anomalies, n_risk, n_alerts, n_travel
-        );
+    info!(
+        "siem pass complete :: actors={} roster_changes={} grants={} role_edges={} sessions={} ti_hits={} anomalies={} risk_scored={} risk_history_folded={} alerts={} travel={} attack_tagged={} owned={} teams={}",
+        n_actors, n_roster, n_grants, n_role_edges, n_sessions, n_ti_hits, n_anomalies, n_risk, n_history, n_alerts, n_travel, n_tagged, n_owned, n_teams
+    );
 
-        // Health/heartbeat row (also clears any prior error).
-        advance_watermark(&mut conn, SOURCE_SIEM, None, None, None, n_actors as i64, n_alerts as i64)
-            .context("advance siem watermark")?;
-        Ok(())
-    })
-    .await
-    .context("join")?
+    // Health/heartbeat row (also clears any prior error).
+    advance_watermark(
+        conn,
+        SOURCE_SIEM,
+        None,
+        None,
+        None,
+        n_actors as i64,
+        n_alerts as i64,
+    )
+    .context("advance siem watermark")?;
+    Ok(())
 }
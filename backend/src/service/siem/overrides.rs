@@ -0,0 +1,386 @@
+//! Analyst overrides on top of identity reconciliation (`actor_overrides`).
+//! `actors::reconcile` loads them every pass: merges and splits steer how a raw
+//! alias is classified, kind overrides pin an actor's kind after the upsert.
+//! Each override records who made it and why; the API layer manages them.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideKind {
+    /// `alias` belongs to `actor_id`.
+    Merge,
+    /// `alias` stands as its own actor instead of folding into whoever the
+    /// heuristics picked.
+    Split,
+    /// `actor_id` is always `actor_kind`.
+    Kind,
+}
+
+impl OverrideKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverrideKind::Merge => "merge",
+            OverrideKind::Split => "split",
+            OverrideKind::Kind => "kind",
+        }
+    }
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct ActorOverride {
+    #[diesel(sql_type = BigInt)]
+    pub id: i64,
+    #[diesel(sql_type = Text)]
+    pub kind: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub alias: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub actor_id: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub actor_kind: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub reason: String,
+    #[diesel(sql_type = Text)]
+    pub created_by: String,
+    #[diesel(sql_type = Timestamptz)]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request body for a new override; which fields apply depends on `kind`.
+#[derive(Deserialize, Debug)]
+pub struct NewOverride {
+    pub kind: OverrideKind,
+    pub alias: Option<String>,
+    pub actor_id: Option<String>,
+    pub actor_kind: Option<String>,
+    pub reason: String,
+}
+
+pub enum CreateOutcome {
+    Created(ActorOverride),
+    /// The request is well-formed JSON but not an applicable override.
+    Rejected(String),
+}
+
+/// The override set as `reconcile` consumes it. Alias keys are lowercased.
+#[derive(Default)]
+pub struct Overrides {
+    pub merges: HashMap<String, String>,
+    pub splits: HashSet<String>,
+    pub kinds: HashMap<String, String>,
+}
+
+impl Overrides {
+    pub fn is_empty(&self) -> bool {
+        self.merges.is_empty() && self.splits.is_empty() && self.kinds.is_empty()
+    }
+}
+
+const SELECT: &str =
+    "SELECT id, kind, alias, actor_id, actor_kind, reason, created_by, created_at FROM actor_overrides";
+
+pub fn load(conn: &mut PgConnection) -> anyhow::Result<Overrides> {
+    let rows: Vec<ActorOverride> = diesel::sql_query(SELECT)
+        .load(conn)
+        .context("load actor overrides")?;
+    let mut out = Overrides::default();
+    for r in rows {
+        match (r.kind.as_str(), r.alias, r.actor_id, r.actor_kind) {
+            ("merge", Some(alias), Some(actor_id), _) => {
+                out.merges.insert(alias.to_lowercase(), actor_id);
+            }
+            ("split", Some(alias), _, _) => {
+                out.splits.insert(alias.to_lowercase());
+            }
+            ("kind", _, Some(actor_id), Some(actor_kind)) => {
+                out.kinds.insert(actor_id, actor_kind);
+            }
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Every override, newest first; with `actor`, only those naming that actor
+/// or one of its aliases.
+pub fn list(conn: &mut PgConnection, actor: Option<&str>) -> anyhow::Result<Vec<ActorOverride>> {
+    let sql = format!(
+        "{SELECT} o WHERE $1::text IS NULL OR o.actor_id = $1 \
+           OR o.alias IN (SELECT lower(alias) FROM actor_aliases WHERE actor_id = $1) \
+         ORDER BY o.created_at DESC"
+    );
+    diesel::sql_query(sql)
+        .bind::<Nullable<Text>, _>(actor)
+        .load(conn)
+        .context("list actor overrides")
+}
+
+#[derive(QueryableByName)]
+struct Resolution {
+    #[diesel(sql_type = Text)]
+    actor_id: String,
+}
+
+fn actor_exists(conn: &mut PgConnection, id: &str) -> anyhow::Result<bool> {
+    let n = diesel::sql_query("SELECT id AS actor_id FROM actors WHERE id = $1")
+        .bind::<Text, _>(id)
+        .load::<Resolution>(conn)
+        .context("look up actor")?
+        .len();
+    Ok(n > 0)
+}
+
+/// Validate and store one override. Replaces an earlier alias-level decision
+/// for the same alias (or kind override for the same actor) rather than
+/// stacking a conflicting one. Takes effect on the next SIEM pass.
+pub fn create(
+    conn: &mut PgConnection,
+    new: NewOverride,
+    who: &str,
+) -> anyhow::Result<CreateOutcome> {
+    let reason = new.reason.trim();
+    if reason.is_empty() {
+        return Ok(CreateOutcome::Rejected("reason is required".into()));
+    }
+    let alias = new
+        .alias
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase);
+    let actor_id = new
+        .actor_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string);
+
+    let (alias, actor_id, actor_kind) = match new.kind {
+        OverrideKind::Merge => {
+            let (Some(alias), Some(target)) = (alias, actor_id) else {
+                return Ok(CreateOutcome::Rejected(
+                    "merge needs alias and actor_id".into(),
+                ));
+            };
+            if alias == target.to_lowercase() {
+                return Ok(CreateOutcome::Rejected(
+                    "cannot merge an actor into itself".into(),
+                ));
+            }
+            if !actor_exists(conn, &target)? {
+                return Ok(CreateOutcome::Rejected(format!(
+                    "unknown actor: {}",
+                    target
+                )));
+            }
+            // The target must be a final identity, not itself merged away —
+            // chains would unravel when the intermediate actor is cleaned up.
+            let existing = load(conn)?;
+            if existing.merges.contains_key(&target.to_lowercase()) {
+                return Ok(CreateOutcome::Rejected(format!(
+                    "{} is itself merged into another actor",
+                    target
+                )));
+            }
+            if existing.merges.values().any(|t| t.to_lowercase() == alias) {
+                return Ok(CreateOutcome::Rejected(format!(
+                    "{} is the target of other merges; re-point those first",
+                    alias
+                )));
+            }
+            (Some(alias), Some(target), None)
+        }
+        OverrideKind::Split => {
+            let Some(alias) = alias else {
+                return Ok(CreateOutcome::Rejected("split needs alias".into()));
+            };
+            let current: Vec<Resolution> =
+                diesel::sql_query("SELECT actor_id FROM actor_aliases WHERE lower(alias) = $1")
+                    .bind::<Text, _>(&alias)
+                    .load(conn)
+                    .context("resolve alias")?;
+            match current.first() {
+                None => return Ok(CreateOutcome::Rejected(format!("unknown alias: {}", alias))),
+                Some(r) if r.actor_id.to_lowercase() == alias => {
+                    return Ok(CreateOutcome::Rejected(format!(
+                        "{} is already its own actor; use a kind override instead",
+                        alias
+                    )))
+                }
+                Some(_) => {}
+            }
+            (Some(alias), None, None)
+        }
+        OverrideKind::Kind => {
+            let Some(target) = actor_id else {
+                return Ok(CreateOutcome::Rejected(
+                    "kind override needs actor_id".into(),
+                ));
+            };
+            let kind = match new.actor_kind.as_deref() {
+                Some(k @ ("person" | "service" | "unresolved")) => k.to_string(),
+                _ => {
+                    return Ok(CreateOutcome::Rejected(
+                        "actor_kind must be person, service or unresolved".into(),
+                    ))
+                }
+            };
+            if !actor_exists(conn, &target)? {
+                return Ok(CreateOutcome::Rejected(format!(
+                    "unknown actor: {}",
+                    target
+                )));
+            }
+            (None, Some(target), Some(kind))
+        }
+    };
+
+    let row = conn.transaction::<_, anyhow::Error, _>(|conn| {
+        match new.kind {
+            OverrideKind::Merge | OverrideKind::Split => {
+                diesel::sql_query(
+                    "DELETE FROM actor_overrides WHERE kind IN ('merge', 'split') AND alias = $1",
+                )
+                .bind::<Nullable<Text>, _>(alias.as_deref())
+                .execute(conn)
+                .context("replace alias override")?;
+            }
+            OverrideKind::Kind => {
+                diesel::sql_query(
+                    "DELETE FROM actor_overrides WHERE kind = 'kind' AND actor_id = $1",
+                )
+                .bind::<Nullable<Text>, _>(actor_id.as_deref())
+                .execute(conn)
+                .context("replace kind override")?;
+            }
+        }
+        diesel::sql_query(
+            "INSERT INTO actor_overrides (kind, alias, actor_id, actor_kind, reason, created_by) \
+             VALUES ($1, $2, $3, $4, $5, $6) \
+             RETURNING id, kind, alias, actor_id, actor_kind, reason, created_by, created_at",
+        )
+        .bind::<Text, _>(new.kind.as_str())
+        .bind::<Nullable<Text>, _>(alias.as_deref())
+        .bind::<Nullable<Text>, _>(actor_id.as_deref())
+        .bind::<Nullable<Text>, _>(actor_kind.as_deref())
+        .bind::<Text, _>(reason)
+        .bind::<Text, _>(who)
+        .get_result::<ActorOverride>(conn)
+        .context("insert actor override")
+    })?;
+    Ok(CreateOutcome::Created(row))
+}
+
+/// Remove an override. Returns rows deleted. A removed kind override drops the
+/// actor back to `unresolved` so the next pass re-derives its kind from
+/// scratch instead of keeping the pinned value.
+pub fn delete(conn: &mut PgConnection, id: i64) -> anyhow::Result<usize> {
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        diesel::sql_query(
+            "UPDATE actors a SET kind = 'unresolved', updated_at = now() \
+             FROM actor_overrides o \
+             WHERE o.id = $1 AND o.kind = 'kind' AND a.id = o.actor_id",
+        )
+        .bind::<BigInt, _>(id)
+        .execute(conn)
+        .context("reset overridden kind")?;
+        diesel::sql_query("DELETE FROM actor_overrides WHERE id = $1")
+            .bind::<BigInt, _>(id)
+            .execute(conn)
+            .context("delete actor override")
+    })
+}
+
+/// Tables whose rows follow an actor into a merge, keyed by a plain
+/// `actor_id` column with no per-actor uniqueness.
+const REHOMED_TABLES: &[&str] = &[
+    "alerts",
+    "anomalies",
+    "sessions",
+    "grants",
+    "threat_intel_hits",
+    "role_edges",
+    "access_review_items",
+];
+
+/// Post-upsert half of the override layer: pin overridden kinds (the actor
+/// upsert never downgrades to `unresolved`, so this must run after it), then
+/// find every actor this pass left with zero aliases, re-home everything keyed
+/// by it onto the actor that took most of its aliases, and drop it. `moved` is
+/// the `(old owner, new owner)` of each alias that changed hands this pass.
+/// Returns actors removed.
+///
+/// The actor FKs cascade (`grants`, `actor_owners`) or null out (`sessions`,
+/// `alerts`), so every table is moved before the delete; only the derived
+/// `risk_scores`/`risk_score_history` rows are left to go with the actor.
+pub fn apply(conn: &mut PgConnection, moved: &[(String, String)]) -> anyhow::Result<usize> {
+    diesel::sql_query(
+        "UPDATE actors a SET kind = o.actor_kind, updated_at = now() \
+         FROM actor_overrides o \
+         WHERE o.kind = 'kind' AND a.id = o.actor_id AND a.kind <> o.actor_kind",
+    )
+    .execute(conn)
+    .context("apply kind overrides")?;
+
+    // Each emptied actor follows the owner that took most of its aliases.
+    let mut votes: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+    for (old, new) in moved {
+        *votes
+            .entry(old.as_str())
+            .or_default()
+            .entry(new.as_str())
+            .or_default() += 1;
+    }
+    let (olds, news): (Vec<String>, Vec<String>) = votes
+        .into_iter()
+        .filter_map(|(old, to)| {
+            let (new, _) = to.into_iter().max_by_key(|&(new, n)| (n, Reverse(new)))?;
+            Some((old.to_string(), new.to_string()))
+        })
+        .unzip();
+    if olds.is_empty() {
+        return Ok(0);
+    }
+
+    let emptied = "SELECT m.old_id, m.new_id \
+                   FROM unnest($1::text[], $2::text[]) AS m (old_id, new_id) \
+                   WHERE NOT EXISTS (SELECT 1 FROM actor_aliases x WHERE x.actor_id = m.old_id) \
+                     AND EXISTS (SELECT 1 FROM actors n WHERE n.id = m.new_id)";
+    for table in REHOMED_TABLES {
+        diesel::sql_query(format!(
+            "UPDATE {table} t SET actor_id = m.new_id FROM ({emptied}) m WHERE t.actor_id = m.old_id"
+        ))
+        .bind::<Array<Text>, _>(&olds)
+        .bind::<Array<Text>, _>(&news)
+        .execute(conn)
+        .with_context(|| format!("re-home {table} of merged actors"))?;
+    }
+    // One owner row per (actor, source): the target's own assignment wins.
+    diesel::sql_query(format!(
+        "UPDATE actor_owners t SET actor_id = m.new_id FROM ({emptied}) m \
+         WHERE t.actor_id = m.old_id \
+           AND NOT EXISTS (SELECT 1 FROM actor_owners x \
+                           WHERE x.actor_id = m.new_id AND x.source = t.source)"
+    ))
+    .bind::<Array<Text>, _>(&olds)
+    .bind::<Array<Text>, _>(&news)
+    .execute(conn)
+    .context("re-home owners of merged actors")?;
+    let removed = diesel::sql_query(format!(
+        "DELETE FROM actors WHERE id IN (SELECT old_id FROM ({emptied}) m)"
+    ))
+    .bind::<Array<Text>, _>(&olds)
+    .bind::<Array<Text>, _>(&news)
+    .execute(conn)
+    .context("drop merged actors")?;
+    Ok(removed)
+}
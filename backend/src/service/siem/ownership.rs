@@ -0,0 +1,632 @@
+//! Ownership of non-human actors (service roles, k8s service accounts, bots).
+//!
+//! The leader-only [`run`] worker refreshes `owner_hints` from systems that
+//! already know who owns what: IAM role tags in each configured account, the
+//! self-service capability roster (capability root id = k8s namespace), and
+//! CODEOWNERS / admin teams of the repos GitHub bots work in. A source that
+//! fails keeps its previous hints. The SIEM pass ([`derive`]) joins hints onto
+//! actors into `actor_owners`, next to analysts' manual assignments, and stamps
+//! each open alert with its actor's effective owner (`actor_owner_effective`)
+//! so it can be routed.
+
+use anyhow::Context;
+use aws_sdk_iam::config::Region;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use futures::stream::{self, StreamExt};
+use log::{error, info, warn};
+use reqwest::header::{HeaderValue, ACCEPT};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+
+use super::actors::{capability_list, fetch_capabilities_json, first_str};
+use crate::db::DbPool;
+use crate::misc::config::{Config, OwnershipConfig};
+
+const SOURCE_AWS_TAGS: &str = "aws_tags";
+const SOURCE_K8S_NAMESPACE: &str = "k8s_namespace";
+const SOURCE_GITHUB: &str = "github";
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+/// `ListRoleTags` calls in flight per account; IAM throttles well before
+/// this matters for listing, but a few thousand serial calls add up.
+const TAG_LOOKUP_CONCURRENCY: usize = 8;
+
+/// One external ownership fact, keyed per source (role ARN, namespace, repo).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub key: String,
+    pub owner: String,
+    pub owner_kind: &'static str,
+    pub evidence: Value,
+}
+
+pub async fn run(cancel: CancellationToken, conf: Config, pool: DbPool) {
+    let interval = std::time::Duration::from_secs(conf.ownership.interval_secs.max(300));
+    info!(
+        "ownership lookups starting :: interval={}s aws_accounts={} roster={} github_max_repos={}",
+        interval.as_secs(),
+        split_list(&conf.ownership.aws_role_arns).len(),
+        !conf.selfservice.base_url.is_empty(),
+        conf.ownership.github_max_repos,
+    );
+
+    loop {
+        refresh(&conf, &pool).await;
+        tokio::select! {
+            _ = cancel.cancelled() => { info!("stopping ownership lookups"); break; }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+#[tracing::instrument(name = "ownership.refresh", skip_all)]
+async fn refresh(conf: &Config, pool: &DbPool) {
+    let aws = if split_list(&conf.ownership.aws_role_arns).is_empty() {
+        None
+    } else {
+        Some(aws_role_hints(&conf.ownership).await)
+    };
+    let k8s = if conf.selfservice.base_url.is_empty() {
+        None
+    } else {
+        Some(namespace_hints(conf).await)
+    };
+    let github = github_hints(conf, pool).await;
+
+    for (source, res) in [
+        (SOURCE_AWS_TAGS, aws),
+        (SOURCE_K8S_NAMESPACE, k8s),
+        (SOURCE_GITHUB, github),
+    ] {
+        let hints = match res {
+            None => continue,
+            Some(Ok(h)) => h,
+            Some(Err(e)) => {
+                warn!(
+                    "ownership: {} lookup failed, keeping previous hints: {:#}",
+                    source, e
+                );
+                continue;
+            }
+        };
+        let pool = pool.clone();
+        let n = hints.len();
+        let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+            let mut conn = pool.get().context("pool get")?;
+            store_hints(&mut conn, source, &hints)
+        })
+        .await;
+        match res {
+            Ok(Ok(dropped)) => info!(
+                "ownership: {} hints refreshed :: hints={} dropped={}",
+                source, n, dropped
+            ),
+            Ok(Err(e)) => error!("ownership: storing {} hints failed: {:#}", source, e),
+            Err(e) => error!("ownership: {} join error: {}", source, e),
+        }
+    }
+}
+
+/// Replace every hint of `source` with `hints`. Returns stale hints dropped.
+fn store_hints(conn: &mut PgConnection, source: &str, hints: &[Hint]) -> anyhow::Result<usize> {
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        for h in hints {
+            diesel::sql_query(
+                "INSERT INTO owner_hints (source, key, owner, owner_kind, evidence, refreshed_at) \
+                 VALUES ($1, $2, $3, $4, $5, now()) \
+                 ON CONFLICT (source, key) DO UPDATE SET \
+                   owner = EXCLUDED.owner, owner_kind = EXCLUDED.owner_kind, \
+                   evidence = EXCLUDED.evidence, refreshed_at = now()",
+            )
+            .bind::<Text, _>(source)
+            .bind::<Text, _>(&h.key)
+            .bind::<Text, _>(&h.owner)
+            .bind::<Text, _>(h.owner_kind)
+            .bind::<Jsonb, _>(&h.evidence)
+            .execute(conn)
+            .context("upsert owner hint")?;
+        }
+        // now() is the transaction start, so everything upserted above survives.
+        diesel::sql_query("DELETE FROM owner_hints WHERE source = $1 AND refreshed_at < now()")
+            .bind::<Text, _>(source)
+            .execute(conn)
+            .context("drop stale owner hints")
+    })
+}
+
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Free-text owners: an email is a person, anything else a team.
+fn owner_kind_of(value: &str) -> &'static str {
+    if value.contains('@') {
+        "person"
+    } else {
+        "team"
+    }
+}
+
+/// Pick an owner out of a role's tags: the first non-empty owner tag in key
+/// order, else the first team tag. Returns `(owner, owner_kind, tag_key)`.
+pub fn owner_from_tags(
+    tags: &[(String, String)],
+    owner_keys: &[String],
+    team_keys: &[String],
+) -> Option<(String, &'static str, String)> {
+    let lookup = |key: &String| {
+        tags.iter()
+            .find(|(k, v)| k.eq_ignore_ascii_case(key) && !v.trim().is_empty())
+            .map(|(k, v)| (v.trim().to_string(), k.clone()))
+    };
+    if let Some((v, k)) = owner_keys.iter().find_map(lookup) {
+        let kind = owner_kind_of(&v);
+        return Some((v, kind, k));
+    }
+    team_keys
+        .iter()
+        .find_map(lookup)
+        .map(|(v, k)| (v, "team", k))
+}
+
+async fn aws_role_hints(conf: &OwnershipConfig) -> anyhow::Result<Vec<Hint>> {
+    let owner_keys = split_list(&conf.owner_tag_keys);
+    let team_keys = split_list(&conf.team_tag_keys);
+    let mut out = Vec::new();
+    for arn in split_list(&conf.aws_role_arns) {
+        // IAM is global; STS and the client just need some region.
+        let region = Region::new("us-east-1");
+        let provider = aws_config::sts::AssumeRoleProvider::builder(arn.clone())
+            .session_name(conf.aws_session_name.clone())
+            .region(region.clone())
+            .build()
+            .await;
+        let sdk = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(region)
+            .credentials_provider(provider)
+            .load()
+            .await;
+        let iam = aws_sdk_iam::Client::new(&sdk);
+        let account = arn.split(':').nth(4).unwrap_or_default().to_string();
+
+        let mut pages = iam.list_roles().into_paginator().send();
+        let mut roles = Vec::new();
+        while let Some(page) = pages.next().await {
+            let page = page.with_context(|| format!("list roles via {}", arn))?;
+            roles.extend(
+                page.roles()
+                    .iter()
+                    .map(|r| (r.arn().to_string(), r.role_name().to_string())),
+            );
+        }
+        let n_roles = roles.len();
+
+        // A role whose tags can't be read (deleted mid-listing, a deny on
+        // one path) is skipped rather than failing the whole account.
+        let tagged: Vec<Option<(String, String, Vec<(String, String)>)>> = stream::iter(roles)
+            .map(|(role_arn, role_name)| {
+                let iam = &iam;
+                async move {
+                    match iam.list_role_tags().role_name(&role_name).send().await {
+                        Ok(tags) => {
+                            let tags = tags
+                                .tags()
+                                .iter()
+                                .map(|t| (t.key().to_string(), t.value().to_string()))
+                                .collect();
+                            Some((role_arn, role_name, tags))
+                        }
+                        Err(e) => {
+                            warn!(
+                                "ownership: list tags of {} failed (skipped): {:#}",
+                                role_arn,
+                                anyhow::Error::from(e)
+                            );
+                            None
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(TAG_LOOKUP_CONCURRENCY)
+            .collect()
+            .await;
+        let n_skipped = tagged.iter().filter(|t| t.is_none()).count();
+
+        for (role_arn, role_name, tags) in tagged.into_iter().flatten() {
+            let Some((owner, owner_kind, tag)) = owner_from_tags(&tags, &owner_keys, &team_keys)
+            else {
+                continue;
+            };
+            out.push(Hint {
+                key: role_arn,
+                owner,
+                owner_kind,
+                evidence: json!({
+                    "account": account,
+                    "role_name": role_name,
+                    "tag": tag,
+                }),
+            });
+        }
+        info!(
+            "ownership: listed IAM roles :: account={} roles={} skipped={}",
+            account, n_roles, n_skipped
+        );
+    }
+    Ok(out)
+}
+
+async fn namespace_hints(conf: &Config) -> anyhow::Result<Vec<Hint>> {
+    let body = fetch_capabilities_json(&conf.selfservice).await?;
+    Ok(parse_namespace_hints(&body))
+}
+
+/// Capability root ids double as k8s namespace names; the capability owns it.
+pub fn parse_namespace_hints(body: &Value) -> Vec<Hint> {
+    capability_list(body)
+        .iter()
+        .filter_map(|cap| {
+            let ns = first_str(cap, &["rootId", "RootId", "id", "Id"])?;
+            let name = first_str(cap, &["name", "Name"]).unwrap_or_else(|| ns.clone());
+            Some(Hint {
+                key: ns.to_lowercase(),
+                owner: name.clone(),
+                owner_kind: "team",
+                evidence: json!({ "capability_id": ns, "capability": name }),
+            })
+        })
+        .collect()
+}
+
+#[derive(QueryableByName)]
+struct RepoRow {
+    #[diesel(sql_type = Text)]
+    repo: String,
+}
+
+async fn github_hints(conf: &Config, pool: &DbPool) -> Option<anyhow::Result<Vec<Hint>>> {
+    let mut gh = conf.github.clone();
+    if !conf.ownership.github_token.is_empty() {
+        gh.audit_pat = conf.ownership.github_token.clone();
+    }
+    if gh.audit_pat.is_empty() || conf.ownership.github_max_repos <= 0 {
+        return None;
+    }
+    Some(github_hints_inner(conf, gh, pool).await)
+}
+
+async fn github_hints_inner(
+    conf: &Config,
+    gh: crate::misc::config::GithubConfig,
+    pool: &DbPool,
+) -> anyhow::Result<Vec<Hint>> {
+    let repos = {
+        let pool = pool.clone();
+        let floor = Utc::now() - Duration::days(conf.siem.window_days.max(1));
+        let limit = conf.ownership.github_max_repos;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<RepoRow>> {
+            let mut conn = pool.get().context("pool get")?;
+            diesel::sql_query(
+                "SELECT g.repo AS repo FROM github_audit_events g \
+                 JOIN actor_aliases aa ON aa.alias = g.actor \
+                 JOIN actors a ON a.id = aa.actor_id AND a.kind <> 'person' \
+                 WHERE g.repo IS NOT NULL AND g.event_time >= $1 \
+                 GROUP BY g.repo ORDER BY count(*) DESC LIMIT $2",
+            )
+            .bind::<Timestamptz, _>(floor)
+            .bind::<BigInt, _>(limit)
+            .load(&mut conn)
+            .context("load bot repos")
+        })
+        .await
+        .context("join")??
+    };
+
+    let client = crate::service::ingest::github::build_client(&gh)?;
+    let api = gh.api_base_url.trim_end_matches('/').to_string();
+    let mut out = Vec::new();
+    for RepoRow { repo } in repos {
+        match repo_owner(&client, &api, &repo).await {
+            Ok(Some(h)) => out.push(h),
+            Ok(None) => {}
+            Err(e) => warn!("ownership: github lookup for {} failed: {:#}", repo, e),
+        }
+    }
+    Ok(out)
+}
+
+/// CODEOWNERS default rule first; a repo without one falls back to its first
+/// team with admin permission. A failed fetch of one CODEOWNERS location moves
+/// on to the next instead of abandoning the repo.
+async fn repo_owner(
+    client: &reqwest::Client,
+    api: &str,
+    repo: &str,
+) -> anyhow::Result<Option<Hint>> {
+    for path in CODEOWNERS_PATHS {
+        let resp = client
+            .get(format!("{}/repos/{}/contents/{}", api, repo, path))
+            .header(
+                ACCEPT,
+                HeaderValue::from_static("application/vnd.github.raw+json"),
+            )
+            .send()
+            .await
+            .context("fetch CODEOWNERS")?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            continue;
+        }
+        if !resp.status().is_success() {
+            warn!(
+                "ownership: {} {} returned {} (trying next location)",
+                repo,
+                path,
+                resp.status()
+            );
+            continue;
+        }
+        let text = resp.text().await.context("read CODEOWNERS")?;
+        if let Some(raw) = codeowners_default_owner(&text) {
+            let (owner, owner_kind) = github_owner(&raw);
+            return Ok(Some(Hint {
+                key: repo.to_string(),
+                owner,
+                owner_kind,
+                evidence: json!({ "repo": repo, "via": "codeowners", "path": path, "entry": raw }),
+            }));
+        }
+    }
+
+    let resp = client
+        .get(format!("{}/repos/{}/teams", api, repo))
+        .send()
+        .await
+        .context("fetch repo teams")?;
+    if !resp.status().is_success() {
+        anyhow::bail!("repo teams returned {}", resp.status());
+    }
+    let teams: Value = resp.json().await.context("parse repo teams")?;
+    let admin = teams.as_array().and_then(|ts| {
+        ts.iter()
+            .find(|t| t.get("permission").and_then(Value::as_str) == Some("admin"))
+            .and_then(|t| first_str(t, &["slug", "name"]))
+    });
+    Ok(admin.map(|slug| Hint {
+        key: repo.to_string(),
+        owner: slug.clone(),
+        owner_kind: "team",
+        evidence: json!({ "repo": repo, "via": "admin_team", "team": slug }),
+    }))
+}
+
+/// First owner of the last catch-all rule (`*`, `/`, `/*`, `/**`) — later rules
+/// win in CODEOWNERS, so the last one is the default.
+pub fn codeowners_default_owner(text: &str) -> Option<String> {
+    let mut owner = None;
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        let Some(pattern) = parts.next() else {
+            continue;
+        };
+        if matches!(pattern, "*" | "/" | "/*" | "/**" | "**") {
+            owner = parts.next().map(str::to_string);
+        }
+    }
+    owner
+}
+
+/// `@org/team` → the team slug, `@user` → that person, an email → that person.
+fn github_owner(raw: &str) -> (String, &'static str) {
+    match raw.strip_prefix('@') {
+        Some(handle) => match handle.split_once('/') {
+            Some((_, team)) => (team.to_string(), "team"),
+            None => (handle.to_string(), "person"),
+        },
+        None => (raw.to_string(), owner_kind_of(raw)),
+    }
+}
+
+/// SIEM stage: map hints onto actors, drop derived ownership no hint supports
+/// anymore, and stamp open alerts with their actor's effective owner. Returns
+/// actors with a derived owner.
+pub fn derive(conn: &mut PgConnection, window_days: i64) -> anyhow::Result<usize> {
+    let floor = Utc::now() - Duration::days(window_days.max(1));
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let upsert = "ON CONFLICT (actor_id, source) DO UPDATE SET \
+                        owner = EXCLUDED.owner, owner_kind = EXCLUDED.owner_kind, \
+                        evidence = EXCLUDED.evidence, updated_at = now()";
+
+        // Role sessions: the role the actor most recently assumed.
+        let mut n = diesel::sql_query(format!(
+            "INSERT INTO actor_owners (actor_id, source, owner, owner_kind, evidence, updated_at) \
+             SELECT DISTINCT ON (aa.actor_id) aa.actor_id, 'aws_tags', h.owner, h.owner_kind, \
+                    h.evidence || jsonb_build_object('role_arn', h.key), now() \
+             FROM cloudtrail_events c \
+             JOIN owner_hints h ON h.source = 'aws_tags' AND h.key = c.assumed_role_arn \
+             JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             JOIN actors a ON a.id = aa.actor_id AND a.kind <> 'person' \
+             WHERE c.event_time >= $1 \
+             ORDER BY aa.actor_id, c.event_time DESC \
+             {upsert}"
+        ))
+        .bind::<Timestamptz, _>(floor)
+        .execute(conn)
+        .context("derive owners from role tags")?;
+
+        // `system:serviceaccount:<namespace>:<name>`.
+        n += diesel::sql_query(format!(
+            "INSERT INTO actor_owners (actor_id, source, owner, owner_kind, evidence, updated_at) \
+             SELECT a.id, 'k8s_namespace', h.owner, h.owner_kind, \
+                    h.evidence || jsonb_build_object('namespace', h.key), now() \
+             FROM actors a \
+             JOIN owner_hints h ON h.source = 'k8s_namespace' \
+                               AND h.key = lower(split_part(a.id, ':', 3)) \
+             WHERE a.id LIKE 'system:serviceaccount:%' AND a.kind <> 'person' \
+             {upsert}"
+        ))
+        .execute(conn)
+        .context("derive owners from namespaces")?;
+
+        // GitHub bots: owner of the repo they act on most.
+        n += diesel::sql_query(format!(
+            "INSERT INTO actor_owners (actor_id, source, owner, owner_kind, evidence, updated_at) \
+             SELECT DISTINCT ON (t.actor_id) t.actor_id, 'github', h.owner, h.owner_kind, \
+                    h.evidence || jsonb_build_object('events', t.n), now() \
+             FROM ( \
+               SELECT aa.actor_id, g.repo, count(*) AS n \
+               FROM github_audit_events g \
+               JOIN actor_aliases aa ON aa.alias = g.actor \
+               JOIN actors a ON a.id = aa.actor_id AND a.kind <> 'person' \
+               WHERE g.repo IS NOT NULL AND g.event_time >= $1 \
+               GROUP BY aa.actor_id, g.repo \
+             ) t \
+             JOIN owner_hints h ON h.source = 'github' AND h.key = t.repo \
+             ORDER BY t.actor_id, t.n DESC \
+             {upsert}"
+        ))
+        .bind::<Timestamptz, _>(floor)
+        .execute(conn)
+        .context("derive owners from github")?;
+
+        diesel::sql_query("DELETE FROM actor_owners WHERE source <> 'manual' AND updated_at < now()")
+            .execute(conn)
+            .context("drop unsupported owners")?;
+
+        diesel::sql_query(
+            "UPDATE alerts al SET owner = e.owner, owner_kind = e.owner_kind \
+             FROM actor_owner_effective e \
+             WHERE e.actor_id = al.actor_id AND al.status <> 'resolved' \
+               AND (al.owner IS DISTINCT FROM e.owner OR al.owner_kind IS DISTINCT FROM e.owner_kind)",
+        )
+        .execute(conn)
+        .context("stamp alert owners")?;
+        diesel::sql_query(
+            "UPDATE alerts al SET owner = NULL, owner_kind = NULL \
+             WHERE al.owner IS NOT NULL AND al.status <> 'resolved' \
+               AND NOT EXISTS (SELECT 1 FROM actor_owner_effective e WHERE e.actor_id = al.actor_id)",
+        )
+        .execute(conn)
+        .context("clear alert owners")?;
+        Ok(n)
+    })
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct OwnerClaim {
+    #[diesel(sql_type = Text)]
+    pub source: String,
+    #[diesel(sql_type = Text)]
+    pub owner: String,
+    #[diesel(sql_type = Text)]
+    pub owner_kind: String,
+    #[diesel(sql_type = Jsonb)]
+    pub evidence: Value,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub assigned_by: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub reason: Option<String>,
+    #[diesel(sql_type = Timestamptz)]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Every ownership claim on an actor, in precedence order (first one wins).
+pub fn claims(conn: &mut PgConnection, actor_id: &str) -> anyhow::Result<Vec<OwnerClaim>> {
+    diesel::sql_query(
+        "SELECT source, owner, owner_kind, evidence, assigned_by, reason, updated_at FROM ( \
+           SELECT source, owner, owner_kind, evidence, assigned_by, reason, updated_at, \
+                  CASE source WHEN 'manual' THEN 0 WHEN 'aws_tags' THEN 1 \
+                              WHEN 'k8s_namespace' THEN 2 ELSE 3 END AS rank \
+           FROM actor_owners WHERE actor_id = $1 \
+           UNION ALL \
+           SELECT 'roster', team, 'team', '{}'::jsonb, NULL, NULL, updated_at, 4 \
+           FROM actors WHERE id = $1 AND kind = 'person' AND team IS NOT NULL \
+         ) c ORDER BY rank",
+    )
+    .bind::<Text, _>(actor_id)
+    .load(conn)
+    .context("load owner claims")
+}
+
+/// Set the manual owner of an actor. Returns rows written (0 = unknown actor).
+pub fn assign(
+    conn: &mut PgConnection,
+    actor_id: &str,
+    owner: &str,
+    owner_kind: &str,
+    reason: &str,
+    who: &str,
+) -> anyhow::Result<usize> {
+    diesel::sql_query(
+        "INSERT INTO actor_owners (actor_id, source, owner, owner_kind, assigned_by, reason, updated_at) \
+         SELECT id, 'manual', $2, $3, $4, $5, now() FROM actors WHERE id = $1 \
+         ON CONFLICT (actor_id, source) DO UPDATE SET \
+           owner = EXCLUDED.owner, owner_kind = EXCLUDED.owner_kind, \
+           assigned_by = EXCLUDED.assigned_by, reason = EXCLUDED.reason, updated_at = now()",
+    )
+    .bind::<Text, _>(actor_id)
+    .bind::<Text, _>(owner)
+    .bind::<Text, _>(owner_kind)
+    .bind::<Text, _>(who)
+    .bind::<Text, _>(reason)
+    .execute(conn)
+    .context("assign owner")
+}
+
+/// Drop the manual owner; derived sources take over on the next pass.
+pub fn unassign(conn: &mut PgConnection, actor_id: &str) -> anyhow::Result<usize> {
+    diesel::sql_query("DELETE FROM actor_owners WHERE actor_id = $1 AND source = 'manual'")
+        .bind::<Text, _>(actor_id)
+        .execute(conn)
+        .context("unassign owner")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codeowners_last_catch_all_wins() {
+        let text = "# default\n* @dfds/platform @someone\n/docs/ @dfds/writers\n/* @dfds/cloud-engineering # later\n";
+        assert_eq!(
+            codeowners_default_owner(text).as_deref(),
+            Some("@dfds/cloud-engineering")
+        );
+        assert_eq!(codeowners_default_owner("/src/ @dfds/x\n"), None);
+        assert_eq!(
+            github_owner("@dfds/platform"),
+            ("platform".to_string(), "team")
+        );
+        assert_eq!(github_owner("@octocat"), ("octocat".to_string(), "person"));
+        assert_eq!(
+            github_owner("a@dfds.com"),
+            ("a@dfds.com".to_string(), "person")
+        );
+    }
+
+    #[test]
+    fn owner_tag_beats_team_tag() {
+        let keys = |s: &str| split_list(s);
+        let tags = vec![
+            ("Team".to_string(), "cloud-engineering".to_string()),
+            ("OWNER".to_string(), "jane@dfds.com".to_string()),
+        ];
+        assert_eq!(
+            owner_from_tags(&tags, &keys("owner"), &keys("team,capability")),
+            Some(("jane@dfds.com".to_string(), "person", "OWNER".to_string()))
+        );
+        let tags = vec![("capability".to_string(), "sandbox-xyz".to_string())];
+        assert_eq!(
+            owner_from_tags(&tags, &keys("owner"), &keys("team,capability")),
+            Some(("sandbox-xyz".to_string(), "team", "capability".to_string()))
+        );
+        assert_eq!(owner_from_tags(&[], &keys("owner"), &keys("team")), None);
+    }
+}
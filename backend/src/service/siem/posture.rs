@@ -0,0 +1,285 @@
+//! Team-level security posture.
+//!
+//! The console is per actor, but capabilities own their posture per team.
+//! [`derive`] aggregates members (the team-kind effective owner, else the
+//! roster `actors.team`) into `team_posture` once per pass and upserts the
+//! day's row of `team_posture_history`; teams left with no members drop out of
+//! the current table but keep their history until it ages past
+//! `siem.team_posture_history_days`.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Date, Double, Integer, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::misc::config::SiemConfig;
+
+/// Team membership, shared by the aggregate and the member listing.
+const MEMBERS_CTE: &str = "members AS ( \
+       SELECT a.id, a.kind, a.last_active, \
+              CASE WHEN e.owner_kind = 'team' THEN e.owner ELSE a.team END AS team \
+       FROM actors a LEFT JOIN actor_owner_effective e ON e.actor_id = a.id \
+     )";
+
+const POSTURE_COLUMNS: &str = "team, members, members_by_kind, risk_max, risk_avg, \
+       risk_distribution, open_alerts, open_alerts_by_severity, privileged_grants, dormant, \
+       anomalies, anomaly_rate, computed_at";
+
+/// Recompute every team's posture. Returns the number of teams written.
+pub fn derive(conn: &mut PgConnection, siem: &SiemConfig) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let window_floor = now - Duration::days(siem.window_days.max(1));
+    let dormant_floor = now - Duration::days(siem.dormant_days.max(1));
+
+    let n = diesel::sql_query(format!(
+        "WITH {MEMBERS_CTE}, \
+         m AS (SELECT * FROM members WHERE team IS NOT NULL AND team <> ''), \
+         base AS ( \
+           SELECT team, count(*)::int AS members, \
+                  count(*) FILTER (WHERE last_active IS NULL OR last_active < $2)::int AS dormant \
+           FROM m GROUP BY team \
+         ), \
+         kinds AS ( \
+           SELECT team, jsonb_object_agg(kind, n) AS by_kind \
+           FROM (SELECT team, kind, count(*) AS n FROM m GROUP BY team, kind) k GROUP BY team \
+         ), \
+         risk AS ( \
+           SELECT m.team, max(r.score) AS risk_max, avg(r.score)::float8 AS risk_avg, \
+                  jsonb_build_object( \
+                    'critical', count(*) FILTER (WHERE r.label = 'critical'), \
+                    'high', count(*) FILTER (WHERE r.label = 'high'), \
+                    'medium', count(*) FILTER (WHERE r.label = 'medium'), \
+                    'low', count(*) FILTER (WHERE r.label = 'low')) AS dist \
+           FROM m JOIN risk_scores r ON r.actor_id = m.id GROUP BY m.team \
+         ), \
+         al AS ( \
+           SELECT m.team, count(*)::int AS total, \
+                  jsonb_build_object( \
+                    'critical', count(*) FILTER (WHERE a.severity = 'critical'), \
+                    'high', count(*) FILTER (WHERE a.severity = 'high'), \
+                    'medium', count(*) FILTER (WHERE a.severity = 'medium'), \
+                    'low', count(*) FILTER (WHERE a.severity = 'low')) AS by_sev \
+           FROM m JOIN alerts a ON a.actor_id = m.id AND a.status IN ('open', 'acked') \
+           GROUP BY m.team \
+         ), \
+         gr AS ( \
+           SELECT m.team, count(*)::int AS privileged \
+           FROM m JOIN grants g ON g.actor_id = m.id AND g.revoked_at IS NULL AND g.privileged \
+           GROUP BY m.team \
+         ), \
+         an AS ( \
+           SELECT m.team, count(*)::int AS n, count(DISTINCT an.actor_id)::int AS actors \
+           FROM m JOIN anomalies an ON an.actor_id = m.id AND an.event_time >= $1 \
+           GROUP BY m.team \
+         ) \
+         INSERT INTO team_posture ({POSTURE_COLUMNS}) \
+         SELECT b.team, b.members, k.by_kind, COALESCE(r.risk_max, 0), COALESCE(r.risk_avg, 0), \
+                COALESCE(r.dist, '{{}}'::jsonb), COALESCE(al.total, 0), COALESCE(al.by_sev, '{{}}'::jsonb), \
+                COALESCE(gr.privileged, 0), b.dormant, COALESCE(an.n, 0), \
+                COALESCE(an.actors, 0)::float8 / b.members, $3 \
+         FROM base b JOIN kinds k USING (team) LEFT JOIN risk r USING (team) \
+         LEFT JOIN al USING (team) LEFT JOIN gr USING (team) LEFT JOIN an USING (team) \
+         ON CONFLICT (team) DO UPDATE SET \
+           members = EXCLUDED.members, members_by_kind = EXCLUDED.members_by_kind, \
+           risk_max = EXCLUDED.risk_max, risk_avg = EXCLUDED.risk_avg, \
+           risk_distribution = EXCLUDED.risk_distribution, open_alerts = EXCLUDED.open_alerts, \
+           open_alerts_by_severity = EXCLUDED.open_alerts_by_severity, \
+           privileged_grants = EXCLUDED.privileged_grants, dormant = EXCLUDED.dormant, \
+           anomalies = EXCLUDED.anomalies, anomaly_rate = EXCLUDED.anomaly_rate, \
+           computed_at = EXCLUDED.computed_at"
+    ))
+    .bind::<Timestamptz, _>(window_floor)
+    .bind::<Timestamptz, _>(dormant_floor)
+    .bind::<Timestamptz, _>(now)
+    .execute(conn)
+    .context("aggregate team posture")?;
+
+    diesel::sql_query("DELETE FROM team_posture WHERE computed_at < $1")
+        .bind::<Timestamptz, _>(now)
+        .execute(conn)
+        .context("drop teams without members")?;
+
+    diesel::sql_query(format!(
+        "INSERT INTO team_posture_history (day, {POSTURE_COLUMNS}) \
+         SELECT (computed_at AT TIME ZONE 'UTC')::date, {POSTURE_COLUMNS} FROM team_posture \
+         ON CONFLICT (team, day) DO UPDATE SET \
+           members = EXCLUDED.members, members_by_kind = EXCLUDED.members_by_kind, \
+           risk_max = EXCLUDED.risk_max, risk_avg = EXCLUDED.risk_avg, \
+           risk_distribution = EXCLUDED.risk_distribution, open_alerts = EXCLUDED.open_alerts, \
+           open_alerts_by_severity = EXCLUDED.open_alerts_by_severity, \
+           privileged_grants = EXCLUDED.privileged_grants, dormant = EXCLUDED.dormant, \
+           anomalies = EXCLUDED.anomalies, anomaly_rate = EXCLUDED.anomaly_rate, \
+           computed_at = EXCLUDED.computed_at"
+    ))
+    .execute(conn)
+    .context("record team posture history")?;
+
+    diesel::sql_query("DELETE FROM team_posture_history WHERE day < $1")
+        .bind::<Date, _>((now - Duration::days(siem.team_posture_history_days.max(1))).date_naive())
+        .execute(conn)
+        .context("prune team posture history")?;
+
+    Ok(n)
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct TeamPosture {
+    #[diesel(sql_type = Text)]
+    pub team: String,
+    #[diesel(sql_type = Integer)]
+    pub members: i32,
+    #[diesel(sql_type = Jsonb)]
+    pub members_by_kind: Value,
+    #[diesel(sql_type = Integer)]
+    pub risk_max: i32,
+    #[diesel(sql_type = Double)]
+    pub risk_avg: f64,
+    #[diesel(sql_type = Jsonb)]
+    pub risk_distribution: Value,
+    #[diesel(sql_type = Integer)]
+    pub open_alerts: i32,
+    #[diesel(sql_type = Jsonb)]
+    pub open_alerts_by_severity: Value,
+    #[diesel(sql_type = Integer)]
+    pub privileged_grants: i32,
+    #[diesel(sql_type = Integer)]
+    pub dormant: i32,
+    #[diesel(sql_type = Integer)]
+    pub anomalies: i32,
+    #[diesel(sql_type = Double)]
+    pub anomaly_rate: f64,
+    #[diesel(sql_type = Timestamptz)]
+    pub computed_at: DateTime<Utc>,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct TeamPostureDay {
+    #[diesel(sql_type = Date)]
+    pub day: NaiveDate,
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub posture: TeamPosture,
+}
+
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct TeamMember {
+    #[diesel(sql_type = Text)]
+    pub actor_id: String,
+    #[diesel(sql_type = Text)]
+    pub kind: String,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub last_active: Option<DateTime<Utc>>,
+    #[diesel(sql_type = Nullable<Integer>)]
+    pub risk_score: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub risk_label: Option<String>,
+    #[diesel(sql_type = Integer)]
+    pub open_alerts: i32,
+    #[diesel(sql_type = Integer)]
+    pub privileged_grants: i32,
+}
+
+/// Leaderboard orderings; each sorts worst first.
+pub const SORTS: &[&str] = &[
+    "risk_avg",
+    "risk_max",
+    "open_alerts",
+    "privileged_grants",
+    "dormant",
+    "anomaly_rate",
+    "members",
+];
+
+/// The column behind a leaderboard `sort`: `risk_avg` when empty, `None`
+/// for anything outside [`SORTS`] so user input never reaches the SQL.
+pub fn sort_column(sort: &str) -> Option<&'static str> {
+    if sort.is_empty() {
+        return Some(SORTS[0]);
+    }
+    SORTS.iter().find(|s| **s == sort).copied()
+}
+
+/// Every team's current posture ordered by `sort` (one of [`SORTS`]).
+pub fn leaderboard(conn: &mut PgConnection, sort: &str) -> anyhow::Result<Vec<TeamPosture>> {
+    let sort = sort_column(sort).unwrap_or(SORTS[0]);
+    diesel::sql_query(format!(
+        "SELECT {POSTURE_COLUMNS} FROM team_posture ORDER BY {sort} DESC, team"
+    ))
+    .load(conn)
+    .context("team leaderboard")
+}
+
+pub fn get(conn: &mut PgConnection, team: &str) -> anyhow::Result<Option<TeamPosture>> {
+    diesel::sql_query(format!(
+        "SELECT {POSTURE_COLUMNS} FROM team_posture WHERE team = $1"
+    ))
+    .bind::<Text, _>(team)
+    .get_result(conn)
+    .optional()
+    .context("load team posture")
+}
+
+/// History span in days, bounded to ten years.
+fn history_days(days: i64) -> i32 {
+    days.clamp(1, 3650) as i32
+}
+
+/// Daily history for one team, oldest first.
+pub fn history(
+    conn: &mut PgConnection,
+    team: &str,
+    days: i64,
+) -> anyhow::Result<Vec<TeamPostureDay>> {
+    diesel::sql_query(format!(
+        "SELECT day, {POSTURE_COLUMNS} FROM team_posture_history \
+         WHERE team = $1 AND day >= (now() AT TIME ZONE 'UTC')::date - $2 ORDER BY day"
+    ))
+    .bind::<Text, _>(team)
+    .bind::<Integer, _>(history_days(days))
+    .load(conn)
+    .context("load team posture history")
+}
+
+/// A team's members, riskiest first.
+pub fn members(conn: &mut PgConnection, team: &str, limit: i64) -> anyhow::Result<Vec<TeamMember>> {
+    diesel::sql_query(format!(
+        "WITH {MEMBERS_CTE} \
+         SELECT m.id AS actor_id, m.kind, m.last_active, r.score AS risk_score, r.label AS risk_label, \
+                (SELECT count(*)::int FROM alerts a \
+                  WHERE a.actor_id = m.id AND a.status IN ('open', 'acked')) AS open_alerts, \
+                (SELECT count(*)::int FROM grants g \
+                  WHERE g.actor_id = m.id AND g.revoked_at IS NULL AND g.privileged) AS privileged_grants \
+         FROM members m LEFT JOIN risk_scores r ON r.actor_id = m.id \
+         WHERE m.team = $1 \
+         ORDER BY r.score DESC NULLS LAST, m.id LIMIT $2"
+    ))
+    .bind::<Text, _>(team)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+    .context("load team members")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_column_defaults_and_rejects_unknown() {
+        assert_eq!(sort_column(""), Some("risk_avg"));
+        assert_eq!(sort_column("dormant"), Some("dormant"));
+        assert_eq!(sort_column("anomaly_rate"), Some("anomaly_rate"));
+        assert_eq!(sort_column("team; DROP TABLE team_posture"), None);
+        assert_eq!(sort_column("Risk_Avg"), None);
+    }
+
+    #[test]
+    fn history_days_is_bounded() {
+        assert_eq!(history_days(90), 90);
+        assert_eq!(history_days(0), 1);
+        assert_eq!(history_days(-5), 1);
+        assert_eq!(history_days(i64::MAX), 3650);
+    }
+}
@@ -1,30 +1,293 @@
+use std::collections::HashMap;
+
 use anyhow::Context;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
-use diesel::sql_types::{BigInt, Double, Text, Timestamptz};
+use diesel::sql_types::{Array, Double, Text, Timestamptz};
 use diesel::PgConnection;
 use serde_json::json;
 
 use crate::misc::config::{RiskConfig, SiemConfig};
+use crate::service::siem::alerts;
+
+/// How a factor's raw value maps onto `[0, 1]` against its cap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    /// `x / cap`.
+    Linear,
+    /// `ln(1 + x) / ln(1 + cap)` — early hits count most, repeats taper off.
+    Log,
+    /// `1` once `x >= cap`, else `0`.
+    Step,
+}
+
+impl Curve {
+    pub fn apply(self, x: f64, cap: f64) -> f64 {
+        if x <= 0.0 || cap <= 0.0 {
+            return 0.0;
+        }
+        let v = match self {
+            Curve::Linear => x / cap,
+            Curve::Log => x.ln_1p() / cap.ln_1p(),
+            Curve::Step => {
+                if x >= cap {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        };
+        v.clamp(0.0, 1.0)
+    }
+}
+
+/// One risk signal. `sql` yields `(actor_id text, raw float8, refs text[])` for
+/// every actor the factor applies to (actors it omits score zero on it), and
+/// may read the pass parameters from the `p` CTE: `p.window_floor`, `p.h24`,
+/// `p.ohs`/`p.ohe` (off-hours bounds), `p.dormant_floor` and `p.max_refs`.
+/// `refs` are the ids behind the value, of kind `ref_kind`, newest first and
+/// capped at `p.max_refs` (= [`MAX_REFS`]).
+pub struct Factor {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub curve: Curve,
+    pub weight: fn(&RiskConfig) -> f64,
+    pub cap: fn(&RiskConfig) -> f64,
+    pub ref_kind: &'static str,
+    pub sql: &'static str,
+}
+
+/// Most ids kept per component; enough to drill into, small enough to store
+/// on every history sample.
+pub const MAX_REFS: usize = 20;
+
+pub const FACTORS: &[Factor] = &[
+    Factor {
+        name: "failed_auth",
+        description: "Failed CloudTrail calls in the last 24h",
+        curve: Curve::Linear,
+        weight: |r| r.w_failed_auth,
+        cap: |r| r.cap_failed_auth,
+        ref_kind: "event",
+        sql: "SELECT aa.actor_id, count(*)::float8 AS raw, \
+                (array_agg(c.event_id ORDER BY c.event_time DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM cloudtrail_events c \
+              JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+              CROSS JOIN p \
+              WHERE c.error_code IS NOT NULL AND c.event_time >= p.h24 \
+              GROUP BY aa.actor_id",
+    },
+    Factor {
+        name: "priv_grants",
+        description: "Live privileged grants",
+        curve: Curve::Linear,
+        weight: |r| r.w_priv_grants,
+        cap: |r| r.cap_priv_grants,
+        ref_kind: "grant",
+        sql: "SELECT actor_id, count(*)::float8 AS raw, \
+                (array_agg(id::text ORDER BY granted_at DESC NULLS LAST))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM grants \
+              WHERE privileged AND revoked_at IS NULL AND actor_id IS NOT NULL \
+              GROUP BY actor_id",
+    },
+    Factor {
+        name: "off_hours",
+        description: "Share of activity outside working hours over the window",
+        curve: Curve::Linear,
+        weight: |r| r.w_off_hours,
+        cap: |_| 1.0,
+        ref_kind: "day",
+        sql: "SELECT aa.actor_id, \
+                CASE WHEN sum(dc.n) > 0 THEN sum(d.off)::float8 / sum(dc.n) ELSE 0 END AS raw, \
+                COALESCE((array_agg(dc.day::text ORDER BY dc.day DESC) FILTER (WHERE d.off > 0))[1:(SELECT max_refs FROM p)], ARRAY[]::text[]) AS refs \
+              FROM actor_daily_counts dc \
+              JOIN actor_aliases aa ON aa.alias = dc.actor \
+              CROSS JOIN p \
+              CROSS JOIN LATERAL ( \
+                SELECT COALESCE(sum(h), 0) AS off \
+                FROM unnest(dc.hourly) WITH ORDINALITY u(h, idx) \
+                WHERE (idx - 1) >= p.ohs OR (idx - 1) < p.ohe \
+              ) d \
+              WHERE dc.day >= p.window_floor::date \
+              GROUP BY aa.actor_id",
+    },
+    Factor {
+        name: "dormant_reactivation",
+        description: "Active in the last 24h after at least dormant_days of silence",
+        curve: Curve::Step,
+        weight: |r| r.w_dormant,
+        cap: |_| 1.0,
+        ref_kind: "day",
+        sql: "SELECT a.id AS actor_id, 1::float8 AS raw, \
+                array_remove(ARRAY[pr.prior_day::text], NULL) AS refs \
+              FROM actors a \
+              CROSS JOIN p \
+              JOIN ( \
+                SELECT aa.actor_id, max(f.last_ts) AS last_ts \
+                FROM actor_source_first_seen f \
+                JOIN actor_aliases aa ON aa.alias = f.actor \
+                GROUP BY aa.actor_id \
+              ) ls ON ls.actor_id = a.id \
+              LEFT JOIN ( \
+                SELECT aa.actor_id, max(dc.day) AS prior_day \
+                FROM actor_daily_counts dc \
+                JOIN actor_aliases aa ON aa.alias = dc.actor \
+                WHERE dc.day < current_date AND dc.n > 0 \
+                GROUP BY aa.actor_id \
+              ) pr ON pr.actor_id = a.id \
+              WHERE ls.last_ts >= p.h24 \
+                AND (pr.prior_day IS NULL OR pr.prior_day < p.dormant_floor::date) \
+                AND a.first_seen < p.dormant_floor",
+    },
+    Factor {
+        name: "flagged_sessions",
+        description: "Sessions currently flagged",
+        curve: Curve::Linear,
+        weight: |r| r.w_flagged_sessions,
+        cap: |r| r.cap_flagged_sessions,
+        ref_kind: "session",
+        sql: "SELECT actor_id, count(*)::float8 AS raw, \
+                (array_agg(id::text ORDER BY last_seen_at DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM sessions \
+              WHERE status = 'flagged' AND actor_id IS NOT NULL \
+              GROUP BY actor_id",
+    },
+    // `raw` is the number of sources *beyond the first*. History samples from
+    // before the pluggable factors stored the total source count here, so a
+    // two-source actor reads 2 in older components and 1 from then on (the
+    // contribution is unchanged). No refs: the sources are on the actor itself.
+    Factor {
+        name: "source_diversity",
+        description: "Sources the actor appears in beyond the first",
+        curve: Curve::Linear,
+        weight: |r| r.w_source_diversity,
+        cap: |r| r.cap_source_diversity,
+        ref_kind: "",
+        sql: "SELECT id AS actor_id, (COALESCE(array_length(sources, 1), 0) - 1)::float8 AS raw, \
+                ARRAY[]::text[] AS refs \
+              FROM actors \
+              WHERE COALESCE(array_length(sources, 1), 0) > 1",
+    },
+    Factor {
+        name: "anomalies",
+        description: "Statistical anomalies over the window",
+        curve: Curve::Linear,
+        weight: |r| r.w_anomalies,
+        cap: |r| r.cap_anomalies,
+        ref_kind: "anomaly",
+        sql: "SELECT an.actor_id, count(*)::float8 AS raw, \
+                (array_agg(an.id::text ORDER BY an.event_time DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM anomalies an CROSS JOIN p \
+              WHERE an.event_time >= p.window_floor AND an.actor_id IS NOT NULL \
+              GROUP BY an.actor_id",
+    },
+    Factor {
+        name: "open_alerts",
+        description: "Open or acknowledged high/critical alerts",
+        curve: Curve::Linear,
+        weight: |r| r.w_open_alerts,
+        cap: |r| r.cap_open_alerts,
+        ref_kind: "alert",
+        // `risk_jump` is itself derived from the score; counting it would
+        // hold a jumped score up for as long as the alert stays open.
+        sql: "SELECT actor_id, count(*)::float8 AS raw, \
+                (array_agg(id::text ORDER BY last_seen DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM alerts \
+              WHERE status IN ('open', 'acked') AND severity IN ('high', 'critical') \
+                AND rule_id <> 'risk_jump' AND actor_id IS NOT NULL \
+              GROUP BY actor_id",
+    },
+    Factor {
+        name: "threat_intel",
+        description: "Events matching a threat-intel indicator over the window",
+        curve: Curve::Log,
+        weight: |r| r.w_threat_intel,
+        cap: |r| r.cap_threat_intel,
+        ref_kind: "ti_hit",
+        sql: "SELECT h.actor_id, count(*)::float8 AS raw, \
+                (array_agg(h.id::text ORDER BY h.event_time DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM threat_intel_hits h CROSS JOIN p \
+              WHERE h.event_time >= p.window_floor AND h.actor_id IS NOT NULL \
+              GROUP BY h.actor_id",
+    },
+    Factor {
+        name: "github_admin",
+        description: "GitHub orgs/repos where the actor's latest membership change left admin rights",
+        curve: Curve::Linear,
+        weight: |r| r.w_github_admin,
+        cap: |r| r.cap_github_admin,
+        ref_kind: "event",
+        sql: "SELECT aa.actor_id, count(*)::float8 AS raw, \
+                (array_agg(m.document_id ORDER BY m.event_time DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM ( \
+                SELECT DISTINCT ON (g.raw->>'user', COALESCE(g.repo, g.org)) \
+                  g.raw->>'user' AS target, g.document_id, g.event_time, g.action, \
+                  COALESCE(g.raw->>'permission', g.raw->>'role', '') AS permission \
+                FROM github_audit_events g \
+                WHERE g.action IN ('org.add_member', 'org.update_member', 'org.remove_member', \
+                                   'repo.add_member', 'repo.update_member', 'repo.remove_member') \
+                  AND g.raw->>'user' IS NOT NULL \
+                ORDER BY g.raw->>'user', COALESCE(g.repo, g.org), g.event_time DESC \
+              ) m \
+              JOIN actor_aliases aa ON aa.alias = m.target \
+              WHERE m.action NOT LIKE '%.remove_member' AND m.permission IN ('admin', 'owner') \
+              GROUP BY aa.actor_id",
+    },
+    Factor {
+        name: "mfa_less_logins",
+        description: "Successful console logins without MFA over the window",
+        curve: Curve::Linear,
+        weight: |r| r.w_mfa_less_logins,
+        cap: |r| r.cap_mfa_less_logins,
+        ref_kind: "event",
+        // `mfa_used` is NULL for federated sign-ins (the IdP's MFA is
+        // invisible), so only password sign-ins known to lack MFA count.
+        sql: "SELECT aa.actor_id, count(*)::float8 AS raw, \
+                (array_agg(c.event_id ORDER BY c.event_time DESC))[1:(SELECT max_refs FROM p)] AS refs \
+              FROM cloudtrail_events c \
+              JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+              CROSS JOIN p \
+              WHERE c.event_name = 'ConsoleLogin' AND c.error_code IS NULL \
+                AND c.raw->'responseElements'->>'ConsoleLogin' = 'Success' \
+                AND c.mfa_used = false \
+                AND c.event_time >= p.window_floor \
+              GROUP BY aa.actor_id",
+    },
+    Factor {
+        name: "priv_mfa_less_logins",
+        description: "Console logins without MFA by root, into a privileged role or while holding a privileged grant, over the window",
+        curve: Curve::Step,
+        weight: |r| r.w_priv_mfa_less_logins,
+        cap: |_| 1.0,
+        ref_kind: "event",
+        // The `privileged_login_without_mfa` rule's condition, so the two agree.
+        sql: concat!(
+            "SELECT aa.actor_id, count(*)::float8 AS raw, \
+               (array_agg(c.event_id ORDER BY c.event_time DESC))[1:(SELECT max_refs FROM p)] AS refs \
+             FROM cloudtrail_events c \
+             JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+             CROSS JOIN p \
+             WHERE c.event_time >= p.window_floor AND ",
+            alerts::privileged_mfa_less_login!(),
+            " GROUP BY aa.actor_id"
+        ),
+    },
+];
 
 #[derive(QueryableByName)]
-struct Factors {
+struct FactorRow {
     #[diesel(sql_type = Text)]
     actor_id: String,
-    #[diesel(sql_type = BigInt)]
-    failed_auth: i64,
-    #[diesel(sql_type = BigInt)]
-    priv_grants: i64,
     #[diesel(sql_type = Double)]
-    off_hours_ratio: f64,
-    #[diesel(sql_type = BigInt)]
-    dormant: i64,
-    #[diesel(sql_type = BigInt)]
-    flagged_sessions: i64,
-    #[diesel(sql_type = BigInt)]
-    source_diversity: i64,
-    #[diesel(sql_type = BigInt)]
-    anomalies: i64,
+    raw: f64,
+    #[diesel(sql_type = Array<Text>)]
+    refs: Vec<String>,
+}
+
+#[derive(QueryableByName)]
+struct ActorId {
+    #[diesel(sql_type = Text)]
+    id: String,
 }
 
 fn label_of(score: i32) -> &'static str {
@@ -46,103 +309,66 @@ pub fn compute(
     let window_floor = now - Duration::days(siem.window_days.max(1));
     let h24 = now - Duration::hours(24);
     let dormant_floor = now - Duration::days(siem.dormant_days.max(1));
-    
-    let factors: Vec<Factors> = diesel::sql_query(
-        "WITH \
-         fa AS ( \
-            SELECT aa.actor_id, count(*) AS n \
-            FROM cloudtrail_events c \
-            JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
-            WHERE c.error_code IS NOT NULL AND c.event_time >= $2 \
-            GROUP BY aa.actor_id \
-         ), \
-         oh AS ( \
-            SELECT aa.actor_id, sum(dc.n) AS total, sum(d.off) AS off \
-            FROM actor_daily_counts dc \
-            JOIN actor_aliases aa ON aa.alias = dc.actor \
-            CROSS JOIN LATERAL ( \
-              SELECT COALESCE(sum(h), 0) AS off \
-              FROM unnest(dc.hourly) WITH ORDINALITY u(h, idx) \
-              WHERE (idx - 1) >= $3 OR (idx - 1) < $4 \
-            ) d \
-            WHERE dc.day >= $1::date \
-            GROUP BY aa.actor_id \
-         ), \
-         last_seen AS ( \
-            SELECT aa.actor_id, max(f.last_ts) AS last_ts \
-            FROM actor_source_first_seen f \
-            JOIN actor_aliases aa ON aa.alias = f.actor \
-            GROUP BY aa.actor_id \
-         ), \
-         prior AS ( \
-            SELECT aa.actor_id, max(dc.day) AS prior_day \
-            FROM actor_daily_counts dc \
-            JOIN actor_aliases aa ON aa.alias = dc.actor \
-            WHERE dc.day < current_date AND dc.n > 0 \
-            GROUP BY aa.actor_id \
-         ), \
-         pg AS (SELECT actor_id, count(*) AS n FROM grants WHERE privileged AND revoked_at IS NULL AND actor_id IS NOT NULL GROUP BY actor_id), \
-         fs AS (SELECT actor_id, count(*) AS n FROM sessions WHERE status = 'flagged' AND actor_id IS NOT NULL GROUP BY actor_id), \
-         an AS (SELECT actor_id, count(*) AS n FROM anomalies WHERE event_time >= $1 AND actor_id IS NOT NULL GROUP BY actor_id) \
-         SELECT a.id AS actor_id, \
-           COALESCE(fa.n, 0) AS failed_auth, \
-           COALESCE(pg.n, 0) AS priv_grants, \
-           CASE WHEN COALESCE(oh.total, 0) > 0 THEN oh.off::float8 / oh.total ELSE 0 END AS off_hours_ratio, \
-           (CASE WHEN last_seen.last_ts >= $2 AND (prior.prior_day IS NULL OR prior.prior_day < $5::date) AND a.first_seen < $5 THEN 1 ELSE 0 END)::bigint AS dormant, \
-           COALESCE(fs.n, 0) AS flagged_sessions, \
-           COALESCE(array_length(a.sources, 1), 0)::bigint AS source_diversity, \
-           COALESCE(an.n, 0) AS anomalies \
-         FROM actors a \
-         LEFT JOIN fa        ON fa.actor_id = a.id \
-         LEFT JOIN oh        ON oh.actor_id = a.id \
-         LEFT JOIN last_seen ON last_seen.actor_id = a.id \
-         LEFT JOIN prior     ON prior.actor_id = a.id \
-         LEFT JOIN pg        ON pg.actor_id = a.id \
-         LEFT JOIN fs        ON fs.actor_id = a.id \
-         LEFT JOIN an        ON an.actor_id = a.id",
-    )
-    .bind::<Timestamptz, _>(window_floor)
-    .bind::<Timestamptz, _>(h24)
-    .bind::<Double, _>(siem.off_hours_start as f64)
-    .bind::<Double, _>(siem.off_hours_end as f64)
-    .bind::<Timestamptz, _>(dormant_floor)
-    .load(conn)
-    .context("load risk factors")?;
+
+    let actors: Vec<ActorId> = diesel::sql_query("SELECT id FROM actors")
+        .load(conn)
+        .context("load actors")?;
+
+    // factor index -> actor -> (raw, refs)
+    let mut values: Vec<HashMap<String, (f64, Vec<String>)>> = Vec::with_capacity(FACTORS.len());
+    for factor in FACTORS {
+        let q = format!(
+            "WITH p AS (SELECT $1::timestamptz AS window_floor, $2::timestamptz AS h24, \
+               $3::float8 AS ohs, $4::float8 AS ohe, $5::timestamptz AS dormant_floor, \
+               $6::int AS max_refs) {}",
+            factor.sql
+        );
+        let rows: Vec<FactorRow> = diesel::sql_query(q)
+            .bind::<Timestamptz, _>(window_floor)
+            .bind::<Timestamptz, _>(h24)
+            .bind::<Double, _>(siem.off_hours_start as f64)
+            .bind::<Double, _>(siem.off_hours_end as f64)
+            .bind::<Timestamptz, _>(dormant_floor)
+            .bind::<diesel::sql_types::Integer, _>(MAX_REFS as i32)
+            .load(conn)
+            .with_context(|| format!("load risk factor {}", factor.name))?;
+        values.push(
+            rows.into_iter()
+                .map(|r| (r.actor_id, (r.raw, r.refs)))
+                .collect(),
+        );
+    }
 
     let mut scored = 0usize;
     conn.transaction::<_, anyhow::Error, _>(|conn| {
-        for f in &factors {
-            // Saturating per-factor transforms into [0, 1].
-            let sat = |x: f64, cap: f64| (x / cap).clamp(0.0, 1.0);
-            let f_failed = sat(f.failed_auth as f64, 10.0);
-            let f_priv = sat(f.priv_grants as f64, 5.0);
-            let f_off = f.off_hours_ratio.clamp(0.0, 1.0);
-            let f_dormant = f.dormant.min(1) as f64;
-            let f_flagged = sat(f.flagged_sessions as f64, 3.0);
-            let f_diversity = sat((f.source_diversity - 1).max(0) as f64, 2.0);
-            let f_anom = sat(f.anomalies as f64, 3.0);
-
-            let contrib = [
-                ("failed_auth", risk.w_failed_auth, f_failed, f.failed_auth as f64),
-                ("priv_grants", risk.w_priv_grants, f_priv, f.priv_grants as f64),
-                ("off_hours", risk.w_off_hours, f_off, f.off_hours_ratio),
-                ("dormant_reactivation", risk.w_dormant, f_dormant, f.dormant as f64),
-                ("flagged_sessions", risk.w_flagged_sessions, f_flagged, f.flagged_sessions as f64),
-                ("source_diversity", risk.w_source_diversity, f_diversity, f.source_diversity as f64),
-                ("anomalies", risk.w_anomalies, f_anom, f.anomalies as f64),
-            ];
-
-            let total: f64 = contrib.iter().map(|(_, w, fv, _)| w * fv).sum();
-            let score = total.round().clamp(0.0, 100.0) as i32;
-            let label = label_of(score);
-
+        for actor in &actors {
+            let mut total = 0.0;
             let mut components = serde_json::Map::new();
-            for (name, w, fv, raw) in contrib.iter() {
+            for (factor, by_actor) in FACTORS.iter().zip(&values) {
+                let (raw, refs) = by_actor
+                    .get(&actor.id)
+                    .map(|(raw, refs)| (*raw, refs.as_slice()))
+                    .unwrap_or((0.0, &[]));
+                let weight = (factor.weight)(risk);
+                let cap = (factor.cap)(risk);
+                let normalized = factor.curve.apply(raw, cap);
+                total += weight * normalized;
                 components.insert(
-                    name.to_string(),
-                    json!({ "raw": raw, "weight": w, "normalized": fv, "contribution": (w * fv * 100.0).round() / 100.0 }),
+                    factor.name.to_string(),
+                    json!({
+                        "raw": raw,
+                        "weight": weight,
+                        "cap": cap,
+                        "normalized": normalized,
+                        "contribution": (weight * normalized * 100.0).round() / 100.0,
+                        "description": factor.description,
+                        "ref_kind": factor.ref_kind,
+                        "refs": &refs[..refs.len().min(MAX_REFS)],
+                    }),
                 );
             }
+            let score = total.round().clamp(0.0, 100.0) as i32;
+            let label = label_of(score);
             let components = serde_json::Value::Object(components);
 
             diesel::sql_query(
@@ -151,7 +377,7 @@ pub fn compute(
                  ON CONFLICT (actor_id) DO UPDATE SET \
                    score = EXCLUDED.score, label = EXCLUDED.label, components = EXCLUDED.components, computed_at = now()",
             )
-            .bind::<Text, _>(&f.actor_id)
+            .bind::<Text, _>(&actor.id)
             .bind::<diesel::sql_types::Integer, _>(score)
             .bind::<Text, _>(label)
             .bind::<diesel::sql_types::Jsonb, _>(components)
@@ -159,8 +385,150 @@ pub fn compute(
             .context("upsert risk score")?;
             scored += 1;
         }
+
+        // Append this pass to the history. `now()` is fixed for the whole
+        // transaction, so it picks up exactly the rows written above. An actor
+        // whose score and label match its latest sample gets no new row.
+        diesel::sql_query(
+            "INSERT INTO risk_score_history \
+               (actor_id, resolution, bucket, score, min_score, max_score, label, components, samples) \
+             SELECT r.actor_id, 'raw', r.computed_at, r.score, r.score, r.score, r.label, r.components, 1 \
+             FROM risk_scores r WHERE r.computed_at = now() \
+               AND NOT EXISTS ( \
+                 SELECT 1 FROM ( \
+                   SELECT h.score, h.label FROM risk_score_history h \
+                   WHERE h.actor_id = r.actor_id ORDER BY h.bucket DESC LIMIT 1 \
+                 ) last WHERE last.score = r.score AND last.label = r.label) \
+             ON CONFLICT DO NOTHING",
+        )
+        .execute(conn)
+        .context("append risk history")?;
         Ok(())
     })?;
 
     Ok(scored)
 }
+
+/// Fold aged history into coarser buckets: per-pass samples older than
+/// `history_raw_hours` become hourly buckets, hourly buckets older than
+/// `history_hourly_days` become daily ones. `score` is the sample-weighted
+/// mean of the folded rows, `min_score`/`max_score` their extremes, and
+/// `label`/`components` are taken from the latest row. Returns rows folded.
+pub fn compact_history(conn: &mut PgConnection, risk: &RiskConfig) -> anyhow::Result<usize> {
+    let mut folded = 0usize;
+    for (from, to, cutoff) in compaction_steps(Utc::now(), risk) {
+        // Only fold whole target buckets so a bucket is never written twice
+        // from partial input.
+        let q = format!(
+            "WITH moved AS ( \
+               DELETE FROM risk_score_history \
+               WHERE resolution = $1 AND bucket < date_trunc('{to}', $3::timestamptz) \
+               RETURNING * \
+             ), \
+             agg AS ( \
+               SELECT actor_id, date_trunc('{to}', bucket) AS bucket, \
+                 round(sum(score::float8 * samples) / sum(samples))::int AS score, \
+                 min(min_score) AS min_score, max(max_score) AS max_score, \
+                 (array_agg(label ORDER BY bucket DESC))[1] AS label, \
+                 (array_agg(components ORDER BY bucket DESC))[1] AS components, \
+                 sum(samples)::int AS samples \
+               FROM moved GROUP BY actor_id, date_trunc('{to}', bucket) \
+             ) \
+             INSERT INTO risk_score_history \
+               (actor_id, resolution, bucket, score, min_score, max_score, label, components, samples) \
+             SELECT actor_id, $2, bucket, score, min_score, max_score, label, components, samples FROM agg \
+             ON CONFLICT (actor_id, resolution, bucket) DO UPDATE SET \
+               score = round((risk_score_history.score::float8 * risk_score_history.samples \
+                              + EXCLUDED.score::float8 * EXCLUDED.samples) \
+                             / (risk_score_history.samples + EXCLUDED.samples))::int, \
+               min_score = LEAST(risk_score_history.min_score, EXCLUDED.min_score), \
+               max_score = GREATEST(risk_score_history.max_score, EXCLUDED.max_score), \
+               samples = risk_score_history.samples + EXCLUDED.samples"
+        );
+        folded += diesel::sql_query(q)
+            .bind::<Text, _>(from)
+            .bind::<Text, _>(to)
+            .bind::<Timestamptz, _>(cutoff)
+            .execute(conn)
+            .with_context(|| format!("compact risk history {from} -> {to}"))?;
+    }
+    Ok(folded)
+}
+
+/// `(from, to, cutoff)` per fold: rows of resolution `from` older than
+/// `cutoff` are merged into `to` buckets. Retention knobs are floored at one
+/// unit so a zero in config never folds the live tail.
+fn compaction_steps(
+    now: DateTime<Utc>,
+    risk: &RiskConfig,
+) -> [(&'static str, &'static str, DateTime<Utc>); 2] {
+    let raw_cutoff = now - Duration::hours(risk.history_raw_hours.max(1));
+    let hourly_cutoff = now - Duration::days(risk.history_hourly_days.max(1));
+    [("raw", "hour", raw_cutoff), ("hour", "day", hourly_cutoff)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_saturate_at_cap() {
+        assert_eq!(Curve::Linear.apply(5.0, 10.0), 0.5);
+        assert_eq!(Curve::Linear.apply(30.0, 10.0), 1.0);
+        assert_eq!(Curve::Log.apply(10.0, 10.0), 1.0);
+        assert!(Curve::Log.apply(1.0, 10.0) > Curve::Linear.apply(1.0, 10.0));
+        assert_eq!(Curve::Step.apply(0.9, 1.0), 0.0);
+        assert_eq!(Curve::Step.apply(1.0, 1.0), 1.0);
+        assert_eq!(Curve::Linear.apply(-1.0, 10.0), 0.0);
+        assert_eq!(Curve::Linear.apply(3.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn factor_names_are_unique_and_capped() {
+        let risk = RiskConfig::default();
+        let mut names: Vec<_> = FACTORS.iter().map(|f| f.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), FACTORS.len());
+        for f in FACTORS {
+            assert!((f.weight)(&risk) >= 0.0, "{} has a negative weight", f.name);
+            assert!((f.cap)(&risk) > 0.0, "{} has no default cap", f.name);
+        }
+    }
+
+    #[test]
+    fn added_factors_ship_unweighted() {
+        // Turning these on by default would move every score at once.
+        let risk = RiskConfig::default();
+        let added = [
+            "open_alerts",
+            "threat_intel",
+            "github_admin",
+            "mfa_less_logins",
+            "priv_mfa_less_logins",
+        ];
+        for name in added {
+            let f = FACTORS.iter().find(|f| f.name == name).unwrap();
+            assert_eq!((f.weight)(&risk), 0.0, "{name} should default to weight 0");
+        }
+    }
+
+    #[test]
+    fn compaction_folds_raw_before_hourly() {
+        let now = Utc::now();
+        let [raw, hourly] = compaction_steps(now, &RiskConfig::default());
+        assert_eq!((raw.0, raw.1), ("raw", "hour"));
+        assert_eq!((hourly.0, hourly.1), ("hour", "day"));
+        assert_eq!(raw.2, now - Duration::hours(48));
+        assert_eq!(hourly.2, now - Duration::days(30));
+
+        let zeroed = RiskConfig {
+            history_raw_hours: 0,
+            history_hourly_days: -3,
+            ..RiskConfig::default()
+        };
+        let [raw, hourly] = compaction_steps(now, &zeroed);
+        assert_eq!(raw.2, now - Duration::hours(1));
+        assert_eq!(hourly.2, now - Duration::days(1));
+    }
+}
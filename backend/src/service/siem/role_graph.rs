@@ -0,0 +1,367 @@
+//! AWS role-assumption graph.
+//!
+//! CloudTrail already tells us who each `AssumeRole*` caller is (the mapper's
+//! `principal_name`, upgraded to the web-identity subject by
+//! `resolve_webidentity_chains`) and which role it was acting as when it made
+//! the call (`assumed_role_arn`, the session issuer). [`derive`] folds the
+//! successful calls into `role_edges`: one hop per caller, target role and STS
+//! call, attributed to the actor behind its latest use. A call made from a
+//! role session is a role → role hop shared by everyone holding that role, so
+//! walking from an actor node through its entry hops and then the role hops
+//! finds every chain it could use, across accounts. Edges keep
+//! their `first_seen` until they go unused for `siem.role_edge_retention_days`,
+//! which is what the `new_privileged_role_path` rule keys off.
+
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use serde::Serialize;
+
+use crate::misc::config::SiemConfig;
+
+/// Role names treated as privileged; the same heuristic as privileged grants.
+/// A literal, so static SQL can `concat!` it; [`PRIVILEGED_ROLE_PATTERN`] is
+/// the same value for runtime use.
+macro_rules! privileged_role_pattern {
+    () => {
+        "Admin|PowerUser|FullAccess"
+    };
+}
+pub(crate) use privileged_role_pattern;
+
+pub const PRIVILEGED_ROLE_PATTERN: &str = privileged_role_pattern!();
+
+/// Longest chain [`paths`] follows.
+pub const MAX_PATH_DEPTH: usize = 6;
+
+const EDGE_COLUMNS: &str = "edge_key, src_kind, src, dst_role, dst_account, src_account, via, \
+       identity_source, actor_id, privileged, first_seen, last_seen, event_count";
+
+/// Fold the trailing `window_days` of `AssumeRole*` calls into `role_edges` and
+/// drop edges unused for `role_edge_retention_days`. Returns the number of
+/// edges inserted/updated.
+pub fn derive(conn: &mut PgConnection, siem: &SiemConfig) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let floor = now - Duration::days(siem.window_days.max(1));
+
+    let n = diesel::sql_query(format!(
+        "INSERT INTO role_edges ({EDGE_COLUMNS}, updated_at) \
+         SELECT \
+           e.src_kind || '|' || e.src || '|' || e.dst_role || '|' || e.via, \
+           e.src_kind, e.src, e.dst_role, split_part(e.dst_role, ':', 5), max(e.src_account), e.via, \
+           (array_agg(e.identity_source ORDER BY e.event_time DESC) FILTER (WHERE e.identity_source IS NOT NULL))[1], \
+           (array_agg(e.actor_id ORDER BY e.event_time DESC) FILTER (WHERE e.actor_id IS NOT NULL))[1], \
+           regexp_replace(e.dst_role, '^.*/', '') ~* '{PRIVILEGED_ROLE_PATTERN}', \
+           min(e.event_time), max(e.event_time), count(*), now() \
+         FROM ( \
+           SELECT \
+             c.event_name AS via, \
+             c.raw #>> '{{requestParameters,roleArn}}' AS dst_role, \
+             c.user_identity_account_id AS src_account, \
+             CASE WHEN c.event_name = 'AssumeRoleWithSAML' \
+               THEN 'saml:' || regexp_replace(COALESCE(c.raw #>> '{{requestParameters,principalArn}}', \
+                      c.raw #>> '{{userIdentity,identityProvider}}', '?'), '^.*saml-provider/', '') \
+               ELSE c.identity_source END AS identity_source, \
+             aa.actor_id, \
+             c.event_time, \
+             CASE \
+               WHEN c.assumed_role_arn IS NOT NULL \
+                AND c.assumed_role_arn <> c.raw #>> '{{requestParameters,roleArn}}' THEN 'role' \
+               WHEN aa.actor_id IS NOT NULL THEN 'actor' \
+               ELSE 'principal' \
+             END AS src_kind, \
+             CASE \
+               WHEN c.assumed_role_arn IS NOT NULL \
+                AND c.assumed_role_arn <> c.raw #>> '{{requestParameters,roleArn}}' THEN c.assumed_role_arn \
+               WHEN aa.actor_id IS NOT NULL THEN aa.actor_id \
+               ELSE COALESCE(c.principal_name, c.principal_arn, '?') \
+             END AS src \
+           FROM cloudtrail_events c \
+           LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
+           WHERE c.event_name IN ('AssumeRole','AssumeRoleWithSAML','AssumeRoleWithWebIdentity') \
+             AND c.error_code IS NULL AND c.event_time >= $1 \
+             AND c.raw #>> '{{requestParameters,roleArn}}' IS NOT NULL \
+         ) e \
+         GROUP BY e.src_kind, e.src, e.dst_role, e.via \
+         ON CONFLICT (edge_key) DO UPDATE SET \
+           first_seen  = LEAST(role_edges.first_seen, EXCLUDED.first_seen), \
+           last_seen   = GREATEST(role_edges.last_seen, EXCLUDED.last_seen), \
+           event_count = EXCLUDED.event_count, \
+           src_account = COALESCE(EXCLUDED.src_account, role_edges.src_account), \
+           identity_source = COALESCE(EXCLUDED.identity_source, role_edges.identity_source), \
+           actor_id    = COALESCE(EXCLUDED.actor_id, role_edges.actor_id), \
+           privileged  = EXCLUDED.privileged, \
+           updated_at  = now()"
+    ))
+    .bind::<Timestamptz, _>(floor)
+    .execute(conn)
+    .context("derive role edges")?;
+
+    diesel::sql_query("DELETE FROM role_edges WHERE last_seen < $1")
+        .bind::<Timestamptz, _>(now - Duration::days(siem.role_edge_retention_days.max(1)))
+        .execute(conn)
+        .context("prune role edges")?;
+
+    Ok(n)
+}
+
+#[derive(QueryableByName, Serialize, Debug, Clone)]
+pub struct RoleEdge {
+    #[diesel(sql_type = Text)]
+    pub edge_key: String,
+    #[diesel(sql_type = Text)]
+    pub src_kind: String,
+    #[diesel(sql_type = Text)]
+    pub src: String,
+    #[diesel(sql_type = Text)]
+    pub dst_role: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub dst_account: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub src_account: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub via: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub identity_source: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub actor_id: Option<String>,
+    #[diesel(sql_type = Bool)]
+    pub privileged: bool,
+    #[diesel(sql_type = Timestamptz)]
+    pub first_seen: DateTime<Utc>,
+    #[diesel(sql_type = Timestamptz)]
+    pub last_seen: DateTime<Utc>,
+    #[diesel(sql_type = BigInt)]
+    pub event_count: i64,
+}
+
+impl RoleEdge {
+    /// Graph node id of the caller: `actor:`, `role:` or `principal:`.
+    pub fn src_node(&self) -> String {
+        format!("{}:{}", self.src_kind, self.src)
+    }
+
+    /// Graph node id of the assumed role.
+    pub fn dst_node(&self) -> String {
+        format!("role:{}", self.dst_role)
+    }
+}
+
+/// One way an actor reached a role: the hops in order, the last one landing on
+/// the target.
+#[derive(Serialize, Debug)]
+pub struct RolePath {
+    pub role: String,
+    pub account: Option<String>,
+    pub privileged: bool,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub hops: Vec<RoleEdge>,
+}
+
+/// Edges for the graph, privileged and most recent first. `actor` keeps the
+/// hops made by or on behalf of one actor; `account` keeps those into or out of
+/// one account; `since`/`until` those seen at some point in that window.
+pub fn edges(
+    conn: &mut PgConnection,
+    actor: Option<&str>,
+    account: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: i64,
+) -> anyhow::Result<Vec<RoleEdge>> {
+    diesel::sql_query(format!(
+        "SELECT {EDGE_COLUMNS} FROM role_edges \
+         WHERE ($1 = '' OR actor_id = $1) AND ($2 = '' OR dst_account = $2 OR src_account = $2) \
+           AND ($3::timestamptz IS NULL OR last_seen >= $3) \
+           AND ($4::timestamptz IS NULL OR first_seen <= $4) \
+         ORDER BY privileged DESC, last_seen DESC LIMIT $5"
+    ))
+    .bind::<Text, _>(actor.unwrap_or(""))
+    .bind::<Text, _>(account.unwrap_or(""))
+    .bind::<Nullable<Timestamptz>, _>(since)
+    .bind::<Nullable<Timestamptz>, _>(until)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+    .context("load role edges")
+}
+
+/// Edges [`edges`] would return without its limit.
+pub fn edge_count(
+    conn: &mut PgConnection,
+    actor: Option<&str>,
+    account: Option<&str>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> anyhow::Result<i64> {
+    #[derive(QueryableByName)]
+    struct Count {
+        #[diesel(sql_type = BigInt)]
+        n: i64,
+    }
+    let row: Count = diesel::sql_query(
+        "SELECT count(*) AS n FROM role_edges \
+         WHERE ($1 = '' OR actor_id = $1) AND ($2 = '' OR dst_account = $2 OR src_account = $2) \
+           AND ($3::timestamptz IS NULL OR last_seen >= $3) \
+           AND ($4::timestamptz IS NULL OR first_seen <= $4)",
+    )
+    .bind::<Text, _>(actor.unwrap_or(""))
+    .bind::<Text, _>(account.unwrap_or(""))
+    .bind::<Nullable<Timestamptz>, _>(since)
+    .bind::<Nullable<Timestamptz>, _>(until)
+    .get_result(conn)
+    .context("count role edges")?;
+    Ok(row.n)
+}
+
+/// Every path by which `actor` can reach a role in `account` (any account when
+/// `None`): its own entry hops, then the role → role hops out of the roles it
+/// holds, whoever used them last. Privileged roles only unless
+/// `privileged_only` is false. Shortest first; the flag is true when
+/// `max_paths` cut the list short.
+pub fn paths(
+    conn: &mut PgConnection,
+    actor: &str,
+    account: Option<&str>,
+    privileged_only: bool,
+    max_depth: usize,
+    max_paths: usize,
+) -> anyhow::Result<(Vec<RolePath>, bool)> {
+    let edges: Vec<RoleEdge> = diesel::sql_query(format!(
+        "SELECT {EDGE_COLUMNS} FROM role_edges WHERE actor_id = $1 OR src_kind = 'role'"
+    ))
+    .bind::<Text, _>(actor)
+    .load(conn)
+    .context("load actor role edges")?;
+
+    let (found, truncated) = find_paths(
+        &edges,
+        &format!("actor:{actor}"),
+        max_depth.clamp(1, MAX_PATH_DEPTH),
+        max_paths,
+        |e| {
+            (!privileged_only || e.privileged)
+                && (account.is_none() || e.dst_account.as_deref() == account)
+        },
+    );
+    let paths = found
+        .into_iter()
+        .map(|idx| {
+            let hops: Vec<RoleEdge> = idx.iter().map(|&i| edges[i].clone()).collect();
+            let last = hops.last().expect("paths have at least one hop");
+            RolePath {
+                role: last.dst_role.clone(),
+                account: last.dst_account.clone(),
+                privileged: last.privileged,
+                // The path exists once its newest hop does.
+                first_seen: hops.iter().map(|h| h.first_seen).max().expect("non-empty"),
+                last_seen: last.last_seen,
+                hops,
+            }
+        })
+        .collect();
+    Ok((paths, truncated))
+}
+
+/// Breadth-first search for simple paths from `start` whose final hop satisfies
+/// `is_target`, at most `max_depth` hops long. Returns the paths as edge
+/// indices, shortest first, and whether `max_paths` truncated them. A path
+/// never revisits a role, so role-chaining loops terminate.
+pub fn find_paths(
+    edges: &[RoleEdge],
+    start: &str,
+    max_depth: usize,
+    max_paths: usize,
+    is_target: impl Fn(&RoleEdge) -> bool,
+) -> (Vec<Vec<usize>>, bool) {
+    let mut by_src: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, e) in edges.iter().enumerate() {
+        by_src.entry(e.src_node()).or_default().push(i);
+    }
+
+    let mut found = Vec::new();
+    let mut queue: VecDeque<Vec<usize>> = by_src
+        .get(start)
+        .into_iter()
+        .flatten()
+        .map(|&i| vec![i])
+        .collect();
+    while let Some(path) = queue.pop_front() {
+        let last = &edges[*path.last().expect("non-empty")];
+        if is_target(last) {
+            if found.len() == max_paths {
+                return (found, true);
+            }
+            found.push(path.clone());
+        }
+        if path.len() >= max_depth {
+            continue;
+        }
+        for &next in by_src.get(&last.dst_node()).into_iter().flatten() {
+            let to = edges[next].dst_node();
+            if to == start || path.iter().any(|&i| edges[i].dst_node() == to) {
+                continue;
+            }
+            let mut longer = path.clone();
+            longer.push(next);
+            queue.push_back(longer);
+        }
+    }
+    (found, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(src_kind: &str, src: &str, dst_role: &str, privileged: bool) -> RoleEdge {
+        let account = dst_role.split(':').nth(4).map(str::to_string);
+        RoleEdge {
+            edge_key: format!("{src_kind}|{src}|{dst_role}"),
+            src_kind: src_kind.to_string(),
+            src: src.to_string(),
+            dst_role: dst_role.to_string(),
+            dst_account: account,
+            src_account: None,
+            via: "AssumeRole".to_string(),
+            identity_source: None,
+            actor_id: Some("alice@dfds.com".to_string()),
+            privileged,
+            first_seen: Utc::now(),
+            last_seen: Utc::now(),
+            event_count: 1,
+        }
+    }
+
+    #[test]
+    fn paths_follow_role_chains_into_the_target_account() {
+        let sso = "arn:aws:iam::111111111111:role/AWSReservedSSO_ReadOnly_abc";
+        let deploy = "arn:aws:iam::111111111111:role/deploy";
+        let admin = "arn:aws:iam::222222222222:role/OrgAdmin";
+        let edges = vec![
+            edge("actor", "alice@dfds.com", sso, false),
+            edge("role", sso, deploy, false),
+            edge("role", deploy, admin, true),
+            edge("role", sso, admin, true),
+            // Chaining back into a role already on the path is not a new path.
+            edge("role", admin, sso, false),
+        ];
+        let (found, truncated) = find_paths(&edges, "actor:alice@dfds.com", 6, 10, |e| {
+            e.privileged && e.dst_account.as_deref() == Some("222222222222")
+        });
+        assert!(!truncated);
+        assert_eq!(found, vec![vec![0, 3], vec![0, 1, 2]]);
+
+        let (shallow, _) = find_paths(&edges, "actor:alice@dfds.com", 2, 10, |e| e.privileged);
+        assert_eq!(shallow, vec![vec![0, 3]]);
+
+        let (capped, truncated) =
+            find_paths(&edges, "actor:alice@dfds.com", 6, 1, |e| e.privileged);
+        assert_eq!(capped.len(), 1);
+        assert!(truncated);
+    }
+}
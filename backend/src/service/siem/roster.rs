@@ -0,0 +1,545 @@
+//! Roster history: joins and leaves between self-service roster snapshots.
+//!
+//! Every SIEM pass hands the freshly fetched roster to [`snapshot`], which diffs
+//! it against the last accepted one (`roster_memberships`) and appends what moved
+//! to `roster_changes`, per capability and for the roster as a whole. The
+//! `roster_leavers` view reads the latter: people whose latest roster-wide change
+//! is a leave. The `activity_after_leave` rule and the departed-identities report
+//! are built on it.
+//!
+//! A diff that drops too much of the roster at once is held in `roster_pending`
+//! behind a `roster_suspect_diff` alert until it repeats or an analyst acks it,
+//! so a truncated response neither floods leavers nor freezes the history.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Bool, Integer, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use log::{info, warn};
+
+use super::actors::RosterMember;
+use crate::misc::config::SiemConfig;
+
+/// Display details carried onto membership and change rows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemberInfo {
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+}
+
+/// `member_key → capabilities`, plus what we know about each member.
+#[derive(Default, Debug)]
+pub struct Snapshot {
+    pub memberships: BTreeMap<String, BTreeSet<String>>,
+    pub info: BTreeMap<String, MemberInfo>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Change {
+    pub member_key: String,
+    /// `None` = the roster as a whole.
+    pub capability: Option<String>,
+    pub join: bool,
+}
+
+/// The roster keyed the way `actors::reconcile` keys actors: lowercased email
+/// for people, lowercased object id for service principals.
+pub fn from_roster(roster: &[RosterMember]) -> Snapshot {
+    let mut snap = Snapshot::default();
+    for m in roster {
+        let key = match (&m.object_id, m.email.contains('@')) {
+            (_, true) => m.email.to_lowercase(),
+            (Some(oid), false) => oid.to_lowercase(),
+            (None, false) => continue,
+        };
+        let caps: BTreeSet<String> = m
+            .capabilities
+            .iter()
+            .chain(m.team.iter())
+            .cloned()
+            .collect();
+        // Only unnamed capabilities: nothing stable to diff against.
+        if caps.is_empty() {
+            continue;
+        }
+        snap.memberships
+            .entry(key.clone())
+            .or_default()
+            .extend(caps);
+        snap.info.insert(
+            key,
+            MemberInfo {
+                email: Some(m.email.clone()).filter(|e| e.contains('@')),
+                display_name: m.display_name.clone(),
+            },
+        );
+    }
+    snap
+}
+
+/// Every capability and roster-wide join/leave from `prev` to `cur`.
+pub fn diff(
+    prev: &BTreeMap<String, BTreeSet<String>>,
+    cur: &BTreeMap<String, BTreeSet<String>>,
+) -> Vec<Change> {
+    let empty = BTreeSet::new();
+    let mut out = Vec::new();
+    let keys: BTreeSet<&String> = prev.keys().chain(cur.keys()).collect();
+    for key in keys {
+        let before = prev.get(key);
+        let after = cur.get(key);
+        if before.is_none() != after.is_none() {
+            out.push(Change {
+                member_key: key.clone(),
+                capability: None,
+                join: after.is_some(),
+            });
+        }
+        let before = before.unwrap_or(&empty);
+        let after = after.unwrap_or(&empty);
+        for cap in after.difference(before) {
+            out.push(Change {
+                member_key: key.clone(),
+                capability: Some(cap.clone()),
+                join: true,
+            });
+        }
+        for cap in before.difference(after) {
+            out.push(Change {
+                member_key: key.clone(),
+                capability: Some(cap.clone()),
+                join: false,
+            });
+        }
+    }
+    out
+}
+
+#[derive(QueryableByName)]
+struct MembershipRow {
+    #[diesel(sql_type = Text)]
+    member_key: String,
+    #[diesel(sql_type = Text)]
+    capability: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    email: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    display_name: Option<String>,
+}
+
+/// Whether `leaves` roster-wide leavers out of `members` is more than the
+/// `max_leave_pct` a single fetch is trusted to drop.
+pub fn is_suspect(leaves: usize, members: usize, max_leave_pct: i64) -> bool {
+    members > 0 && (leaves as i64) * 100 > max_leave_pct * members as i64
+}
+
+/// How many fetches in a row have now produced `leavers`, given what is held.
+pub fn seen_count(held: Option<(&[String], i32)>, leavers: &[String]) -> i32 {
+    match held {
+        Some((prev, seen)) if prev == leavers => seen + 1,
+        _ => 1,
+    }
+}
+
+#[derive(QueryableByName)]
+struct PendingRow {
+    #[diesel(sql_type = Array<Text>)]
+    leavers: Vec<String>,
+    #[diesel(sql_type = Integer)]
+    seen: i32,
+    #[diesel(sql_type = Timestamptz)]
+    first_seen_at: DateTime<Utc>,
+}
+
+#[derive(QueryableByName)]
+struct AckedRow {
+    #[diesel(sql_type = Bool)]
+    acked: bool,
+}
+
+/// Hold a suspect diff back, or accept it once the same leavers came back
+/// `roster_confirm_fetches` times or its alert was acked. Returns whether the
+/// diff should be applied now.
+fn hold_or_accept(
+    conn: &mut PgConnection,
+    leavers: &[String],
+    members: usize,
+    at: DateTime<Utc>,
+    siem: &SiemConfig,
+) -> anyhow::Result<bool> {
+    let held: Option<PendingRow> =
+        diesel::sql_query("SELECT leavers, seen, first_seen_at FROM roster_pending FOR UPDATE")
+            .get_result(conn)
+            .optional()
+            .context("load pending roster diff")?;
+    let prev = held.as_ref().map(|p| (p.leavers.as_slice(), p.seen));
+    let seen = seen_count(prev, leavers);
+    // A different leaver set starts over, under a new alert.
+    let first_seen = match &held {
+        Some(p) if seen > 1 => p.first_seen_at,
+        _ => at,
+    };
+    let fingerprint = format!("roster_suspect_diff:{}", first_seen.timestamp());
+
+    let acked = diesel::sql_query(
+        "SELECT EXISTS (SELECT 1 FROM alerts WHERE fingerprint = $1 AND status = 'acked') AS acked",
+    )
+    .bind::<Text, _>(&fingerprint)
+    .get_result::<AckedRow>(conn)
+    .context("check roster alert ack")?
+    .acked;
+    let required = siem.roster_confirm_fetches.max(1);
+    if acked || seen as i64 >= required {
+        diesel::sql_query("DELETE FROM roster_pending")
+            .execute(conn)
+            .context("clear pending roster diff")?;
+        diesel::sql_query(
+            "UPDATE alerts SET status = 'resolved', resolved_at = now(), updated_at = now() \
+             WHERE fingerprint = $1 AND status <> 'resolved'",
+        )
+        .bind::<Text, _>(&fingerprint)
+        .execute(conn)
+        .context("resolve roster alert")?;
+        info!(
+            "siem/roster: accepting held diff :: leavers={} members={} seen={} acked={}",
+            leavers.len(),
+            members,
+            seen,
+            acked
+        );
+        return Ok(true);
+    }
+
+    diesel::sql_query(
+        "INSERT INTO roster_pending (id, leavers, members, seen, first_seen_at, last_seen_at) \
+         VALUES (true, $1, $2, $3, $4, $5) \
+         ON CONFLICT (id) DO UPDATE SET \
+           leavers = EXCLUDED.leavers, members = EXCLUDED.members, seen = EXCLUDED.seen, \
+           first_seen_at = EXCLUDED.first_seen_at, last_seen_at = EXCLUDED.last_seen_at",
+    )
+    .bind::<Array<Text>, _>(leavers)
+    .bind::<Integer, _>(members as i32)
+    .bind::<Integer, _>(seen)
+    .bind::<Timestamptz, _>(first_seen)
+    .bind::<Timestamptz, _>(at)
+    .execute(conn)
+    .context("hold roster diff")?;
+    diesel::sql_query(
+        "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
+         VALUES ($1, 'roster_suspect_diff', 'medium', 'Roster fetch would remove many members', \
+           $2 || ' of ' || $3 || ' members missing from the roster; diff held (' || $4 || '/' || $5 || ' identical fetches)', \
+           NULL, 'siem', $6, $7, $4, 'open', \
+           jsonb_build_object('leaves', $2, 'members', $3, 'seen', $4, 'required', $5, 'leavers', $8::text[]), now()) \
+         ON CONFLICT (fingerprint) DO UPDATE SET \
+           last_seen = EXCLUDED.last_seen, description = EXCLUDED.description, \
+           event_count = EXCLUDED.event_count, evidence = EXCLUDED.evidence, updated_at = now()",
+    )
+    .bind::<Text, _>(&fingerprint)
+    .bind::<Integer, _>(leavers.len() as i32)
+    .bind::<Integer, _>(members as i32)
+    .bind::<Integer, _>(seen)
+    .bind::<BigInt, _>(required)
+    .bind::<Timestamptz, _>(first_seen)
+    .bind::<Timestamptz, _>(at)
+    .bind::<Array<Text>, _>(&leavers[..leavers.len().min(MAX_ALERT_LEAVERS)])
+    .execute(conn)
+    .context("raise roster alert")?;
+    warn!(
+        "siem/roster: {} of {} members missing from this roster — diff held ({}/{})",
+        leavers.len(),
+        members,
+        seen,
+        required
+    );
+    Ok(false)
+}
+
+/// Leaver keys listed on a `roster_suspect_diff` alert.
+const MAX_ALERT_LEAVERS: usize = 50;
+
+/// Diff `roster` (as fetched at `at`) against the stored snapshot, record the
+/// changes and make it the new snapshot. Returns changes recorded.
+///
+/// An empty roster is what a failed or unconfigured fetch looks like, so it is
+/// never diffed. The first snapshot is a baseline and records nothing — nobody
+/// "joined" just because we started looking. A diff dropping more than
+/// `roster_max_leave_pct` of the members goes through [`hold_or_accept`].
+pub fn snapshot(
+    conn: &mut PgConnection,
+    roster: &[RosterMember],
+    at: DateTime<Utc>,
+    siem: &SiemConfig,
+) -> anyhow::Result<usize> {
+    let cur = from_roster(roster);
+    if cur.memberships.is_empty() {
+        info!("siem/roster: no roster this pass — snapshot diff skipped");
+        return Ok(0);
+    }
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let rows: Vec<MembershipRow> = diesel::sql_query(
+            "SELECT member_key, capability, email, display_name FROM roster_memberships",
+        )
+        .load(conn)
+        .context("load roster snapshot")?;
+        let baseline = rows.is_empty();
+        let mut prev: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut prev_info: BTreeMap<String, MemberInfo> = BTreeMap::new();
+        for r in rows {
+            prev.entry(r.member_key.clone())
+                .or_default()
+                .insert(r.capability);
+            prev_info.insert(
+                r.member_key,
+                MemberInfo {
+                    email: r.email,
+                    display_name: r.display_name,
+                },
+            );
+        }
+
+        let changes = if baseline {
+            Vec::new()
+        } else {
+            diff(&prev, &cur.memberships)
+        };
+        // `diff` walks keys in order, so the leaver list is already sorted.
+        let leavers: Vec<String> = changes
+            .iter()
+            .filter(|c| c.capability.is_none() && !c.join)
+            .map(|c| c.member_key.clone())
+            .collect();
+        if is_suspect(leavers.len(), prev.len(), siem.roster_max_leave_pct) {
+            if !hold_or_accept(conn, &leavers, prev.len(), at, siem)? {
+                return Ok(0);
+            }
+        } else {
+            diesel::sql_query("DELETE FROM roster_pending")
+                .execute(conn)
+                .context("clear pending roster diff")?;
+        }
+
+        for c in &changes {
+            let info = cur
+                .info
+                .get(&c.member_key)
+                .or_else(|| prev_info.get(&c.member_key))
+                .cloned()
+                .unwrap_or_default();
+            diesel::sql_query(
+                "INSERT INTO roster_changes (member_key, capability, change, email, display_name, changed_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind::<Text, _>(&c.member_key)
+            .bind::<Nullable<Text>, _>(c.capability.as_deref())
+            .bind::<Text, _>(if c.join { "join" } else { "leave" })
+            .bind::<Nullable<Text>, _>(info.email.as_deref())
+            .bind::<Nullable<Text>, _>(info.display_name.as_deref())
+            .bind::<Timestamptz, _>(at)
+            .execute(conn)
+            .context("record roster change")?;
+        }
+
+        for (key, caps) in &cur.memberships {
+            let info = cur.info.get(key).cloned().unwrap_or_default();
+            for cap in caps {
+                diesel::sql_query(
+                    "INSERT INTO roster_memberships (member_key, capability, email, display_name, first_seen_at, last_seen_at) \
+                     VALUES ($1, $2, $3, $4, $5, $5) \
+                     ON CONFLICT (member_key, capability) DO UPDATE SET \
+                       email = COALESCE(EXCLUDED.email, roster_memberships.email), \
+                       display_name = COALESCE(EXCLUDED.display_name, roster_memberships.display_name), \
+                       last_seen_at = EXCLUDED.last_seen_at",
+                )
+                .bind::<Text, _>(key)
+                .bind::<Text, _>(cap)
+                .bind::<Nullable<Text>, _>(info.email.as_deref())
+                .bind::<Nullable<Text>, _>(info.display_name.as_deref())
+                .bind::<Timestamptz, _>(at)
+                .execute(conn)
+                .context("upsert roster membership")?;
+            }
+        }
+        diesel::sql_query("DELETE FROM roster_memberships WHERE last_seen_at < $1")
+            .bind::<Timestamptz, _>(at)
+            .execute(conn)
+            .context("drop departed memberships")?;
+
+        if baseline {
+            info!(
+                "siem/roster: baseline snapshot taken :: members={}",
+                cur.memberships.len()
+            );
+        }
+        Ok(changes.len())
+    })
+}
+
+#[derive(QueryableByName, serde::Serialize, Debug)]
+pub struct RosterChangeRow {
+    #[diesel(sql_type = BigInt)]
+    pub id: i64,
+    #[diesel(sql_type = Text)]
+    pub member_key: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub capability: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub change: String,
+    #[diesel(sql_type = Timestamptz)]
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Roster changes of one actor (any key that resolves to it), newest first.
+pub fn changes_of(
+    conn: &mut PgConnection,
+    actor_id: &str,
+    limit: i64,
+) -> anyhow::Result<Vec<RosterChangeRow>> {
+    diesel::sql_query(
+        "SELECT rc.id, rc.member_key, rc.capability, rc.change, rc.changed_at \
+         FROM roster_changes rc \
+         WHERE rc.member_key = lower($1) \
+            OR rc.member_key IN (SELECT lower(alias) FROM actor_aliases WHERE actor_id = $1) \
+         ORDER BY rc.changed_at DESC, rc.id DESC LIMIT $2",
+    )
+    .bind::<Text, _>(actor_id)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+    .context("load roster changes")
+}
+
+#[derive(QueryableByName, serde::Serialize, Debug)]
+pub struct DepartedRow {
+    #[diesel(sql_type = Text)]
+    pub actor_id: String,
+    #[diesel(sql_type = Text)]
+    pub member_key: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub email: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub display_name: Option<String>,
+    #[diesel(sql_type = Timestamptz)]
+    pub left_at: DateTime<Utc>,
+    /// Capabilities they were dropped from in that same leave.
+    #[diesel(sql_type = Array<Text>)]
+    pub capabilities: Vec<String>,
+    #[diesel(sql_type = BigInt)]
+    pub active_grants: i64,
+    #[diesel(sql_type = BigInt)]
+    pub privileged_grants: i64,
+    /// Unrevoked `grants` rows: system, role, scope, privileged, granted_at.
+    #[diesel(sql_type = Jsonb)]
+    pub grants: serde_json::Value,
+    /// GitHub orgs/repos whose latest membership event for one of the actor's
+    /// logins is not a removal: scope, login, action, permission, at.
+    #[diesel(sql_type = Jsonb)]
+    pub github_access: serde_json::Value,
+    #[diesel(sql_type = Nullable<Timestamptz>)]
+    pub last_activity: Option<DateTime<Utc>>,
+}
+
+/// People who left the roster, with whatever access they still hold. Unless
+/// `include_all`, only those with an active grant or GitHub membership.
+pub fn departed(
+    conn: &mut PgConnection,
+    include_all: bool,
+    limit: i64,
+) -> anyhow::Result<Vec<DepartedRow>> {
+    diesel::sql_query(
+        "SELECT l.actor_id, l.member_key, l.email, l.display_name, l.left_at, \
+                COALESCE(caps.capabilities, '{}') AS capabilities, \
+                g.active_grants, g.privileged_grants, g.grants, gh.github_access, act.last_activity \
+         FROM roster_leavers l \
+         LEFT JOIN LATERAL ( \
+           SELECT array_agg(DISTINCT rc.capability ORDER BY rc.capability) AS capabilities \
+           FROM roster_changes rc \
+           WHERE rc.member_key = l.member_key AND rc.capability IS NOT NULL \
+             AND rc.change = 'leave' AND rc.changed_at = l.left_at \
+         ) caps ON true \
+         CROSS JOIN LATERAL ( \
+           SELECT count(*) AS active_grants, count(*) FILTER (WHERE gr.privileged) AS privileged_grants, \
+             COALESCE(jsonb_agg(jsonb_build_object('system', gr.system, 'role', gr.role, 'scope', gr.scope, \
+               'privileged', gr.privileged, 'granted_at', gr.granted_at) \
+               ORDER BY gr.privileged DESC, gr.granted_at DESC), '[]'::jsonb) AS grants \
+           FROM grants gr WHERE gr.actor_id = l.actor_id AND gr.revoked_at IS NULL \
+         ) g \
+         CROSS JOIN LATERAL ( \
+           SELECT COALESCE(jsonb_agg(jsonb_build_object('scope', m.scope, 'login', m.login, 'action', m.action, \
+               'permission', m.permission, 'at', m.event_time) ORDER BY m.event_time DESC), '[]'::jsonb) AS github_access \
+           FROM ( \
+             SELECT DISTINCT ON (ge.raw->>'user', COALESCE(ge.repo, ge.org)) \
+               ge.raw->>'user' AS login, COALESCE(ge.repo, ge.org) AS scope, ge.action, ge.event_time, \
+               COALESCE(ge.raw->>'permission', ge.raw->>'role') AS permission \
+             FROM github_audit_events ge \
+             JOIN actor_aliases aa ON aa.alias = ge.raw->>'user' AND aa.actor_id = l.actor_id \
+             WHERE ge.action IN ('org.add_member', 'org.update_member', 'org.remove_member', \
+                                 'repo.add_member', 'repo.update_member', 'repo.remove_member') \
+             ORDER BY ge.raw->>'user', COALESCE(ge.repo, ge.org), ge.event_time DESC \
+           ) m \
+           WHERE m.action NOT LIKE '%.remove_member' \
+         ) gh \
+         LEFT JOIN LATERAL ( \
+           SELECT max(f.last_ts) AS last_activity \
+           FROM actor_aliases aa JOIN actor_source_first_seen f ON f.actor = aa.alias \
+           WHERE aa.actor_id = l.actor_id AND f.source <> 'ssu-mgmt' \
+         ) act ON true \
+         WHERE $1 OR g.active_grants > 0 OR jsonb_array_length(gh.github_access) > 0 \
+         ORDER BY g.privileged_grants DESC, l.left_at DESC LIMIT $2",
+    )
+    .bind::<Bool, _>(include_all)
+    .bind::<BigInt, _>(limit)
+    .load(conn)
+    .context("load departed identities")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(entries: &[(&str, &[&str])]) -> BTreeMap<String, BTreeSet<String>> {
+        entries
+            .iter()
+            .map(|(k, caps)| (k.to_string(), caps.iter().map(|c| c.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_records_capability_and_roster_wide_moves() {
+        let prev = snap(&[("alice@dfds.com", &["a", "b"]), ("bob@dfds.com", &["a"])]);
+        let cur = snap(&[("alice@dfds.com", &["b", "c"]), ("carol@dfds.com", &["c"])]);
+        let got: Vec<(String, Option<String>, bool)> = diff(&prev, &cur)
+            .into_iter()
+            .map(|c| (c.member_key, c.capability, c.join))
+            .collect();
+        let s = |x: &str| x.to_string();
+        assert_eq!(
+            got,
+            vec![
+                (s("alice@dfds.com"), Some(s("c")), true),
+                (s("alice@dfds.com"), Some(s("a")), false),
+                (s("bob@dfds.com"), None, false),
+                (s("bob@dfds.com"), Some(s("a")), false),
+                (s("carol@dfds.com"), None, true),
+                (s("carol@dfds.com"), Some(s("c")), true),
+            ]
+        );
+        assert!(diff(&cur, &cur).is_empty());
+    }
+
+    #[test]
+    fn suspect_diffs_are_held_until_they_repeat() {
+        assert!(!is_suspect(2, 10, 20));
+        assert!(is_suspect(3, 10, 20));
+        assert!(!is_suspect(0, 0, 20));
+        assert!(!is_suspect(10, 10, 100));
+
+        let a = vec!["alice@dfds.com".to_string(), "bob@dfds.com".to_string()];
+        let b = vec!["alice@dfds.com".to_string()];
+        assert_eq!(seen_count(None, &a), 1);
+        assert_eq!(seen_count(Some((&a, 1)), &a), 2);
+        assert_eq!(seen_count(Some((&a, 2)), &b), 1);
+    }
+}
@@ -0,0 +1,579 @@
+//! Fixture-driven tests for the SIEM rules and detectors.
+//!
+//! Each `fixtures/rules/*.json` case holds raw CloudTrail records and GitHub audit
+//! entries (and optionally inline threat-intel feeds) plus the `alerts`/`anomalies`
+//! rows a full pass is expected to produce.
+//! The harness migrates a throwaway Postgres schema, loads the records through
+//! the real ingest mappers (`cloudtrail::map_record`, `github::map_entry`), runs
+//! `siem::derive_all` and diffs the derived rows against the expectation.
+//!
+//! Needs a disposable database: set `SSU_TEST_DATABASE_URL`
+//! (e.g. `postgres://postgres:p@localhost:5432/postgres` against the compose db).
+//! Unset, the suite is skipped rather than failed so `cargo test` stays usable
+//! without Postgres.
+//!
+//! A case may also list the `sessions` a pass should stitch (`actor_id`,
+//! `source`, `source_ip`, `event_count` and `vias`, the chain's login calls);
+//! cases without the key don't check sessions.
+//!
+//! A case can also give `roster_history` — earlier roster snapshots (`at`,
+//! `roster`) diffed in order before the pass — to exercise joins and leaves.
+//!
+//! Fixture timestamps are relative so cases don't rot: any string value equal to
+//! `$now`, `$now-<n><s|m|h|d>` or `$day-<n>@HH:MM` (UTC midnight `n` days ago plus
+//! the clock time) is replaced before mapping. Expected rows are partial objects —
+//! only the keys a case lists are compared — and must match the produced rows
+//! one-to-one.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, BigInt, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use diesel_migrations::MigrationHarness;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tokio_util::sync::CancellationToken;
+
+use crate::db::model::{CloudtrailEventInsert, GithubAuditEventInsert};
+use crate::misc::config::{Config, ThreatIntelConfig};
+use crate::service::ingest::{cloudtrail, github};
+use crate::service::siem::actors::RosterMember;
+use crate::service::siem::geoip::GeoIp;
+use crate::service::siem::roster;
+use crate::service::siem::sessions::SESSIONS_WATERMARK_SOURCE;
+use crate::service::siem::threat_intel::{self, FeedFormat};
+
+const DB_URL_ENV: &str = "SSU_TEST_DATABASE_URL";
+
+/// Rows are stamped as ingested this long ago. The harvests only fold rows some
+/// minutes behind the newest `created_at`, so the loaded batch has to sit behind
+/// the tick row (see `load_tick`).
+const LOADED_AGO_MINS: i64 = 30;
+const TICK_AGO_MINS: i64 = 16;
+
+#[derive(Deserialize)]
+struct Fixture {
+    #[serde(default)]
+    description: String,
+    /// Partial `SiemConfig` overrides, merged over the defaults.
+    #[serde(default)]
+    siem: Map<String, Value>,
+    #[serde(default)]
+    roster: Vec<FixtureMember>,
+    /// Earlier roster snapshots, diffed in order before the pass (the first one
+    /// is the baseline); the pass itself then diffs against `roster`.
+    #[serde(default)]
+    roster_history: Vec<FixtureSnapshot>,
+    #[serde(default)]
+    cloudtrail: Vec<Value>,
+    #[serde(default)]
+    github: Vec<Value>,
+    /// Inline threat-intel feeds, loaded through the real manifest reload.
+    #[serde(default)]
+    threat_intel: Vec<FixtureFeed>,
+    expect: Expect,
+}
+
+#[derive(Deserialize)]
+struct FixtureFeed {
+    name: String,
+    format: FeedFormat,
+    #[serde(default)]
+    confidence: Option<i32>,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct FixtureSnapshot {
+    at: DateTime<Utc>,
+    roster: Vec<FixtureMember>,
+}
+
+#[derive(Deserialize)]
+struct FixtureMember {
+    email: String,
+    #[serde(default)]
+    team: Option<String>,
+    #[serde(default)]
+    display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Expect {
+    #[serde(default)]
+    alerts: Vec<Map<String, Value>>,
+    #[serde(default)]
+    anomalies: Vec<Map<String, Value>>,
+    #[serde(default)]
+    sessions: Option<Vec<Map<String, Value>>>,
+}
+
+#[derive(QueryableByName)]
+struct AlertRow {
+    #[diesel(sql_type = Text)]
+    fingerprint: String,
+    #[diesel(sql_type = Text)]
+    rule_id: String,
+    #[diesel(sql_type = Text)]
+    severity: String,
+    #[diesel(sql_type = Text)]
+    status: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    actor_id: Option<String>,
+    #[diesel(sql_type = Text)]
+    source: String,
+    #[diesel(sql_type = Array<Nullable<Text>>)]
+    attack_techniques: Vec<Option<String>>,
+}
+
+#[derive(QueryableByName)]
+struct AnomalyRow {
+    #[diesel(sql_type = Text)]
+    fingerprint: String,
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = Text)]
+    severity: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    actor_id: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct SessionRow {
+    #[diesel(sql_type = Nullable<Text>)]
+    actor_id: Option<String>,
+    #[diesel(sql_type = Text)]
+    source: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    source_ip: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    event_count: i64,
+    #[diesel(sql_type = Jsonb)]
+    chain: Value,
+}
+
+fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/rules")
+}
+
+/// Expand the relative-time placeholders described in the module docs.
+fn expand_time(s: &str, now: DateTime<Utc>) -> Option<String> {
+    let t = if s == "$now" {
+        now
+    } else if let Some(rest) = s.strip_prefix("$now-") {
+        let (n, unit) = rest.split_at(rest.len().checked_sub(1)?);
+        let n: i64 = n.parse().ok()?;
+        let d = match unit {
+            "s" => Duration::seconds(n),
+            "m" => Duration::minutes(n),
+            "h" => Duration::hours(n),
+            "d" => Duration::days(n),
+            _ => return None,
+        };
+        now - d
+    } else if let Some(rest) = s.strip_prefix("$day-") {
+        let (n, hm) = rest.split_once('@')?;
+        let n: i64 = n.parse().ok()?;
+        let clock = NaiveTime::parse_from_str(hm, "%H:%M").ok()?;
+        (now.date_naive() - Duration::days(n))
+            .and_time(clock)
+            .and_utc()
+    } else {
+        return None;
+    };
+    Some(t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+}
+
+fn expand(v: &mut Value, now: DateTime<Utc>) {
+    match v {
+        Value::String(s) if s.starts_with('$') => {
+            if let Some(t) = expand_time(s, now) {
+                *s = t;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|i| expand(i, now)),
+        Value::Object(map) => map.values_mut().for_each(|i| expand(i, now)),
+        _ => {}
+    }
+}
+
+fn scratch_schema(case: &str) -> String {
+    let slug: String = case
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("siem_rules_{}_{}", std::process::id(), slug)
+}
+
+/// Fresh schema, first on the `search_path`, with every migration applied.
+fn migrate_scratch(conn: &mut PgConnection, schema: &str) -> anyhow::Result<()> {
+    diesel::sql_query(format!("DROP SCHEMA IF EXISTS {schema} CASCADE"))
+        .execute(conn)
+        .context("drop stale scratch schema")?;
+    diesel::sql_query(format!("CREATE SCHEMA {schema}"))
+        .execute(conn)
+        .context("create scratch schema")?;
+    diesel::sql_query(format!("SET search_path TO {schema}, public"))
+        .execute(conn)
+        .context("set search_path")?;
+    conn.run_pending_migrations(crate::db::MIGRATIONS)
+        .map_err(|e| anyhow::anyhow!("migrate scratch schema: {e}"))?;
+    Ok(())
+}
+
+fn load_events(
+    conn: &mut PgConnection,
+    case: &str,
+    fx: &Fixture,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let loaded_at = now - Duration::minutes(LOADED_AGO_MINS);
+    let epoch = DateTime::<Utc>::from_timestamp(0, 0).expect("epoch");
+    // Map with the production allowlist: a rule whose events aren't ingested by
+    // default would never fire, and the fixture should say so loudly. Root calls
+    // are ingested whatever their name, as in production.
+    let allowlist = cloudtrail::default_allowlist();
+    for rec in &fx.cloudtrail {
+        let name = rec.get("eventName").and_then(Value::as_str).unwrap_or("");
+        if !cloudtrail::is_root(rec) && !allowlist.iter().any(|a| a == name) {
+            anyhow::bail!("{case}: eventName {name:?} is not in cloudtrail::default_allowlist");
+        }
+    }
+
+    let key = format!("fixtures/rules/{case}.json");
+    let ct: Vec<CloudtrailEventInsert> = fx
+        .cloudtrail
+        .iter()
+        .enumerate()
+        .map(|(i, rec)| {
+            cloudtrail::map_record(rec.clone(), &allowlist, false, epoch, loaded_at, &key)
+                .with_context(|| format!("{case}: cloudtrail record #{i} was not mapped"))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let gh: Vec<GithubAuditEventInsert> = fx
+        .github
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            github::map_entry(entry)
+                .map(|mut row| {
+                    row.created_at = loaded_at;
+                    row
+                })
+                .with_context(|| format!("{case}: github entry #{i} was not mapped"))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    diesel::insert_into(crate::schema::cloudtrail_events::table)
+        .values(&ct)
+        .execute(conn)
+        .context("insert cloudtrail fixtures")?;
+    diesel::insert_into(crate::schema::github_audit_events::table)
+        .values(&gh)
+        .execute(conn)
+        .context("insert github fixtures")?;
+    load_tick(conn, now)?;
+    load_feeds(conn, case, fx)?;
+
+    // A fresh sessions watermark starts at "now" and would skip the fixtures, as
+    // a fresh deployment skips history; start it at the epoch instead.
+    diesel::sql_query(
+        "INSERT INTO ingest_watermarks (source, last_event_at, last_run_at, objects_scanned, events_applied) \
+         VALUES ($1, $2, now(), 0, 0)",
+    )
+    .bind::<Text, _>(SESSIONS_WATERMARK_SOURCE)
+    .bind::<Timestamptz, _>(epoch)
+    .execute(conn)
+    .context("seed sessions watermark")?;
+    Ok(())
+}
+
+/// An actor-less CloudTrail row ingested after the fixtures. The first-seen and
+/// identity-context harvests lag the newest `created_at` by a few minutes, so
+/// without a later row they'd never fold the fixture batch itself. Every rule
+/// and harvest drops rows without a principal, so the tick is otherwise inert.
+fn load_tick(conn: &mut PgConnection, now: DateTime<Utc>) -> anyhow::Result<()> {
+    let at = now - Duration::minutes(TICK_AGO_MINS);
+    diesel::insert_into(crate::schema::cloudtrail_events::table)
+        .values(&CloudtrailEventInsert {
+            event_id: "harness-tick".to_string(),
+            event_time: at,
+            event_name: "HarnessTick".to_string(),
+            event_source: "harness".to_string(),
+            aws_region: None,
+            recipient_account_id: None,
+            user_identity_account_id: None,
+            principal_arn: None,
+            principal_type: None,
+            principal_name: None,
+            assumed_role_arn: None,
+            identity_source: None,
+            auth_method: None,
+            mfa_used: None,
+            source_ip: None,
+            user_agent: None,
+            error_code: None,
+            read_only: Some(true),
+            management_event: Some(true),
+            s3_object_key: None,
+            raw: json!({ "eventName": "HarnessTick" }),
+            created_at: at,
+        })
+        .execute(conn)
+        .context("insert harness tick")?;
+    Ok(())
+}
+
+/// Write the fixture's feeds and a manifest naming them to a scratch directory,
+/// then run the loader's `reload` against it.
+fn load_feeds(conn: &mut PgConnection, case: &str, fx: &Fixture) -> anyhow::Result<()> {
+    if fx.threat_intel.is_empty() {
+        return Ok(());
+    }
+    let dir = std::env::temp_dir().join(scratch_schema(case));
+    std::fs::create_dir_all(&dir).context("create feed dir")?;
+    let mut feeds = Vec::new();
+    for feed in &fx.threat_intel {
+        let path = dir.join(&feed.name);
+        std::fs::write(&path, &feed.content).context("write feed")?;
+        feeds.push(json!({
+            "name": feed.name,
+            "path": path.to_string_lossy(),
+            "format": feed.format,
+            "confidence": feed.confidence,
+        }));
+    }
+    let manifest = dir.join("manifest.json");
+    std::fs::write(&manifest, json!({ "feeds": feeds }).to_string()).context("write manifest")?;
+
+    let conf = ThreatIntelConfig {
+        manifest_path: manifest.to_string_lossy().into_owned(),
+        ..ThreatIntelConfig::default()
+    };
+    threat_intel::reload(conn, &conf)
+        .with_context(|| format!("{case}: load threat-intel feeds"))?;
+    Ok(())
+}
+
+fn config_for(fx: &Fixture) -> anyhow::Result<Config> {
+    let mut conf = Config::default();
+    let mut siem = serde_json::to_value(&conf.siem).context("serialize siem config")?;
+    if let Value::Object(base) = &mut siem {
+        for (k, v) in &fx.siem {
+            if !base.contains_key(k) {
+                anyhow::bail!("unknown siem override {k}");
+            }
+            base.insert(k.clone(), v.clone());
+        }
+    }
+    conf.siem = serde_json::from_value(siem).context("apply siem overrides")?;
+    Ok(conf)
+}
+
+/// Pair every expectation with one produced row whose listed keys all match;
+/// report what's left on either side.
+fn diff(label: &str, expected: &[Map<String, Value>], produced: Vec<Value>) -> Vec<String> {
+    let mut remaining = produced;
+    let mut problems = Vec::new();
+    for want in expected {
+        let hit = remaining
+            .iter()
+            .position(|row| want.iter().all(|(k, v)| row.get(k) == Some(v)));
+        match hit {
+            Some(i) => {
+                remaining.swap_remove(i);
+            }
+            None => problems.push(format!("missing {label}: {}", Value::Object(want.clone()))),
+        }
+    }
+    for row in remaining {
+        problems.push(format!("unexpected {label}: {row}"));
+    }
+    problems
+}
+
+fn roster_members(members: &[FixtureMember]) -> Vec<RosterMember> {
+    members
+        .iter()
+        .map(|m| RosterMember {
+            email: m.email.clone(),
+            upn: None,
+            display_name: m.display_name.clone(),
+            team: m.team.clone(),
+            capabilities: m.team.iter().cloned().collect(),
+            object_id: None,
+        })
+        .collect()
+}
+
+fn run_case(conn: &mut PgConnection, case: &str, fx: &Fixture) -> anyhow::Result<Vec<String>> {
+    let schema = scratch_schema(case);
+    migrate_scratch(conn, &schema)?;
+
+    let outcome = (|| -> anyhow::Result<Vec<String>> {
+        let now = Utc::now();
+        load_events(conn, case, fx, now)?;
+
+        let conf = config_for(fx)?;
+        for snap in &fx.roster_history {
+            roster::snapshot(conn, &roster_members(&snap.roster), snap.at, &conf.siem)
+                .context("roster history")?;
+        }
+        let roster = roster_members(&fx.roster);
+        super::derive_all(
+            conn,
+            &conf,
+            &roster,
+            &GeoIp::default(),
+            &CancellationToken::new(),
+        )
+        .context("siem pass")?;
+
+        let alerts: Vec<AlertRow> = diesel::sql_query(
+            "SELECT fingerprint, rule_id, severity, status, actor_id, source, attack_techniques FROM alerts ORDER BY fingerprint",
+        )
+        .load(conn)
+        .context("load alerts")?;
+        let anomalies: Vec<AnomalyRow> = diesel::sql_query(
+            "SELECT fingerprint, kind, severity, actor_id FROM anomalies ORDER BY fingerprint",
+        )
+        .load(conn)
+        .context("load anomalies")?;
+
+        let mut problems = diff(
+            "alert",
+            &fx.expect.alerts,
+            alerts
+                .into_iter()
+                .map(|a| {
+                    json!({
+                        "fingerprint": a.fingerprint, "rule_id": a.rule_id, "severity": a.severity,
+                        "status": a.status, "actor_id": a.actor_id, "source": a.source,
+                        "attack_techniques": a.attack_techniques,
+                    })
+                })
+                .collect(),
+        );
+        problems.extend(diff(
+            "anomaly",
+            &fx.expect.anomalies,
+            anomalies
+                .into_iter()
+                .map(|a| {
+                    json!({
+                        "fingerprint": a.fingerprint, "kind": a.kind,
+                        "severity": a.severity, "actor_id": a.actor_id,
+                    })
+                })
+                .collect(),
+        ));
+        if let Some(expected) = &fx.expect.sessions {
+            let sessions: Vec<SessionRow> = diesel::sql_query(
+                "SELECT actor_id, source, source_ip, event_count, chain FROM sessions ORDER BY session_key",
+            )
+            .load(conn)
+            .context("load sessions")?;
+            problems.extend(diff(
+                "session",
+                expected,
+                sessions
+                    .into_iter()
+                    .map(|s| {
+                        let vias: Vec<Value> = s
+                            .chain
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(|l| l.get("via").cloned().unwrap_or(Value::Null))
+                            .collect();
+                        json!({
+                            "actor_id": s.actor_id, "source": s.source, "source_ip": s.source_ip,
+                            "event_count": s.event_count, "vias": vias,
+                        })
+                    })
+                    .collect(),
+            ));
+        }
+        Ok(problems)
+    })();
+
+    let _ = diesel::sql_query(format!("DROP SCHEMA IF EXISTS {schema} CASCADE")).execute(conn);
+    let _ = diesel::sql_query("SET search_path TO DEFAULT").execute(conn);
+    outcome
+}
+
+#[test]
+fn expansion_of_relative_times() {
+    let now = DateTime::parse_from_rfc3339("2026-06-20T12:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    assert_eq!(
+        expand_time("$now", now).as_deref(),
+        Some("2026-06-20T12:00:00.000Z")
+    );
+    assert_eq!(
+        expand_time("$now-90m", now).as_deref(),
+        Some("2026-06-20T10:30:00.000Z")
+    );
+    assert_eq!(
+        expand_time("$day-2@03:15", now).as_deref(),
+        Some("2026-06-18T03:15:00.000Z")
+    );
+    assert_eq!(expand_time("$now-3w", now), None);
+    assert_eq!(expand_time("$literal", now), None);
+}
+
+#[test]
+fn rule_fixtures() {
+    let Ok(url) = std::env::var(DB_URL_ENV) else {
+        eprintln!("{DB_URL_ENV} unset — skipping SIEM rule fixtures");
+        return;
+    };
+    let mut conn = PgConnection::establish(&url).expect("connect to test database");
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(fixture_dir())
+        .expect("read fixtures/rules")
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|x| x == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no rule fixtures found");
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let case = path.file_stem().unwrap().to_string_lossy().to_string();
+        let raw = std::fs::read_to_string(path).expect("read fixture");
+        let mut doc: Value = serde_json::from_str(&raw)
+            .unwrap_or_else(|e| panic!("{case}: invalid fixture json: {e}"));
+        expand(&mut doc, Utc::now());
+        let fx: Fixture =
+            serde_json::from_value(doc).unwrap_or_else(|e| panic!("{case}: fixture shape: {e}"));
+
+        match run_case(&mut conn, &case, &fx) {
+            Ok(problems) if problems.is_empty() => {}
+            Ok(problems) => failures.push(format!(
+                "{case} ({}):\n  {}",
+                fx.description,
+                problems.join("\n  ")
+            )),
+            Err(e) => failures.push(format!("{case}: {e:#}")),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} rule fixtures failed:\n{}",
+        failures.len(),
+        paths.len(),
+        failures.join("\n")
+    );
+}
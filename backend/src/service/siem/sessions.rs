@@ -1,50 +1,141 @@
+//! Cross-source work sessions.
+//!
+//! A session is one actor's run of activity across every `ssumgmt_events`
+//! source (CloudTrail, GitHub, self-service, this console), split wherever the
+//! actor is silent for more than `siem.session_gap_mins` or shows up from a new
+//! source IP, so every session has at most one address. Each row carries
+//! per-source event counts and a `chain` linking the SSO `ConsoleLogin` /
+//! `GetRoleCredentials` calls to the role sessions they spawned, plus the
+//! authentication strength of those sign-ins.
+//!
+//! Sessions ending within the settle horizon are rebuilt from scratch every
+//! pass, so late-delivered events land in the right session; older sessions
+//! are final. Service and unknown actors are busy around the clock and would
+//! form one endless session, so theirs are also split at each UTC midnight.
+//!
+//! Rows written before stitching (`principal|ip|day` keys) are left for
+//! retention to age out; stitching starts after the last of them.
+
+use std::collections::BTreeMap;
+
 use anyhow::Context;
 use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
-use diesel::sql_types::{Array, BigInt, Nullable, Text, Timestamptz};
+use diesel::sql_types::{Array, BigInt, Bool, Integer, Jsonb, Nullable, Text, Timestamptz};
 use diesel::PgConnection;
+use serde::Serialize;
+use serde_json::{json, Value};
 
+use crate::misc::config::SiemConfig;
 use crate::service::ingest::get_watermark;
-use crate::service::siem::geoip::GeoIp;
+use crate::service::siem::geoip::{GeoIp, NetworkClass, NetworkInfo};
 
 const ACTIVE_WINDOW_MINS: i64 = 15;
 
 pub const SESSIONS_WATERMARK_SOURCE: &str = "siem_sessions";
-const SESSIONS_SAFETY_MARGIN_MINS: i64 = 15;
-const MAX_SESSIONS_STEP_HOURS: i64 = 3;
+/// Sessions still seeing activity this recently are rebuilt every pass; the
+/// slowest source (GitHub audit polling) delivers well inside it.
+const SESSIONS_SETTLE_HOURS: i64 = 6;
 const SESSIONS_UPSERT_CHUNK: usize = 1000;
+/// Prefix of stitched session keys (`stitch|<actor>|<started_at>`).
+const STITCH_KEY_PREFIX: &str = "stitch|";
 
 /// A single derived session row, ready to upsert.
 struct SessionUpsert {
     session_key: String,
-    actor_id: Option<String>,
+    actor_id: String,
+    source: String,
     device: Option<String>,
     source_ip: Option<String>,
     location: Option<String>,
+    asn: Option<i64>,
+    as_org: Option<String>,
+    network_class: Option<&'static str>,
     started_at: DateTime<Utc>,
     last_seen_at: DateTime<Utc>,
     event_count: i64,
     status: String,
+    source_counts: Value,
+    chain: Value,
+    auth_method: Option<String>,
+    mfa_used: Option<bool>,
 }
 
+/// One row of the stitching query: either a per-(session, source, role)
+/// aggregate (`kind = 'agg'`) or a single SSO login event (`kind = 'login'`).
 #[derive(QueryableByName)]
-struct SessionCandidate {
+struct StitchRow {
+    #[diesel(sql_type = Text)]
+    kind: String,
+    #[diesel(sql_type = Text)]
+    actor_id: String,
+    #[diesel(sql_type = BigInt)]
+    sn: i64,
     #[diesel(sql_type = Text)]
-    principal: String,
+    source: String,
     #[diesel(sql_type = Nullable<Text>)]
-    source_ip: Option<String>,
-    #[diesel(sql_type = Timestamptz)]
-    day: DateTime<Utc>,
+    role: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    action: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    n: i64,
     #[diesel(sql_type = Timestamptz)]
-    started_at: DateTime<Utc>,
+    first_ts: DateTime<Utc>,
     #[diesel(sql_type = Timestamptz)]
-    last_seen_at: DateTime<Utc>,
-    #[diesel(sql_type = BigInt)]
-    event_count: i64,
+    last_ts: DateTime<Utc>,
+    #[diesel(sql_type = Nullable<Text>)]
+    source_ip: Option<String>,
     #[diesel(sql_type = Nullable<Text>)]
     user_agent: Option<String>,
     #[diesel(sql_type = Nullable<Text>)]
-    actor_id: Option<String>,
+    role_name: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    role_account: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    auth_method: Option<String>,
+    #[diesel(sql_type = Nullable<Bool>)]
+    mfa_used: Option<bool>,
+}
+
+/// An SSO login inside a session.
+#[derive(Debug, Clone)]
+struct Login {
+    action: String,
+    at: DateTime<Utc>,
+    source_ip: Option<String>,
+    /// The role the event itself ran as (`ConsoleLogin` into an SSO role).
+    role_arn: Option<String>,
+    /// `GetRoleCredentials` request: the permission set and account asked for.
+    role_name: Option<String>,
+    account: Option<String>,
+    /// `cloudtrail_events.auth_method` / `mfa_used` of a `ConsoleLogin`.
+    auth_method: Option<String>,
+    mfa_used: Option<bool>,
+}
+
+/// CloudTrail activity of one role inside a session.
+#[derive(Debug, Clone)]
+struct RoleActivity {
+    role_arn: String,
+    events: i64,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+/// One link of a session's chain: a login and the role session it spawned,
+/// or (`via: None`) role activity with no login in the session — typically
+/// CLI credentials fetched earlier.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ChainLink {
+    pub via: Option<String>,
+    pub at: DateTime<Utc>,
+    pub source_ip: Option<String>,
+    pub role_arn: Option<String>,
+    pub role_events: i64,
+    pub role_first_seen: Option<DateTime<Utc>>,
+    pub role_last_seen: Option<DateTime<Utc>>,
+    pub auth_method: Option<String>,
+    pub mfa_used: Option<bool>,
 }
 
 /// Best-effort device label from a CloudTrail user agent.
@@ -70,72 +161,337 @@ fn device_of(ua: Option<&str>) -> Option<String> {
     Some(label.to_string())
 }
 
-pub fn derive(conn: &mut PgConnection, geoip: &GeoIp, window_days: i64) -> anyhow::Result<usize> {
+/// Device label for a session without CloudTrail activity.
+fn device_of_source(source: &str) -> Option<String> {
+    let label = match source {
+        "github" => "GitHub",
+        "selfservice" => "Self-service portal",
+        "ssu-mgmt" => "SSU console",
+        _ => return None,
+    };
+    Some(label.to_string())
+}
+
+/// Session location label. A trusted egress is labelled as such instead of
+/// with the GeoLite2 city, which would only place our own proxy (and would read
+/// as a real login country downstream). Hosting/VPN egress keeps its GeoLite2
+/// location; `network_class` marks it for the detectors.
+fn location_of(geoip: &GeoIp, ip: Option<&str>, net: Option<&NetworkInfo>) -> Option<String> {
+    let ip = ip?;
+    match net.map(|n| (n.class, n.org.as_deref())) {
+        Some((NetworkClass::Trusted, Some(org))) => Some(format!("Trusted egress ({})", org)),
+        Some((NetworkClass::Trusted, None)) => Some("Trusted egress".to_string()),
+        _ => geoip.lookup(ip),
+    }
+}
+
+/// Does `arn` name the role a `GetRoleCredentials` call for `name` in
+/// `account` hands out? SSO permission sets surface as
+/// `…:role/aws-reserved/sso.amazonaws.com/<region>/AWSReservedSSO_<name>_<hash>`.
+fn role_matches(arn: &str, name: &str, account: Option<&str>) -> bool {
+    let mut parts = arn.splitn(6, ':');
+    let acct = parts.nth(4);
+    if account.is_some_and(|a| Some(a) != acct) {
+        return false;
+    }
+    let leaf = arn.rsplit('/').next().unwrap_or(arn);
+    leaf == name || leaf.starts_with(&format!("AWSReservedSSO_{name}_"))
+}
+
+/// Pair each login with the role session it spawned. A `ConsoleLogin` runs as
+/// its role already; a `GetRoleCredentials` names a permission set and account,
+/// matched against the roles active in the session at or after the login.
+/// Roles no login claims follow as `via: None` links.
+fn link_chain(logins: &[Login], roles: &[RoleActivity]) -> Vec<ChainLink> {
+    let mut claimed = vec![false; roles.len()];
+    let mut chain = Vec::with_capacity(logins.len());
+    for l in logins {
+        let idx = match (l.action.as_str(), l.role_name.as_deref()) {
+            ("GetRoleCredentials", Some(name)) => roles.iter().position(|r| {
+                r.last_seen >= l.at && role_matches(&r.role_arn, name, l.account.as_deref())
+            }),
+            _ => l
+                .role_arn
+                .as_deref()
+                .and_then(|arn| roles.iter().position(|r| r.role_arn == arn)),
+        };
+        if let Some(i) = idx {
+            claimed[i] = true;
+        }
+        let role = idx.map(|i| &roles[i]);
+        chain.push(ChainLink {
+            via: Some(l.action.clone()),
+            at: l.at,
+            source_ip: l.source_ip.clone(),
+            role_arn: role
+                .map(|r| r.role_arn.clone())
+                .or_else(|| l.role_arn.clone()),
+            role_events: role.map_or(0, |r| r.events),
+            role_first_seen: role.map(|r| r.first_seen),
+            role_last_seen: role.map(|r| r.last_seen),
+            auth_method: l.auth_method.clone(),
+            mfa_used: l.mfa_used,
+        });
+    }
+    for (r, _) in roles.iter().zip(&claimed).filter(|(_, c)| !**c) {
+        chain.push(ChainLink {
+            via: None,
+            at: r.first_seen,
+            source_ip: None,
+            role_arn: Some(r.role_arn.clone()),
+            role_events: r.events,
+            role_first_seen: Some(r.first_seen),
+            role_last_seen: Some(r.last_seen),
+            auth_method: None,
+            mfa_used: None,
+        });
+    }
+    chain.sort_by_key(|c| c.at);
+    chain
+}
+
+/// A session's authentication strength: the first sign-in's method, and the
+/// weakest MFA outcome of all of them (one sign-in without MFA outweighs any
+/// with it; `None` when no sign-in told).
+fn session_auth(logins: &[Login]) -> (Option<String>, Option<bool>) {
+    let method = logins.iter().find_map(|l| l.auth_method.clone());
+    let mfa = logins
+        .iter()
+        .filter_map(|l| l.mfa_used)
+        .reduce(|a, b| a && b);
+    (method, mfa)
+}
+
+/// One session's rows from the stitching query, before it becomes an upsert.
+#[derive(Default)]
+struct Stitched<'a> {
+    aggs: Vec<&'a StitchRow>,
+    logins: Vec<Login>,
+}
+
+pub fn derive(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyhow::Result<usize> {
     let now = Utc::now();
-    let event_floor = now - Duration::days(window_days.max(1));
+    let event_floor = now - Duration::days(siem.window_days.max(1));
     let active_floor = now - Duration::minutes(ACTIVE_WINDOW_MINS);
-    let target = now - Duration::minutes(SESSIONS_SAFETY_MARGIN_MINS);
+    let gap_mins = siem.session_gap_mins.max(1);
+    let gap = Duration::minutes(gap_mins);
 
+    // Everything since the last pass is unsettled too, so a stalled worker
+    // catches up; a fresh deployment starts at "now" and skips history.
     let w = get_watermark(conn, SESSIONS_WATERMARK_SOURCE)
         .context("read sessions watermark")?
         .and_then(|wm| wm.last_event_at)
-        .unwrap_or(target);
-    let boundary = target.min(w + Duration::hours(MAX_SESSIONS_STEP_HOURS));
-
-    let candidates: Vec<SessionCandidate> = diesel::sql_query(
-        "SELECT \
-           c.principal_name AS principal, \
-           c.source_ip AS source_ip, \
-           date_trunc('day', c.event_time) AS day, \
-           min(c.event_time) AS started_at, \
-           max(c.event_time) AS last_seen_at, \
-           count(*) AS event_count, \
-           (array_agg(c.user_agent ORDER BY c.event_time DESC))[1] AS user_agent, \
-           min(aa.actor_id) AS actor_id \
-         FROM cloudtrail_events c \
-         LEFT JOIN actor_aliases aa ON aa.alias = COALESCE(c.principal_name, c.principal_arn) \
-         WHERE c.event_name IN ('ConsoleLogin','AssumeRole','AssumeRoleWithSAML','AssumeRoleWithWebIdentity') \
-           AND c.created_at > $1 AND c.created_at <= $2 \
-           AND c.event_time >= $3 \
-           AND c.principal_name IS NOT NULL \
-         GROUP BY c.principal_name, c.source_ip, date_trunc('day', c.event_time)",
+        .unwrap_or(now);
+    let settle = w.min(now - Duration::hours(SESSIONS_SETTLE_HOURS)) - gap;
+
+    #[derive(QueryableByName)]
+    struct Bound {
+        #[diesel(sql_type = Nullable<Timestamptz>)]
+        at: Option<DateTime<Utc>>,
+    }
+    // Sessions ending after `settle` are rebuilt, so start at the earliest of them.
+    let open_start = diesel::sql_query(
+        "SELECT min(started_at) AS at FROM sessions \
+         WHERE session_key LIKE 'stitch|%' AND last_seen_at >= $1",
+    )
+    .bind::<Timestamptz, _>(settle)
+    .get_result::<Bound>(conn)
+    .context("load unsettled sessions")?
+    .at;
+    // Pre-stitching rows already cover their events; never stitch those again.
+    let legacy_end = diesel::sql_query(
+        "SELECT max(last_seen_at) AS at FROM sessions WHERE session_key NOT LIKE 'stitch|%'",
     )
-    .bind::<Timestamptz, _>(w)
-    .bind::<Timestamptz, _>(boundary)
-    .bind::<Timestamptz, _>(event_floor)
+    .get_result::<Bound>(conn)
+    .context("load legacy sessions")?
+    .at;
+    let floor = open_start
+        .map_or(settle, |s| s.min(settle))
+        .max(event_floor)
+        .max(legacy_end.map_or(event_floor, |l| l + Duration::microseconds(1)));
+
+    // Events just after a settled session would extend it; they are late past
+    // the settle horizon, so they are dropped rather than re-opening it. Only
+    // IP literals split sessions: AWS-internal callers report a service name
+    // (`sso.amazonaws.com`, `AWS Internal`), and sources without an address
+    // carry the last one forward.
+    let rows: Vec<StitchRow> = diesel::sql_query(
+        "WITH kept AS ( \
+           SELECT actor_id, max(last_seen_at) + make_interval(mins => $4) AS after \
+           FROM sessions \
+           WHERE session_key LIKE 'stitch|%' AND last_seen_at < $2 AND actor_id IS NOT NULL \
+           GROUP BY actor_id \
+         ), \
+         ev AS ( \
+           SELECT aa.actor_id, a.kind AS actor_kind, e.source, e.uid, e.ts, e.source_ip, \
+                  CASE WHEN e.source_ip ~ '^[0-9.]+$|:' THEN e.source_ip END AS ip, \
+                  e.action, e.status, e.raw, \
+                  CASE WHEN e.source = 'cloudtrail' THEN e.role END AS role \
+           FROM ssumgmt_events e \
+           JOIN actor_aliases aa ON aa.alias = e.actor \
+           JOIN actors a ON a.id = aa.actor_id \
+           LEFT JOIN kept k ON k.actor_id = aa.actor_id \
+           WHERE e.ts >= $1 AND e.ts <= $3 AND (k.after IS NULL OR e.ts > k.after) \
+         ), \
+         ipg AS ( \
+           SELECT ev.*, \
+                  count(ip) OVER (PARTITION BY actor_id ORDER BY ts, uid ROWS UNBOUNDED PRECEDING) AS ipgrp \
+           FROM ev \
+         ), \
+         carried AS ( \
+           SELECT ipg.*, max(ip) OVER (PARTITION BY actor_id, ipgrp) AS cur_ip FROM ipg \
+         ), \
+         marked AS ( \
+           SELECT carried.*, \
+                  CASE WHEN ts - lag(ts) OVER w <= make_interval(mins => $4) \
+                        AND coalesce(cur_ip = lag(cur_ip) OVER w, true) \
+                        AND (actor_kind = 'person' \
+                             OR (ts AT TIME ZONE 'UTC')::date = (lag(ts) OVER w AT TIME ZONE 'UTC')::date) \
+                       THEN 0 ELSE 1 END AS brk \
+           FROM carried WINDOW w AS (PARTITION BY actor_id ORDER BY ts, uid) \
+         ), \
+         s AS ( \
+           SELECT marked.*, \
+                  sum(brk) OVER (PARTITION BY actor_id ORDER BY ts, uid ROWS UNBOUNDED PRECEDING)::bigint AS sn \
+           FROM marked \
+         ) \
+         SELECT 'agg' AS kind, actor_id, sn, source, role, NULL::text AS action, count(*) AS n, \
+                min(ts) AS first_ts, max(ts) AS last_ts, \
+                max(cur_ip) AS source_ip, \
+                (array_agg(raw->>'userAgent' ORDER BY ts DESC) FILTER (WHERE source = 'cloudtrail'))[1] AS user_agent, \
+                NULL::text AS role_name, NULL::text AS role_account, \
+                NULL::text AS auth_method, NULL::boolean AS mfa_used \
+         FROM s GROUP BY actor_id, sn, source, role \
+         UNION ALL \
+         SELECT 'login', s.actor_id, s.sn, s.source, s.role, s.action, 1, s.ts, s.ts, s.source_ip, NULL, \
+                s.raw->'requestParameters'->>'roleName', s.raw->'requestParameters'->>'accountId', \
+                c.auth_method, c.mfa_used \
+         FROM s \
+         LEFT JOIN cloudtrail_events c ON c.event_id = s.uid AND c.event_time = s.ts \
+         WHERE s.source = 'cloudtrail' AND s.status = 'success' \
+           AND s.action IN ('ConsoleLogin', 'GetRoleCredentials')",
+    )
+    .bind::<Timestamptz, _>(floor)
+    .bind::<Timestamptz, _>(settle)
+    .bind::<Timestamptz, _>(now)
+    .bind::<Integer, _>(gap_mins as i32)
     .load(conn)
-    .context("load session candidates")?;
+    .context("stitch sessions")?;
+
+    let mut sessions: BTreeMap<(&str, i64), Stitched> = BTreeMap::new();
+    for r in &rows {
+        let s = sessions.entry((r.actor_id.as_str(), r.sn)).or_default();
+        if r.kind == "login" {
+            s.logins.push(Login {
+                action: r.action.clone().unwrap_or_default(),
+                at: r.first_ts,
+                source_ip: r.source_ip.clone(),
+                role_arn: r.role.clone(),
+                role_name: r.role_name.clone(),
+                account: r.role_account.clone(),
+                auth_method: r.auth_method.clone(),
+                mfa_used: r.mfa_used,
+            });
+        } else {
+            s.aggs.push(r);
+        }
+    }
+
+    let upserts: Vec<SessionUpsert> = sessions
+        .into_iter()
+        .filter(|(_, s)| !s.aggs.is_empty())
+        .map(|((actor_id, _), mut s)| {
+            let started_at = s.aggs.iter().map(|a| a.first_ts).min().unwrap_or(now);
+            let last_seen_at = s.aggs.iter().map(|a| a.last_ts).max().unwrap_or(now);
+            let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+            for a in &s.aggs {
+                *counts.entry(a.source.as_str()).or_default() += a.n;
+            }
+            // The source that opened the session.
+            let source = s
+                .aggs
+                .iter()
+                .min_by_key(|a| a.first_ts)
+                .map(|a| a.source.clone())
+                .unwrap_or_default();
+            // An IP change splits the session, so every agg carries the same one.
+            let ip = s.aggs.iter().find_map(|a| a.source_ip.clone());
+            let ua = s
+                .aggs
+                .iter()
+                .filter(|a| a.user_agent.is_some())
+                .max_by_key(|a| a.last_ts)
+                .and_then(|a| a.user_agent.as_deref());
+            let device = device_of(ua).or_else(|| device_of_source(&source));
+            let net = ip.as_deref().and_then(|i| geoip.network(i));
+            let class = net.as_ref().map_or(NetworkClass::Public, |n| n.class);
+
+            let roles: Vec<RoleActivity> = s
+                .aggs
+                .iter()
+                .filter_map(|a| {
+                    Some(RoleActivity {
+                        role_arn: a.role.clone()?,
+                        events: a.n,
+                        first_seen: a.first_ts,
+                        last_seen: a.last_ts,
+                    })
+                })
+                .collect();
+            s.logins.sort_by_key(|l| l.at);
+            let chain = link_chain(&s.logins, &roles);
+            let (auth_method, mfa_used) = session_auth(&s.logins);
 
-    let rows: Vec<SessionUpsert> = candidates
-        .iter()
-        .map(|c| {
-            let ip = c.source_ip.clone().unwrap_or_else(|| "-".to_string());
             SessionUpsert {
-                session_key: format!("{}|{}|{}", c.principal, ip, c.day.format("%Y-%m-%d")),
-                actor_id: c.actor_id.clone(),
-                device: device_of(c.user_agent.as_deref()),
-                source_ip: c.source_ip.clone(),
-                location: c.source_ip.as_deref().and_then(|i| geoip.lookup(i)),
-                started_at: c.started_at,
-                last_seen_at: c.last_seen_at,
-                event_count: c.event_count,
-                status: if c.last_seen_at >= active_floor {
+                session_key: format!(
+                    "{STITCH_KEY_PREFIX}{}|{}",
+                    actor_id,
+                    started_at.format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                ),
+                actor_id: actor_id.to_string(),
+                source,
+                device,
+                location: location_of(geoip, ip.as_deref(), net.as_ref()),
+                source_ip: ip,
+                asn: net.as_ref().and_then(|n| n.asn).map(i64::from),
+                as_org: net.as_ref().and_then(|n| n.org.clone()),
+                network_class: (class != NetworkClass::Public).then(|| class.as_str()),
+                started_at,
+                last_seen_at,
+                event_count: counts.values().sum(),
+                status: if last_seen_at >= active_floor {
                     "active"
                 } else {
                     "closed"
                 }
                 .to_string(),
+                source_counts: json!(counts),
+                chain: json!(chain),
+                auth_method,
+                mfa_used,
             }
         })
         .collect();
 
-    let upserted = rows.len();
+    let upserted = upserts.len();
+    let keys: Vec<&str> = upserts.iter().map(|r| r.session_key.as_str()).collect();
 
     conn.transaction::<_, anyhow::Error, _>(|conn| {
-        for chunk in rows.chunks(SESSIONS_UPSERT_CHUNK) {
+        for chunk in upserts.chunks(SESSIONS_UPSERT_CHUNK) {
             upsert_chunk(conn, chunk)?;
         }
 
+        // Rebuilt sessions whose key moved (a late event changed the start, or
+        // a gap now splits them) leave their old row behind.
+        diesel::sql_query(
+            "DELETE FROM sessions \
+             WHERE session_key LIKE 'stitch|%' AND last_seen_at >= $1 AND NOT (session_key = ANY($2))",
+        )
+        .bind::<Timestamptz, _>(settle)
+        .bind::<Array<Text>, _>(&keys)
+        .execute(conn)
+        .context("drop superseded sessions")?;
+
         diesel::sql_query(
             "UPDATE sessions SET status = 'closed' \
              WHERE status = 'active' AND last_seen_at < $1",
@@ -153,7 +509,7 @@ pub fn derive(conn: &mut PgConnection, geoip: &GeoIp, window_days: i64) -> anyho
                last_run_at   = now()",
         )
         .bind::<Text, _>(SESSIONS_WATERMARK_SOURCE)
-        .bind::<Timestamptz, _>(boundary)
+        .bind::<Timestamptz, _>(now)
         .execute(conn)
         .context("advance sessions watermark")?;
         Ok(())
@@ -167,33 +523,55 @@ fn upsert_chunk(conn: &mut PgConnection, chunk: &[SessionUpsert]) -> anyhow::Res
         return Ok(());
     }
     let keys: Vec<&str> = chunk.iter().map(|r| r.session_key.as_str()).collect();
-    let actor_ids: Vec<Option<&str>> = chunk.iter().map(|r| r.actor_id.as_deref()).collect();
+    let actor_ids: Vec<&str> = chunk.iter().map(|r| r.actor_id.as_str()).collect();
+    let sources: Vec<&str> = chunk.iter().map(|r| r.source.as_str()).collect();
     let devices: Vec<Option<&str>> = chunk.iter().map(|r| r.device.as_deref()).collect();
     let ips: Vec<Option<&str>> = chunk.iter().map(|r| r.source_ip.as_deref()).collect();
     let locations: Vec<Option<&str>> = chunk.iter().map(|r| r.location.as_deref()).collect();
+    let asns: Vec<Option<i64>> = chunk.iter().map(|r| r.asn).collect();
+    let orgs: Vec<Option<&str>> = chunk.iter().map(|r| r.as_org.as_deref()).collect();
+    let classes: Vec<Option<&str>> = chunk.iter().map(|r| r.network_class).collect();
     let starts: Vec<DateTime<Utc>> = chunk.iter().map(|r| r.started_at).collect();
     let lasts: Vec<DateTime<Utc>> = chunk.iter().map(|r| r.last_seen_at).collect();
     let counts: Vec<i64> = chunk.iter().map(|r| r.event_count).collect();
     let statuses: Vec<&str> = chunk.iter().map(|r| r.status.as_str()).collect();
+    let source_counts: Vec<&Value> = chunk.iter().map(|r| &r.source_counts).collect();
+    let chains: Vec<&Value> = chunk.iter().map(|r| &r.chain).collect();
+    let methods: Vec<Option<&str>> = chunk.iter().map(|r| r.auth_method.as_deref()).collect();
+    let mfas: Vec<Option<bool>> = chunk.iter().map(|r| r.mfa_used).collect();
 
+    // A rebuilt session replaces its previous row outright; only a flag sticks.
     diesel::sql_query(
         "INSERT INTO sessions \
-           (session_key, actor_id, source, device, source_ip, location, started_at, last_seen_at, event_count, status) \
-         SELECT k, a, 'cloudtrail', d, ip, loc, st, ls, ec, status \
-         FROM unnest($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], \
-                     $6::timestamptz[], $7::timestamptz[], $8::bigint[], $9::text[]) \
-              AS t(k, a, d, ip, loc, st, ls, ec, status) \
+           (session_key, actor_id, source, device, source_ip, location, started_at, last_seen_at, event_count, status, \
+            asn, as_org, network_class, source_counts, chain, auth_method, mfa_used) \
+         SELECT k, a, src, d, ip, loc, st, ls, ec, status, asn, org, cls, sc, ch, am, mfa \
+         FROM unnest($1::text[], $2::text[], $3::text[], $4::text[], $5::text[], $6::text[], \
+                     $7::timestamptz[], $8::timestamptz[], $9::bigint[], $10::text[], \
+                     $11::bigint[], $12::text[], $13::text[], $14::jsonb[], $15::jsonb[], \
+                     $16::text[], $17::boolean[]) \
+              AS t(k, a, src, d, ip, loc, st, ls, ec, status, asn, org, cls, sc, ch, am, mfa) \
          ON CONFLICT (session_key) DO UPDATE SET \
-           actor_id     = COALESCE(EXCLUDED.actor_id, sessions.actor_id), \
-           device       = COALESCE(EXCLUDED.device, sessions.device), \
-           location     = COALESCE(EXCLUDED.location, sessions.location), \
-           started_at   = LEAST(EXCLUDED.started_at, sessions.started_at), \
-           last_seen_at = GREATEST(EXCLUDED.last_seen_at, sessions.last_seen_at), \
-           event_count  = sessions.event_count + EXCLUDED.event_count, \
-           status       = CASE WHEN sessions.status = 'flagged' THEN 'flagged' ELSE EXCLUDED.status END",
+           actor_id      = EXCLUDED.actor_id, \
+           source        = EXCLUDED.source, \
+           device        = EXCLUDED.device, \
+           source_ip     = EXCLUDED.source_ip, \
+           location      = EXCLUDED.location, \
+           asn           = EXCLUDED.asn, \
+           as_org        = EXCLUDED.as_org, \
+           network_class = EXCLUDED.network_class, \
+           started_at    = EXCLUDED.started_at, \
+           last_seen_at  = EXCLUDED.last_seen_at, \
+           event_count   = EXCLUDED.event_count, \
+           source_counts = EXCLUDED.source_counts, \
+           chain         = EXCLUDED.chain, \
+           auth_method   = EXCLUDED.auth_method, \
+           mfa_used      = EXCLUDED.mfa_used, \
+           status        = CASE WHEN sessions.status = 'flagged' THEN 'flagged' ELSE EXCLUDED.status END",
     )
     .bind::<Array<Text>, _>(keys)
-    .bind::<Array<Nullable<Text>>, _>(actor_ids)
+    .bind::<Array<Text>, _>(actor_ids)
+    .bind::<Array<Text>, _>(sources)
     .bind::<Array<Nullable<Text>>, _>(devices)
     .bind::<Array<Nullable<Text>>, _>(ips)
     .bind::<Array<Nullable<Text>>, _>(locations)
@@ -201,7 +579,128 @@ fn upsert_chunk(conn: &mut PgConnection, chunk: &[SessionUpsert]) -> anyhow::Res
     .bind::<Array<Timestamptz>, _>(lasts)
     .bind::<Array<BigInt>, _>(counts)
     .bind::<Array<Text>, _>(statuses)
+    .bind::<Array<Nullable<BigInt>>, _>(asns)
+    .bind::<Array<Nullable<Text>>, _>(orgs)
+    .bind::<Array<Nullable<Text>>, _>(classes)
+    .bind::<Array<Jsonb>, _>(source_counts)
+    .bind::<Array<Jsonb>, _>(chains)
+    .bind::<Array<Nullable<Text>>, _>(methods)
+    .bind::<Array<Nullable<Bool>>, _>(mfas)
     .execute(conn)
     .context("batch upsert sessions")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(m: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-19T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::minutes(m)
+    }
+
+    const SSO_ADMIN: &str = "arn:aws:iam::123456789012:role/aws-reserved/sso.amazonaws.com/eu-west-1/AWSReservedSSO_CloudAdmin_0123456789abcdef";
+    const SSO_READ: &str = "arn:aws:iam::210987654321:role/aws-reserved/sso.amazonaws.com/eu-west-1/AWSReservedSSO_ReadOnly_fedcba9876543210";
+
+    #[test]
+    fn chain_links_logins_to_spawned_roles() {
+        let logins = vec![
+            Login {
+                action: "ConsoleLogin".into(),
+                at: at(0),
+                source_ip: Some("198.51.100.7".into()),
+                role_arn: Some(SSO_ADMIN.into()),
+                role_name: None,
+                account: None,
+                auth_method: Some("sso".into()),
+                mfa_used: None,
+            },
+            Login {
+                action: "GetRoleCredentials".into(),
+                at: at(20),
+                source_ip: Some("198.51.100.7".into()),
+                role_arn: None,
+                role_name: Some("ReadOnly".into()),
+                account: Some("210987654321".into()),
+                auth_method: None,
+                mfa_used: None,
+            },
+        ];
+        let roles = vec![
+            RoleActivity {
+                role_arn: SSO_ADMIN.into(),
+                events: 12,
+                first_seen: at(0),
+                last_seen: at(15),
+            },
+            RoleActivity {
+                role_arn: SSO_READ.into(),
+                events: 40,
+                first_seen: at(21),
+                last_seen: at(50),
+            },
+            RoleActivity {
+                role_arn: "arn:aws:iam::123456789012:role/deploy".into(),
+                events: 3,
+                first_seen: at(30),
+                last_seen: at(31),
+            },
+        ];
+        let chain = link_chain(&logins, &roles);
+        assert_eq!(session_auth(&logins), (Some("sso".into()), None));
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[0].via.as_deref(), Some("ConsoleLogin"));
+        assert_eq!(chain[0].role_arn.as_deref(), Some(SSO_ADMIN));
+        assert_eq!(chain[0].role_events, 12);
+        assert_eq!(chain[1].via.as_deref(), Some("GetRoleCredentials"));
+        assert_eq!(chain[1].role_arn.as_deref(), Some(SSO_READ));
+        assert_eq!(chain[1].role_events, 40);
+        // Role activity no login in the session accounts for.
+        assert_eq!(chain[2].via, None);
+        assert_eq!(
+            chain[2].role_arn.as_deref(),
+            Some("arn:aws:iam::123456789012:role/deploy")
+        );
+    }
+
+    #[test]
+    fn role_match_respects_account_and_permission_set() {
+        assert!(role_matches(SSO_ADMIN, "CloudAdmin", Some("123456789012")));
+        assert!(!role_matches(SSO_ADMIN, "CloudAdmin", Some("210987654321")));
+        assert!(!role_matches(SSO_ADMIN, "Cloud", None));
+        assert!(role_matches(
+            "arn:aws:iam::123456789012:role/deploy",
+            "deploy",
+            None
+        ));
+    }
+
+    #[test]
+    fn session_auth_keeps_the_weakest_mfa_outcome() {
+        let login = |method: &str, mfa: Option<bool>| Login {
+            action: "ConsoleLogin".into(),
+            at: at(0),
+            source_ip: None,
+            role_arn: None,
+            role_name: None,
+            account: None,
+            auth_method: Some(method.into()),
+            mfa_used: mfa,
+        };
+        assert_eq!(session_auth(&[]), (None, None));
+        assert_eq!(
+            session_auth(&[login("password", Some(true)), login("sso", None)]),
+            (Some("password".into()), Some(true))
+        );
+        assert_eq!(
+            session_auth(&[
+                login("password", Some(true)),
+                login("password", Some(false))
+            ]),
+            (Some("password".into()), Some(false))
+        );
+    }
+}
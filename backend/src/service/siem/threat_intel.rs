@@ -0,0 +1,854 @@
+//! Threat-intel enrichment from local indicator feeds.
+//!
+//! Feeds are listed in a manifest (`SSU__THREAT_INTEL__MANIFEST_PATH`, YAML or
+//! JSON) that names each file, its format (`csv`, `stix` 2.1 bundle, or a plain
+//! `list` of IPs/CIDRs/domains) and its confidence + TTL. The leader-only [`run`]
+//! worker re-reads the manifest every `interval_secs`, so feeds are added,
+//! dropped, or refreshed by editing files on disk — no restart. A feed whose file
+//! is unchanged (same mtime, size and manifest entry — the `content_hash`
+//! fingerprint) is not re-parsed, but its indicators without their own validity
+//! end still have their TTL pushed forward; one that stops being listed ages
+//! out as its indicators hit `expires_at`.
+//!
+//! Matching runs inside the SIEM pass ([`match_events`]): newly ingested events
+//! are checked against every live indicator, and newly loaded indicators are
+//! retro-hunted across the trailing window. Hits land in `threat_intel_hits`; the
+//! `threat_intel_match` rule in `alerts` raises on them.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Integer, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+use log::{error, info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::db::DbPool;
+use crate::misc::config::ThreatIntelConfig;
+use crate::service::ingest::get_watermark;
+
+pub const THREAT_INTEL_WATERMARK_SOURCE: &str = "siem_threat_intel";
+/// Ingest commits lag `created_at` slightly; stay this far behind `now()` so a
+/// still-open batch isn't skipped past.
+const THREAT_INTEL_SAFETY_MARGIN_MINS: i64 = 5;
+const INDICATOR_UPSERT_CHUNK: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    Csv,
+    Stix,
+    List,
+}
+
+impl FeedFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            FeedFormat::Csv => "csv",
+            FeedFormat::Stix => "stix",
+            FeedFormat::List => "list",
+        }
+    }
+}
+
+/// One manifest entry. `confidence`/`ttl_days` fall back to the configured
+/// defaults; per-indicator values in the file (CSV columns, STIX properties) win.
+#[derive(Deserialize, Debug, Clone, Hash)]
+pub struct FeedSpec {
+    pub name: String,
+    pub path: String,
+    pub format: FeedFormat,
+    pub confidence: Option<i32>,
+    pub ttl_days: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Manifest {
+    #[serde(default)]
+    feeds: Vec<FeedSpec>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndicatorKind {
+    Ip,
+    Domain,
+    UserAgent,
+}
+
+impl IndicatorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IndicatorKind::Ip => "ip",
+            IndicatorKind::Domain => "domain",
+            IndicatorKind::UserAgent => "user_agent",
+        }
+    }
+
+    /// Feed-side type labels (CSV `type` column, MISP-style exports).
+    fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "ip" | "ipv4" | "ipv6" | "cidr" | "ip-src" | "ip-dst" | "ipv4-addr" | "ipv6-addr" => {
+                Some(IndicatorKind::Ip)
+            }
+            "domain" | "hostname" | "fqdn" | "domain-name" => Some(IndicatorKind::Domain),
+            "user_agent" | "user-agent" | "useragent" | "ua" => Some(IndicatorKind::UserAgent),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed indicator. `value` is normalised (canonical network for IPs,
+/// lower-cased for domains and user agents) so reloads upsert onto the same row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Indicator {
+    pub kind: IndicatorKind,
+    pub value: String,
+    pub network: Option<String>,
+    pub confidence: Option<i32>,
+    pub description: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Indicator {
+    fn new(kind: IndicatorKind, raw: &str) -> Option<Self> {
+        let (value, network) = match kind {
+            IndicatorKind::Ip => {
+                let net = normalise_network(raw)?;
+                (net.clone(), Some(net))
+            }
+            IndicatorKind::Domain => (normalise_domain(raw)?, None),
+            IndicatorKind::UserAgent => {
+                let ua = raw.trim().to_lowercase();
+                if ua.is_empty() {
+                    return None;
+                }
+                (ua, None)
+            }
+        };
+        Some(Self {
+            kind,
+            value,
+            network,
+            confidence: None,
+            description: None,
+            expires_at: None,
+        })
+    }
+
+    /// Classify an untyped token (plain lists, CSVs without a `type` column):
+    /// an address or CIDR is an IP indicator, a dotted hostname a domain.
+    fn infer(raw: &str) -> Option<Self> {
+        Self::new(IndicatorKind::Ip, raw).or_else(|| Self::new(IndicatorKind::Domain, raw))
+    }
+}
+
+/// Canonical `addr/prefix` for an address or CIDR, with host bits masked off —
+/// Postgres `cidr` rejects `10.0.0.1/8`, and feeds routinely write it that way.
+fn normalise_network(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let (addr, prefix) = match raw.split_once('/') {
+        Some((a, p)) => (a.parse::<IpAddr>().ok()?, Some(p.parse::<u8>().ok()?)),
+        None => (raw.parse::<IpAddr>().ok()?, None),
+    };
+    match addr {
+        IpAddr::V4(v4) => {
+            let prefix = prefix.unwrap_or(32);
+            if prefix > 32 {
+                return None;
+            }
+            let mask = if prefix == 0 {
+                0
+            } else {
+                u32::MAX << (32 - prefix)
+            };
+            let net = std::net::Ipv4Addr::from(u32::from(v4) & mask);
+            Some(format!("{}/{}", net, prefix))
+        }
+        IpAddr::V6(v6) => {
+            let prefix = prefix.unwrap_or(128);
+            if prefix > 128 {
+                return None;
+            }
+            let mask = if prefix == 0 {
+                0
+            } else {
+                u128::MAX << (128 - prefix)
+            };
+            let net = std::net::Ipv6Addr::from(u128::from(v6) & mask);
+            Some(format!("{}/{}", net, prefix))
+        }
+    }
+}
+
+fn normalise_domain(raw: &str) -> Option<String> {
+    let d = raw.trim().trim_end_matches('.').to_lowercase();
+    let d = d.strip_prefix("*.").unwrap_or(&d).to_string();
+    let valid = d.contains('.')
+        && d.parse::<IpAddr>().is_err()
+        && d.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_');
+    valid.then_some(d)
+}
+
+fn parse_expiry(raw: &str) -> Option<DateTime<Utc>> {
+    let raw = raw.trim();
+    DateTime::parse_from_rfc3339(raw)
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|n| n.and_utc())
+        })
+}
+
+/// One indicator per line; `#`/`;` start a comment (Spamhaus DROP writes
+/// `1.2.3.0/24 ; SBL123`) and only the first token of a line is read.
+pub fn parse_list(text: &str) -> Vec<Indicator> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.split(['#', ';']).next().unwrap_or("").trim();
+            let token = line.split_whitespace().next()?;
+            Indicator::infer(token)
+        })
+        .collect()
+}
+
+/// Header-driven CSV. The indicator column is the first of `indicator`/`value`/
+/// `ioc`/`ip`/`domain`/`user_agent` present; `type`, `confidence`, `expires_at`
+/// (or `valid_until`) and `description` are optional.
+pub fn parse_csv(text: &str) -> anyhow::Result<Vec<Indicator>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_reader(text.as_bytes());
+    let headers: Vec<String> = rdr
+        .headers()
+        .context("read csv header")?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    let col = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|n| headers.iter().position(|h| h == n))
+    };
+
+    let value_col = col(&[
+        "indicator",
+        "value",
+        "ioc",
+        "ip",
+        "ip_address",
+        "cidr",
+        "domain",
+        "user_agent",
+    ])
+    .context("csv has no indicator column")?;
+    // A column named after the kind types its rows when there is no `type`.
+    let implied = IndicatorKind::from_label(&headers[value_col]);
+    let type_col = col(&["type", "kind", "indicator_type"]);
+    let conf_col = col(&["confidence", "score"]);
+    let exp_col = col(&["expires_at", "expires", "valid_until"]);
+    let desc_col = col(&["description", "comment", "name", "malware"]);
+
+    let mut out = Vec::new();
+    for rec in rdr.records() {
+        let rec = rec.context("read csv record")?;
+        let field = |i: Option<usize>| i.and_then(|i| rec.get(i)).filter(|s| !s.is_empty());
+        let Some(raw) = field(Some(value_col)) else {
+            continue;
+        };
+        let kind = field(type_col)
+            .and_then(IndicatorKind::from_label)
+            .or(implied);
+        let parsed = match kind {
+            Some(k) => Indicator::new(k, raw),
+            None => Indicator::infer(raw),
+        };
+        let Some(mut ind) = parsed else {
+            continue;
+        };
+        ind.confidence = field(conf_col)
+            .and_then(|c| c.parse::<f64>().ok())
+            .map(|c| c.round() as i32);
+        ind.expires_at = field(exp_col).and_then(parse_expiry);
+        ind.description = field(desc_col).map(str::to_string);
+        out.push(ind);
+    }
+    Ok(out)
+}
+
+fn stix_comparison() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"([a-z0-9-]+):([A-Za-z0-9_.'\-]+)\s*(?:=|ISSUBSET)\s*'((?:[^'\\]|\\.)*)'")
+            .unwrap()
+    })
+}
+
+/// STIX 2.1 bundle (or a bare `indicator` object). Each `indicator` with a
+/// `stix` pattern contributes every IP/domain/User-Agent comparison in it;
+/// revoked indicators are skipped. STIX `confidence` is already 0–100.
+pub fn parse_stix(text: &str) -> anyhow::Result<Vec<Indicator>> {
+    let doc: serde_json::Value = serde_json::from_str(text).context("parse stix json")?;
+    let objects = match doc.get("objects").and_then(|o| o.as_array()) {
+        Some(objs) => objs.clone(),
+        None => vec![doc],
+    };
+
+    let mut out = Vec::new();
+    for obj in &objects {
+        if obj.get("type").and_then(|t| t.as_str()) != Some("indicator")
+            || obj.get("revoked").and_then(|r| r.as_bool()) == Some(true)
+            || obj
+                .get("pattern_type")
+                .and_then(|p| p.as_str())
+                .unwrap_or("stix")
+                != "stix"
+        {
+            continue;
+        }
+        let Some(pattern) = obj.get("pattern").and_then(|p| p.as_str()) else {
+            continue;
+        };
+        let confidence = obj
+            .get("confidence")
+            .and_then(|c| c.as_i64())
+            .map(|c| c as i32);
+        let expires_at = obj
+            .get("valid_until")
+            .and_then(|v| v.as_str())
+            .and_then(parse_expiry);
+        let description = obj
+            .get("name")
+            .or_else(|| obj.get("description"))
+            .and_then(|d| d.as_str())
+            .map(str::to_string);
+
+        for cap in stix_comparison().captures_iter(pattern) {
+            let kind = match (&cap[1], &cap[2]) {
+                ("ipv4-addr" | "ipv6-addr", "value") => IndicatorKind::Ip,
+                ("domain-name", "value") => IndicatorKind::Domain,
+                ("network-traffic", path) if path.to_lowercase().contains("user-agent") => {
+                    IndicatorKind::UserAgent
+                }
+                _ => continue,
+            };
+            let raw = cap[3].replace("\\'", "'").replace("\\\\", "\\");
+            if let Some(mut ind) = Indicator::new(kind, &raw) {
+                ind.confidence = confidence;
+                ind.expires_at = expires_at;
+                ind.description = description.clone();
+                out.push(ind);
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn parse_feed(format: FeedFormat, text: &str) -> anyhow::Result<Vec<Indicator>> {
+    match format {
+        FeedFormat::Csv => parse_csv(text),
+        FeedFormat::Stix => parse_stix(text),
+        FeedFormat::List => Ok(parse_list(text)),
+    }
+}
+
+/// Leader-only feed loader: reload on start, then every `interval_secs`.
+pub async fn run(cancel: CancellationToken, conf: ThreatIntelConfig, pool: DbPool) {
+    let interval = std::time::Duration::from_secs(conf.interval_secs.max(60));
+    info!(
+        "threat-intel feed loader starting :: interval={}s manifest={}",
+        interval.as_secs(),
+        conf.manifest_path,
+    );
+
+    loop {
+        let pool2 = pool.clone();
+        let conf2 = conf.clone();
+        let res = tokio::task::spawn_blocking(move || -> anyhow::Result<usize> {
+            let mut conn = pool2.get().context("pool get")?;
+            reload(&mut conn, &conf2)
+        })
+        .await;
+        match res {
+            Ok(Ok(n)) => info!("threat-intel reload complete :: feeds_loaded={}", n),
+            Ok(Err(e)) => error!("threat-intel reload failed: {:#}", e),
+            Err(e) => error!("threat-intel reload join error: {}", e),
+        }
+
+        tokio::select! {
+            _ = cancel.cancelled() => { info!("stopping threat-intel feed loader"); break; }
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+fn read_manifest(path: &str) -> anyhow::Result<Manifest> {
+    if path.is_empty() {
+        return Ok(Manifest::default());
+    }
+    let text = std::fs::read_to_string(path).with_context(|| format!("read manifest {}", path))?;
+    serde_yaml::from_str(&text).with_context(|| format!("parse manifest {}", path))
+}
+
+#[derive(QueryableByName)]
+struct FeedState {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    content_hash: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    last_error: Option<String>,
+}
+
+/// Change key for a feed: the manifest entry plus the file's mtime and size, so a
+/// rewritten file (or an edited confidence/TTL) reloads and an untouched one
+/// doesn't. Only ever compared against a value this binary wrote.
+fn feed_fingerprint(spec: &FeedSpec, meta: &std::fs::Metadata) -> String {
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    spec.hash(&mut h);
+    meta.len().hash(&mut h);
+    if let Ok(m) = meta.modified() {
+        m.hash(&mut h);
+    }
+    format!("{:016x}", h.finish())
+}
+
+/// Sync the DB with the manifest: load changed feeds, drop feeds no longer
+/// listed (cascading their indicators), and purge expired indicators. A feed
+/// that fails to read or parse records `last_error` and keeps its previous
+/// indicators until they expire. Returns the number of feeds (re)loaded.
+pub fn reload(conn: &mut PgConnection, conf: &ThreatIntelConfig) -> anyhow::Result<usize> {
+    let manifest = read_manifest(&conf.manifest_path)?;
+
+    let states: Vec<FeedState> =
+        diesel::sql_query("SELECT name, content_hash, last_error FROM threat_intel_feeds")
+            .load(conn)
+            .context("read feed state")?;
+    let states: HashMap<String, FeedState> =
+        states.into_iter().map(|s| (s.name.clone(), s)).collect();
+
+    let names: Vec<String> = manifest.feeds.iter().map(|f| f.name.clone()).collect();
+    let dropped = diesel::sql_query("DELETE FROM threat_intel_feeds WHERE NOT (name = ANY($1))")
+        .bind::<Array<Text>, _>(&names)
+        .execute(conn)
+        .context("drop unlisted feeds")?;
+    if dropped > 0 {
+        info!(
+            "threat-intel: dropped {} feed(s) no longer in the manifest",
+            dropped
+        );
+    }
+
+    let mut loaded = 0usize;
+    for spec in &manifest.feeds {
+        let confidence = spec
+            .confidence
+            .unwrap_or(conf.default_confidence)
+            .clamp(0, 100);
+        let ttl_days = spec.ttl_days.unwrap_or(conf.default_ttl_days).max(1);
+
+        let attempt = std::fs::metadata(&spec.path)
+            .with_context(|| format!("stat {}", spec.path))
+            .map(|meta| feed_fingerprint(spec, &meta));
+        let fingerprint = match attempt {
+            Ok(fp) => fp,
+            Err(e) => {
+                warn!("threat-intel: feed {}: {:#}", spec.name, e);
+                record_feed_error(conn, spec, confidence, ttl_days, &format!("{:#}", e))?;
+                continue;
+            }
+        };
+        let unchanged = states.get(&spec.name).is_some_and(|s| {
+            s.last_error.is_none() && s.content_hash.as_deref() == Some(fingerprint.as_str())
+        });
+        if unchanged {
+            // Still listed: keep feed-TTL indicators alive as a reload would.
+            diesel::sql_query(
+                "UPDATE threat_indicators SET expires_at = now() + make_interval(days => $2), refreshed_at = now() \
+                 WHERE feed = $1 AND valid_until IS NULL",
+            )
+            .bind::<Text, _>(&spec.name)
+            .bind::<Integer, _>(ttl_days as i32)
+            .execute(conn)
+            .with_context(|| format!("refresh feed {}", spec.name))?;
+            continue;
+        }
+
+        let parsed = std::fs::read_to_string(&spec.path)
+            .with_context(|| format!("read {}", spec.path))
+            .and_then(|text| parse_feed(spec.format, &text));
+        match parsed {
+            Ok(indicators) => {
+                let n = store_feed(conn, spec, confidence, ttl_days, &fingerprint, &indicators)
+                    .with_context(|| format!("store feed {}", spec.name))?;
+                info!("threat-intel: loaded feed {} ({} indicators)", spec.name, n);
+                loaded += 1;
+            }
+            Err(e) => {
+                warn!("threat-intel: feed {}: {:#}", spec.name, e);
+                record_feed_error(conn, spec, confidence, ttl_days, &format!("{:#}", e))?;
+            }
+        }
+    }
+
+    let expired = diesel::sql_query("DELETE FROM threat_indicators WHERE expires_at <= now()")
+        .execute(conn)
+        .context("purge expired indicators")?;
+    if expired > 0 {
+        info!("threat-intel: purged {} expired indicator(s)", expired);
+    }
+    Ok(loaded)
+}
+
+fn record_feed_error(
+    conn: &mut PgConnection,
+    spec: &FeedSpec,
+    confidence: i32,
+    ttl_days: i64,
+    err: &str,
+) -> anyhow::Result<()> {
+    diesel::sql_query(
+        "INSERT INTO threat_intel_feeds (name, path, format, confidence, ttl_days, last_error, updated_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, now()) \
+         ON CONFLICT (name) DO UPDATE SET \
+           path = EXCLUDED.path, format = EXCLUDED.format, confidence = EXCLUDED.confidence, \
+           ttl_days = EXCLUDED.ttl_days, last_error = EXCLUDED.last_error, updated_at = now()",
+    )
+    .bind::<Text, _>(&spec.name)
+    .bind::<Text, _>(&spec.path)
+    .bind::<Text, _>(spec.format.as_str())
+    .bind::<Integer, _>(confidence)
+    .bind::<Integer, _>(ttl_days as i32)
+    .bind::<Text, _>(err)
+    .execute(conn)
+    .context("record feed error")?;
+    Ok(())
+}
+
+/// Replace one feed's indicators in a single transaction. Rows still listed keep
+/// their `loaded_at` (no re-hunt); rows the file no longer carries are removed.
+fn store_feed(
+    conn: &mut PgConnection,
+    spec: &FeedSpec,
+    confidence: i32,
+    ttl_days: i64,
+    fingerprint: &str,
+    indicators: &[Indicator],
+) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let default_expiry = now + Duration::days(ttl_days);
+
+    // Last occurrence wins on duplicates; a row already past its own expiry is dropped.
+    let mut dedup: HashMap<(&'static str, String), &Indicator> = HashMap::new();
+    for ind in indicators {
+        if ind.expires_at.is_some_and(|e| e <= now) {
+            continue;
+        }
+        dedup.insert((ind.kind.as_str(), ind.value.clone()), ind);
+    }
+    let rows: Vec<&Indicator> = dedup.into_values().collect();
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        diesel::sql_query(
+            "INSERT INTO threat_intel_feeds (name, path, format, confidence, ttl_days, content_hash, indicator_count, loaded_at, last_error, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, now(), NULL, now()) \
+             ON CONFLICT (name) DO UPDATE SET \
+               path = EXCLUDED.path, format = EXCLUDED.format, confidence = EXCLUDED.confidence, \
+               ttl_days = EXCLUDED.ttl_days, content_hash = EXCLUDED.content_hash, \
+               indicator_count = EXCLUDED.indicator_count, loaded_at = now(), last_error = NULL, updated_at = now()",
+        )
+        .bind::<Text, _>(&spec.name)
+        .bind::<Text, _>(&spec.path)
+        .bind::<Text, _>(spec.format.as_str())
+        .bind::<Integer, _>(confidence)
+        .bind::<Integer, _>(ttl_days as i32)
+        .bind::<Text, _>(fingerprint)
+        .bind::<diesel::sql_types::BigInt, _>(rows.len() as i64)
+        .execute(conn)
+        .context("upsert feed")?;
+
+        for chunk in rows.chunks(INDICATOR_UPSERT_CHUNK) {
+            let kinds: Vec<&str> = chunk.iter().map(|i| i.kind.as_str()).collect();
+            let values: Vec<&str> = chunk.iter().map(|i| i.value.as_str()).collect();
+            let networks: Vec<Option<&str>> = chunk.iter().map(|i| i.network.as_deref()).collect();
+            let confs: Vec<i32> = chunk
+                .iter()
+                .map(|i| i.confidence.unwrap_or(confidence).clamp(0, 100))
+                .collect();
+            let descs: Vec<Option<&str>> = chunk.iter().map(|i| i.description.as_deref()).collect();
+            let valid_untils: Vec<Option<DateTime<Utc>>> =
+                chunk.iter().map(|i| i.expires_at).collect();
+            let expiries: Vec<DateTime<Utc>> = chunk
+                .iter()
+                .map(|i| i.expires_at.unwrap_or(default_expiry))
+                .collect();
+            diesel::sql_query(
+                "INSERT INTO threat_indicators (feed, kind, value, network, confidence, description, valid_until, expires_at, loaded_at, refreshed_at) \
+                 SELECT $1, k, v, n::cidr, c, d, vu, e, now(), now() \
+                 FROM unnest($2::text[], $3::text[], $4::text[], $5::int[], $6::text[], $7::timestamptz[], $8::timestamptz[]) \
+                      AS t(k, v, n, c, d, vu, e) \
+                 ON CONFLICT (feed, kind, value) DO UPDATE SET \
+                   network = EXCLUDED.network, confidence = EXCLUDED.confidence, description = EXCLUDED.description, \
+                   valid_until = EXCLUDED.valid_until, expires_at = EXCLUDED.expires_at, refreshed_at = now()",
+            )
+            .bind::<Text, _>(&spec.name)
+            .bind::<Array<Text>, _>(kinds)
+            .bind::<Array<Text>, _>(values)
+            .bind::<Array<Nullable<Text>>, _>(networks)
+            .bind::<Array<Integer>, _>(confs)
+            .bind::<Array<Nullable<Text>>, _>(descs)
+            .bind::<Array<Nullable<Timestamptz>>, _>(valid_untils)
+            .bind::<Array<Timestamptz>, _>(expiries)
+            .execute(conn)
+            .context("upsert indicators")?;
+        }
+
+        // `now()` is fixed for the transaction, so anything not touched above is gone
+        // from the file.
+        diesel::sql_query("DELETE FROM threat_indicators WHERE feed = $1 AND refreshed_at < now()")
+            .bind::<Text, _>(&spec.name)
+            .execute(conn)
+            .context("remove delisted indicators")?;
+        Ok(())
+    })?;
+    Ok(rows.len())
+}
+
+/// Column map for one event table the matcher scans.
+struct EventSource {
+    source: &'static str,
+    table: &'static str,
+    uid: &'static str,
+    actor: &'static str,
+    ip: &'static str,
+    ua: &'static str,
+    time: &'static str,
+}
+
+const EVENT_SOURCES: &[EventSource] = &[
+    EventSource {
+        source: "cloudtrail",
+        table: "cloudtrail_events",
+        uid: "e.event_id",
+        actor: "COALESCE(e.principal_name, e.principal_arn)",
+        ip: "e.source_ip",
+        ua: "e.user_agent",
+        time: "e.event_time",
+    },
+    EventSource {
+        source: "github",
+        table: "github_audit_events",
+        uid: "e.document_id",
+        actor: "e.actor",
+        ip: "e.source_ip",
+        ua: "e.user_agent",
+        time: "e.event_time",
+    },
+    EventSource {
+        source: "ssu-mgmt",
+        table: "ssumgmt_audit",
+        uid: "e.id::text",
+        actor: "e.actor",
+        ip: "e.source_ip",
+        ua: "NULL::text",
+        time: "e.ts",
+    },
+];
+
+/// Per-kind join predicate. IPs go through `try_inet` (source_ip is free text)
+/// and the GiST index; domains only ever match the non-address `source_ip`
+/// values (service hostnames) by exact name or subdomain.
+fn kind_predicate(kind: IndicatorKind, src: &EventSource) -> Option<String> {
+    const ADDR: &str = "'^[0-9A-Fa-f:.]+$'";
+    Some(match kind {
+        IndicatorKind::Ip => format!(
+            "i.kind = 'ip' AND {ip} ~ {ADDR} AND i.network >>= try_inet({ip})",
+            ip = src.ip
+        ),
+        IndicatorKind::Domain => format!(
+            "i.kind = 'domain' AND {ip} !~ {ADDR} AND (lower({ip}) = i.value OR lower({ip}) LIKE '%.' || i.value)",
+            ip = src.ip
+        ),
+        IndicatorKind::UserAgent if src.ua != "NULL::text" => format!(
+            "i.kind = 'user_agent' AND strpos(lower({ua}), i.value) > 0",
+            ua = src.ua
+        ),
+        IndicatorKind::UserAgent => return None,
+    })
+}
+
+/// Record indicator hits for the window `(watermark, now - margin]`: events
+/// ingested in it against every live indicator, then indicators first loaded in
+/// it against the trailing `window_days` of events (retro-hunt). Idempotent on
+/// `(feed, kind, indicator, source, event_uid)`. Returns hits inserted.
+pub fn match_events(conn: &mut PgConnection, window_days: i64) -> anyhow::Result<usize> {
+    let now = Utc::now();
+    let target = now - Duration::minutes(THREAT_INTEL_SAFETY_MARGIN_MINS);
+    let event_floor = now - Duration::days(window_days.max(1));
+    let from = get_watermark(conn, THREAT_INTEL_WATERMARK_SOURCE)
+        .context("read threat-intel watermark")?
+        .and_then(|w| w.last_event_at)
+        .unwrap_or(event_floor);
+    if from >= target {
+        return Ok(0);
+    }
+
+    let mut hits = 0usize;
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        for src in EVENT_SOURCES {
+            for kind in [IndicatorKind::Ip, IndicatorKind::Domain, IndicatorKind::UserAgent] {
+                let Some(pred) = kind_predicate(kind, src) else {
+                    continue;
+                };
+                // New events × live indicators, then new indicators × windowed events.
+                for retro in [false, true] {
+                    let scope = if retro {
+                        format!("i.loaded_at > $1 AND i.loaded_at <= $2 AND {} >= $3", src.time)
+                    } else {
+                        "e.created_at > $1 AND e.created_at <= $2".to_string()
+                    };
+                    let sql = format!(
+                        "INSERT INTO threat_intel_hits (feed, kind, indicator, confidence, source, event_uid, actor, actor_id, source_ip, user_agent, event_time) \
+                         SELECT i.feed, i.kind, i.value, i.confidence, '{source}', {uid}, {actor}, aa.actor_id, {ip}, {ua}, {time} \
+                         FROM {table} e \
+                         JOIN threat_indicators i ON {pred} \
+                         LEFT JOIN actor_aliases aa ON aa.alias = {actor} \
+                         WHERE i.expires_at > now() AND {scope} \
+                         ON CONFLICT (feed, kind, indicator, source, event_uid) DO NOTHING",
+                        source = src.source,
+                        uid = src.uid,
+                        actor = src.actor,
+                        ip = src.ip,
+                        ua = src.ua,
+                        time = src.time,
+                        table = src.table,
+                    );
+                    let q = diesel::sql_query(&sql)
+                        .bind::<Timestamptz, _>(from)
+                        .bind::<Timestamptz, _>(target);
+                    let n = if retro {
+                        q.bind::<Timestamptz, _>(event_floor).execute(conn)
+                    } else {
+                        q.execute(conn)
+                    };
+                    hits += n.with_context(|| format!("match {} {}", src.source, kind.as_str()))?;
+                }
+            }
+        }
+
+        diesel::sql_query(
+            "INSERT INTO ingest_watermarks \
+               (source, last_event_at, last_run_at, objects_scanned, events_applied) \
+             VALUES ($1, $2, now(), 0, 0) \
+             ON CONFLICT (source) DO UPDATE SET \
+               last_event_at = GREATEST(EXCLUDED.last_event_at, ingest_watermarks.last_event_at), \
+               last_run_at   = now()",
+        )
+        .bind::<Text, _>(THREAT_INTEL_WATERMARK_SOURCE)
+        .bind::<Timestamptz, _>(target)
+        .execute(conn)
+        .context("advance threat-intel watermark")?;
+        Ok(())
+    })?;
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn networks_are_canonicalised() {
+        assert_eq!(
+            normalise_network("203.0.113.7").as_deref(),
+            Some("203.0.113.7/32")
+        );
+        assert_eq!(
+            normalise_network("10.1.2.3/8").as_deref(),
+            Some("10.0.0.0/8")
+        );
+        assert_eq!(
+            normalise_network("2001:db8::1/32").as_deref(),
+            Some("2001:db8::/32")
+        );
+        assert_eq!(normalise_network("10.0.0.0/33"), None);
+        assert_eq!(normalise_network("evil.example"), None);
+    }
+
+    #[test]
+    fn list_skips_comments_and_infers_kind() {
+        let text = "# header\n198.51.100.0/24 ; SBL123\n\nbad.example.com.\n203.0.113.9 extra\n";
+        let got = parse_list(text);
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0].kind, IndicatorKind::Ip);
+        assert_eq!(got[0].value, "198.51.100.0/24");
+        assert_eq!(got[1].kind, IndicatorKind::Domain);
+        assert_eq!(got[1].value, "bad.example.com");
+        assert_eq!(got[2].network.as_deref(), Some("203.0.113.9/32"));
+    }
+
+    #[test]
+    fn csv_reads_typed_rows_and_overrides() {
+        let text = "indicator,type,confidence,expires_at,description\n\
+                    203.0.113.5,ip,90,2099-01-01,c2 node\n\
+                    \"python-requests/2.0 evilbot\",user_agent,,,\n\
+                    not an ioc,,,,\n";
+        let got = parse_csv(text).unwrap();
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0].confidence, Some(90));
+        assert_eq!(got[0].description.as_deref(), Some("c2 node"));
+        assert!(got[0].expires_at.is_some());
+        assert_eq!(got[1].kind, IndicatorKind::UserAgent);
+        assert_eq!(got[1].value, "python-requests/2.0 evilbot");
+    }
+
+    #[test]
+    fn csv_kind_from_column_name() {
+        let got = parse_csv("domain\nc2.example.net\n").unwrap();
+        assert_eq!(got[0].kind, IndicatorKind::Domain);
+        assert!(parse_csv("foo,bar\n1,2\n").is_err());
+    }
+
+    #[test]
+    fn stix_bundle_patterns() {
+        let text = r#"{
+          "type": "bundle",
+          "objects": [
+            {"type": "indicator", "pattern_type": "stix", "confidence": 75,
+             "valid_until": "2099-01-01T00:00:00Z", "name": "APT infra",
+             "pattern": "[ipv4-addr:value = '198.51.100.1'] OR [ipv4-addr:value ISSUBSET '192.0.2.0/24'] OR [domain-name:value = 'Evil.Example']"},
+            {"type": "indicator", "pattern_type": "stix",
+             "pattern": "[network-traffic:extensions.'http-request-ext'.request_header.'User-Agent' = 'BadUA/1.0']"},
+            {"type": "indicator", "revoked": true, "pattern": "[ipv4-addr:value = '203.0.113.1']"},
+            {"type": "malware", "name": "x"}
+          ]
+        }"#;
+        let got = parse_stix(text).unwrap();
+        let values: Vec<&str> = got.iter().map(|i| i.value.as_str()).collect();
+        assert_eq!(
+            values,
+            [
+                "198.51.100.1/32",
+                "192.0.2.0/24",
+                "evil.example",
+                "badua/1.0"
+            ]
+        );
+        assert_eq!(got[0].confidence, Some(75));
+        assert_eq!(got[0].description.as_deref(), Some("APT infra"));
+        assert_eq!(got[3].kind, IndicatorKind::UserAgent);
+        assert_eq!(got[3].confidence, None);
+    }
+}
@@ -5,11 +5,11 @@ use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
 use diesel::sql_types::{Text, Timestamptz};
 use diesel::PgConnection;
-use log::debug;
+use log::{debug, info};
 use serde_json::json;
 
 use crate::misc::config::SiemConfig;
-use crate::service::siem::geoip::{haversine_km, GeoIp, GeoPoint};
+use crate::service::siem::geoip::{haversine_km, GeoIp, GeoPoint, NetworkClass, NetworkInfo};
 
 #[derive(QueryableByName)]
 struct Transition {
@@ -27,6 +27,9 @@ struct Transition {
 
 /// Detect impossible travel across logins in the trailing window. Returns the
 /// number of alert rows touched. A no-op (returns 0) when GeoIP is disabled.
+/// Hops touching trusted egress are skipped: the geo point is our own proxy's,
+/// so the "distance" is an artefact of routing. Hosting/VPN hops still alert,
+/// flagged in the evidence and down-ranked to `low`.
 pub fn detect(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyhow::Result<usize> {
     if !geoip.enabled() {
         debug!("impossible_travel: geoip disabled — skipping");
@@ -60,8 +63,25 @@ pub fn detect(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyh
             .clone()
     };
     
+    let mut net_cache: HashMap<String, Option<NetworkInfo>> = HashMap::new();
+    let mut net = |ip: &str| -> Option<NetworkInfo> {
+        net_cache
+            .entry(ip.to_string())
+            .or_insert_with(|| geoip.network(ip))
+            .clone()
+    };
+
     let mut pairs: Vec<TravelPair> = Vec::new();
+    let mut suppressed = 0usize;
     for t in &transitions {
+        let from_net = net(&t.prev_ip);
+        let to_net = net(&t.source_ip);
+        let egress =
+            |n: &Option<NetworkInfo>| n.as_ref().is_some_and(|n| n.class == NetworkClass::Trusted);
+        if egress(&from_net) || egress(&to_net) {
+            suppressed += 1;
+            continue;
+        }
         let from = geo(&t.prev_ip);
         let to = geo(&t.source_ip);
         let (Some(from_point), Some(to_point)) = (from, to) else {
@@ -77,6 +97,8 @@ pub fn detect(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyh
                 to_ip: t.source_ip.clone(),
                 from_loc: from_point.label.clone(),
                 to_loc: to_point.label.clone(),
+                from_net,
+                to_net,
                 km,
                 hours,
                 kmh,
@@ -84,6 +106,12 @@ pub fn detect(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyh
             });
         }
     }
+    if suppressed > 0 {
+        info!(
+            "impossible_travel: skipped {} hop(s) via trusted egress",
+            suppressed
+        );
+    }
 
     let mut touched = 0usize;
     conn.transaction::<_, anyhow::Error, _>(|conn| {
@@ -104,18 +132,25 @@ pub fn detect(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyh
                 p.hours,
                 p.kmh.round() as i64
             );
+            let hosting = [&p.from_net, &p.to_net]
+                .iter()
+                .any(|n| n.as_ref().is_some_and(|n| n.class == NetworkClass::Hosting));
             let evidence = json!({
                 "from_ip": p.from_ip, "to_ip": p.to_ip,
                 "from": p.from_loc, "to": p.to_loc,
+                "from_asn": p.from_net.as_ref().and_then(|n| n.asn),
+                "from_org": p.from_net.as_ref().and_then(|n| n.org.as_deref()),
+                "to_asn": p.to_net.as_ref().and_then(|n| n.asn),
+                "to_org": p.to_net.as_ref().and_then(|n| n.org.as_deref()),
                 "km": (p.km * 10.0).round() / 10.0, "hours": (p.hours * 100.0).round() / 100.0,
-                "kmh": p.kmh.round(),
+                "kmh": p.kmh.round(), "hosting": hosting,
             });
             touched += diesel::sql_query(
                 "INSERT INTO alerts (fingerprint, rule_id, severity, title, description, actor_id, source, first_seen, last_seen, event_count, status, evidence, updated_at) \
-                 VALUES ($1, 'impossible_travel', 'medium', 'Impossible travel', $2, $3, 'cloudtrail', $4, $4, 1, 'open', $5, now()) \
+                 VALUES ($1, 'impossible_travel', $6, 'Impossible travel', $2, $3, 'cloudtrail', $4, $4, 1, 'open', $5, now()) \
                  ON CONFLICT (fingerprint) DO UPDATE SET \
                    last_seen = GREATEST(alerts.last_seen, EXCLUDED.last_seen), description = EXCLUDED.description, \
-                   evidence = EXCLUDED.evidence, \
+                   evidence = EXCLUDED.evidence, severity = EXCLUDED.severity, \
                    status = CASE WHEN alerts.status = 'resolved' AND EXCLUDED.last_seen > COALESCE(alerts.resolved_at, alerts.last_seen) THEN 'open' ELSE alerts.status END, \
                    updated_at = now()",
             )
@@ -124,6 +159,7 @@ pub fn detect(conn: &mut PgConnection, geoip: &GeoIp, siem: &SiemConfig) -> anyh
             .bind::<Text, _>(&p.actor_id)
             .bind::<Timestamptz, _>(p.at)
             .bind::<diesel::sql_types::Jsonb, _>(evidence)
+            .bind::<Text, _>(if hosting { "low" } else { "medium" })
             .execute(conn)
             .context("upsert impossible_travel alert")?;
         }
@@ -139,6 +175,8 @@ struct TravelPair {
     to_ip: String,
     from_loc: String,
     to_loc: String,
+    from_net: Option<NetworkInfo>,
+    to_net: Option<NetworkInfo>,
     km: f64,
     hours: f64,
     kmh: f64,
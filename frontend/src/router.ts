@@ -26,6 +26,7 @@ export const router = createRouter({
         { path: 'query', name: 'console-query', component: () => import('./views/QueryView.vue') },
         { path: 'graph', name: 'console-graph', component: () => import('./views/GraphView.vue') },
         { path: 'actors', name: 'console-actors', component: () => import('./views/ActorsView.vue') },
+        { path: 'inspect/ip/:ip', name: 'console-inspect-ip', component: () => import('./views/IpView.vue') },
         { path: 'inspect/:id?', name: 'console-inspect', component: () => import('./views/EntityView.vue') },
       ],
     },
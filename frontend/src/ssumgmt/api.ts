@@ -95,7 +95,8 @@ const ORDERABLE: ReadonlySet<string> = new Set<string>([
 // Query AST — mirrors the backend `query_ast::Node` serde enum (tagged on
 // `kind`). The query *text* is parsed here into this tree and sent to the
 // backend as a single URL-encoded JSON `ast=` param; the server walks the
-// typed tree (it never parses a query string).
+// typed tree. The server also accepts the same text as `q=` (query_text.rs
+// implements this grammar), for scripts and links that don't carry an AST.
 // ---------------------------------------------------------------------------
 
 export type BoolOp = 'and' | 'or';
@@ -576,7 +577,7 @@ export function eventParams(p: EventQueryParams): URLSearchParams {
   return params;
 }
 
-function qs(params: URLSearchParams): string {
+export function qs(params: URLSearchParams): string {
   const s = params.toString();
   return s ? `?${s}` : '';
 }
@@ -614,6 +615,138 @@ export function eventsExportCsvUrl(p: EventQueryParams): string {
   return `/api/query/export.csv${qs(eventParams(p))}`;
 }
 
+// The derived tables (alerts, anomalies, sessions, grants) through the same
+// filter language. Each has its own field names (see the backend
+// `query_ast` datasets), so the filter is query text; `ts` and from/to refer to
+// the table's main timestamp. Rows come back as plain column objects.
+export type Dataset = 'events' | 'alerts' | 'anomalies' | 'sessions' | 'grants';
+
+export interface DatasetQueryParams
+  extends Omit<EventQueryParams, 'ast' | 'orderBy'> {
+  dataset: Exclude<Dataset, 'events'>;
+  q?: string;
+  orderBy?: string;
+}
+
+export interface DatasetResult {
+  rows: Record<string, unknown>[];
+  total: number | null;
+  total_capped: boolean;
+}
+
+function datasetParams(p: DatasetQueryParams): URLSearchParams {
+  const { dataset, q, orderBy, ...rest } = p;
+  const params = eventParams(rest);
+  params.set('dataset', dataset);
+  if (q?.trim()) params.set('q', q);
+  if (orderBy) params.set('order_by', orderBy);
+  return params;
+}
+
+export async function fetchDataset(p: DatasetQueryParams): Promise<DatasetResult> {
+  const url = `/api/query${qs(datasetParams(p))}`;
+  const res = await apiFetch(url);
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `GET ${url}: ${res.status}`);
+  }
+  const data = (await res.json()) as Partial<DatasetResult>;
+  return {
+    rows: data.rows ?? [],
+    total: data.total ?? null,
+    total_capped: data.total_capped ?? false,
+  };
+}
+
+export function datasetExportCsvUrl(p: DatasetQueryParams): string {
+  return `/api/query/export.csv${qs(datasetParams(p))}`;
+}
+
+// Aggregation over the same filter: group-by fields (EventField names or
+// `json.` paths), aggregates (`count`, `distinct:<field>`, `min_ts`,
+// `max_ts`), optional time bucket and top-N.
+export interface AggregateParams extends Pick<EventQueryParams, 'ast' | 'status' | 'source' | 'from' | 'to'> {
+  groupBy?: string[];
+  aggs?: string[];
+  bucket?: 'minute' | 'hour' | 'day' | 'week';
+  top?: number;
+  scanCap?: number;
+}
+
+export interface AggregateRow {
+  key: Record<string, string | null>;
+  bucket?: string;
+  [agg: string]: unknown;
+}
+
+export interface AggregateResult {
+  group_by: string[];
+  aggs: string[];
+  bucket: string | null;
+  rows: AggregateRow[];
+  scanned: number;
+  capped: boolean;
+  truncated: boolean;
+}
+
+export async function fetchAggregate(p: AggregateParams): Promise<AggregateResult> {
+  const params = eventParams(p);
+  for (const g of p.groupBy ?? []) params.append('group_by', g);
+  for (const a of p.aggs ?? []) params.append('agg', a);
+  if (p.bucket) params.set('bucket', p.bucket);
+  if (p.top !== undefined) params.set('top', String(p.top));
+  if (p.scanCap !== undefined) params.set('scan_cap', String(p.scanCap));
+  const url = `/api/query/aggregate${qs(params)}`;
+  const res = await apiFetch(url);
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `GET ${url}: ${res.status}`);
+  }
+  return (await res.json()) as AggregateResult;
+}
+
+// Top values of one field (EventField name or `json.` path) for filter
+// building and autocomplete. Unfiltered requests are answered from the
+// server's value dictionary; with an ast the newest matching events are
+// counted.
+export interface FacetParams extends Pick<EventQueryParams, 'ast' | 'status' | 'source' | 'from' | 'to'> {
+  field: string;
+  prefix?: string;
+  top?: number;
+  scanCap?: number;
+}
+
+export interface FacetValue {
+  value: string;
+  count: number;
+}
+
+export interface FacetResult {
+  field: string;
+  values: FacetValue[];
+  from_dictionary: boolean;
+  scanned: number | null;
+  capped: boolean;
+}
+
+export async function fetchFacets(p: FacetParams): Promise<FacetResult> {
+  const params = eventParams(p);
+  params.set('field', p.field);
+  if (p.prefix) params.set('prefix', p.prefix);
+  if (p.top !== undefined) params.set('top', String(p.top));
+  if (p.scanCap !== undefined) params.set('scan_cap', String(p.scanCap));
+  const url = `/api/query/facets${qs(params)}`;
+  const res = await apiFetch(url);
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `GET ${url}: ${res.status}`);
+  }
+  return (await res.json()) as FacetResult;
+}
+
 export interface TimelineParams {
   bucket?: 'minute' | 'hour' | 'day';
   from?: string;
@@ -672,6 +805,9 @@ export interface Kpis {
   actors_tracked: number;
   high_risk_actors: number;
   active_sessions: number;
+  // Share of password console logins (IAM user/root) with MFA over 7 days;
+  // federated sign-ins are excluded. `null` when there were none.
+  mfa_login_share_7d: number | null;
 }
 
 // Statistical anomaly (soft signal feeding risk + timeline markers).
@@ -711,6 +847,9 @@ export interface Alert {
   resolved_by: string | null;
   resolved_at: string | null;
   updated_at: string;
+  /** Routing target stamped from the actor's effective owner. */
+  owner: string | null;
+  owner_kind: 'team' | 'person' | null;
 }
 
 export interface SourceStat {
@@ -739,6 +878,11 @@ export interface RiskComponent {
   weight: number;
   normalized: number;
   contribution: number;
+  cap?: number;
+  description?: string;
+  /** What `refs` point at: event, grant, anomaly, alert, session, ti_hit, day or source. */
+  ref_kind?: string;
+  refs?: string[];
 }
 
 export interface RiskScore {
@@ -775,6 +919,31 @@ export interface SessionRow {
   event_count: number;
   status: string;
   flag_reason: string | null;
+  // GeoLite2-ASN owner of source_ip; network_class is 'trusted' (our egress),
+  // 'hosting' (cloud/VPN provider) or null for an ordinary network.
+  asn: number | null;
+  as_org: string | null;
+  network_class: 'trusted' | 'hosting' | null;
+  /** Events per source; `source` is the one that opened the session. */
+  source_counts: Record<string, number>;
+  chain: SessionChainLink[];
+  /** How the session's login authenticated: 'password' (IAM user/root), 'sso' or 'federated'. */
+  auth_method: string | null;
+  /** Weakest MFA outcome across its logins; `null` when unknown (federated sign-ins). */
+  mfa_used: boolean | null;
+}
+
+/** An SSO login and the role session it spawned; `via: null` is role activity with no login in the session. */
+export interface SessionChainLink {
+  via: 'ConsoleLogin' | 'GetRoleCredentials' | null;
+  at: string;
+  source_ip: string | null;
+  role_arn: string | null;
+  role_events: number;
+  role_first_seen: string | null;
+  role_last_seen: string | null;
+  auth_method: string | null;
+  mfa_used: boolean | null;
 }
 
 export interface GrantRow {
@@ -818,6 +987,30 @@ export interface EntityDetail {
   anomalies: Anomaly[];
   activity: SsuMgmtEvent[];
   activity_total: number;
+  /** Effective owner (first of `owners`), if any. */
+  owner: OwnerClaim | null;
+  owners: OwnerClaim[];
+  /** Roster joins/leaves, newest first. `capability: null` = the roster as a whole. */
+  roster_changes: RosterChange[];
+}
+
+export interface RosterChange {
+  id: number;
+  member_key: string;
+  capability: string | null;
+  change: 'join' | 'leave';
+  changed_at: string;
+}
+
+/** One ownership claim on an actor, in precedence order. */
+export interface OwnerClaim {
+  source: 'manual' | 'aws_tags' | 'k8s_namespace' | 'github' | 'roster';
+  owner: string;
+  owner_kind: 'team' | 'person';
+  evidence: Record<string, unknown>;
+  assigned_by: string | null;
+  reason: string | null;
+  updated_at: string;
 }
 
 export interface GraphNode {
@@ -825,6 +1018,14 @@ export interface GraphNode {
   type: string;
   label: string;
   risk: number;
+  /** Role nodes (`roles` mode): the AWS account the role lives in. */
+  account?: string;
+  /** Role nodes: whether the role name looks privileged. */
+  privileged?: boolean;
+  /** Actor nodes: the actor's kind (`human`, `service`, …). */
+  kind?: string;
+  /** Actor nodes: the directory team. */
+  team?: string;
 }
 
 export interface GraphEdge {
@@ -833,6 +1034,10 @@ export interface GraphEdge {
   kind: string;
   weight: number;
   failure: boolean;
+  /** Role edges: `<STS call> · <identity provider>`. */
+  label?: string;
+  first_seen: string;
+  last_seen: string;
 }
 
 export interface GraphResult {
@@ -888,6 +1093,60 @@ export function fetchActorsByRisk(limit = 10): Promise<ActorRisk[]> {
   return getJson<ActorRisk[]>(`/api/overview/actors-by-risk?limit=${limit}`);
 }
 
+export interface RiskMover {
+  id: string;
+  display_name: string | null;
+  email: string | null;
+  team: string | null;
+  kind: string;
+  score: number;
+  label: string;
+  baseline: number;
+  baseline_at: string;
+  delta: number;
+}
+
+export function fetchRiskMovers(
+  p: { hours?: number; limit?: number; direction?: 'up' | 'down' | 'both' } = {},
+): Promise<RiskMover[]> {
+  const params = new URLSearchParams();
+  if (p.hours !== undefined) params.set('hours', String(p.hours));
+  if (p.limit !== undefined) params.set('limit', String(p.limit));
+  if (p.direction) params.set('direction', p.direction);
+  return getJson<RiskMover[]>(`/api/overview/risk-movers${qs(params)}`);
+}
+
+export interface RiskTrendPoint {
+  day: string;
+  avg_score: number;
+  max_score: number;
+  actors: number;
+}
+
+export function fetchRiskTrend(p: { team?: string; days?: number } = {}): Promise<RiskTrendPoint[]> {
+  const params = new URLSearchParams();
+  if (p.team) params.set('team', p.team);
+  if (p.days !== undefined) params.set('days', String(p.days));
+  return getJson<RiskTrendPoint[]>(`/api/overview/risk-trend${qs(params)}`);
+}
+
+// Successful console logins per account split by MFA outcome; `unknown` are
+// federated sign-ins whose MFA happened at the IdP. Least-covered first.
+export interface MfaLoginAccount {
+  account: string | null;
+  logins: number;
+  mfa: number;
+  no_mfa: number;
+  unknown: number;
+  share: number | null;
+}
+
+export function fetchMfaLogins(days?: number): Promise<MfaLoginAccount[]> {
+  const params = new URLSearchParams();
+  if (days !== undefined) params.set('days', String(days));
+  return getJson<MfaLoginAccount[]>(`/api/overview/mfa-logins${qs(params)}`);
+}
+
 // ---------------------------------------------------------------------------
 // Actor discovery — the paginated/filterable table over the `actors` spine
 // (/api/actors). Distinct from actors-by-risk (top-N rollup): this browses every
@@ -907,6 +1166,9 @@ export interface ActorListRow {
   label: string | null;
   first_seen: string | null;
   last_active: string | null;
+  owner: string | null;
+  owner_kind: 'team' | 'person' | null;
+  owner_source: OwnerClaim['source'] | null;
 }
 
 export interface ActorsPage {
@@ -918,6 +1180,8 @@ export interface ActorsQuery {
   q?: string;
   kind?: string;
   origin?: string;
+  /** Exact effective owner; `none` → unowned actors. */
+  owner?: string;
   sort?: 'risk' | 'recent' | 'name';
   dir?: 'asc' | 'desc';
   limit?: number;
@@ -929,6 +1193,7 @@ export function fetchActors(p: ActorsQuery = {}): Promise<ActorsPage> {
   if (p.q) params.set('q', p.q);
   if (p.kind) params.set('kind', p.kind);
   if (p.origin) params.set('origin', p.origin);
+  if (p.owner) params.set('owner', p.owner);
   if (p.sort) params.set('sort', p.sort);
   if (p.dir) params.set('dir', p.dir);
   if (p.limit !== undefined) params.set('limit', String(p.limit));
@@ -936,6 +1201,424 @@ export function fetchActors(p: ActorsQuery = {}): Promise<ActorsPage> {
   return getJson<ActorsPage>(`/api/actors${qs(params)}`);
 }
 
+/** Analyst correction to identity reconciliation; applied by the next SIEM pass. */
+export interface ActorOverride {
+  id: number;
+  kind: 'merge' | 'split' | 'kind';
+  alias: string | null;
+  actor_id: string | null;
+  actor_kind: 'person' | 'service' | 'unresolved' | null;
+  reason: string;
+  created_by: string;
+  created_at: string;
+}
+
+export type NewActorOverride = Pick<ActorOverride, 'kind' | 'reason'> &
+  Partial<Pick<ActorOverride, 'alias' | 'actor_id' | 'actor_kind'>>;
+
+export function fetchActorOverrides(actor?: string): Promise<ActorOverride[]> {
+  const params = new URLSearchParams();
+  if (actor) params.set('actor', actor);
+  return getJson<ActorOverride[]>(`/api/actors/overrides${qs(params)}`);
+}
+
+export async function createActorOverride(o: NewActorOverride): Promise<ActorOverride> {
+  const url = '/api/actors/overrides';
+  const res = await apiFetch(url, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify(o),
+  });
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `POST ${url}: ${res.status}`);
+  }
+  return (await res.json()) as ActorOverride;
+}
+
+export async function deleteActorOverride(id: number): Promise<void> {
+  const url = `/api/actors/overrides/${id}`;
+  const res = await apiFetch(url, { method: 'DELETE' });
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `DELETE ${url}: ${res.status}`);
+  }
+}
+
+/** Someone who left the roster, with the access they still hold. */
+export interface DepartedIdentity {
+  actor_id: string;
+  member_key: string;
+  email: string | null;
+  display_name: string | null;
+  left_at: string;
+  capabilities: string[];
+  active_grants: number;
+  privileged_grants: number;
+  grants: { system: string; role: string; scope: string | null; privileged: boolean; granted_at: string | null }[];
+  github_access: { scope: string; login: string; action: string; permission: string | null; at: string }[];
+  last_activity: string | null;
+}
+
+/** Departed identities; by default only those still holding grants or GitHub access. */
+export function fetchDepartedIdentities(p: { all?: boolean; limit?: number } = {}): Promise<DepartedIdentity[]> {
+  const params = new URLSearchParams();
+  if (p.all) params.set('all', 'true');
+  if (p.limit !== undefined) params.set('limit', String(p.limit));
+  return getJson<DepartedIdentity[]>(`/api/actors/departed${qs(params)}`);
+}
+
+/** Manually own an actor; beats every derived source from the next SIEM pass. */
+export async function assignActorOwner(
+  id: string,
+  a: { owner: string; owner_kind?: 'team' | 'person'; reason: string },
+): Promise<void> {
+  const url = `/api/actors/${encodeURIComponent(id)}/owner`;
+  const res = await apiFetch(url, {
+    method: 'PUT',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify(a),
+  });
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `PUT ${url}: ${res.status}`);
+  }
+}
+
+export async function unassignActorOwner(id: string): Promise<void> {
+  const url = `/api/actors/${encodeURIComponent(id)}/owner`;
+  const res = await apiFetch(url, { method: 'DELETE' });
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `DELETE ${url}: ${res.status}`);
+  }
+}
+
+// --- Access reviews ---------------------------------------------------------
+
+export interface ReviewCampaign {
+  id: number;
+  name: string;
+  description: string | null;
+  scope: { system?: string | null; privileged?: boolean; team?: string | null };
+  status: 'open' | 'closed';
+  due_at: string | null;
+  created_by: string;
+  created_at: string;
+  /** Sign-off: who closed the fully decided campaign, and when. */
+  closed_by: string | null;
+  closed_at: string | null;
+  total: number;
+  approved: number;
+  revoked: number;
+  pending: number;
+  unassigned: number;
+}
+
+export interface NewReviewCampaign {
+  name: string;
+  description?: string;
+  system?: string;
+  privileged?: boolean;
+  team?: string;
+  due_at?: string;
+}
+
+/** One grant under review, as snapshotted when the campaign opened. */
+export interface ReviewItem {
+  id: number;
+  campaign_id: number;
+  grant_id: number | null;
+  grant_key: string;
+  actor_id: string | null;
+  team: string | null;
+  system: string;
+  role: string;
+  scope: string | null;
+  privileged: boolean;
+  granted_at: string | null;
+  reviewer: string | null;
+  reviewer_source: 'owner' | 'team_lead' | 'manual' | 'unassigned';
+  decision: 'approve' | 'revoke' | null;
+  comment: string | null;
+  decided_by: string | null;
+  decided_at: string | null;
+}
+
+export interface TeamLead {
+  team: string;
+  lead: string;
+  updated_by: string;
+  updated_at: string;
+}
+
+async function sendJson<T>(method: string, url: string, body: unknown): Promise<T> {
+  const res = await apiFetch(url, {
+    method,
+    headers: { 'Content-Type': 'application/json' },
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  if (res.status === 403) throw new ForbiddenError(ROLE_MSG);
+  if (!res.ok) {
+    const text = await res.text().catch(() => '');
+    throw new Error(text || `${method} ${url}: ${res.status}`);
+  }
+  return (await res.json()) as T;
+}
+
+export function fetchReviewCampaigns(): Promise<ReviewCampaign[]> {
+  return getJson<ReviewCampaign[]>('/api/reviews/campaigns');
+}
+
+export function createReviewCampaign(c: NewReviewCampaign): Promise<ReviewCampaign> {
+  return sendJson<ReviewCampaign>('POST', '/api/reviews/campaigns', c);
+}
+
+/** A campaign and its items; `reviewer: 'me'` narrows to the caller's queue. */
+export function fetchReviewCampaign(
+  id: number,
+  p: { reviewer?: string; pending?: boolean } = {},
+): Promise<{ campaign: ReviewCampaign; items: ReviewItem[] }> {
+  const params = new URLSearchParams();
+  if (p.reviewer) params.set('reviewer', p.reviewer);
+  if (p.pending) params.set('pending', 'true');
+  return getJson(`/api/reviews/campaigns/${id}${qs(params)}`);
+}
+
+/** Sign off; rejected while any item is undecided. */
+export function closeReviewCampaign(id: number): Promise<ReviewCampaign> {
+  return sendJson<ReviewCampaign>('POST', `/api/reviews/campaigns/${id}/close`, undefined);
+}
+
+export function reviewExportUrl(id: number, format: 'csv' | 'json' = 'csv'): string {
+  return `/api/reviews/campaigns/${id}/export?format=${format}`;
+}
+
+/** 403 unless the caller is the item's reviewer or holds `ce.reviewadmin`. */
+export function decideReviewItem(
+  id: number,
+  d: { action: 'approve' | 'revoke' | 'comment'; comment?: string },
+): Promise<ReviewItem> {
+  return sendJson<ReviewItem>('POST', `/api/reviews/items/${id}/decision`, d);
+}
+
+export function assignReviewItem(id: number, reviewer: string): Promise<ReviewItem> {
+  return sendJson<ReviewItem>('PUT', `/api/reviews/items/${id}/reviewer`, { reviewer });
+}
+
+export function fetchTeamLeads(): Promise<TeamLead[]> {
+  return getJson<TeamLead[]>('/api/reviews/team-leads');
+}
+
+export async function setTeamLead(team: string, lead: string): Promise<void> {
+  await sendJson<unknown>('PUT', `/api/reviews/team-leads/${encodeURIComponent(team)}`, { lead });
+}
+
+// --- Saved searches -----------------------------------------------------------
+
+/** A named filter, private to its owner or shared. `every_mins` set = scheduled. */
+export interface SavedSearch {
+  id: number;
+  name: string;
+  description: string | null;
+  owner: string;
+  ast: QueryNode;
+  visibility: 'private' | 'shared';
+  every_mins: number | null;
+  /** A slice matching more than this many events raises alert `search:<id>`. */
+  threshold: number;
+  severity: 'low' | 'medium' | 'high' | 'critical';
+  last_run_at: string | null;
+  last_run_to: string | null;
+  last_match_count: number | null;
+  last_error: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
+/** Give the filter as `ast` or as query text `q`, not both. */
+export interface NewSavedSearch {
+  name: string;
+  description?: string;
+  ast?: QueryNode;
+  q?: string;
+  visibility?: 'private' | 'shared';
+}
+
+export function fetchSavedSearches(): Promise<SavedSearch[]> {
+  return getJson<SavedSearch[]>('/api/searches');
+}
+
+export function createSavedSearch(s: NewSavedSearch): Promise<SavedSearch> {
+  return sendJson<SavedSearch>('POST', '/api/searches', s);
+}
+
+export function updateSavedSearch(id: number, s: NewSavedSearch): Promise<SavedSearch> {
+  return sendJson<SavedSearch>('PUT', `/api/searches/${id}`, s);
+}
+
+export async function deleteSavedSearch(id: number): Promise<void> {
+  const url = `/api/searches/${id}`;
+  const res = await apiFetch(url, { method: 'DELETE' });
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `DELETE ${url}: ${res.status}`);
+  }
+}
+
+/** Shared searches only: the alert it raises is visible to every console user. */
+export function scheduleSavedSearch(
+  id: number,
+  s: { every_mins: number; threshold?: number; severity?: SavedSearch['severity'] },
+): Promise<SavedSearch> {
+  return sendJson<SavedSearch>('PUT', `/api/searches/${id}/schedule`, s);
+}
+
+export function unscheduleSavedSearch(id: number): Promise<SavedSearch> {
+  return sendJson<SavedSearch>('DELETE', `/api/searches/${id}/schedule`, undefined);
+}
+
+// --- Query jobs ---------------------------------------------------------------
+
+/** What a query job runs: the `/api/query` filter, with `ast` as an object. */
+export interface QueryJobRequest {
+  ast?: QueryNode;
+  q?: string;
+  status?: string;
+  source?: string;
+  from?: string;
+  to?: string;
+  order_by?: string;
+  order_dir?: 'asc' | 'desc';
+}
+
+/** A query run in the background; `rows_done` grows while it runs. */
+export interface QueryJob {
+  id: string;
+  owner: string;
+  spec: QueryJobRequest;
+  status: 'queued' | 'running' | 'done' | 'failed' | 'cancelled';
+  cancel_requested: boolean;
+  rows_done: number;
+  /** More rows matched than the server keeps per job. */
+  truncated: boolean;
+  error: string | null;
+  created_at: string;
+  started_at: string | null;
+  finished_at: string | null;
+  /** The job and its rows are deleted after this. */
+  expires_at: string;
+}
+
+export interface QueryJobPage {
+  job: QueryJob;
+  rows: SsuMgmtEvent[];
+  offset: number;
+  /** `null` at the end of the rows materialised so far. */
+  next_offset: number | null;
+}
+
+/** Rejects with the server's message when the per-user job limit is reached. */
+export function submitQueryJob(req: QueryJobRequest): Promise<QueryJob> {
+  return sendJson<QueryJob>('POST', '/api/jobs', req);
+}
+
+export function fetchQueryJobs(): Promise<QueryJob[]> {
+  return getJson<QueryJob[]>('/api/jobs');
+}
+
+export function fetchQueryJob(id: string): Promise<QueryJob> {
+  return getJson<QueryJob>(`/api/jobs/${encodeURIComponent(id)}`);
+}
+
+export function fetchQueryJobResults(
+  id: string,
+  offset = 0,
+  limit = 100,
+): Promise<QueryJobPage> {
+  return getJson<QueryJobPage>(
+    `/api/jobs/${encodeURIComponent(id)}/results${qs(new URLSearchParams({ offset: String(offset), limit: String(limit) }))}`,
+  );
+}
+
+export function cancelQueryJob(id: string): Promise<QueryJob> {
+  return sendJson<QueryJob>('POST', `/api/jobs/${encodeURIComponent(id)}/cancel`, undefined);
+}
+
+export async function deleteQueryJob(id: string): Promise<void> {
+  const url = `/api/jobs/${encodeURIComponent(id)}`;
+  const res = await apiFetch(url, { method: 'DELETE' });
+  if (!res.ok) {
+    const body = await res.text().catch(() => '');
+    throw new Error(body || `DELETE ${url}: ${res.status}`);
+  }
+}
+
+// --- Team posture -----------------------------------------------------------
+
+/** One team's aggregate posture, recomputed every SIEM pass. */
+export interface TeamPosture {
+  team: string;
+  members: number;
+  members_by_kind: Record<string, number>;
+  risk_max: number;
+  risk_avg: number;
+  risk_distribution: Record<'critical' | 'high' | 'medium' | 'low', number>;
+  /** Open + acked. */
+  open_alerts: number;
+  open_alerts_by_severity: Record<'critical' | 'high' | 'medium' | 'low', number>;
+  privileged_grants: number;
+  dormant: number;
+  anomalies: number;
+  /** Share of members with an anomaly inside the SIEM window (0–1). */
+  anomaly_rate: number;
+  computed_at: string;
+}
+
+export interface TeamMember {
+  actor_id: string;
+  kind: string;
+  last_active: string | null;
+  risk_score: number | null;
+  risk_label: string | null;
+  open_alerts: number;
+  privileged_grants: number;
+}
+
+export interface TeamDetail {
+  team: string;
+  /** Null once the team has no members left; history remains. */
+  posture: TeamPosture | null;
+  history: (TeamPosture & { day: string })[];
+  members: TeamMember[];
+}
+
+export type TeamSort =
+  | 'risk_avg'
+  | 'risk_max'
+  | 'open_alerts'
+  | 'privileged_grants'
+  | 'dormant'
+  | 'anomaly_rate'
+  | 'members';
+
+export function fetchTeamLeaderboard(sort?: TeamSort): Promise<TeamPosture[]> {
+  const params = new URLSearchParams();
+  if (sort) params.set('sort', sort);
+  return getJson<TeamPosture[]>(`/api/teams${qs(params)}`);
+}
+
+export function fetchTeamDetail(team: string, p: { days?: number; limit?: number } = {}): Promise<TeamDetail> {
+  const params = new URLSearchParams();
+  if (p.days !== undefined) params.set('days', String(p.days));
+  if (p.limit !== undefined) params.set('limit', String(p.limit));
+  return getJson<TeamDetail>(`/api/teams/${encodeURIComponent(team)}${qs(params)}`);
+}
+
 /** The origin taxonomy, in display order — drives the Actors/inspect filter dropdowns. */
 export const ACTOR_ORIGINS: readonly string[] = [
   'kubernetes',
@@ -949,6 +1632,7 @@ export const ACTOR_ORIGINS: readonly string[] = [
 export interface AlertsQuery {
   severity?: string;
   status?: string;
+  owner?: string;
   limit?: number;
   offset?: number;
 }
@@ -963,6 +1647,7 @@ function alertParams(p: AlertsQuery): URLSearchParams {
   const params = new URLSearchParams();
   if (p.severity) params.set('severity', p.severity);
   if (p.status) params.set('status', p.status);
+  if (p.owner) params.set('owner', p.owner);
   if (p.limit !== undefined) params.set('limit', String(p.limit));
   if (p.offset !== undefined) params.set('offset', String(p.offset));
   return params;
@@ -998,16 +1683,262 @@ export function fetchEntityTimeline(id: string, p: TimelineParams = {}): Promise
   return getJson<TimelineBucket[]>(`/api/entity/${encodeURIComponent(id)}/timeline${qs(params)}`);
 }
 
+// Resource entities — what was touched rather than who did it. Ids are
+// `<kind>:<name>` (e.g. `bucket:logs`, `role:123456789012/Admin`, `repo:org/app`).
+export type ResourceKind = 'bucket' | 'role' | 'account' | 'repo' | 'org' | 'path';
+
+export interface Resource {
+  id: string;
+  kind: ResourceKind;
+  name: string;
+  first_seen: string;
+  last_seen: string;
+  events: number;
+}
+
+export interface ResourceActor {
+  actor: string;
+  /** Reconciled actor, when the raw identifier resolves — link to `/entity/:id`. */
+  actor_id: string | null;
+  events: number;
+  failed: number;
+  last_seen: string;
+}
+
+export interface ResourceAction {
+  action: string;
+  events: number;
+  failed: number;
+  failure_rate: number;
+  last_seen: string;
+}
+
+export interface ResourceDetail {
+  resource: Resource;
+  window_days: number;
+  stats: { events_24h: number; events: number; failed: number; failure_rate: number; actors: number };
+  actors: ResourceActor[];
+  actions: ResourceAction[];
+  activity: SsuMgmtEvent[];
+}
+
+export function fetchResources(p: { kind?: ResourceKind; q?: string; limit?: number } = {}): Promise<Resource[]> {
+  const params = new URLSearchParams();
+  if (p.kind) params.set('kind', p.kind);
+  if (p.q) params.set('q', p.q);
+  if (p.limit !== undefined) params.set('limit', String(p.limit));
+  return getJson<Resource[]>(`/api/entity/resources${qs(params)}`);
+}
+
+export function fetchResource(id: string, p: { days?: number } = {}): Promise<ResourceDetail> {
+  const params = new URLSearchParams();
+  if (p.days !== undefined) params.set('days', String(p.days));
+  return getJson<ResourceDetail>(`/api/entity/resource/${encodeURIComponent(id)}${qs(params)}`);
+}
+
+export function fetchResourceActivity(id: string, p: { limit?: number; offset?: number } = {}): Promise<QueryResult> {
+  const params = new URLSearchParams();
+  if (p.limit !== undefined) params.set('limit', String(p.limit));
+  if (p.offset !== undefined) params.set('offset', String(p.offset));
+  return getJson<QueryResult>(`/api/entity/resource/${encodeURIComponent(id)}/activity${qs(params)}`);
+}
+
+export function fetchResourceTimeline(id: string, p: TimelineParams = {}): Promise<TimelineBucket[]> {
+  const params = new URLSearchParams();
+  if (p.bucket) params.set('bucket', p.bucket);
+  if (p.from) params.set('from', p.from);
+  if (p.to) params.set('to', p.to);
+  return getJson<TimelineBucket[]>(`/api/entity/resource/${encodeURIComponent(id)}/timeline${qs(params)}`);
+}
+
+/** GeoIP/ASN enrichment of one address; fields are null when the DB is not loaded. */
+export interface IpEnrichment {
+  location: string | null;
+  lat: number | null;
+  lon: number | null;
+  asn: number | null;
+  as_org: string | null;
+  network_class: 'trusted' | 'hosting' | 'public' | null;
+}
+
+export interface IpSeen {
+  ip: string;
+  events: number;
+  first_seen: string;
+  last_seen: string;
+  enrichment: IpEnrichment;
+}
+
+export interface IpSourceCount {
+  source: string;
+  events: number;
+  failed: number;
+  first_seen: string;
+  last_seen: string;
+}
+
+export interface IpActor extends ResourceActor {
+  first_seen: string;
+}
+
+/** A reconciled actor on an address more than one actor used. */
+export interface IpPeer {
+  ip: string | null;
+  peer: string;
+  weight: number;
+  first_seen: string;
+  last_seen: string;
+}
+
+export interface IpDetail {
+  /** The address, or the range in `addr/prefix` form. */
+  ip: string;
+  cidr: boolean;
+  window_days: number;
+  /** Only for a single address; a range's is per entry of `ips`. */
+  enrichment: IpEnrichment | null;
+  first_seen: string | null;
+  last_seen: string | null;
+  events: number;
+  ips: IpSeen[];
+  ips_truncated: boolean;
+  sources: IpSourceCount[];
+  actors: IpActor[];
+  peers: IpPeer[];
+  sessions: SessionRow[];
+  alerts: Alert[];
+}
+
+/** Whether `v` is an IPv4 or IPv6 literal. `source_ip` also holds service
+ *  names (CloudTrail's `cloudtrail.amazonaws.com`), which have no IP page. */
+export function isIpLiteral(v: string | null | undefined): v is string {
+  if (!v) return false;
+  if (/^((25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)$/.test(v)) return true;
+  if (!v.includes(':')) return false;
+  try {
+    new URL(`http://[${v}]/`);
+    return true;
+  } catch {
+    return false;
+  }
+}
+
+/** `ip` may be a CIDR range (`10.0.0.0/8`). */
+export function fetchIpEntity(ip: string, p: { days?: number } = {}): Promise<IpDetail> {
+  const params = new URLSearchParams();
+  if (p.days !== undefined) params.set('days', String(p.days));
+  return getJson<IpDetail>(`/api/entity/ip/${encodeURIComponent(ip)}${qs(params)}`);
+}
+
+/** One point of an actor's risk trajectory; older points are hourly/daily rollups. */
+export interface RiskHistoryPoint {
+  bucket: string;
+  resolution: 'raw' | 'hour' | 'day';
+  score: number;
+  min_score: number;
+  max_score: number;
+  label: string;
+  samples: number;
+  components: Record<string, RiskComponent> | null;
+}
+
+export function fetchEntityRiskHistory(
+  id: string,
+  p: { from?: string; to?: string; components?: boolean } = {},
+): Promise<RiskHistoryPoint[]> {
+  const params = new URLSearchParams();
+  if (p.from) params.set('from', p.from);
+  if (p.to) params.set('to', p.to);
+  if (p.components) params.set('components', 'true');
+  return getJson<RiskHistoryPoint[]>(`/api/entity/${encodeURIComponent(id)}/risk-history${qs(params)}`);
+}
+
 export interface GraphQuery {
-  mode?: 'surface' | 'investigate' | 'entity';
+  mode?: 'surface' | 'investigate' | 'entity' | 'roles';
   actor?: string;
+  /** `roles` mode: only hops into or out of this AWS account. */
+  account?: string;
+  /** Activity modes default to the last 7 days; `roles` to every hop. */
+  from?: string;
+  to?: string;
 }
 
-export function fetchGraph(p: GraphQuery = {}): Promise<GraphResult> {
+export type GraphExportFormat = 'graphml' | 'gexf' | 'cytoscape';
+
+function graphParams(p: GraphQuery): URLSearchParams {
   const params = new URLSearchParams();
   if (p.mode) params.set('mode', p.mode);
   if (p.actor) params.set('actor', p.actor);
-  return getJson<GraphResult>(`/api/graph${qs(params)}`);
+  if (p.account) params.set('account', p.account);
+  if (p.from) params.set('from', p.from);
+  if (p.to) params.set('to', p.to);
+  return params;
+}
+
+export function fetchGraph(p: GraphQuery = {}): Promise<GraphResult> {
+  return getJson<GraphResult>(`/api/graph${qs(graphParams(p))}`);
+}
+
+/** The same graph as a GraphML, GEXF or Cytoscape JSON download. */
+export function graphExportUrl(p: GraphQuery, format: GraphExportFormat): string {
+  const params = graphParams(p);
+  params.set('format', format);
+  return `/api/graph/export${qs(params)}`;
+}
+
+/** One observed `AssumeRole*` hop (`role_edges`). */
+export interface RoleEdge {
+  edge_key: string;
+  src_kind: 'actor' | 'role' | 'principal';
+  src: string;
+  dst_role: string;
+  dst_account: string | null;
+  src_account: string | null;
+  via: 'AssumeRole' | 'AssumeRoleWithSAML' | 'AssumeRoleWithWebIdentity';
+  /** Identity provider and actor behind the latest call over this hop. */
+  identity_source: string | null;
+  actor_id: string | null;
+  privileged: boolean;
+  first_seen: string;
+  last_seen: string;
+  event_count: number;
+}
+
+export interface RolePath {
+  role: string;
+  account: string | null;
+  privileged: boolean;
+  /** When the newest hop of the path was first observed. */
+  first_seen: string;
+  last_seen: string;
+  hops: RoleEdge[];
+}
+
+export interface RolePathResult {
+  actor: string;
+  account: string | null;
+  privilegedOnly: boolean;
+  paths: RolePath[];
+  truncated: boolean;
+}
+
+export interface RolePathQuery {
+  actor: string;
+  account?: string;
+  /** Include paths ending in non-privileged roles. */
+  all?: boolean;
+  depth?: number;
+  limit?: number;
+}
+
+/** Every observed path by which an actor reached a (privileged) role. */
+export function fetchRolePaths(p: RolePathQuery): Promise<RolePathResult> {
+  const params = new URLSearchParams({ actor: p.actor });
+  if (p.account) params.set('account', p.account);
+  if (p.all) params.set('all', 'true');
+  if (p.depth != null) params.set('depth', String(p.depth));
+  if (p.limit != null) params.set('limit', String(p.limit));
+  return getJson<RolePathResult>(`/api/graph/paths${qs(params)}`);
 }
 
 async function postTriage(url: string): Promise<void> {
@@ -0,0 +1,127 @@
+import { ref } from 'vue';
+import { getAccessToken } from '../auth/useAuth';
+import { eventParams, qs } from './api';
+import type { EventQueryParams, SsuMgmtEvent } from './api';
+
+interface TailMessage {
+  type: 'events' | 'rate_limited' | 'error';
+  payload: unknown;
+}
+
+/** An ingest window whose matches exceeded the server's rate cap. */
+export interface TailGap {
+  from: string | null;
+  to: string;
+}
+
+export type TailParams = Pick<EventQueryParams, 'ast' | 'status' | 'source'>;
+
+const AUTH_CLOSE_CODES = new Set([1008, 4401]);
+const BACKOFF_MIN_MS = 1_000;
+const BACKOFF_MAX_MS = 30_000;
+/** Rows kept in memory, newest first. */
+const MAX_ROWS = 1_000;
+
+/**
+ * Live tail of newly ingested events matching a filter. Unlike the console
+ * stream each caller gets its own socket, since the filter is per view. The
+ * server starts every (re)connection at "now", so a reconnect can miss rows
+ * ingested while disconnected.
+ */
+export function useLiveTail() {
+  const rows = ref<SsuMgmtEvent[]>([]);
+  const gaps = ref<TailGap[]>([]);
+  const error = ref<string | null>(null);
+  const connected = ref(false);
+
+  let socket: WebSocket | null = null;
+  let params: TailParams | null = null;
+  let backoff = BACKOFF_MIN_MS;
+  let reconnectTimer: ReturnType<typeof setTimeout> | undefined;
+
+  function applyMessage(msg: TailMessage): void {
+    switch (msg.type) {
+      case 'events': {
+        const fresh = (msg.payload as SsuMgmtEvent[]).slice().reverse();
+        rows.value = [...fresh, ...rows.value].slice(0, MAX_ROWS);
+        break;
+      }
+      case 'rate_limited':
+        gaps.value = [...gaps.value, msg.payload as TailGap];
+        break;
+      case 'error':
+        error.value = String(msg.payload);
+        break;
+    }
+  }
+
+  async function open(): Promise<void> {
+    if (!params || socket) return;
+    const token = await getAccessToken();
+    if (!params) return;
+
+    const proto = location.protocol === 'https:' ? 'wss' : 'ws';
+    const url = `${proto}://${location.host}/api/tail/ws${qs(eventParams(params))}`;
+    // Token rides in the subprotocol, as on the console stream.
+    const ws = token ? new WebSocket(url, ['bearer', token]) : new WebSocket(url);
+    socket = ws;
+
+    ws.onopen = () => {
+      connected.value = true;
+      error.value = null;
+      backoff = BACKOFF_MIN_MS;
+    };
+    ws.onmessage = (ev) => {
+      try {
+        applyMessage(JSON.parse(ev.data as string) as TailMessage);
+      } catch {
+        /* ignore malformed frame */
+      }
+    };
+    ws.onclose = (ev) => {
+      connected.value = false;
+      socket = null;
+      if (!params) return;
+      if (AUTH_CLOSE_CODES.has(ev.code)) {
+        void getAccessToken().finally(() => void open());
+        return;
+      }
+      scheduleReconnect();
+    };
+  }
+
+  function scheduleReconnect(): void {
+    if (!params || reconnectTimer) return;
+    reconnectTimer = setTimeout(() => {
+      reconnectTimer = undefined;
+      backoff = Math.min(backoff * 2, BACKOFF_MAX_MS);
+      void open();
+    }, backoff);
+  }
+
+  /** (Re)start tailing with a new filter, clearing what was shown. */
+  function start(p: TailParams): void {
+    stop();
+    params = p;
+    rows.value = [];
+    gaps.value = [];
+    error.value = null;
+    void open();
+  }
+
+  function stop(): void {
+    params = null;
+    if (reconnectTimer) {
+      clearTimeout(reconnectTimer);
+      reconnectTimer = undefined;
+    }
+    if (socket) {
+      socket.onclose = null; // an intentional close doesn't reconnect
+      socket.close();
+      socket = null;
+    }
+    connected.value = false;
+  }
+
+  return { rows, gaps, error, connected, start, stop };
+}